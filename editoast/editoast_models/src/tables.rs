@@ -99,6 +99,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    infra_edit (id) {
+        id -> Int8,
+        infra_id -> Int8,
+        version -> Int8,
+        author_id -> Nullable<Int8>,
+        created -> Timestamptz,
+        operations -> Jsonb,
+        inverse_operations -> Jsonb,
+        reverted_edit_id -> Nullable<Int8>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -410,6 +426,19 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    infra_validation_rule (id) {
+        id -> Int8,
+        infra_id -> Int8,
+        #[max_length = 128]
+        rule_id -> Varchar,
+        settings -> Jsonb,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -618,6 +647,23 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    simulation_result (hash) {
+        hash -> Varchar,
+        infra_id -> Int8,
+        #[max_length = 40]
+        infra_version -> Varchar,
+        #[max_length = 255]
+        rolling_stock_name -> Varchar,
+        electrical_profile_set_id -> Nullable<Int8>,
+        result -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -630,6 +676,8 @@ diesel::table! {
         response -> Jsonb,
         created -> Timestamptz,
         user_id -> Nullable<Int8>,
+        #[max_length = 255]
+        rolling_stock_name -> Nullable<Varchar>,
     }
 }
 
@@ -646,6 +694,19 @@ diesel::table! {
         search_window_begin -> Timestamptz,
         search_window_end -> Timestamptz,
         temporary_speed_limit_group_id -> Nullable<Int8>,
+        #[max_length = 255]
+        name -> Varchar,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    stdcm_search_environment_access (id) {
+        id -> Int8,
+        search_environment_id -> Int8,
+        subject_id -> Int8,
     }
 }
 
@@ -763,6 +824,21 @@ diesel::table! {
         speed_limit_tag -> Nullable<Varchar>,
         power_restrictions -> Jsonb,
         options -> Jsonb,
+        train_service_id -> Nullable<Int8>,
+        calendar -> Nullable<Jsonb>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    train_service (id) {
+        id -> Int8,
+        timetable_id -> Int8,
+        base -> Jsonb,
+        recurrence -> Jsonb,
+        expanded -> Bool,
     }
 }
 
@@ -799,6 +875,8 @@ diesel::joinable!(authn_group_membership -> authn_group (group));
 diesel::joinable!(authn_group_membership -> authn_user (user));
 diesel::joinable!(authn_user -> authn_subject (id));
 diesel::joinable!(authz_role -> authn_subject (subject));
+diesel::joinable!(infra_edit -> authn_user (author_id));
+diesel::joinable!(infra_edit -> infra (infra_id));
 diesel::joinable!(infra_layer_buffer_stop -> infra (infra_id));
 diesel::joinable!(infra_layer_detector -> infra (infra_id));
 diesel::joinable!(infra_layer_electrification -> infra (infra_id));
@@ -822,6 +900,7 @@ diesel::joinable!(infra_object_signal -> infra (infra_id));
 diesel::joinable!(infra_object_speed_section -> infra (infra_id));
 diesel::joinable!(infra_object_switch -> infra (infra_id));
 diesel::joinable!(infra_object_track_section -> infra (infra_id));
+diesel::joinable!(infra_validation_rule -> infra (infra_id));
 diesel::joinable!(macro_node -> scenario (scenario_id));
 diesel::joinable!(project -> document (image_id));
 diesel::joinable!(rolling_stock_livery -> document (compound_image_id));
@@ -837,15 +916,21 @@ diesel::joinable!(search_project -> project (id));
 diesel::joinable!(search_scenario -> scenario (id));
 diesel::joinable!(search_signal -> infra_object_signal (id));
 diesel::joinable!(search_study -> study (id));
+diesel::joinable!(simulation_result -> electrical_profile_set (electrical_profile_set_id));
+diesel::joinable!(simulation_result -> infra (infra_id));
 diesel::joinable!(stdcm_logs -> authn_user (user_id));
 diesel::joinable!(stdcm_search_environment -> electrical_profile_set (electrical_profile_set_id));
 diesel::joinable!(stdcm_search_environment -> infra (infra_id));
 diesel::joinable!(stdcm_search_environment -> temporary_speed_limit_group (temporary_speed_limit_group_id));
 diesel::joinable!(stdcm_search_environment -> timetable (timetable_id));
 diesel::joinable!(stdcm_search_environment -> work_schedule_group (work_schedule_group_id));
+diesel::joinable!(stdcm_search_environment_access -> authn_subject (subject_id));
+diesel::joinable!(stdcm_search_environment_access -> stdcm_search_environment (search_environment_id));
 diesel::joinable!(study -> project (project_id));
 diesel::joinable!(temporary_speed_limit -> temporary_speed_limit_group (temporary_speed_limit_group_id));
 diesel::joinable!(train_schedule -> timetable (timetable_id));
+diesel::joinable!(train_schedule -> train_service (train_service_id));
+diesel::joinable!(train_service -> timetable (timetable_id));
 diesel::joinable!(work_schedule -> work_schedule_group (work_schedule_group_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
@@ -857,6 +942,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     document,
     electrical_profile_set,
     infra,
+    infra_edit,
     infra_layer_buffer_stop,
     infra_layer_detector,
     infra_layer_electrification,
@@ -880,6 +966,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     infra_object_speed_section,
     infra_object_switch,
     infra_object_track_section,
+    infra_validation_rule,
     macro_node,
     project,
     rolling_stock,
@@ -892,14 +979,17 @@ diesel::allow_tables_to_appear_in_same_query!(
     search_signal,
     search_study,
     search_track,
+    simulation_result,
     stdcm_logs,
     stdcm_search_environment,
+    stdcm_search_environment_access,
     study,
     temporary_speed_limit,
     temporary_speed_limit_group,
     timetable,
     towed_rolling_stock,
     train_schedule,
+    train_service,
     work_schedule,
     work_schedule_group,
 );
@@ -9,6 +9,7 @@ use super::Curve;
 use super::LoadingGaugeLimit;
 use super::Slope;
 use super::TrackSectionExtensions;
+use crate::primitives::haversine_distance;
 use crate::primitives::BoundingBox;
 use crate::primitives::Identifier;
 use crate::primitives::OSRDIdentified;
@@ -62,6 +63,27 @@ impl TrackSection {
     pub fn geo_bbox(&self) -> BoundingBox {
         Self::bbox(&self.geo)
     }
+
+    /// Computes the length in meters of a track section geometry, `0` if it isn't a LineString
+    pub fn line_length(geom: &Geometry) -> f64 {
+        match &geom.value {
+            LineString(points) => points
+                .windows(2)
+                .map(|segment| {
+                    haversine_distance(
+                        (segment[0][0], segment[0][1]),
+                        (segment[1][0], segment[1][1]),
+                    )
+                })
+                .sum(),
+            _ => 0.0,
+        }
+    }
+
+    /// The length in meters of the geometry of the track section
+    pub fn geo_length(&self) -> f64 {
+        Self::line_length(&self.geo)
+    }
 }
 
 #[cfg(test)]
@@ -69,7 +91,9 @@ mod tests {
     use geojson;
     use serde_json::from_str;
 
+    use super::TrackSection;
     use super::TrackSectionExtensions;
+    use crate::primitives::haversine_distance;
     use crate::primitives::BoundingBox;
 
     /// Test bounding box from linestring
@@ -89,6 +113,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_line_string_length() {
+        let geo = geojson::Geometry::new(geojson::Value::LineString(vec![
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![0.0, 2.0],
+        ]));
+        let length = TrackSection::line_length(&geo);
+        assert!((length - 222_637.69).abs() < 1.0);
+    }
+
     #[test]
     fn test_track_extensions_deserialization() {
         from_str::<TrackSectionExtensions>(r#"{}"#).unwrap();
@@ -5,6 +5,7 @@ mod non_blank_string;
 mod object_ref;
 mod object_type;
 
+pub use bounding_box::haversine_distance;
 pub use bounding_box::BoundingBox;
 pub use duration::PositiveDuration;
 pub use identifier::Identifier;
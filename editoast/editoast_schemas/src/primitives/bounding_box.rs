@@ -78,30 +78,33 @@ impl BoundingBox {
     /// assert_eq!(diagonal_length, 230908.62753622115);
     /// ```
     pub fn diagonal_length(&self) -> f64 {
-        // Earth's mean radius in meters
-        let r: f64 = 6_378_100.0;
+        haversine_distance(self.0, self.1)
+    }
+}
 
-        let a_lon = self.0 .0;
-        let a_lat = self.0 .1;
-        let b_lon = self.1 .0;
-        let b_lat = self.1 .1;
+/// Calculates the distance in meters between two `(longitude, latitude)` points
+/// using the Haversine formula.
+pub fn haversine_distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    // Earth's mean radius in meters
+    let r: f64 = 6_378_100.0;
 
-        // Calculate differences in longitude and latitude in radians
-        let d_lon: f64 = (b_lon - a_lon).to_radians();
-        let d_lat: f64 = (b_lat - a_lat).to_radians();
+    let (a_lon, a_lat) = a;
+    let (b_lon, b_lat) = b;
 
-        // Convert latitude to radians
-        let lat1: f64 = a_lat.to_radians();
-        let lat2: f64 = b_lat.to_radians();
+    // Calculate differences in longitude and latitude in radians
+    let d_lon: f64 = (b_lon - a_lon).to_radians();
+    let d_lat: f64 = (b_lat - a_lat).to_radians();
 
-        // Haversine formula
-        let a: f64 = ((d_lat / 2.0).sin()) * ((d_lat / 2.0).sin())
-            + ((d_lon / 2.0).sin()) * ((d_lon / 2.0).sin()) * (lat1.cos()) * (lat2.cos());
-        let c: f64 = 2.0 * ((a.sqrt()).atan2((1.0 - a).sqrt()));
+    // Convert latitude to radians
+    let lat1: f64 = a_lat.to_radians();
+    let lat2: f64 = b_lat.to_radians();
 
-        // Calculate diagonal length
-        r * c
-    }
+    // Haversine formula
+    let a: f64 = ((d_lat / 2.0).sin()) * ((d_lat / 2.0).sin())
+        + ((d_lon / 2.0).sin()) * ((d_lon / 2.0).sin()) * (lat1.cos()) * (lat2.cos());
+    let c: f64 = 2.0 * ((a.sqrt()).atan2((1.0 - a).sqrt()));
+
+    r * c
 }
 
 impl Default for BoundingBox {
@@ -28,6 +28,12 @@ pub use comfort::Comfort;
 mod train_schedule_base;
 pub use train_schedule_base::TrainScheduleBase;
 
+mod recurrence;
+pub use recurrence::Recurrence;
+
+mod operating_calendar;
+pub use operating_calendar::OperatingCalendar;
+
 mod allowance;
 pub use allowance::Allowance;
 pub use allowance::AllowanceDistribution;
@@ -48,6 +54,8 @@ editoast_common::schemas! {
     power_restriction_item::schemas(),
     distribution::schemas(),
     comfort::schemas(),
+    recurrence::schemas(),
+    operating_calendar::schemas(),
     // TODO TrainSchedule V1 (it will be removed)
     allowance::schemas(),
     rjs_power_restriction_range::schemas(),
@@ -0,0 +1,165 @@
+use chrono::Datelike;
+use chrono::NaiveDate;
+use serde::de::Error as SerdeError;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+editoast_common::schemas! {
+    OperatingCalendar,
+}
+
+/// The days (in UTC) on which a train runs
+///
+/// The train departs at the time of day of its start time on each running day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OperatingCalendar {
+    /// The train runs on some weekdays of a validity period
+    Period {
+        /// First day of the validity period, included
+        start_date: NaiveDate,
+        /// Last day of the validity period, included
+        end_date: NaiveDate,
+        /// Seven `0` or `1` characters, starting on Monday (e.g. `1111100` for working days)
+        #[schema(example = "1111100", pattern = "^[01]{7}$")]
+        weekdays: String,
+    },
+    /// The train runs on each day marked with a `1`
+    Bitmap {
+        /// The day of the first character of `days`
+        start_date: NaiveDate,
+        /// One `0` or `1` character per day
+        #[schema(example = "1101111", pattern = "^[01]*$")]
+        days: String,
+    },
+}
+
+impl<'de> Deserialize<'de> for OperatingCalendar {
+    fn deserialize<D>(deserializer: D) -> Result<OperatingCalendar, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+        enum Internal {
+            Period {
+                start_date: NaiveDate,
+                end_date: NaiveDate,
+                weekdays: String,
+            },
+            Bitmap {
+                start_date: NaiveDate,
+                days: String,
+            },
+        }
+
+        let is_mask = |mask: &str| mask.chars().all(|c| c == '0' || c == '1');
+        match Internal::deserialize(deserializer)? {
+            Internal::Period {
+                start_date,
+                end_date,
+                weekdays,
+            } => {
+                if end_date < start_date {
+                    return Err(SerdeError::custom(
+                        "The validity period ends before it starts",
+                    ));
+                }
+                if weekdays.len() != 7 || !is_mask(&weekdays) {
+                    return Err(SerdeError::custom(
+                        "The weekdays must be seven '0' or '1' characters",
+                    ));
+                }
+                Ok(OperatingCalendar::Period {
+                    start_date,
+                    end_date,
+                    weekdays,
+                })
+            }
+            Internal::Bitmap { start_date, days } => {
+                if !is_mask(&days) {
+                    return Err(SerdeError::custom("The days must be '0' or '1' characters"));
+                }
+                Ok(OperatingCalendar::Bitmap { start_date, days })
+            }
+        }
+    }
+}
+
+impl OperatingCalendar {
+    /// Whether the train runs on the given day
+    pub fn runs_on(&self, day: NaiveDate) -> bool {
+        match self {
+            OperatingCalendar::Period {
+                start_date,
+                end_date,
+                weekdays,
+            } => {
+                (*start_date..=*end_date).contains(&day)
+                    && weekdays.as_bytes()[day.weekday().num_days_from_monday() as usize] == b'1'
+            }
+            OperatingCalendar::Bitmap { start_date, days } => {
+                let offset = (day - *start_date).num_days();
+                usize::try_from(offset)
+                    .ok()
+                    .and_then(|offset| days.as_bytes().get(offset))
+                    .is_some_and(|&c| c == b'1')
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use serde_json::from_value;
+    use serde_json::json;
+
+    use super::OperatingCalendar;
+
+    fn running_days(calendar: &OperatingCalendar) -> Vec<u32> {
+        (1..=20)
+            .filter(|&day| calendar.runs_on(NaiveDate::from_ymd_opt(2025, 3, day).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn period_runs_on_weekdays() {
+        // From Monday 3rd to Sunday 16th, working days only
+        let calendar: OperatingCalendar = from_value(json!({
+            "type": "period",
+            "start_date": "2025-03-03",
+            "end_date": "2025-03-16",
+            "weekdays": "1111100",
+        }))
+        .unwrap();
+        assert_eq!(
+            running_days(&calendar),
+            vec![3, 4, 5, 6, 7, 10, 11, 12, 13, 14]
+        );
+    }
+
+    #[test]
+    fn bitmap_runs_on_marked_days() {
+        let calendar: OperatingCalendar = from_value(json!({
+            "type": "bitmap",
+            "start_date": "2025-03-10",
+            "days": "1011",
+        }))
+        .unwrap();
+        assert_eq!(running_days(&calendar), vec![10, 12, 13]);
+    }
+
+    #[test]
+    fn invalid_calendars_are_rejected() {
+        let calendars = [
+            json!({"type": "period", "start_date": "2025-03-10", "end_date": "2025-03-01", "weekdays": "1111111"}),
+            json!({"type": "period", "start_date": "2025-03-01", "end_date": "2025-03-10", "weekdays": "11111"}),
+            json!({"type": "bitmap", "start_date": "2025-03-01", "days": "10x1"}),
+        ];
+        for calendar in calendars {
+            assert!(from_value::<OperatingCalendar>(calendar).is_err());
+        }
+    }
+}
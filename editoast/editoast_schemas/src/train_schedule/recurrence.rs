@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use serde::de::Error as SerdeError;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::primitives::PositiveDuration;
+
+editoast_common::schemas! {
+    Recurrence,
+}
+
+/// How a train service repeats its base schedule
+///
+/// The first occurrence departs at the start time of the base schedule, the next ones every
+/// `interval` until `last_departure`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Recurrence {
+    /// Time between two consecutive departures, must be positive
+    #[schema(value_type = chrono::Duration)]
+    pub interval: PositiveDuration,
+    /// Latest departure of an occurrence, included
+    pub last_departure: DateTime<Utc>,
+    /// Days (in UTC) on which the service does not run
+    #[serde(default)]
+    pub exception_days: Vec<NaiveDate>,
+}
+
+impl<'de> Deserialize<'de> for Recurrence {
+    fn deserialize<D>(deserializer: D) -> Result<Recurrence, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Internal {
+            interval: PositiveDuration,
+            last_departure: DateTime<Utc>,
+            #[serde(default)]
+            exception_days: Vec<NaiveDate>,
+        }
+        let internal = Internal::deserialize(deserializer)?;
+
+        if internal.interval.num_milliseconds() == 0 {
+            return Err(SerdeError::custom(
+                "The recurrence interval must be positive",
+            ));
+        }
+
+        Ok(Recurrence {
+            interval: internal.interval,
+            last_departure: internal.last_departure,
+            exception_days: internal.exception_days,
+        })
+    }
+}
+
+impl Recurrence {
+    /// The departures of the occurrences, starting at `first_departure`
+    ///
+    /// Departures falling on an exception day are skipped, see [Recurrence::scheduled_departures].
+    pub fn departures(
+        &self,
+        first_departure: DateTime<Utc>,
+    ) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let exception_days: HashSet<_> = self.exception_days.iter().collect();
+        self.scheduled_departures(first_departure)
+            .filter(move |departure| !exception_days.contains(&departure.date_naive()))
+    }
+
+    /// The departures every interval from `first_departure` to the last departure, including
+    /// the ones falling on an exception day
+    ///
+    /// Yields nothing if the interval is not positive.
+    pub fn scheduled_departures(
+        &self,
+        first_departure: DateTime<Utc>,
+    ) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let interval = *self.interval;
+        std::iter::successors(Some(first_departure), move |departure| {
+            Some(*departure + interval)
+        })
+        .take_while(move |departure| {
+            interval > chrono::Duration::zero() && *departure <= self.last_departure
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono::Utc;
+    use serde_json::from_str;
+    use serde_json::json;
+
+    use super::Recurrence;
+
+    #[test]
+    fn departures_every_half_hour_with_exception_day() {
+        let recurrence: Recurrence = from_str(
+            &json!({
+                "interval": "PT30M",
+                "last_departure": "2025-01-02T01:00:00Z",
+                "exception_days": ["2025-01-01"]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let first_departure = Utc.with_ymd_and_hms(2024, 12, 31, 23, 0, 0).unwrap();
+        let departures: Vec<_> = recurrence.departures(first_departure).collect();
+
+        assert_eq!(
+            departures,
+            vec![
+                Utc.with_ymd_and_hms(2024, 12, 31, 23, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 12, 31, 23, 30, 0).unwrap(),
+                Utc.with_ymd_and_hms(2025, 1, 2, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2025, 1, 2, 0, 30, 0).unwrap(),
+                Utc.with_ymd_and_hms(2025, 1, 2, 1, 0, 0).unwrap(),
+            ]
+        );
+        // The 48 departures of the exception day are still scheduled
+        assert_eq!(recurrence.scheduled_departures(first_departure).count(), 53);
+    }
+
+    #[test]
+    fn deserialize_null_interval() {
+        let recurrence = json!({
+            "interval": "PT0S",
+            "last_departure": "2025-01-02T07:00:00Z",
+        });
+        assert!(from_str::<Recurrence>(&recurrence.to_string()).is_err());
+    }
+}
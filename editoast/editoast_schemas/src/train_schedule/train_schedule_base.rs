@@ -12,6 +12,7 @@ use utoipa::ToSchema;
 use super::Comfort;
 use super::Distribution;
 use super::Margins;
+use super::OperatingCalendar;
 use super::PathItem;
 use super::PowerRestrictionItem;
 use super::ScheduleItem;
@@ -50,6 +51,9 @@ pub struct TrainScheduleBase {
     #[schema(inline)]
     #[serde(default)]
     pub options: TrainScheduleOptions,
+    /// The days on which the train runs, the train only runs on the day of its start time if not set
+    #[serde(default)]
+    pub calendar: Option<OperatingCalendar>,
 }
 
 impl<'de> Deserialize<'de> for TrainScheduleBase {
@@ -81,6 +85,8 @@ impl<'de> Deserialize<'de> for TrainScheduleBase {
             power_restrictions: Vec<PowerRestrictionItem>,
             #[serde(default)]
             options: TrainScheduleOptions,
+            #[serde(default)]
+            calendar: Option<OperatingCalendar>,
         }
         let internal = Internal::deserialize(deserializer)?;
 
@@ -152,6 +158,7 @@ impl<'de> Deserialize<'de> for TrainScheduleBase {
             speed_limit_tag: internal.speed_limit_tag,
             power_restrictions: internal.power_restrictions,
             options: internal.options,
+            calendar: internal.calendar,
         })
     }
 }
@@ -0,0 +1,11 @@
+CREATE TABLE infra_edit (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    infra_id int8 NOT NULL REFERENCES infra(id) ON DELETE CASCADE,
+    version int8 NOT NULL,
+    author_id int8 REFERENCES authn_user(id) ON DELETE SET NULL,
+    created timestamptz NOT NULL DEFAULT NOW(),
+    operations jsonb NOT NULL,
+    inverse_operations jsonb NOT NULL,
+    reverted_edit_id int8 REFERENCES infra_edit(id) ON DELETE SET NULL,
+    UNIQUE (infra_id, version)
+);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS infra_validation_rule;
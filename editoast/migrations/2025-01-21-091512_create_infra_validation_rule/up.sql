@@ -0,0 +1,7 @@
+CREATE TABLE infra_validation_rule (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    infra_id int8 NOT NULL REFERENCES infra(id) ON DELETE CASCADE,
+    rule_id varchar(128) NOT NULL,
+    settings jsonb NOT NULL,
+    UNIQUE (infra_id, rule_id)
+);
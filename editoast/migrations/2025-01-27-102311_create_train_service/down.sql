@@ -0,0 +1,2 @@
+ALTER TABLE train_schedule DROP COLUMN IF EXISTS train_service_id;
+DROP TABLE IF EXISTS train_service;
@@ -0,0 +1,11 @@
+CREATE TABLE train_service (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    timetable_id int8 NOT NULL REFERENCES timetable(id) ON DELETE CASCADE,
+    base jsonb NOT NULL,
+    recurrence jsonb NOT NULL,
+    expanded boolean NOT NULL
+);
+
+ALTER TABLE train_schedule
+ADD COLUMN train_service_id int8 REFERENCES train_service(id) ON DELETE CASCADE;
+CREATE INDEX train_schedule_train_service_id_idx ON train_schedule(train_service_id);
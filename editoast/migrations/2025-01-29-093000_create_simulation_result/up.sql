@@ -0,0 +1,13 @@
+CREATE TABLE simulation_result (
+    hash varchar PRIMARY KEY,
+    infra_id int8 NOT NULL REFERENCES infra(id) ON DELETE CASCADE,
+    infra_version varchar(40) NOT NULL,
+    rolling_stock_name varchar(255) NOT NULL,
+    electrical_profile_set_id int8 REFERENCES electrical_profile_set(id) ON DELETE CASCADE,
+    result jsonb NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX simulation_result_infra_id_idx ON simulation_result(infra_id);
+CREATE INDEX simulation_result_rolling_stock_name_idx ON simulation_result(rolling_stock_name);
+CREATE INDEX simulation_result_electrical_profile_set_id_idx ON simulation_result(electrical_profile_set_id);
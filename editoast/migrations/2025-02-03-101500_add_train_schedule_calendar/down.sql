@@ -0,0 +1 @@
+ALTER TABLE train_schedule DROP COLUMN IF EXISTS calendar;
@@ -0,0 +1 @@
+ALTER TABLE train_schedule ADD COLUMN calendar jsonb;
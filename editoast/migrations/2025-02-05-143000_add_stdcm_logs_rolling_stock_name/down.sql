@@ -0,0 +1 @@
+ALTER TABLE stdcm_logs DROP COLUMN IF EXISTS rolling_stock_name;
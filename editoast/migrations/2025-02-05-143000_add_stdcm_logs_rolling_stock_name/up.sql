@@ -0,0 +1 @@
+ALTER TABLE stdcm_logs ADD COLUMN rolling_stock_name varchar(255);
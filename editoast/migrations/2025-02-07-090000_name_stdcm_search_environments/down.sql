@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS stdcm_search_environment_access;
+ALTER TABLE stdcm_search_environment DROP COLUMN IF EXISTS name;
@@ -0,0 +1,19 @@
+ALTER TABLE stdcm_search_environment ADD COLUMN name varchar(255);
+
+UPDATE stdcm_search_environment SET name = 'environment-' || id;
+UPDATE stdcm_search_environment SET name = 'default'
+WHERE id = (
+    SELECT id FROM stdcm_search_environment
+    ORDER BY search_window_end DESC, search_window_begin ASC
+    LIMIT 1
+);
+
+ALTER TABLE stdcm_search_environment ALTER COLUMN name SET NOT NULL;
+ALTER TABLE stdcm_search_environment ADD CONSTRAINT stdcm_search_environment_name_key UNIQUE (name);
+
+CREATE TABLE stdcm_search_environment_access (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    search_environment_id int8 NOT NULL REFERENCES stdcm_search_environment(id) ON DELETE CASCADE,
+    subject_id int8 NOT NULL REFERENCES authn_subject(id) ON DELETE CASCADE,
+    UNIQUE (search_environment_id, subject_id)
+);
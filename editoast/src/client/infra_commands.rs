@@ -1,11 +1,22 @@
-use std::{error::Error, fs::File, io::BufReader, path::PathBuf, sync::Arc};
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use clap::{Args, Subcommand};
+use clap::{Args, Subcommand, ValueEnum};
 use colored::Colorize as _;
 use editoast_models::{DbConnection, DbConnectionPoolV2};
-use editoast_schemas::infra::RailJson;
+use editoast_schemas::{infra::RailJson, primitives::BoundingBox};
 
-use crate::map::MapLayers;
+use crate::map::{MapLayers, MbTilesWriter, PmTilesWriter, Tile, TileArchive, TilesetMetadata};
+use crate::models::infra::geo_export::{
+    export_layer_name, exported_object_types, into_feature_collection, write_geopackage,
+};
+use crate::models::layers::geo_json_and_data::{create_and_fill_mvt_tile, GeoJsonAndData};
 use crate::models::prelude::*;
 use crate::{infra_cache::InfraCache, models::Infra, views::infra::InfraApiError, CliError};
 use crate::{map, ValkeyClient};
@@ -16,8 +27,11 @@ use super::ValkeyConfig;
 pub enum InfraCommands {
     Clone(InfraCloneArgs),
     Clear(ClearArgs),
+    Diff(DiffArgs),
     Generate(GenerateArgs),
     ImportRailjson(ImportRailjsonArgs),
+    ExportTiles(ExportTilesArgs),
+    ExportGeo(ExportGeoArgs),
 }
 
 #[derive(Args, Debug, Clone)]
@@ -36,6 +50,30 @@ pub struct ClearArgs {
     infra_ids: Vec<u64>,
 }
 
+#[derive(Args, Debug, Clone)]
+#[command(
+    about,
+    long_about = "Compare the objects of two infras, or two versions of an infra, listing the changes leading from the first to the second"
+)]
+pub struct DiffArgs {
+    /// The infra to compare from
+    base_id: u64,
+    /// The infra to compare to
+    other_id: u64,
+    /// A previous version of the first infra, rebuilt from its edition history
+    #[arg(long)]
+    base_version: Option<i64>,
+    /// A previous version of the second infra, rebuilt from its edition history
+    #[arg(long)]
+    other_version: Option<i64>,
+    /// The output file path (defaults to the standard output)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Output the diff as an edition batch, which can be applied to another infra
+    #[arg(long)]
+    operations: bool,
+}
+
 #[derive(Args, Debug)]
 #[command(about, long_about = "Refresh infra generated data")]
 pub struct GenerateArgs {
@@ -58,6 +96,61 @@ pub struct ImportRailjsonArgs {
     generate: bool,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum TileArchiveFormat {
+    Mbtiles,
+    Pmtiles,
+}
+
+impl TileArchiveFormat {
+    /// Guesses the format from the extension of the archive
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::from_str(extension, true).ok()
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+#[command(
+    about,
+    long_about = "Pre-render the map layers of an infra into an MBTiles or PMTiles archive"
+)]
+pub struct ExportTilesArgs {
+    /// Infrastructure ID
+    infra_id: u64,
+    /// The archive file path
+    output: PathBuf,
+    /// The lowest zoom level to render
+    #[arg(long, default_value_t = 5)]
+    min_zoom: u64,
+    /// The highest zoom level to render
+    #[arg(long, default_value_t = 16)]
+    max_zoom: u64,
+    /// The archive format, guessed from the output file extension if not set
+    #[arg(long, value_enum)]
+    format: Option<TileArchiveFormat>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum GeoExportFormat {
+    Geojson,
+    Geopackage,
+}
+
+#[derive(Args, Debug, Clone)]
+#[command(
+    about,
+    long_about = "Export the objects of an infra with their geometry, as GeoJSON or GeoPackage"
+)]
+pub struct ExportGeoArgs {
+    /// Infrastructure ID
+    infra_id: u64,
+    /// The GeoPackage file path, or the directory to write a GeoJSON file per object type into
+    output: PathBuf,
+    #[arg(long, value_enum, default_value_t = GeoExportFormat::Geopackage)]
+    format: GeoExportFormat,
+}
+
 pub async fn clone_infra(
     infra_args: InfraCloneArgs,
     db_pool: Arc<DbConnectionPoolV2>,
@@ -130,6 +223,57 @@ pub async fn import_railjson(
     Ok(())
 }
 
+/// Run the diff subcommand
+/// This command writes the differences between two infras, or two versions of an infra, as JSON
+pub async fn diff_infras(
+    args: DiffArgs,
+    db_pool: Arc<DbConnectionPoolV2>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let ids = [args.base_id, args.other_id];
+    let conn = &mut db_pool.get().await?;
+    let infras = batch_retrieve_infras(conn, &ids).await?;
+    let infra = |infra_id: u64| {
+        infras
+            .iter()
+            .find(|infra| infra.id == infra_id as i64)
+            .expect("the infras were retrieved")
+    };
+
+    let diff = infra(args.base_id)
+        .diff(
+            conn,
+            args.base_version,
+            infra(args.other_id),
+            args.other_version,
+        )
+        .await?;
+    let summary = format!(
+        "{} added, {} removed, {} modified",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.modified.len()
+    );
+    let output = if args.operations {
+        serde_json::to_value(diff.into_operations())?
+    } else {
+        serde_json::to_value(diff)?
+    };
+
+    match args.output {
+        Some(path) => {
+            serde_json::to_writer_pretty(File::create(&path)?, &output)?;
+            println!(
+                "✅ Diff between infras {} and {} ({summary}) written to {}",
+                args.base_id,
+                args.other_id,
+                path.to_string_lossy()
+            );
+        }
+        None => println!("{}", serde_json::to_string_pretty(&output)?),
+    }
+    Ok(())
+}
+
 /// Run the clear subcommand
 /// This command clear all generated data for the given infra
 pub async fn clear_infra(
@@ -206,6 +350,196 @@ pub async fn generate_infra(
     Ok(())
 }
 
+/// Run the export-tiles subcommand
+/// This command renders every view of every map layer of the infra, for each tile covering its
+/// track sections, and writes them into an archive
+///
+/// The layers of a tile are merged into a single MVT. They are named after their map layer, or
+/// after their map layer and view for map layers with several views.
+pub async fn export_tiles(
+    args: ExportTilesArgs,
+    db_pool: Arc<DbConnectionPoolV2>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(format) = args
+        .format
+        .or_else(|| TileArchiveFormat::from_path(&args.output))
+    else {
+        let error = CliError::new(
+            1,
+            "❌ Unknown archive format, use a .mbtiles or .pmtiles extension or set --format",
+        );
+        return Err(Box::new(error));
+    };
+    if args.min_zoom > args.max_zoom || args.max_zoom > 24 {
+        let error = CliError::new(
+            1,
+            format!(
+                "❌ Invalid zoom range {}-{}, zoom levels must be ordered and at most 24",
+                args.min_zoom, args.max_zoom
+            ),
+        );
+        return Err(Box::new(error));
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let infra = batch_retrieve_infras(conn, &[args.infra_id])
+        .await?
+        .pop()
+        .expect("the infra was retrieved");
+    if infra.generated_version.as_ref() != Some(&infra.version) {
+        println!(
+            "🚨 Infra {}[{}] generated data is not up to date, you may want to run {} first.",
+            infra.name.clone().bold(),
+            infra.id,
+            "editoast infra generate".bold()
+        );
+    }
+    let infra_cache = InfraCache::load(conn, &infra).await?;
+    let track_extents: Vec<BoundingBox> = infra_cache
+        .track_sections()
+        .values()
+        .map(|track| track.unwrap_track_section().bbox_geo.clone())
+        .collect();
+    let mut bounds = BoundingBox::default();
+    for extent in track_extents.iter() {
+        bounds.union(extent);
+    }
+
+    let map_layers = MapLayers::default();
+    let mut layer_slugs: Vec<_> = map_layers.layers.keys().collect();
+    layer_slugs.sort();
+    let mut views = vec![];
+    for layer_slug in layer_slugs {
+        let layer = &map_layers.layers[layer_slug];
+        let mut view_slugs: Vec<_> = layer.views.keys().collect();
+        view_slugs.sort();
+        for view_slug in view_slugs.iter() {
+            let name = if view_slugs.len() == 1 {
+                layer_slug.clone()
+            } else {
+                format!("{layer_slug}_{view_slug}")
+            };
+            views.push((name, layer, &layer.views[*view_slug]));
+        }
+    }
+
+    println!(
+        "🍞 Exporting the tiles of infra {}[{}] from zoom {} to {}",
+        infra.name.clone().bold(),
+        infra.id,
+        args.min_zoom,
+        args.max_zoom
+    );
+    let mut archive = match format {
+        TileArchiveFormat::Mbtiles => TileArchive::MbTiles(MbTilesWriter::create(&args.output)?),
+        TileArchiveFormat::Pmtiles => TileArchive::PmTiles(PmTilesWriter::create(&args.output)?),
+    };
+    for z in args.min_zoom..=args.max_zoom {
+        let tiles: BTreeSet<(u64, u64)> = track_extents
+            .iter()
+            .flat_map(|extent| Tile::covering(extent, z))
+            .map(|tile| (tile.x, tile.y))
+            .collect();
+        let mut tile_count = 0;
+        for (x, y) in tiles {
+            // Tiles are sequences of layers, concatenated tiles form a single valid tile
+            let mut mvt = vec![];
+            for (name, layer, view) in views.iter() {
+                let records =
+                    GeoJsonAndData::get_records(conn, layer, view, infra.id, (x, y, z)).await?;
+                if !records.is_empty() {
+                    let bytes =
+                        create_and_fill_mvt_tile(name, records)
+                            .to_bytes()
+                            .map_err(|error| {
+                                CliError::new(
+                                    1,
+                                    format!(
+                                        "❌ Could not encode tile {z}/{x}/{y} of {name}: {error}"
+                                    ),
+                                )
+                            })?;
+                    mvt.extend(bytes);
+                }
+            }
+            if !mvt.is_empty() {
+                archive.add_tile(&Tile { x, y, z }, &mvt)?;
+                tile_count += 1;
+            }
+        }
+        println!("  Zoom {z}: {tile_count} tiles");
+    }
+
+    let mut attributions: Vec<_> = views
+        .iter()
+        .filter_map(|(_, layer, _)| layer.attribution.clone())
+        .filter(|attribution| !attribution.is_empty())
+        .collect();
+    attributions.sort();
+    attributions.dedup();
+    archive.finish(&TilesetMetadata {
+        name: infra.name.clone(),
+        attribution: attributions.join(", "),
+        bounds,
+        min_zoom: args.min_zoom,
+        max_zoom: args.max_zoom,
+        vector_layers: views.into_iter().map(|(name, _, _)| name).collect(),
+    })?;
+    println!(
+        "✅ Tiles of infra {}[{}] written to {}",
+        infra.name.bold(),
+        infra.id,
+        args.output.to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Run the export-geo subcommand
+/// This command exports every object type having a generated geometry
+pub async fn export_geo(
+    args: ExportGeoArgs,
+    db_pool: Arc<DbConnectionPoolV2>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let conn = &mut db_pool.get().await?;
+    let infra = batch_retrieve_infras(conn, &[args.infra_id])
+        .await?
+        .pop()
+        .expect("the infra was retrieved");
+    if infra.generated_version.as_ref() != Some(&infra.version) {
+        println!(
+            "🚨 Infra {}[{}] generated data is not up to date, you may want to run {} first.",
+            infra.name.clone().bold(),
+            infra.id,
+            "editoast infra generate".bold()
+        );
+    }
+
+    let mut layers = vec![];
+    for object_type in exported_object_types() {
+        let objects = infra.get_objects_with_geometry(conn, object_type).await?;
+        let layer_name = export_layer_name(object_type).expect("exported types have a layer");
+        println!("  {layer_name}: {} objects", objects.len());
+        layers.push((layer_name, into_feature_collection(objects)));
+    }
+    match args.format {
+        GeoExportFormat::Geojson => {
+            std::fs::create_dir_all(&args.output)?;
+            for (layer_name, collection) in layers {
+                let path = args.output.join(format!("{layer_name}.geojson"));
+                serde_json::to_writer(File::create(path)?, &collection)?;
+            }
+        }
+        GeoExportFormat::Geopackage => std::fs::write(&args.output, write_geopackage(layers)?)?,
+    }
+    println!(
+        "✅ Objects of infra {}[{}] exported to {}",
+        infra.name.bold(),
+        infra.id,
+        args.output.to_string_lossy()
+    );
+    Ok(())
+}
+
 async fn build_valkey_pool_and_invalidate_all_cache(
     valkey_config: ValkeyConfig,
     infra_id: i64,
@@ -305,4 +639,43 @@ mod tests {
         // THEN
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn tile_archive_format_from_path() {
+        assert_eq!(
+            TileArchiveFormat::from_path(Path::new("export/infra.PMTiles")),
+            Some(TileArchiveFormat::Pmtiles)
+        );
+        assert_eq!(
+            TileArchiveFormat::from_path(Path::new("infra.mbtiles")),
+            Some(TileArchiveFormat::Mbtiles)
+        );
+        assert_eq!(TileArchiveFormat::from_path(Path::new("infra.zip")), None);
+    }
+
+    #[rstest::rstest]
+    async fn diff_infras_ko_infra_not_found() {
+        // GIVEN
+        let args = DiffArgs {
+            base_id: 123456789,
+            other_id: 123456789,
+            base_version: None,
+            other_version: None,
+            output: None,
+            operations: false,
+        };
+
+        // WHEN
+        let result = diff_infras(args, DbConnectionPoolV2::for_tests().into()).await;
+
+        // THEN
+        assert_eq!(
+            result
+                .unwrap_err()
+                .downcast_ref::<CliError>()
+                .unwrap()
+                .exit_code,
+            1
+        );
+    }
 }
@@ -7,6 +7,9 @@ mod postgres_config;
 pub mod roles;
 pub mod runserver;
 pub mod search_commands;
+pub mod simulation_store_commands;
+mod simulation_store_config;
+pub mod stdcm_commands;
 pub mod stdcm_search_env_commands;
 mod telemetry_config;
 pub mod timetables_commands;
@@ -29,6 +32,9 @@ use roles::RolesCommand;
 use runserver::CoreArgs;
 use runserver::RunserverArgs;
 use search_commands::SearchCommands;
+use simulation_store_commands::SimulationStoreCommands;
+pub use simulation_store_config::SimulationStoreConfig;
+use stdcm_commands::StdcmCommands;
 use stdcm_search_env_commands::StdcmSearchEnvCommands;
 pub use telemetry_config::TelemetryConfig;
 pub use telemetry_config::TelemetryKind;
@@ -49,6 +55,8 @@ pub struct Client {
     #[command(flatten)]
     pub valkey_config: ValkeyConfig,
     #[command(flatten)]
+    pub simulation_store_config: SimulationStoreConfig,
+    #[command(flatten)]
     pub telemetry_config: TelemetryConfig,
     #[arg(long, env, value_enum, default_value_t = Color::Auto)]
     pub color: Color,
@@ -90,6 +98,8 @@ pub enum Commands {
         long_about = "STDCM search environment management commands"
     )]
     STDCMSearchEnv(StdcmSearchEnvCommands),
+    #[command(subcommand, about, long_about = "STDCM related commands")]
+    Stdcm(StdcmCommands),
     #[command(subcommand, about, long_about = "Roles related commands")]
     Roles(RolesCommand),
     #[command(subcommand, about, long_about = "Group related commands")]
@@ -98,6 +108,12 @@ pub enum Commands {
     User(UserCommand),
     #[command(about, long_about = "Healthcheck")]
     Healthcheck(CoreArgs),
+    #[command(
+        subcommand,
+        about,
+        long_about = "Persistent simulation result store commands"
+    )]
+    SimulationStore(SimulationStoreCommands),
 }
 
 /// Prints the OpenApi to stdout
@@ -1,10 +1,17 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
 use chrono::Duration;
 use clap::Args;
 use url::Url;
 
+use crate::core::http_client;
+use crate::core::mq_client;
+use crate::core::CoreClient;
+use crate::generated_data::rules::ValidationRulesConfig;
 use crate::views;
 
-use super::{PostgresConfig, ValkeyConfig};
+use super::{PostgresConfig, SimulationStoreConfig, ValkeyConfig};
 
 #[derive(Args, Debug, Clone)]
 struct MapLayersConfig {
@@ -23,6 +30,36 @@ pub struct CoreArgs {
     pub(super) core_single_worker: bool,
     #[clap(long, env = "CORE_CLIENT_CHANNELS_SIZE", default_value_t = 8)]
     pub(super) core_client_channels_size: usize,
+    /// The URL of a core worker to send requests to over HTTP, instead of the message queue
+    #[clap(long, env = "EDITOAST_CORE_URL")]
+    pub(super) core_url: Option<Url>,
+    /// How many times a request sent over HTTP is retried when the connection to the core is lost
+    #[clap(long, env = "EDITOAST_CORE_RETRIES", default_value_t = 3)]
+    pub(super) core_retries: u8,
+}
+
+impl CoreArgs {
+    /// Build a client to the core from the command line arguments
+    pub(super) async fn core_client(self) -> anyhow::Result<CoreClient> {
+        let client = match self.core_url {
+            Some(url) => CoreClient::new_http(http_client::Options {
+                url,
+                timeout: self.core_timeout,
+                retries: self.core_retries,
+            })?,
+            None => {
+                CoreClient::new_mq(mq_client::Options {
+                    uri: self.mq_url,
+                    worker_pool_identifier: String::from("core"),
+                    timeout: self.core_timeout,
+                    single_worker: self.core_single_worker,
+                    num_channels: self.core_client_channels_size,
+                })
+                .await?
+            }
+        };
+        Ok(client)
+    }
 }
 
 #[derive(Args, Debug)]
@@ -52,6 +89,9 @@ pub struct RunserverArgs {
     /// The timeout to use when performing the healthcheck, in milliseconds
     #[clap(long, env = "EDITOAST_HEALTH_CHECK_TIMEOUT_MS", default_value_t = 1000)]
     health_check_timeout_ms: u64,
+    /// A YAML file enabling, disabling or changing the severity of the infra validation rules
+    #[clap(long, env = "EDITOAST_VALIDATION_RULES")]
+    validation_rules: Option<PathBuf>,
 }
 
 /// Create and run the server
@@ -66,15 +106,24 @@ pub async fn runserver(
                 core_timeout,
                 core_single_worker,
                 core_client_channels_size,
+                core_url,
+                core_retries,
             },
         disable_authorization,
         enable_stdcm_logging,
         osrdyne_api_url,
         health_check_timeout_ms,
+        validation_rules,
     }: RunserverArgs,
     postgres: PostgresConfig,
     valkey: ValkeyConfig,
+    simulation_store: SimulationStoreConfig,
 ) -> anyhow::Result<()> {
+    let validation_rules = match validation_rules {
+        Some(path) => ValidationRulesConfig::load(&path)
+            .with_context(|| format!("failed to load validation rules from {}", path.display()))?,
+        None => ValidationRulesConfig::default(),
+    };
     let config = views::ServerConfig {
         port,
         address,
@@ -90,9 +139,13 @@ pub async fn runserver(
                 timeout: Duration::seconds(core_timeout as i64),
                 single_worker: core_single_worker,
                 num_channels: core_client_channels_size,
+                url: core_url,
+                retries: core_retries,
             },
         },
         valkey_config: valkey.into(),
+        validation_rules,
+        simulation_store: simulation_store.into(),
     };
 
     let server = views::Server::new(config).await?;
@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use clap::Args;
+use clap::Subcommand;
+use editoast_models::DbConnectionPoolV2;
+
+use super::runserver::CoreArgs;
+use super::SimulationStoreConfig;
+use super::ValkeyConfig;
+use crate::client::simulation_store_config::SimulationStoreKind;
+use crate::core::simulation::SimulationResponse;
+use crate::models::prelude::*;
+use crate::models::timetable::TimetableWithTrains;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::Infra;
+use crate::simulation_store::Invalidation;
+use crate::simulation_store::SimulationResultStore;
+use crate::views::train_schedule::train_simulation_batch;
+use crate::CliError;
+use crate::ValkeyClient;
+
+#[derive(Subcommand, Debug)]
+pub enum SimulationStoreCommands {
+    Warm(WarmSimulationStoreArgs),
+    Invalidate(InvalidateSimulationStoreArgs),
+}
+
+#[derive(Args, Debug)]
+#[command(
+    about,
+    long_about = "Simulate the trains of a timetable and persist the results"
+)]
+pub struct WarmSimulationStoreArgs {
+    /// The timetable whose trains are simulated
+    timetable_id: i64,
+    /// The infra the trains are simulated on
+    infra_id: i64,
+    /// The electrical profile set used by the simulations
+    #[arg(long)]
+    electrical_profile_set_id: Option<i64>,
+    #[command(flatten)]
+    core: CoreArgs,
+}
+
+#[derive(Args, Debug)]
+#[command(about, long_about = "Discard persisted simulation results")]
+#[group(required = true, multiple = false)]
+pub struct InvalidateSimulationStoreArgs {
+    /// Discard the results computed on any version of this infra
+    #[arg(long)]
+    infra_id: Option<i64>,
+    /// Discard the results computed with this rolling stock
+    #[arg(long)]
+    rolling_stock_name: Option<String>,
+    /// Discard the results computed with this electrical profile set
+    #[arg(long)]
+    electrical_profile_set_id: Option<i64>,
+}
+
+fn build_store(
+    config: SimulationStoreConfig,
+    db_pool: Arc<DbConnectionPoolV2>,
+) -> Result<Arc<dyn SimulationResultStore>, CliError> {
+    if let SimulationStoreKind::None = config.simulation_store {
+        return Err(CliError::new(
+            1,
+            "❌ No simulation store is configured, see --simulation-store",
+        ));
+    }
+    let config: crate::simulation_store::SimulationStoreConfig = config.into();
+    Ok(config.build(db_pool))
+}
+
+pub async fn warm_simulation_store(
+    args: WarmSimulationStoreArgs,
+    db_pool: Arc<DbConnectionPoolV2>,
+    simulation_store_config: SimulationStoreConfig,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let simulation_store = build_store(simulation_store_config, db_pool.clone())?;
+    let conn = &mut db_pool.get().await?;
+
+    let Some(timetable) = TimetableWithTrains::retrieve(conn, args.timetable_id).await? else {
+        let error = CliError::new(
+            1,
+            format!("❌ Timetable not found, id: {0}", args.timetable_id),
+        );
+        return Err(Box::new(error));
+    };
+    let Some(infra) = Infra::retrieve(conn, args.infra_id).await? else {
+        let error = CliError::new(1, format!("❌ Infra not found, id: {0}", args.infra_id));
+        return Err(Box::new(error));
+    };
+    let (train_schedules, _): (Vec<_>, _) =
+        TrainSchedule::retrieve_batch(conn, timetable.train_ids).await?;
+
+    // Valkey is bypassed, otherwise the results it still caches would never reach the store
+    let valkey = ValkeyClient::new(
+        ValkeyConfig {
+            no_cache: true,
+            ..Default::default()
+        }
+        .into(),
+    )?;
+    let core_client = args.core.core_client().await?;
+    let simulations = train_simulation_batch(
+        conn,
+        valkey.into(),
+        simulation_store.as_ref(),
+        core_client.into(),
+        &train_schedules,
+        &infra,
+        args.electrical_profile_set_id,
+    )
+    .await?;
+
+    let nb_stored = simulations
+        .iter()
+        .filter(|(simulation, _)| matches!(simulation, SimulationResponse::Success { .. }))
+        .count();
+    println!(
+        "✅ {nb_stored} of the {} trains of timetable {} are stored",
+        train_schedules.len(),
+        args.timetable_id
+    );
+    Ok(())
+}
+
+pub async fn invalidate_simulation_store(
+    args: InvalidateSimulationStoreArgs,
+    db_pool: Arc<DbConnectionPoolV2>,
+    simulation_store_config: SimulationStoreConfig,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let simulation_store = build_store(simulation_store_config, db_pool)?;
+    let invalidation = match args {
+        InvalidateSimulationStoreArgs {
+            infra_id: Some(infra_id),
+            ..
+        } => Invalidation::Infra { infra_id },
+        InvalidateSimulationStoreArgs {
+            rolling_stock_name: Some(name),
+            ..
+        } => Invalidation::RollingStock { name },
+        InvalidateSimulationStoreArgs {
+            electrical_profile_set_id: Some(electrical_profile_set_id),
+            ..
+        } => Invalidation::ElectricalProfileSet {
+            electrical_profile_set_id,
+        },
+        _ => unreachable!("clap requires one of the arguments"),
+    };
+    let deleted = simulation_store.invalidate(&invalidation).await?;
+    println!("✅ {deleted} simulation results discarded");
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use clap::ValueEnum;
+use derivative::Derivative;
+
+use crate::simulation_store;
+
+#[derive(ValueEnum, Debug, Default, Clone, Copy)]
+pub enum SimulationStoreKind {
+    /// Only cache simulation results in Valkey
+    #[default]
+    None,
+    /// Store simulation results in the database
+    Postgres,
+    /// Store simulation results as files in `--simulation-store-path`
+    Filesystem,
+}
+
+#[derive(Args, Debug, Derivative, Clone)]
+#[derivative(Default)]
+pub struct SimulationStoreConfig {
+    /// Where simulation results are kept when Valkey evicts them
+    #[clap(long, env = "EDITOAST_SIMULATION_STORE", value_enum, default_value_t = SimulationStoreKind::None)]
+    pub simulation_store: SimulationStoreKind,
+    /// The directory of the filesystem simulation store
+    #[derivative(Default(value = r#"PathBuf::from("simulation_store")"#))]
+    #[clap(
+        long,
+        env = "EDITOAST_SIMULATION_STORE_PATH",
+        default_value = "simulation_store"
+    )]
+    pub simulation_store_path: PathBuf,
+}
+
+impl From<SimulationStoreConfig> for simulation_store::SimulationStoreConfig {
+    fn from(
+        SimulationStoreConfig {
+            simulation_store,
+            simulation_store_path,
+        }: SimulationStoreConfig,
+    ) -> Self {
+        match simulation_store {
+            SimulationStoreKind::None => Self::Disabled,
+            SimulationStoreKind::Postgres => Self::Postgres,
+            SimulationStoreKind::Filesystem => Self::Filesystem {
+                root: simulation_store_path,
+            },
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::DateTime;
+use chrono::Utc;
+use clap::Args;
+use clap::Subcommand;
+use editoast_models::DbConnectionPoolV2;
+use itertools::Itertools;
+use serde::Serialize;
+
+use super::runserver::CoreArgs;
+use super::SimulationStoreConfig;
+use super::ValkeyConfig;
+use crate::core::simulation::SimulationResponse;
+use crate::core::stdcm::Response;
+use crate::core::AsCoreRequest;
+use crate::models::prelude::*;
+use crate::models::stdcm_log::StdcmLog;
+use crate::models::Infra;
+use crate::views::timetable::stdcm::batch::process_batch;
+use crate::views::timetable::stdcm::batch::StdcmBatchForm;
+use crate::views::timetable::stdcm::batch::StdcmBatchResult;
+use crate::views::Authentication;
+use crate::CliError;
+use crate::ValkeyClient;
+
+#[derive(Subcommand, Debug)]
+pub enum StdcmCommands {
+    Batch(StdcmBatchArgs),
+    Replay(StdcmReplayArgs),
+}
+
+#[derive(Args, Debug)]
+#[command(
+    about,
+    long_about = "Process STDCM requests in order, each found path being taken into account by the next ones"
+)]
+pub struct StdcmBatchArgs {
+    /// The timetable whose trains the paths are searched among
+    timetable_id: i64,
+    /// The infra the paths are searched on
+    infra_id: i64,
+    /// A JSON file holding the list of STDCM requests
+    path: PathBuf,
+    /// Create a train schedule in this timetable for each found path
+    #[arg(long)]
+    target_timetable_id: Option<i64>,
+    /// The created train schedules are named after this prefix and the position of their request
+    #[arg(long, default_value = "STDCM")]
+    train_name_prefix: String,
+    /// A label of the created train schedules
+    #[arg(long = "label")]
+    labels: Vec<String>,
+    /// Write the JSON report of the batch to this file
+    #[arg(long)]
+    report: Option<PathBuf>,
+    #[command(flatten)]
+    core: CoreArgs,
+}
+
+#[derive(Args, Debug)]
+#[command(
+    about,
+    long_about = "Replay logged STDCM requests against the current core and report the changed outcomes"
+)]
+pub struct StdcmReplayArgs {
+    /// Replay the logs with these ids
+    #[arg(
+        long,
+        value_delimiter = ',',
+        required_unless_present = "from",
+        conflicts_with = "from"
+    )]
+    ids: Vec<i64>,
+    /// Replay the logs created from this time
+    #[arg(long, requires = "to")]
+    from: Option<DateTime<Utc>>,
+    /// Replay the logs created until this time
+    #[arg(long, requires = "from")]
+    to: Option<DateTime<Utc>>,
+    /// Run time differences up to this duration, in ms, are ignored
+    #[arg(long, default_value_t = 1000)]
+    run_time_tolerance: u64,
+    /// Write the JSON report of the changed outcomes to this file
+    #[arg(long)]
+    report: Option<PathBuf>,
+    #[command(flatten)]
+    core: CoreArgs,
+}
+
+pub async fn stdcm_batch(
+    args: StdcmBatchArgs,
+    db_pool: Arc<DbConnectionPoolV2>,
+    valkey_config: ValkeyConfig,
+    simulation_store_config: SimulationStoreConfig,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let requests = serde_json::from_reader(BufReader::new(File::open(&args.path)?))?;
+    let Some(infra) = Infra::retrieve(&mut db_pool.get().await?, args.infra_id).await? else {
+        let error = CliError::new(1, format!("❌ Infra not found, id: {0}", args.infra_id));
+        return Err(Box::new(error));
+    };
+
+    let valkey = ValkeyClient::new(valkey_config.into())?;
+    let simulation_store =
+        crate::simulation_store::SimulationStoreConfig::from(simulation_store_config)
+            .build(db_pool.clone());
+    let core_client = args.core.core_client().await?;
+    let report = process_batch(
+        db_pool,
+        valkey.into(),
+        simulation_store.as_ref(),
+        core_client.into(),
+        // The CLI is trusted with every search environment
+        &Authentication::SkipAuthorization,
+        args.timetable_id,
+        &infra,
+        StdcmBatchForm {
+            requests,
+            target_timetable_id: args.target_timetable_id,
+            train_name_prefix: args.train_name_prefix,
+            labels: args.labels,
+        },
+    )
+    .await?;
+
+    for (index, result) in report.results.iter().enumerate() {
+        match result {
+            StdcmBatchResult::Success {
+                departure_time,
+                train_schedule_id: Some(train_schedule_id),
+                ..
+            } => println!(
+                "✅ Request {}: departing at {departure_time}, train schedule {train_schedule_id}",
+                index + 1
+            ),
+            StdcmBatchResult::Success { departure_time, .. } => {
+                println!("✅ Request {}: departing at {departure_time}", index + 1)
+            }
+            StdcmBatchResult::PathNotFound => println!("❌ Request {}: no path found", index + 1),
+            StdcmBatchResult::PreprocessingSimulationError { .. } => {
+                println!("❌ Request {}: the train could not be simulated", index + 1)
+            }
+        }
+    }
+    println!(
+        "🚆 A path was found for {} of the {} requests",
+        report.success_count,
+        report.results.len()
+    );
+    if let Some(path) = args.report {
+        serde_json::to_writer_pretty(File::create(path)?, &report)?;
+    }
+    Ok(())
+}
+
+/// How the outcome of a logged STDCM request changed when replayed
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+enum OutcomeChange {
+    DepartureMoved {
+        logged: DateTime<Utc>,
+        replayed: DateTime<Utc>,
+    },
+    /// Run times in ms
+    RunTimeChanged {
+        logged: u64,
+        replayed: u64,
+    },
+    SuccessBecameFailure,
+    FailureBecameSuccess,
+    ReplayFailed {
+        error: String,
+    },
+}
+
+impl std::fmt::Display for OutcomeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutcomeChange::DepartureMoved { logged, replayed } => {
+                write!(f, "departure moved from {logged} to {replayed}")
+            }
+            OutcomeChange::RunTimeChanged { logged, replayed } => write!(
+                f,
+                "run time changed from {}s to {}s",
+                *logged as f64 / 1000.,
+                *replayed as f64 / 1000.
+            ),
+            OutcomeChange::SuccessBecameFailure => write!(f, "no path is found anymore"),
+            OutcomeChange::FailureBecameSuccess => write!(f, "a path is found now"),
+            OutcomeChange::ReplayFailed { error } => write!(f, "the replay failed: {error}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ReplayedStdcmLog {
+    stdcm_log_id: i64,
+    trace_id: Option<String>,
+    changes: Vec<OutcomeChange>,
+}
+
+#[derive(Debug, Serialize)]
+struct StdcmReplayReport {
+    replayed_count: usize,
+    /// Only the logs whose outcome changed
+    changed: Vec<ReplayedStdcmLog>,
+}
+
+/// The run time of a found path, in ms
+fn run_time(simulation: &SimulationResponse) -> Option<u64> {
+    match simulation {
+        SimulationResponse::Success { final_output, .. } => {
+            final_output.report_train.times.last().copied()
+        }
+        _ => None,
+    }
+}
+
+/// Compare the logged outcome of an STDCM request to its replayed outcome
+///
+/// Run time differences up to `run_time_tolerance` ms are ignored.
+fn compare_outcomes(
+    logged: &Response,
+    replayed: &Response,
+    run_time_tolerance: u64,
+) -> Vec<OutcomeChange> {
+    match (logged, replayed) {
+        (
+            Response::Success {
+                simulation: logged_simulation,
+                departure_time: logged_departure_time,
+                ..
+            },
+            Response::Success {
+                simulation: replayed_simulation,
+                departure_time: replayed_departure_time,
+                ..
+            },
+        ) => {
+            let mut changes = vec![];
+            if logged_departure_time != replayed_departure_time {
+                changes.push(OutcomeChange::DepartureMoved {
+                    logged: *logged_departure_time,
+                    replayed: *replayed_departure_time,
+                });
+            }
+            if let (Some(logged), Some(replayed)) =
+                (run_time(logged_simulation), run_time(replayed_simulation))
+            {
+                if logged.abs_diff(replayed) > run_time_tolerance {
+                    changes.push(OutcomeChange::RunTimeChanged { logged, replayed });
+                }
+            }
+            changes
+        }
+        (Response::Success { .. }, _) => vec![OutcomeChange::SuccessBecameFailure],
+        (_, Response::Success { .. }) => vec![OutcomeChange::FailureBecameSuccess],
+        _ => vec![],
+    }
+}
+
+pub async fn stdcm_replay(
+    args: StdcmReplayArgs,
+    db_pool: Arc<DbConnectionPoolV2>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let conn = &mut db_pool.get().await?;
+    let stdcm_logs = match (args.from, args.to) {
+        (Some(from), Some(to)) => StdcmLog::created_between(conn, from, to).await?,
+        _ => {
+            let (mut stdcm_logs, missing): (Vec<StdcmLog>, _) =
+                StdcmLog::retrieve_batch(conn, args.ids).await?;
+            for stdcm_log_id in missing.into_iter().sorted() {
+                println!("🚨 STDCM log not found, id: {stdcm_log_id}");
+            }
+            stdcm_logs.sort_by_key(|stdcm_log| stdcm_log.id);
+            stdcm_logs
+        }
+    };
+
+    let core_client = args.core.core_client().await?;
+    let mut changed = vec![];
+    for stdcm_log in &stdcm_logs {
+        let changes = match stdcm_log.request.fetch(&core_client).await {
+            Ok(response) => {
+                compare_outcomes(&stdcm_log.response, &response, args.run_time_tolerance)
+            }
+            Err(error) => vec![OutcomeChange::ReplayFailed {
+                error: error.to_string(),
+            }],
+        };
+        for change in &changes {
+            println!("🔀 STDCM log {}: {change}", stdcm_log.id);
+        }
+        if !changes.is_empty() {
+            changed.push(ReplayedStdcmLog {
+                stdcm_log_id: stdcm_log.id,
+                trace_id: stdcm_log.trace_id.clone(),
+                changes,
+            });
+        }
+    }
+
+    let report = StdcmReplayReport {
+        replayed_count: stdcm_logs.len(),
+        changed,
+    };
+    if let Some(path) = args.report {
+        serde_json::to_writer_pretty(File::create(path)?, &report)?;
+    }
+    if !report.changed.is_empty() {
+        let error = CliError::new(
+            1,
+            format!(
+                "❌ The outcome of {} of the {} replayed STDCM logs changed",
+                report.changed.len(),
+                report.replayed_count
+            ),
+        );
+        return Err(Box::new(error));
+    }
+    println!(
+        "✅ The outcome of the {} replayed STDCM logs is unchanged",
+        report.replayed_count
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::core::pathfinding::PathfindingResultSuccess;
+
+    fn success(departure_time: &str, run_time: u64) -> Response {
+        let mut simulation = SimulationResponse::default();
+        if let SimulationResponse::Success { final_output, .. } = &mut simulation {
+            final_output.report_train.times = vec![0, run_time];
+        }
+        Response::Success {
+            simulation,
+            path: PathfindingResultSuccess {
+                blocks: vec![],
+                routes: vec![],
+                track_section_ranges: vec![],
+                length: 0,
+                path_item_positions: vec![],
+            },
+            departure_time: DateTime::parse_from_rfc3339(departure_time)
+                .unwrap()
+                .to_utc(),
+        }
+    }
+
+    #[test]
+    fn replayed_paths_are_compared() {
+        let logged = success("2025-03-03T08:00:00Z", 3_600_000);
+
+        assert_eq!(
+            compare_outcomes(&logged, &success("2025-03-03T08:00:00Z", 3_600_500), 1000),
+            vec![]
+        );
+        assert_eq!(
+            compare_outcomes(&logged, &success("2025-03-03T08:05:00Z", 3_700_000), 1000),
+            vec![
+                OutcomeChange::DepartureMoved {
+                    logged: DateTime::parse_from_rfc3339("2025-03-03T08:00:00Z")
+                        .unwrap()
+                        .to_utc(),
+                    replayed: DateTime::parse_from_rfc3339("2025-03-03T08:05:00Z")
+                        .unwrap()
+                        .to_utc(),
+                },
+                OutcomeChange::RunTimeChanged {
+                    logged: 3_600_000,
+                    replayed: 3_700_000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lost_paths_are_reported() {
+        let logged = success("2025-03-03T08:00:00Z", 3_600_000);
+
+        assert_eq!(
+            compare_outcomes(&logged, &Response::PathNotFound, 1000),
+            vec![OutcomeChange::SuccessBecameFailure]
+        );
+        assert_eq!(
+            compare_outcomes(&Response::PathNotFound, &logged, 1000),
+            vec![OutcomeChange::FailureBecameSuccess]
+        );
+        assert_eq!(
+            compare_outcomes(&Response::PathNotFound, &Response::PathNotFound, 1000),
+            vec![]
+        );
+    }
+}
@@ -1,10 +1,12 @@
 use crate::models::electrical_profiles::ElectricalProfileSet;
 use crate::models::stdcm_search_environment::StdcmSearchEnvironment;
+use crate::models::temporary_speed_limits::TemporarySpeedLimitGroup;
 use crate::models::timetable::Timetable;
 use crate::models::work_schedules::WorkScheduleGroup;
 use crate::models::Infra;
 use crate::models::Scenario;
 use crate::CliError;
+use crate::DeleteStatic;
 use crate::Exists;
 use crate::Model;
 use crate::Retrieve;
@@ -21,7 +23,14 @@ use std::error::Error;
 pub enum StdcmSearchEnvCommands {
     SetFromScenario(SetSTDCMSearchEnvFromScenarioArgs),
     SetFromScratch(SetSTDCMSearchEnvFromScratchArgs),
-    Show,
+    Show(ShowSTDCMSearchEnvArgs),
+    /// List the STDCM search environments, the latest first
+    List,
+    Delete(DeleteSTDCMSearchEnvArgs),
+    /// Restrict a STDCM search environment to some users or groups
+    Grant(STDCMSearchEnvAccessArgs),
+    /// Revoke the access of some users or groups to a STDCM search environment
+    Revoke(STDCMSearchEnvAccessArgs),
 }
 
 pub async fn handle_stdcm_search_env_command(
@@ -36,7 +45,11 @@ pub async fn handle_stdcm_search_env_command(
         StdcmSearchEnvCommands::SetFromScratch(args) => {
             set_stdcm_search_env_from_scratch(args, conn).await
         }
-        StdcmSearchEnvCommands::Show => show_stdcm_search_env(conn).await,
+        StdcmSearchEnvCommands::Show(args) => show_stdcm_search_env(args, conn).await,
+        StdcmSearchEnvCommands::List => list_stdcm_search_envs(conn).await,
+        StdcmSearchEnvCommands::Delete(args) => delete_stdcm_search_env(args, conn).await,
+        StdcmSearchEnvCommands::Grant(args) => grant_stdcm_search_env(args, conn).await,
+        StdcmSearchEnvCommands::Revoke(args) => revoke_stdcm_search_env(args, conn).await,
     }
 }
 
@@ -52,15 +65,35 @@ async fn check_exists<T: Exists<i64>>(
     Ok(())
 }
 
+async fn retrieve_search_env(
+    conn: &mut DbConnection,
+    name: String,
+) -> Result<StdcmSearchEnvironment, Box<dyn Error + Send + Sync>> {
+    let search_env_option = StdcmSearchEnvironment::retrieve(conn, name.clone()).await?;
+    let search_env = search_env_option.ok_or_else(|| {
+        let error = CliError::new(
+            1,
+            format!("❌ STDCM search environment not found, name: {0}", name),
+        );
+        Box::new(error)
+    })?;
+    Ok(search_env)
+}
+
 #[derive(Args, Debug)]
 #[command(
     about,
-    long_about = "Set the current STDCM search env by copying most data from a scenario"
+    long_about = "Set a STDCM search env by copying most data from a scenario"
 )]
 pub struct SetSTDCMSearchEnvFromScenarioArgs {
     pub scenario_id: i64,
+    /// The search env with this name is replaced, if any
+    #[arg(long, default_value = "default")]
+    pub name: String,
     #[arg(long)]
     pub work_schedule_group_id: Option<i64>,
+    #[arg(long)]
+    pub temporary_speed_limit_group_id: Option<i64>,
     /// If omitted, set to the earliest train start time in the timetable
     #[arg(long)]
     pub search_window_begin: Option<DateTime<Utc>>,
@@ -78,6 +111,15 @@ async fn set_stdcm_search_env_from_scenario(
             .await?;
     }
 
+    if let Some(temporary_speed_limit_group_id) = args.temporary_speed_limit_group_id {
+        check_exists::<TemporarySpeedLimitGroup>(
+            conn,
+            temporary_speed_limit_group_id,
+            "Temporary Speed Limit Group",
+        )
+        .await?;
+    }
+
     let scenario_option = Scenario::retrieve(conn, args.scenario_id).await?;
 
     let scenario = scenario_option.ok_or_else(|| {
@@ -97,9 +139,11 @@ async fn set_stdcm_search_env_from_scenario(
     .await?;
 
     StdcmSearchEnvironment::changeset()
+        .name(args.name)
         .infra_id(scenario.infra_id)
         .electrical_profile_set_id(scenario.electrical_profile_set_id)
         .work_schedule_group_id(args.work_schedule_group_id)
+        .temporary_speed_limit_group_id(args.temporary_speed_limit_group_id)
         .timetable_id(scenario.timetable_id)
         .search_window_begin(begin)
         .search_window_end(end)
@@ -113,9 +157,12 @@ async fn set_stdcm_search_env_from_scenario(
 #[derive(Args, Debug)]
 #[command(
     about,
-    long_about = "Set the current STDCM search env by specifying each attribute"
+    long_about = "Set a STDCM search env by specifying each attribute"
 )]
 pub struct SetSTDCMSearchEnvFromScratchArgs {
+    /// The search env with this name is replaced, if any
+    #[arg(long, default_value = "default")]
+    pub name: String,
     #[arg(long)]
     pub infra_id: i64,
     #[arg(long)]
@@ -123,6 +170,8 @@ pub struct SetSTDCMSearchEnvFromScratchArgs {
     #[arg(long)]
     pub work_schedule_group_id: Option<i64>,
     #[arg(long)]
+    pub temporary_speed_limit_group_id: Option<i64>,
+    #[arg(long)]
     pub timetable_id: i64,
     #[arg(long)]
     /// If omitted, set to the earliest train start time in the timetable
@@ -155,6 +204,15 @@ async fn set_stdcm_search_env_from_scratch(
             .await?;
     }
 
+    if let Some(temporary_speed_limit_group_id) = args.temporary_speed_limit_group_id {
+        check_exists::<TemporarySpeedLimitGroup>(
+            conn,
+            temporary_speed_limit_group_id,
+            "Temporary Speed Limit Group",
+        )
+        .await?;
+    }
+
     let (begin, end) = resolve_search_window(
         args.timetable_id,
         args.search_window_begin,
@@ -164,9 +222,11 @@ async fn set_stdcm_search_env_from_scratch(
     .await?;
 
     StdcmSearchEnvironment::changeset()
+        .name(args.name)
         .infra_id(args.infra_id)
         .electrical_profile_set_id(args.electrical_profile_set_id)
         .work_schedule_group_id(args.work_schedule_group_id)
+        .temporary_speed_limit_group_id(args.temporary_speed_limit_group_id)
         .timetable_id(args.timetable_id)
         .search_window_begin(begin)
         .search_window_end(end)
@@ -210,21 +270,127 @@ async fn resolve_search_window(
     Ok((begin, end))
 }
 
+#[derive(Args, Debug)]
+#[command(
+    about,
+    long_about = "Show a STDCM search env, the latest one if no name is given"
+)]
+pub struct ShowSTDCMSearchEnvArgs {
+    pub name: Option<String>,
+}
+
 async fn show_stdcm_search_env(
+    args: ShowSTDCMSearchEnvArgs,
     conn: &mut DbConnection,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let search_env = StdcmSearchEnvironment::retrieve_latest(conn).await;
+    let search_env = match args.name {
+        Some(name) => Some(retrieve_search_env(conn, name).await?),
+        None => StdcmSearchEnvironment::retrieve_latest(conn).await,
+    };
     if let Some(search_env) = search_env {
         println!("{search_env:#?}");
 
         let n_trains = Timetable::trains_count(search_env.timetable_id, conn).await?;
         println!("🚆 Number of trains in timetable: {0}", n_trains);
+
+        let subject_ids = search_env.granted_subjects(conn).await?;
+        if subject_ids.is_empty() {
+            println!("🔓 Open to every STDCM user");
+        } else {
+            println!("🔒 Restricted to subjects: {0:?}", subject_ids);
+        }
     } else {
         println!("🔎 No STDCM search environment has been set up yet")
     };
     Ok(())
 }
 
+async fn list_stdcm_search_envs(
+    conn: &mut DbConnection,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let search_envs = StdcmSearchEnvironment::list_latest_first(conn).await?;
+    if search_envs.is_empty() {
+        println!("🔎 No STDCM search environment has been set up yet");
+    }
+    for search_env in search_envs {
+        println!(
+            "{0} - {1} (timetable {2}, infra {3}, from {4} to {5})",
+            search_env.id,
+            search_env.name,
+            search_env.timetable_id,
+            search_env.infra_id,
+            search_env.search_window_begin,
+            search_env.search_window_end
+        );
+    }
+    Ok(())
+}
+
+#[derive(Args, Debug)]
+#[command(about, long_about = "Delete a STDCM search env")]
+pub struct DeleteSTDCMSearchEnvArgs {
+    pub name: String,
+}
+
+async fn delete_stdcm_search_env(
+    args: DeleteSTDCMSearchEnvArgs,
+    conn: &mut DbConnection,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !StdcmSearchEnvironment::delete_static(conn, args.name.clone()).await? {
+        let err_msg = format!(
+            "❌ STDCM search environment not found, name: {0}",
+            args.name
+        );
+        return Err(Box::new(CliError::new(1, err_msg)));
+    }
+    println!("✅ STDCM search environment {0} deleted", args.name);
+    Ok(())
+}
+
+#[derive(Args, Debug)]
+pub struct STDCMSearchEnvAccessArgs {
+    /// The name of the search env
+    pub name: String,
+    /// The ids of the users or groups
+    #[arg(required = true)]
+    pub subject_ids: Vec<i64>,
+}
+
+async fn grant_stdcm_search_env(
+    args: STDCMSearchEnvAccessArgs,
+    conn: &mut DbConnection,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let search_env = retrieve_search_env(conn, args.name).await?;
+    search_env.grant(conn, &args.subject_ids).await?;
+    println!(
+        "✅ STDCM search environment {0} restricted to subjects: {1:?}",
+        search_env.name,
+        search_env.granted_subjects(conn).await?
+    );
+    Ok(())
+}
+
+async fn revoke_stdcm_search_env(
+    args: STDCMSearchEnvAccessArgs,
+    conn: &mut DbConnection,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let search_env = retrieve_search_env(conn, args.name).await?;
+    search_env.revoke(conn, &args.subject_ids).await?;
+    let subject_ids = search_env.granted_subjects(conn).await?;
+    if subject_ids.is_empty() {
+        println!(
+            "✅ STDCM search environment {0} is open to every STDCM user",
+            search_env.name
+        );
+    } else {
+        println!(
+            "✅ STDCM search environment {0} restricted to subjects: {1:?}",
+            search_env.name, subject_ids
+        );
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -381,7 +547,9 @@ mod tests {
 
         let args = SetSTDCMSearchEnvFromScenarioArgs {
             scenario_id: scenario_fixture_set.scenario.id,
+            name: "test_stdcm_set_search_env_from_scenario".to_owned(),
             work_schedule_group_id: Some(work_schedule_group.id),
+            temporary_speed_limit_group_id: None,
             search_window_begin: None,
             search_window_end: None,
         };
@@ -389,7 +557,12 @@ mod tests {
         let result = set_stdcm_search_env_from_scenario(args, conn).await;
         assert!(result.is_ok());
 
-        let search_env = StdcmSearchEnvironment::retrieve_latest(conn).await;
+        let search_env = StdcmSearchEnvironment::retrieve(
+            conn,
+            "test_stdcm_set_search_env_from_scenario".to_owned(),
+        )
+        .await
+        .expect("Failed to retrieve search environment");
 
         assert!(search_env.is_some());
         let search_env = search_env.unwrap();
@@ -422,9 +595,11 @@ mod tests {
         create_train_schedules_from_start_times(start_times, timetable.id, conn).await;
 
         let args = SetSTDCMSearchEnvFromScratchArgs {
+            name: "test_set_stdcm_search_env_from_scratch".to_owned(),
             infra_id: infra.id,
             electrical_profile_set_id: Some(electrical_profile_set.id),
             work_schedule_group_id: Some(work_schedule_group.id),
+            temporary_speed_limit_group_id: None,
             timetable_id: timetable.id,
             search_window_begin: None,
             search_window_end: None,
@@ -433,7 +608,12 @@ mod tests {
         let result = set_stdcm_search_env_from_scratch(args, conn).await;
         assert!(result.is_ok());
 
-        let search_env = StdcmSearchEnvironment::retrieve_latest(conn).await;
+        let search_env = StdcmSearchEnvironment::retrieve(
+            conn,
+            "test_set_stdcm_search_env_from_scratch".to_owned(),
+        )
+        .await
+        .expect("Failed to retrieve search environment");
 
         assert!(search_env.is_some());
         let search_env = search_env.unwrap();
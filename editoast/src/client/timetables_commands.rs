@@ -1,12 +1,17 @@
 use std::io::BufReader;
+use std::io::Read;
 use std::{error::Error, fs::File, path::PathBuf, sync::Arc};
 
-use clap::{Args, Subcommand};
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use clap::{Args, Subcommand, ValueEnum};
 use editoast_models::DbConnectionPoolV2;
 use editoast_schemas::train_schedule::TrainScheduleBase;
 
 use crate::models::prelude::*;
+use crate::models::timetable::exchange::{ExchangeFormat, ExchangeTimetable, UnmatchedStop};
 use crate::models::train_schedule::TrainSchedule;
+use crate::models::Infra;
 use crate::views::train_schedule::TrainScheduleForm;
 use crate::{
     models::timetable::{Timetable, TimetableWithTrains},
@@ -20,23 +25,95 @@ pub enum TimetablesCommands {
     Export(ExportTimetableArgs),
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum TimetableFileFormat {
+    /// A JSON array of train schedules
+    Json,
+    /// A railML 3.2 document
+    Railml,
+    /// A zipped GTFS feed
+    Gtfs,
+}
+
+impl TimetableFileFormat {
+    fn exchange_format(self) -> Option<ExchangeFormat> {
+        match self {
+            TimetableFileFormat::Json => None,
+            TimetableFileFormat::Railml => Some(ExchangeFormat::Railml),
+            TimetableFileFormat::Gtfs => Some(ExchangeFormat::Gtfs),
+        }
+    }
+}
+
 #[derive(Args, Debug)]
-#[command(about, long_about = "Import a train schedule given a JSON file")]
+#[command(
+    about,
+    long_about = "Import train schedules given a JSON, railML or GTFS file"
+)]
 pub struct ImportTimetableArgs {
     /// The timetable id on which attach the trains to
     #[arg(long)]
     id: Option<i64>,
     /// The input file path
     path: PathBuf,
+    #[arg(long, value_enum, default_value_t = TimetableFileFormat::Json)]
+    format: TimetableFileFormat,
+    /// The infra the stops are matched in, required by railML and GTFS
+    #[arg(long)]
+    infra_id: Option<i64>,
+    /// The rolling stock of the imported trains, required by railML and GTFS
+    #[arg(long)]
+    rolling_stock_name: Option<String>,
+    /// Only import the trains running on this day, for railML and GTFS
+    #[arg(long)]
+    date: Option<NaiveDate>,
 }
 
 #[derive(Args, Debug)]
-#[command(about, long_about = "Export the train schedules of a given timetable")]
+#[command(
+    about,
+    long_about = "Export the train schedules of a given timetable as JSON, railML or GTFS"
+)]
 pub struct ExportTimetableArgs {
     /// The timetable id on which get the train schedules from
     id: i64,
     /// The output file path
     path: PathBuf,
+    #[arg(long, value_enum, default_value_t = TimetableFileFormat::Json)]
+    format: TimetableFileFormat,
+    /// The infra the stops are looked up in, required by railML and GTFS
+    #[arg(long)]
+    infra_id: Option<i64>,
+    /// The IANA timezone the times and the running days are written in, for railML and GTFS
+    #[arg(long, default_value = "UTC")]
+    timezone: String,
+}
+
+async fn check_infra_exists(
+    db_pool: &DbConnectionPoolV2,
+    infra_id: Option<i64>,
+    format: ExchangeFormat,
+) -> Result<i64, Box<dyn Error + Send + Sync>> {
+    let Some(infra_id) = infra_id else {
+        let error = CliError::new(1, format!("❌ An infra is required for {format:?} files"));
+        return Err(Box::new(error));
+    };
+    if !Infra::exists(&mut db_pool.get().await?, infra_id).await? {
+        let error = CliError::new(1, format!("❌ Infra not found, id: {infra_id}"));
+        return Err(Box::new(error));
+    }
+    Ok(infra_id)
+}
+
+fn print_unmatched_stops(unmatched_stops: &[UnmatchedStop]) {
+    for stop in unmatched_stops {
+        println!(
+            "🚨 Stop {} ({}) not found in the infra, called at by: {}",
+            stop.id,
+            stop.name.as_deref().unwrap_or("unnamed"),
+            stop.train_names.join(", ")
+        );
+    }
 }
 
 pub async fn trains_export(
@@ -56,6 +133,38 @@ pub async fn trains_export(
 
     assert!(missing.is_empty());
 
+    if let Some(format) = args.format.exchange_format() {
+        let infra_id = check_infra_exists(&db_pool, args.infra_id, format).await?;
+        let Ok(timezone) = args.timezone.parse::<Tz>() else {
+            let error = CliError::new(1, format!("❌ Unknown timezone {}", args.timezone));
+            return Err(Box::new(error));
+        };
+        let (timetable, unmatched_stops) = ExchangeTimetable::from_train_schedules(
+            &mut db_pool.get().await?,
+            infra_id,
+            &train_schedules,
+            timezone,
+        )
+        .await?;
+        if !unmatched_stops.is_empty() {
+            print_unmatched_stops(&unmatched_stops);
+            let error = CliError::new(
+                1,
+                format!(
+                    "❌ {} stops could not be found in infra {infra_id}",
+                    unmatched_stops.len()
+                ),
+            );
+            return Err(Box::new(error));
+        }
+        std::fs::write(&args.path, format.write(&timetable)?)?;
+        println!(
+            "✅ Train schedules exported to {0}",
+            args.path.to_string_lossy()
+        );
+        return Ok(());
+    }
+
     let train_schedules: Vec<TrainScheduleBase> = train_schedules
         .into_iter()
         .map(|ts| Into::<TrainScheduleResult>::into(ts).train_schedule)
@@ -98,8 +207,27 @@ pub async fn trains_import(
         None => Timetable::create(&mut db_pool.get().await?).await?,
     };
 
-    let train_schedules: Vec<TrainScheduleBase> =
-        serde_json::from_reader(BufReader::new(train_file))?;
+    let train_schedules: Vec<TrainScheduleBase> = match args.format.exchange_format() {
+        None => serde_json::from_reader(BufReader::new(train_file))?,
+        Some(format) => {
+            let infra_id = check_infra_exists(&db_pool, args.infra_id, format).await?;
+            let Some(rolling_stock_name) = args.rolling_stock_name else {
+                let error = CliError::new(
+                    1,
+                    format!("❌ A rolling stock name is required for {format:?} files"),
+                );
+                return Err(Box::new(error));
+            };
+            let mut data = vec![];
+            BufReader::new(train_file).read_to_end(&mut data)?;
+            let (train_schedules, unmatched_stops) = format
+                .read(&data, args.date)?
+                .into_train_schedules(&mut db_pool.get().await?, infra_id, &rolling_stock_name)
+                .await?;
+            print_unmatched_stops(&unmatched_stops);
+            train_schedules
+        }
+    };
     let changesets: Vec<Changeset<TrainSchedule>> = train_schedules
         .into_iter()
         .map(|train_schedule| {
@@ -148,6 +276,10 @@ mod tests {
         let args = ImportTimetableArgs {
             path: file.path().into(),
             id: Some(timetable.id),
+            format: TimetableFileFormat::Json,
+            infra_id: None,
+            rolling_stock_name: None,
+            date: None,
         };
         let result = trains_import(args, db_pool.clone().into()).await;
         assert!(result.is_ok(), "{:?}", result);
@@ -157,6 +289,9 @@ mod tests {
         let args = ExportTimetableArgs {
             path: export_file.path().into(),
             id: timetable.id,
+            format: TimetableFileFormat::Json,
+            infra_id: None,
+            timezone: "UTC".to_owned(),
         };
         let export_result = trains_export(args, db_pool.clone().into()).await;
         assert!(export_result.is_ok(), "{:?}", export_result);
@@ -165,6 +300,10 @@ mod tests {
         let reimport_args = ImportTimetableArgs {
             path: export_file.path().into(),
             id: Some(timetable.id),
+            format: TimetableFileFormat::Json,
+            infra_id: None,
+            rolling_stock_name: None,
+            date: None,
         };
         let reimport_result = trains_import(reimport_args, db_pool.clone().into()).await;
         assert!(reimport_result.is_ok(), "{:?}", reimport_result);
@@ -0,0 +1,129 @@
+//! Direct HTTP transport to a core worker
+//!
+//! Requests are sent to a single core worker, without going through the message queue and
+//! osrdyne. This is meant for local development and small deployments.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::info;
+use url::Url;
+
+use super::CoreError;
+
+pub struct Options {
+    /// The base URL of the core worker, for instance `http://localhost:8080`
+    pub url: Url,
+    /// Timeout of the requests, in seconds
+    pub timeout: u64,
+    /// How many times a request is sent again when the connection to the core is lost
+    pub retries: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    url: Url,
+    client: reqwest::Client,
+    retries: u8,
+}
+
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: reqwest::StatusCode,
+    /// The URL the request was sent to
+    pub url: String,
+    pub payload: Vec<u8>,
+}
+
+impl HttpClient {
+    pub fn new(options: Options) -> Result<Self, CoreError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(options.timeout))
+            .build()?;
+        Ok(Self {
+            url: options.url,
+            client,
+            retries: options.retries,
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.url.as_str().trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
+    /// Sends a request to the core, retrying it if the connection is lost
+    ///
+    /// Responses are returned whatever their status, timeouts are reported as
+    /// [CoreError::ResponseTimeout].
+    pub async fn call<B: Serialize>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<HttpResponse, CoreError> {
+        let url = self.url(path);
+        let mut attempt = 0;
+        let response = loop {
+            let mut request = self.client.request(method.clone(), &url);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            match request.send().await.map_err(CoreError::from) {
+                Err(
+                    CoreError::ConnectionClosedBeforeMessageCompleted
+                    | CoreError::ConnectionResetByPeer
+                    | CoreError::BrokenPipe,
+                ) if attempt < self.retries => {
+                    attempt += 1;
+                    info!(
+                        target: "editoast::coreclient",
+                        "Core request '{method} {path}': connection lost. Retry [{attempt}/{}]",
+                        self.retries
+                    );
+                }
+                response => break response?,
+            }
+        };
+        let status = response.status();
+        let payload = response.bytes().await?.to_vec();
+        Ok(HttpResponse {
+            status,
+            url,
+            payload,
+        })
+    }
+
+    pub async fn ping(&self) -> Result<bool, CoreError> {
+        let response = self
+            .call::<()>(reqwest::Method::GET, "/version", None)
+            .await?;
+        Ok(response.status.is_success())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn request_url_joins_base_url_and_path() {
+        for base_url in ["http://core:8080", "http://core:8080/"] {
+            let client = HttpClient::new(Options {
+                url: Url::parse(base_url).unwrap(),
+                timeout: 1,
+                retries: 0,
+            })
+            .unwrap();
+            assert_eq!(
+                client.url("/v2/pathfinding/blocks"),
+                "http://core:8080/v2/pathfinding/blocks"
+            );
+        }
+    }
+}
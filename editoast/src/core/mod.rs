@@ -1,4 +1,5 @@
 pub mod conflict_detection;
+pub mod http_client;
 pub mod infra_loading;
 #[cfg(test)]
 pub mod mocking;
@@ -42,6 +43,7 @@ editoast_common::schemas! {
 #[derive(Debug, Clone)]
 pub enum CoreClient {
     MessageQueue(RabbitMQClient),
+    Http(http_client::HttpClient),
     #[cfg(test)]
     Mocked(mocking::MockingClient),
 }
@@ -53,6 +55,12 @@ impl CoreClient {
         Ok(Self::MessageQueue(client))
     }
 
+    pub fn new_http(options: http_client::Options) -> Result<Self> {
+        let client = http_client::HttpClient::new(options)?;
+
+        Ok(Self::Http(client))
+    }
+
     fn handle_error(&self, bytes: &[u8], url: String) -> InternalError {
         // We try to deserialize the response as an StandardCoreError in order to retain the context of the core error
         if let Ok(mut core_error) = <Json<StandardCoreError>>::from_bytes(bytes) {
@@ -78,6 +86,7 @@ impl CoreClient {
             CoreClient::MessageQueue(mq_client) => {
                 mq_client.ping().await.map_err(|_| CoreError::BrokenPipe)
             }
+            CoreClient::Http(http_client) => http_client.ping().await,
             #[cfg(test)]
             CoreClient::Mocked(_) => Ok(true),
         }
@@ -121,6 +130,15 @@ impl CoreClient {
 
                 todo!("TODO: handle protocol errors")
             }
+            CoreClient::Http(client) => {
+                let response = client.call(method, path, body).await?;
+
+                if response.status.is_success() {
+                    return R::from_bytes(&response.payload);
+                }
+
+                Err(self.handle_error(&response.payload, response.url))
+            }
             #[cfg(test)]
             CoreClient::Mocked(client) => {
                 match client.fetch_mocked::<_, B, R>(method, path, body) {
@@ -285,6 +303,9 @@ pub enum CoreError {
     #[error("Core connection broken. Should retry.")]
     #[editoast_error(status = 500)]
     BrokenPipe,
+    #[error("Core request timed out")]
+    #[editoast_error(status = 500)]
+    ResponseTimeout,
 
     #[cfg(test)]
     #[error("The mocked response had no body configured - check out StubResponseBuilder::body if this is unexpected")]
@@ -334,6 +355,9 @@ impl Display for StandardCoreError {
 
 impl From<reqwest::Error> for CoreError {
     fn from(value: reqwest::Error) -> Self {
+        if value.is_timeout() {
+            return Self::ResponseTimeout;
+        }
         // Since we should retry the request it's useful to have its own kind of error.
         if value
             .to_string()
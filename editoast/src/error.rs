@@ -231,6 +231,16 @@ impl EditoastError for serde_json::Error {
     }
 }
 
+impl EditoastError for rusqlite::Error {
+    fn get_status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    fn get_type(&self) -> &str {
+        "editoast:SqliteError"
+    }
+}
+
 impl EditoastError for json_patch::PatchError {
     fn get_status(&self) -> StatusCode {
         StatusCode::INTERNAL_SERVER_ERROR
@@ -3,6 +3,7 @@ use std::collections::HashSet;
 
 use rangemap::RangeMap;
 
+use super::rules::MIN_ELECTRIFICATION_GAP_LENGTH;
 use super::GlobalErrorGenerator;
 use super::NoContext;
 use crate::generated_data::error::ObjectErrorGenerator;
@@ -10,6 +11,8 @@ use crate::generated_data::infra_error::InfraError;
 use crate::infra_cache::Graph;
 use crate::infra_cache::InfraCache;
 use crate::infra_cache::ObjectCache;
+use editoast_schemas::infra::Endpoint;
+use editoast_schemas::infra::TrackEndpoint;
 use editoast_schemas::primitives::OSRDIdentified;
 use editoast_schemas::primitives::ObjectRef;
 use editoast_schemas::primitives::ObjectType;
@@ -19,8 +22,10 @@ pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 2] = [
     ObjectErrorGenerator::new(2, check_electrification_track_ranges),
 ];
 
-pub const GLOBAL_GENERATORS: [GlobalErrorGenerator<NoContext>; 1] =
-    [GlobalErrorGenerator::new(check_overlapping)];
+pub const GLOBAL_GENERATORS: [GlobalErrorGenerator<NoContext>; 2] = [
+    GlobalErrorGenerator::new(check_overlapping),
+    GlobalErrorGenerator::new(check_short_gaps),
+];
 
 /// Check if a track section has empty electrification
 pub fn check_empty(electrification: &ObjectCache, _: &InfraCache, _: &Graph) -> Vec<InfraError> {
@@ -109,14 +114,162 @@ pub fn check_overlapping(infra_cache: &InfraCache, _: &Graph) -> Vec<InfraError>
         .collect()
 }
 
+/// Checks that non electrified gaps between electrifications are not shorter than a train
+///
+/// Gaps are followed across the links between track sections, so that gaps spanning several
+/// track sections, including track sections without any electrification, are reported.
+pub fn check_short_gaps(infra_cache: &InfraCache, graph: &Graph) -> Vec<InfraError> {
+    // Key: Track
+    // Value: electrified ranges (begin, end, ElectrificationId) sorted by begin
+    let mut ranges: HashMap<&String, Vec<(f64, f64, &String)>> = Default::default();
+    for electrification in infra_cache.electrifications().values() {
+        let electrification = electrification.unwrap_electrification();
+        for track_range in electrification.track_ranges.iter() {
+            let begin = track_range.begin.min(track_range.end);
+            let end = track_range.begin.max(track_range.end);
+            ranges.entry(&track_range.track.0).or_default().push((
+                begin,
+                end,
+                electrification.get_id(),
+            ));
+        }
+    }
+    for track_ranges in ranges.values_mut() {
+        track_ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    // Key: (ElectrificationId, ElectrificationId)
+    // Value: gap length
+    let mut gaps: HashMap<(&str, &str), f64> = Default::default();
+
+    // Gaps inside a track section
+    for track_ranges in ranges.values() {
+        let (_, mut electrified_until, mut last_id) = track_ranges[0];
+        for &(begin, end, id) in &track_ranges[1..] {
+            add_short_gap(&mut gaps, last_id, id, begin - electrified_until);
+            if end > electrified_until {
+                electrified_until = end;
+                last_id = id;
+            }
+        }
+    }
+
+    // Gaps across track sections, from the first and last electrified ranges of each track
+    for (&track_id, track_ranges) in ranges.iter() {
+        let Some(length) = track_length(infra_cache, track_id) else {
+            continue;
+        };
+        let (first_begin, _, first_id) = track_ranges[0];
+        let (last_end, last_id) = last_electrified(track_ranges);
+        for (endpoint, gap_length, id) in [
+            (Endpoint::Begin, first_begin, first_id),
+            (Endpoint::End, length - last_end, last_id),
+        ] {
+            let mut visited = HashSet::from([track_id]);
+            let mut to_visit = vec![(TrackEndpoint::new(track_id, endpoint), gap_length.max(0.))];
+            while let Some((track_endpoint, gap_length)) = to_visit.pop() {
+                for neighbour in graph.get_all_neighbours(&track_endpoint) {
+                    let neighbour_id = &neighbour.track.0;
+                    let Some(neighbour_length) = track_length(infra_cache, neighbour_id) else {
+                        continue;
+                    };
+                    if let Some(neighbour_ranges) = ranges.get(neighbour_id) {
+                        // The gap ends at the first electrified range met on the neighbour
+                        let (neighbour_gap_length, other_id) = match neighbour.endpoint {
+                            Endpoint::Begin => (neighbour_ranges[0].0, neighbour_ranges[0].2),
+                            Endpoint::End => {
+                                let (end, other_id) = last_electrified(neighbour_ranges);
+                                (neighbour_length - end, other_id)
+                            }
+                        };
+                        add_short_gap(
+                            &mut gaps,
+                            id,
+                            other_id,
+                            gap_length + neighbour_gap_length.max(0.),
+                        );
+                    } else if gap_length + neighbour_length < MIN_ELECTRIFICATION_GAP_LENGTH
+                        && visited.insert(neighbour_id)
+                    {
+                        // The neighbour is not electrified at all, the gap goes through it
+                        let opposite = match neighbour.endpoint {
+                            Endpoint::Begin => Endpoint::End,
+                            Endpoint::End => Endpoint::Begin,
+                        };
+                        to_visit.push((
+                            TrackEndpoint::new(neighbour_id, opposite),
+                            gap_length + neighbour_length,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    gaps.into_iter()
+        .map(|((before, after), gap_length)| {
+            InfraError::new_short_electrification_gap(before, after, gap_length)
+        })
+        .collect()
+}
+
+/// Records a gap between two electrifications if it is not empty and shorter than a train
+///
+/// A gap found from both of its sides is only recorded once.
+fn add_short_gap<'a>(
+    gaps: &mut HashMap<(&'a str, &'a str), f64>,
+    before: &'a str,
+    after: &'a str,
+    gap_length: f64,
+) {
+    if gap_length > 0. && gap_length < MIN_ELECTRIFICATION_GAP_LENGTH {
+        let key = if before <= after {
+            (before, after)
+        } else {
+            (after, before)
+        };
+        gaps.insert(key, gap_length);
+    }
+}
+
+/// The end of the last electrified range of a track section, and its electrification
+fn last_electrified<'a>(track_ranges: &[(f64, f64, &'a String)]) -> (f64, &'a String) {
+    track_ranges
+        .iter()
+        .fold((f64::MIN, track_ranges[0].2), |last, &(_, end, id)| {
+            if end > last.0 {
+                (end, id)
+            } else {
+                last
+            }
+        })
+}
+
+fn track_length(infra_cache: &InfraCache, track_id: &str) -> Option<f64> {
+    infra_cache
+        .track_sections()
+        .get(track_id)
+        .map(|track| track.unwrap_track_section().length)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use super::check_electrification_track_ranges;
     use super::InfraError;
     use crate::generated_data::error::electrifications::check_overlapping;
+    use crate::generated_data::error::electrifications::check_short_gaps;
     use crate::infra_cache::tests::create_electrification_cache;
     use crate::infra_cache::tests::create_small_infra_cache;
+    use crate::infra_cache::tests::create_switch_cache_link;
+    use crate::infra_cache::tests::create_switch_connection;
+    use crate::infra_cache::tests::create_switch_type_cache;
+    use crate::infra_cache::tests::create_track_endpoint;
+    use crate::infra_cache::tests::create_track_section_cache;
     use crate::infra_cache::Graph;
+    use crate::infra_cache::InfraCache;
+    use editoast_schemas::infra::Endpoint;
     use editoast_schemas::primitives::ObjectRef;
     use editoast_schemas::primitives::ObjectType;
 
@@ -172,4 +325,53 @@ mod tests {
             InfraError::new_overlapping_electrifications("Cat_error_1", "Cat_error_2");
         assert_eq!(infra_error, errors[0]);
     }
+
+    #[test]
+    fn short_gap() {
+        let mut infra_cache = create_small_infra_cache();
+        let electrification_1 = create_electrification_cache("Cat_1", vec![("A", 0., 200.)]);
+        infra_cache.add(electrification_1).unwrap();
+        let track_ranges_2 = vec![("A", 300., 500.), ("B", 0., 20.), ("B", 480., 500.)];
+        let electrification_2 = create_electrification_cache("Cat_2", track_ranges_2);
+        infra_cache.add(electrification_2).unwrap();
+        let errors = check_short_gaps(&infra_cache, &Graph::load(&infra_cache));
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_short_electrification_gap("Cat_1", "Cat_2", 100.);
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn short_gap_across_track_sections() {
+        // A (500m) -> B (100m) -> C (500m), B is not electrified at all
+        let mut infra_cache = InfraCache::default();
+        for (id, length) in [("A", 500.), ("B", 100.), ("C", 500.)] {
+            infra_cache
+                .add(create_track_section_cache(id, length))
+                .unwrap();
+        }
+        infra_cache
+            .add(create_switch_type_cache(
+                "link",
+                vec!["A".into(), "B".into()],
+                HashMap::from([("LINK".into(), vec![create_switch_connection("A", "B")])]),
+            ))
+            .unwrap();
+        for (id, src, dst) in [("link_AB", "A", "B"), ("link_BC", "B", "C")] {
+            let link = create_switch_cache_link(
+                id.into(),
+                ("A", create_track_endpoint(Endpoint::End, src)),
+                ("B", create_track_endpoint(Endpoint::Begin, dst)),
+                "link".into(),
+            );
+            infra_cache.add(link).unwrap();
+        }
+        let electrification_1 = create_electrification_cache("Cat_1", vec![("A", 0., 450.)]);
+        infra_cache.add(electrification_1).unwrap();
+        let electrification_2 = create_electrification_cache("Cat_2", vec![("C", 50., 500.)]);
+        infra_cache.add(electrification_2).unwrap();
+        let errors = check_short_gaps(&infra_cache, &Graph::load(&infra_cache));
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_short_electrification_gap("Cat_1", "Cat_2", 200.);
+        assert_eq!(infra_error, errors[0]);
+    }
 }
@@ -7,6 +7,7 @@ use serde::Deserialize;
 use serde::Serialize;
 use strum::AsRefStr;
 use strum::EnumDiscriminants;
+use strum::EnumIter;
 use strum::EnumString;
 use utoipa::ToSchema;
 
@@ -29,7 +30,15 @@ pub struct InfraError {
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, EnumDiscriminants, Clone, ToSchema)]
 #[strum(serialize_all = "snake_case")]
-#[strum_discriminants(derive(ToSchema, Deserialize, EnumString, AsRefStr))]
+#[strum_discriminants(derive(
+    ToSchema,
+    Serialize,
+    Deserialize,
+    Hash,
+    EnumString,
+    EnumIter,
+    AsRefStr
+))]
 #[strum_discriminants(name(InfraErrorTypeLabel))]
 #[strum_discriminants(serde(rename_all = "snake_case", deny_unknown_fields))]
 #[strum_discriminants(strum(serialize_all = "snake_case"))]
@@ -39,6 +48,12 @@ pub enum InfraErrorType {
         original_group_path: String,
     },
     EmptyObject,
+    GeometryLengthMismatch {
+        geometry_length: f64,
+    },
+    ImplausibleSpeed {
+        speed: f64,
+    },
     InvalidGroup {
         group: String,
         switch_type: String,
@@ -52,6 +67,9 @@ pub enum InfraErrorType {
     MissingBufferStop {
         endpoint: Endpoint,
     },
+    MissingDetector {
+        reference: ObjectRef,
+    },
     NodeEndpointsNotUnique,
     ObjectOutOfPath {
         reference: ObjectRef,
@@ -70,6 +88,14 @@ pub enum InfraErrorType {
     OverlappingSwitches {
         reference: ObjectRef,
     },
+    ShortElectrificationGap {
+        reference: ObjectRef,
+        gap_length: f64,
+    },
+    SignalTooClose {
+        reference: ObjectRef,
+        distance: f64,
+    },
     UnknownPortName {
         port_name: String,
     },
@@ -78,6 +104,36 @@ pub enum InfraErrorType {
     },
 }
 
+impl InfraErrorType {
+    /// The object the error refers to, besides the object it is reported on
+    pub fn reference(&self) -> Option<&ObjectRef> {
+        match self {
+            Self::InvalidReference { reference }
+            | Self::MissingDetector { reference }
+            | Self::ObjectOutOfPath { reference }
+            | Self::OverlappingElectrifications { reference }
+            | Self::OverlappingSpeedSections { reference }
+            | Self::OverlappingSwitches { reference }
+            | Self::ShortElectrificationGap { reference, .. }
+            | Self::SignalTooClose { reference, .. } => Some(reference),
+            Self::DuplicatedGroup { .. }
+            | Self::EmptyObject
+            | Self::GeometryLengthMismatch { .. }
+            | Self::ImplausibleSpeed { .. }
+            | Self::InvalidGroup { .. }
+            | Self::InvalidRoute
+            | Self::InvalidSwitchPorts
+            | Self::MissingRoute
+            | Self::MissingBufferStop { .. }
+            | Self::NodeEndpointsNotUnique
+            | Self::OddBufferStopLocation
+            | Self::OutOfRange { .. }
+            | Self::UnknownPortName { .. }
+            | Self::UnusedPort { .. } => None,
+        }
+    }
+}
+
 impl InfraError {
     pub fn new_invalid_reference<T: AsRef<str>, O: OSRDObject>(
         obj: &O,
@@ -309,6 +365,80 @@ impl InfraError {
         }
     }
 
+    pub fn new_geometry_length_mismatch<O: OSRDObject>(obj: &O, geometry_length: f64) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: Some("geo".into()),
+            is_warning: true,
+            sub_type: InfraErrorType::GeometryLengthMismatch { geometry_length },
+        }
+    }
+
+    pub fn new_implausible_speed<T: AsRef<str>, O: OSRDObject>(
+        obj: &O,
+        field: T,
+        speed: f64,
+    ) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: Some(field.as_ref().into()),
+            is_warning: true,
+            sub_type: InfraErrorType::ImplausibleSpeed { speed },
+        }
+    }
+
+    /// Create a new missing detector error.
+    /// Takes the signal and the previous signal on its track section as arguments.
+    pub fn new_missing_detector<O: OSRDObject, T: AsRef<str>>(obj: &O, previous: T) -> Self {
+        let reference = ObjectRef::new(ObjectType::Signal, previous);
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: Some("position".into()),
+            is_warning: true,
+            sub_type: InfraErrorType::MissingDetector { reference },
+        }
+    }
+
+    /// Create a new short electrification gap error.
+    /// Takes the electrifications before and after the gap as arguments.
+    pub fn new_short_electrification_gap<T1: AsRef<str>, T2: AsRef<str>>(
+        before: T1,
+        after: T2,
+        gap_length: f64,
+    ) -> Self {
+        let reference = ObjectRef::new(ObjectType::Electrification, after.as_ref());
+        Self {
+            obj_id: before.as_ref().into(),
+            obj_type: ObjectType::Electrification,
+            field: Some("track_ranges".into()),
+            is_warning: true,
+            sub_type: InfraErrorType::ShortElectrificationGap {
+                reference,
+                gap_length,
+            },
+        }
+    }
+
+    pub fn new_signal_too_close<O: OSRDObject>(
+        obj: &O,
+        reference: ObjectRef,
+        distance: f64,
+    ) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: Some("position".into()),
+            is_warning: true,
+            sub_type: InfraErrorType::SignalTooClose {
+                reference,
+                distance,
+            },
+        }
+    }
+
     pub fn get_sub_type(&self) -> &InfraErrorType {
         &self.sub_type
     }
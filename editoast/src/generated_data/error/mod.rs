@@ -4,6 +4,8 @@ pub mod electrifications;
 pub mod infra_error;
 pub mod operational_points;
 pub mod routes;
+pub mod rules;
+pub mod scope;
 pub mod signals;
 pub mod speed_sections;
 pub mod switch_types;
@@ -26,6 +28,7 @@ use editoast_models::DbConnection;
 use editoast_schemas::primitives::OSRDObject;
 use editoast_schemas::primitives::ObjectType;
 use futures_util::Future;
+use itertools::Either;
 use itertools::Itertools;
 use serde_json::to_value;
 use sha1::Digest;
@@ -33,6 +36,7 @@ use sha1::Sha1;
 use std::ops::DerefMut;
 use tracing::warn;
 
+use self::scope::ErrorScope;
 use super::GeneratedData;
 use crate::error::Result;
 use crate::generated_data::infra_error::InfraError;
@@ -43,6 +47,7 @@ use crate::infra_cache::ObjectCache;
 
 editoast_common::schemas! {
     infra_error::schemas(),
+    rules::schemas(),
 }
 
 /// Empty context used when no context is needed
@@ -130,18 +135,31 @@ impl From<InfraError> for ErrorWithHash {
 /// Generate errors given static object and global error generators.
 /// This function assume that object error generators list isn't empty and sorted by priority.
 /// Global errors are generated at the end.
+/// Only the errors in the given scope are generated.
 async fn generate_errors<Ctx: Default>(
     object_type: ObjectType,
     infra_cache: &InfraCache,
     graph: &Graph<'_>,
     object_err_generators: &'static ObjectErrorGenerators<Ctx>,
     global_err_generators: &'static GlobalErrorGenerators<Ctx>,
+    scope: &ErrorScope,
 ) -> Vec<InfraError> {
     let mut errors = Vec::new();
     let mut context = Ctx::default();
 
+    let objects = infra_cache.get_objects_by_type(object_type);
+    let objects = if scope.regenerates_all(object_type) {
+        Either::Left(objects.values())
+    } else {
+        Either::Right(
+            scope
+                .objects(object_type)
+                .filter_map(|obj_id| objects.get(obj_id)),
+        )
+    };
+
     // Generate object errors
-    for el in infra_cache.get_objects_by_type(object_type).values() {
+    for el in objects {
         let mut found_error = false;
         let mut current_priority = 0;
         for f in object_err_generators.iter() {
@@ -168,6 +186,10 @@ async fn generate_errors<Ctx: Default>(
         }
     }
 
+    if !scope.regenerates_global(object_type) {
+        return errors;
+    }
+
     // Generate global errors
     for f in global_err_generators.iter() {
         let new_errors = match f {
@@ -185,6 +207,14 @@ async fn generate_errors<Ctx: Default>(
 }
 
 pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError> {
+    generate_infra_errors_in_scope(infra_cache, &ErrorScope::full()).await
+}
+
+/// Generate the errors of the infra which are in the given scope
+pub async fn generate_infra_errors_in_scope(
+    infra_cache: &InfraCache,
+    scope: &ErrorScope,
+) -> Vec<InfraError> {
     // Create a graph for topological errors
     let graph = Graph::load(infra_cache);
     // Generate the errors
@@ -195,13 +225,15 @@ pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError>
             &graph,
             &track_sections::OBJECT_GENERATORS,
             &[],
+            scope,
         )),
         Box::pin(generate_errors(
             ObjectType::Signal,
             infra_cache,
             &graph,
             &signals::OBJECT_GENERATORS,
-            &[],
+            &signals::GLOBAL_GENERATORS,
+            scope,
         )),
         Box::pin(generate_errors(
             ObjectType::SpeedSection,
@@ -209,6 +241,7 @@ pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError>
             &graph,
             &speed_sections::OBJECT_GENERATORS,
             &speed_sections::GLOBAL_GENERATORS,
+            scope,
         )),
         Box::pin(generate_errors(
             ObjectType::Route,
@@ -216,6 +249,7 @@ pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError>
             &graph,
             &routes::OBJECT_GENERATORS,
             &routes::GLOBAL_GENERATORS,
+            scope,
         )),
         Box::pin(generate_errors(
             ObjectType::SwitchType,
@@ -223,6 +257,7 @@ pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError>
             &graph,
             &switch_types::OBJECT_GENERATORS,
             &[],
+            scope,
         )),
         Box::pin(generate_errors(
             ObjectType::Detector,
@@ -230,6 +265,7 @@ pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError>
             &graph,
             &detectors::OBJECT_GENERATORS,
             &[],
+            scope,
         )),
         Box::pin(generate_errors(
             ObjectType::BufferStop,
@@ -237,6 +273,7 @@ pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError>
             &graph,
             &buffer_stops::OBJECT_GENERATORS,
             &buffer_stops::GLOBAL_GENERATORS,
+            scope,
         )),
         Box::pin(generate_errors(
             ObjectType::OperationalPoint,
@@ -244,6 +281,7 @@ pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError>
             &graph,
             &operational_points::OBJECT_GENERATORS,
             &[],
+            scope,
         )),
         Box::pin(generate_errors(
             ObjectType::Switch,
@@ -251,6 +289,7 @@ pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError>
             &graph,
             &switches::OBJECT_GENERATORS,
             &[],
+            scope,
         )),
         Box::pin(generate_errors(
             ObjectType::Electrification,
@@ -258,6 +297,7 @@ pub async fn generate_infra_errors(infra_cache: &InfraCache) -> Vec<InfraError>
             &graph,
             &electrifications::OBJECT_GENERATORS,
             &electrifications::GLOBAL_GENERATORS,
+            scope,
         )),
     ];
 
@@ -313,6 +353,26 @@ async fn retrieve_current_errors_hash(
         .await?)
 }
 
+/// Retrieve the current errors of a given infra
+async fn retrieve_current_errors(
+    conn: &mut DbConnection,
+    infra_id: i64,
+) -> Result<Vec<ErrorWithHash>> {
+    use editoast_models::tables::infra_layer_error::dsl;
+    let errors: Vec<(serde_json::Value, ErrorHash)> = dsl::infra_layer_error
+        .filter(dsl::infra_id.eq(infra_id))
+        .select((dsl::information, dsl::info_hash))
+        .load(conn.write().await.deref_mut())
+        .await?;
+    errors
+        .into_iter()
+        .map(|(information, hash)| {
+            let error = serde_json::from_value(information)?;
+            Ok(ErrorWithHash { error, hash })
+        })
+        .collect()
+}
+
 /// Remove a list of errors given an infra and a list of error hashes
 async fn remove_errors_from_hashes(
     conn: &mut DbConnection,
@@ -358,12 +418,14 @@ async fn create_errors(
 }
 
 /// Insert a heterogeneous list of infra errors in DB with a minimum number of queries and operations
-/// This function retrieve the existing errors in DB, compare them with the new ones and insert only
-/// the new ones. It also remove the errors that are not present anymore.
+/// This function compare the existing errors in DB with the new ones and insert only the new ones.
+/// It also remove the replaced errors that are not present anymore.
 async fn update_errors(
     conn: &mut DbConnection,
     infra_id: i64,
     errors: Vec<InfraError>,
+    current_errors_hash: &HashSet<ErrorHash>,
+    replaced_errors_hash: &HashSet<ErrorHash>,
 ) -> Result<()> {
     let new_errors_with_hash: Vec<ErrorWithHash> = errors.into_iter().map_into().collect();
     let new_errors_hash = new_errors_with_hash
@@ -371,18 +433,15 @@ async fn update_errors(
         .map(|e| e.hash.clone())
         .collect::<HashSet<_>>();
 
-    let current_errors_hash = retrieve_current_errors_hash(conn, infra_id).await?;
-    let current_errors_hash = current_errors_hash.into_iter().collect::<HashSet<_>>();
-
     // Filter errors that must be removed
-    let to_remove = current_errors_hash
+    let to_remove = replaced_errors_hash
         .difference(&new_errors_hash)
         .collect_vec();
     remove_errors_from_hashes(conn, infra_id, &to_remove).await?;
 
     // Filter errors that must be created
     let errors_hash_to_create = new_errors_hash
-        .difference(&current_errors_hash)
+        .difference(current_errors_hash)
         .collect_vec();
     let mut errors_to_create: Vec<_> = new_errors_with_hash
         .into_iter()
@@ -414,28 +473,69 @@ impl GeneratedData for ErrorLayer {
         let infra_errors = generate_infra_errors(infra_cache).await;
 
         // Insert new errors and remove old ones in DB
-        update_errors(conn, infra_id, infra_errors).await
+        let current_errors_hash: HashSet<_> = retrieve_current_errors_hash(conn, infra_id)
+            .await?
+            .into_iter()
+            .collect();
+        update_errors(
+            conn,
+            infra_id,
+            infra_errors,
+            &current_errors_hash,
+            &current_errors_hash,
+        )
+        .await
     }
 
     async fn update(
         conn: &mut DbConnection,
         infra_id: i64,
-        _operations: &[CacheOperation],
+        operations: &[CacheOperation],
         infra_cache: &InfraCache,
     ) -> Result<()> {
-        // Generate already act like an update
-        Self::generate(conn, infra_id, infra_cache).await
+        if operations.is_empty() {
+            return Ok(());
+        }
+        // Only regenerate the errors of the edited objects and of their neighbours
+        let current_errors = retrieve_current_errors(conn, infra_id).await?;
+        let scope = ErrorScope::from_operations(
+            operations,
+            infra_cache,
+            current_errors.iter().map(|e| &e.error),
+        );
+        let infra_errors = generate_infra_errors_in_scope(infra_cache, &scope).await;
+
+        // Replace the regenerated errors in DB
+        let current_errors_hash = current_errors.iter().map(|e| e.hash.clone()).collect();
+        let replaced_errors_hash = current_errors
+            .into_iter()
+            .filter(|e| scope.contains(&e.error))
+            .map(|e| e.hash)
+            .collect();
+        update_errors(
+            conn,
+            infra_id,
+            infra_errors,
+            &current_errors_hash,
+            &replaced_errors_hash,
+        )
+        .await
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
+    use pretty_assertions::assert_eq;
     use rstest::rstest;
 
     use super::buffer_stops;
     use super::detectors;
     use super::electrifications;
     use super::generate_errors;
+    use super::generate_infra_errors;
+    use super::generate_infra_errors_in_scope;
     use super::operational_points;
     use super::routes;
     use super::signals;
@@ -443,11 +543,27 @@ mod tests {
     use super::switch_types;
     use super::switches;
     use super::track_sections;
+    use super::ErrorHash;
+    use super::ErrorScope;
+    use super::ErrorWithHash;
     use super::Graph;
+    use super::InfraError;
+    use crate::infra_cache::operation::CacheOperation;
     use crate::infra_cache::tests::create_buffer_stop_cache;
+    use crate::infra_cache::tests::create_electrification_cache;
+    use crate::infra_cache::tests::create_signal_cache;
     use crate::infra_cache::tests::create_small_infra_cache;
+    use crate::infra_cache::tests::create_track_section_cache;
+    use editoast_schemas::primitives::ObjectRef;
     use editoast_schemas::primitives::ObjectType;
 
+    fn hashes(errors: Vec<InfraError>) -> HashSet<ErrorHash> {
+        errors
+            .into_iter()
+            .map(|error| ErrorWithHash::from(error).hash)
+            .collect()
+    }
+
     #[rstest]
     async fn small_infra_cache_validation() {
         let small_infra_cache = create_small_infra_cache();
@@ -461,6 +577,7 @@ mod tests {
             &graph,
             &track_sections::OBJECT_GENERATORS,
             &[],
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -469,7 +586,8 @@ mod tests {
             &small_infra_cache,
             &graph,
             &signals::OBJECT_GENERATORS,
-            &[],
+            &signals::GLOBAL_GENERATORS,
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -479,6 +597,7 @@ mod tests {
             &graph,
             &speed_sections::OBJECT_GENERATORS,
             &speed_sections::GLOBAL_GENERATORS,
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -488,6 +607,7 @@ mod tests {
             &graph,
             &switch_types::OBJECT_GENERATORS,
             &[],
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -497,6 +617,7 @@ mod tests {
             &graph,
             &detectors::OBJECT_GENERATORS,
             &[],
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -506,6 +627,7 @@ mod tests {
             &graph,
             &buffer_stops::OBJECT_GENERATORS,
             &buffer_stops::GLOBAL_GENERATORS,
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -515,6 +637,7 @@ mod tests {
             &graph,
             &routes::OBJECT_GENERATORS,
             &routes::GLOBAL_GENERATORS,
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -524,6 +647,7 @@ mod tests {
             &graph,
             &operational_points::OBJECT_GENERATORS,
             &[],
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -533,6 +657,7 @@ mod tests {
             &graph,
             &switches::OBJECT_GENERATORS,
             &[],
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -542,6 +667,7 @@ mod tests {
             &graph,
             &electrifications::OBJECT_GENERATORS,
             &electrifications::GLOBAL_GENERATORS,
+            &ErrorScope::full(),
         )
         .await
         .is_empty());
@@ -560,8 +686,134 @@ mod tests {
             &graph,
             &buffer_stops::OBJECT_GENERATORS,
             &[],
+            &ErrorScope::full(),
         )
         .await;
         assert_eq!(1, errors.len());
     }
+
+    #[rstest]
+    #[case::track_shortened(vec![CacheOperation::Update(
+        create_track_section_cache("C", 400.).into(),
+    )])]
+    #[case::signal_created(vec![CacheOperation::Create(
+        create_signal_cache("S2", "B", 495.).into(),
+    )])]
+    #[case::buffer_stop_moved(vec![CacheOperation::Update(
+        create_buffer_stop_cache("BF2", "C", 20.).into(),
+    )])]
+    #[case::detector_deleted(vec![CacheOperation::Delete(ObjectRef::new(
+        ObjectType::Detector,
+        "D1",
+    ))])]
+    #[case::switch_deleted(vec![CacheOperation::Delete(ObjectRef::new(
+        ObjectType::Switch,
+        "switch",
+    ))])]
+    async fn incremental_generation_matches_full_generation(
+        #[case] operations: Vec<CacheOperation>,
+    ) {
+        let mut infra_cache = create_small_infra_cache();
+        // Too close to the switch at the beginning of its track
+        infra_cache
+            .add(create_signal_cache("S1", "C", 10.))
+            .unwrap();
+        let errors: Vec<ErrorWithHash> = generate_infra_errors(&infra_cache)
+            .await
+            .into_iter()
+            .map(ErrorWithHash::from)
+            .collect();
+
+        infra_cache.apply_operations(&operations).unwrap();
+        let scope =
+            ErrorScope::from_operations(&operations, &infra_cache, errors.iter().map(|e| &e.error));
+        let mut incremental: HashSet<_> = errors
+            .into_iter()
+            .filter(|e| !scope.contains(&e.error))
+            .map(|e| e.hash)
+            .collect();
+        incremental.extend(hashes(
+            generate_infra_errors_in_scope(&infra_cache, &scope).await,
+        ));
+
+        let full = hashes(generate_infra_errors(&infra_cache).await);
+        assert_eq!(incremental, full);
+    }
+
+    #[rstest]
+    // The gap from A to B grows from 100m to 500m
+    #[case::gap_lengthened(900., None)]
+    // The gap from A to B shrinks from 100m to 70m
+    #[case::gap_shortened(470., Some(70.))]
+    async fn short_electrification_gap_follows_track_length(
+        #[case] length: f64,
+        #[case] gap_length: Option<f64>,
+    ) {
+        let mut infra_cache = create_small_infra_cache();
+        infra_cache
+            .add(create_electrification_cache("Cat_1", vec![("A", 0., 450.)]))
+            .unwrap();
+        infra_cache
+            .add(create_electrification_cache(
+                "Cat_2",
+                vec![("B", 50., 500.)],
+            ))
+            .unwrap();
+        let errors: Vec<ErrorWithHash> = generate_infra_errors(&infra_cache)
+            .await
+            .into_iter()
+            .map(ErrorWithHash::from)
+            .collect();
+        let gap_error = |gap_length| {
+            ErrorWithHash::from(InfraError::new_short_electrification_gap(
+                "Cat_1", "Cat_2", gap_length,
+            ))
+            .hash
+        };
+        assert!(errors.iter().any(|e| e.hash == gap_error(100.)));
+
+        // Only the track section is edited, not the electrifications
+        let operations = vec![CacheOperation::Update(
+            create_track_section_cache("A", length).into(),
+        )];
+        infra_cache.apply_operations(&operations).unwrap();
+        let scope =
+            ErrorScope::from_operations(&operations, &infra_cache, errors.iter().map(|e| &e.error));
+        let mut incremental: HashSet<_> = errors
+            .into_iter()
+            .filter(|e| !scope.contains(&e.error))
+            .map(|e| e.hash)
+            .collect();
+        incremental.extend(hashes(
+            generate_infra_errors_in_scope(&infra_cache, &scope).await,
+        ));
+
+        assert!(!incremental.contains(&gap_error(100.)));
+        if let Some(gap_length) = gap_length {
+            assert!(incremental.contains(&gap_error(gap_length)));
+        }
+        assert_eq!(
+            incremental,
+            hashes(generate_infra_errors(&infra_cache).await)
+        );
+    }
+
+    #[test]
+    fn scope_of_signal_edition() {
+        let infra_cache = create_small_infra_cache();
+        let operations = vec![CacheOperation::Create(
+            create_signal_cache("S1", "B", 100.).into(),
+        )];
+
+        let scope = ErrorScope::from_operations(&operations, &infra_cache, []);
+
+        assert!(scope.regenerates_global(ObjectType::Signal));
+        assert!(!scope.regenerates_all(ObjectType::Signal));
+        assert!(!scope.regenerates_all(ObjectType::Route));
+        assert_eq!(
+            scope.objects(ObjectType::Signal).collect::<Vec<_>>(),
+            vec!["S1"]
+        );
+        assert_eq!(scope.objects(ObjectType::TrackSection).count(), 0);
+    }
 }
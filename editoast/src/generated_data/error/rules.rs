@@ -0,0 +1,256 @@
+//! Registry of the rules checked by the infra validation
+//!
+//! Every rule reports errors of a single [InfraErrorType](super::infra_error::InfraErrorType),
+//! the id of a rule is the label of this error type. Each rule has a default severity which can
+//! be overridden, and the rule can be disabled, either globally by a configuration file or
+//! per infra.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use utoipa::ToSchema;
+
+use super::infra_error::InfraErrorTypeLabel;
+
+editoast_common::schemas! {
+    Severity,
+    RuleSettings,
+    ValidationRule,
+}
+
+/// Non electrified gaps shorter than this length (in meters), a long train, are reported
+/// by the [ShortElectrificationGap](InfraErrorTypeLabel::ShortElectrificationGap) rule
+pub const MIN_ELECTRIFICATION_GAP_LENGTH: f64 = 400.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl InfraErrorTypeLabel {
+    /// The severity of the errors reported by the rule, unless configured otherwise
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Self::InvalidGroup
+            | Self::InvalidReference
+            | Self::InvalidRoute
+            | Self::InvalidSwitchPorts
+            | Self::NodeEndpointsNotUnique
+            | Self::ObjectOutOfPath
+            | Self::OutOfRange
+            | Self::OverlappingSwitches
+            | Self::UnknownPortName => Severity::Error,
+            Self::DuplicatedGroup
+            | Self::EmptyObject
+            | Self::GeometryLengthMismatch
+            | Self::ImplausibleSpeed
+            | Self::MissingBufferStop
+            | Self::MissingDetector
+            | Self::MissingRoute
+            | Self::OddBufferStopLocation
+            | Self::OverlappingElectrifications
+            | Self::OverlappingSpeedSections
+            | Self::ShortElectrificationGap
+            | Self::SignalTooClose
+            | Self::UnusedPort => Severity::Warning,
+        }
+    }
+
+    /// A short description of what the rule checks
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::DuplicatedGroup => "A switch type declares the same group twice",
+            Self::EmptyObject => "An object has no effect on the infrastructure",
+            Self::GeometryLengthMismatch => "A track section geometry is far from its length",
+            Self::ImplausibleSpeed => "A speed limit is not a plausible train speed",
+            Self::InvalidGroup => "A switch references a group unknown to its switch type",
+            Self::InvalidReference => "An object references an object that does not exist",
+            Self::InvalidRoute => "A route path is not continuous",
+            Self::InvalidSwitchPorts => "The ports of a switch do not match its switch type",
+            Self::MissingBufferStop => "A track section end is neither connected nor a buffer stop",
+            Self::MissingDetector => "Two consecutive signals have no detector in between",
+            Self::MissingRoute => "A track section is not covered by any route",
+            Self::NodeEndpointsNotUnique => {
+                "A track section endpoint is shared by several switches"
+            }
+            Self::ObjectOutOfPath => "An object referenced by a route is not on its path",
+            Self::OddBufferStopLocation => "A buffer stop is not located at a track section end",
+            Self::OutOfRange => "A position is outside of its track section",
+            Self::OverlappingElectrifications => "Two electrifications overlap",
+            Self::OverlappingSpeedSections => "Two speed sections with the same tags overlap",
+            Self::OverlappingSwitches => "Two switches share a track section endpoint",
+            Self::ShortElectrificationGap => "A non electrified gap is shorter than a train",
+            Self::SignalTooClose => "A signal is too close to a switch or a buffer stop",
+            Self::UnknownPortName => "A switch references a port unknown to its switch type",
+            Self::UnusedPort => "A port of a switch is not connected",
+        }
+    }
+
+    /// The fixed parameters of the rule, such as its thresholds, by name
+    pub fn parameters(&self) -> BTreeMap<String, f64> {
+        match self {
+            Self::ShortElectrificationGap => {
+                BTreeMap::from([("min_gap_length".to_owned(), MIN_ELECTRIFICATION_GAP_LENGTH)])
+            }
+            _ => BTreeMap::new(),
+        }
+    }
+}
+
+/// Overrides of the default settings of a validation rule
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSettings {
+    /// Whether the rule is checked, left unchanged if not set
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// The severity of the errors reported by the rule, left unchanged if not set
+    #[serde(default)]
+    pub severity: Option<Severity>,
+}
+
+impl RuleSettings {
+    pub fn is_empty(&self) -> bool {
+        self.enabled.is_none() && self.severity.is_none()
+    }
+}
+
+/// A rule checked by the infra validation, with its effective settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ValidationRule {
+    /// The id of the rule, which is the type of the errors it reports
+    #[schema(value_type = InfraErrorTypeLabel)]
+    pub id: InfraErrorTypeLabel,
+    pub description: String,
+    /// The fixed parameters of the rule, such as its thresholds, by name
+    pub parameters: BTreeMap<String, f64>,
+    pub enabled: bool,
+    pub severity: Severity,
+}
+
+impl ValidationRule {
+    fn new(id: InfraErrorTypeLabel) -> Self {
+        Self {
+            id,
+            description: id.description().to_owned(),
+            parameters: id.parameters(),
+            enabled: true,
+            severity: id.default_severity(),
+        }
+    }
+
+    fn apply(&mut self, settings: &RuleSettings) {
+        if let Some(enabled) = settings.enabled {
+            self.enabled = enabled;
+        }
+        if let Some(severity) = settings.severity {
+            self.severity = severity;
+        }
+    }
+
+    /// Whether the rule is enabled and reports errors with the given severity, if any
+    pub fn matches(&self, severity: Option<Severity>) -> bool {
+        self.enabled && severity.is_none_or(|severity| severity == self.severity)
+    }
+}
+
+/// The validation rules configuration shared by all infras
+///
+/// ```yaml
+/// rules:
+///   missing_buffer_stop:
+///     enabled: false
+///   missing_route:
+///     severity: info
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidationRulesConfig {
+    #[serde(default)]
+    rules: HashMap<InfraErrorTypeLabel, RuleSettings>,
+}
+
+impl ValidationRulesConfig {
+    /// Loads the configuration from a YAML (or JSON) file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_yaml::from_reader(file)?)
+    }
+
+    /// Resolves the settings of every rule, given the overrides of an infra
+    ///
+    /// The infra overrides take precedence over the configuration, which takes precedence
+    /// over the defaults of the rules.
+    pub fn resolve(
+        &self,
+        infra_overrides: &HashMap<InfraErrorTypeLabel, RuleSettings>,
+    ) -> Vec<ValidationRule> {
+        InfraErrorTypeLabel::iter()
+            .map(|id| {
+                let mut rule = ValidationRule::new(id);
+                for settings in [self.rules.get(&id), infra_overrides.get(&id)]
+                    .into_iter()
+                    .flatten()
+                {
+                    rule.apply(settings);
+                }
+                rule
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn infra_overrides_take_precedence_over_config() {
+        let config: ValidationRulesConfig = serde_yaml::from_str(
+            r#"
+            rules:
+              missing_buffer_stop:
+                enabled: false
+              missing_route:
+                severity: info
+            "#,
+        )
+        .unwrap();
+        let infra_overrides = HashMap::from([(
+            InfraErrorTypeLabel::MissingRoute,
+            RuleSettings {
+                enabled: None,
+                severity: Some(Severity::Error),
+            },
+        )]);
+
+        let rules: HashMap<_, _> = config
+            .resolve(&infra_overrides)
+            .into_iter()
+            .map(|rule| (rule.id, rule))
+            .collect();
+
+        let missing_buffer_stop = &rules[&InfraErrorTypeLabel::MissingBufferStop];
+        assert!(!missing_buffer_stop.enabled);
+        assert_eq!(missing_buffer_stop.severity, Severity::Warning);
+        let missing_route = &rules[&InfraErrorTypeLabel::MissingRoute];
+        assert!(missing_route.enabled);
+        assert_eq!(missing_route.severity, Severity::Error);
+        let invalid_reference = &rules[&InfraErrorTypeLabel::InvalidReference];
+        assert!(invalid_reference.matches(Some(Severity::Error)));
+        let short_electrification_gap = &rules[&InfraErrorTypeLabel::ShortElectrificationGap];
+        assert_eq!(
+            short_electrification_gap.parameters["min_gap_length"],
+            MIN_ELECTRIFICATION_GAP_LENGTH
+        );
+    }
+}
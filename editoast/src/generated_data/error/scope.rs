@@ -0,0 +1,246 @@
+//! Scope of the errors to regenerate after an edition
+//!
+//! The object errors of most types only depend on the object itself and on the objects of its
+//! track sections. After an edition, only the errors of the edited objects and of their
+//! neighbours need to be regenerated. The types whose errors depend on more objects are listed
+//! in [ERROR_DEPENDENCIES].
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use editoast_schemas::primitives::OSRDIdentified;
+use editoast_schemas::primitives::OSRDObject;
+use editoast_schemas::primitives::ObjectRef;
+use editoast_schemas::primitives::ObjectType;
+use strum::IntoEnumIterator;
+
+use super::infra_error::InfraError;
+use super::infra_error::InfraErrorTypeLabel;
+use crate::infra_cache::operation::CacheOperation;
+use crate::infra_cache::Cache;
+use crate::infra_cache::InfraCache;
+
+/// The objects the errors of a type depend on, besides the objects of their track sections
+struct ErrorDependencies {
+    obj_type: ObjectType,
+    /// Editing objects of these types requires to regenerate all the errors of the type
+    ///
+    /// If the type itself is listed, its objects are checked against each other and any of
+    /// them in scope requires to regenerate all of them.
+    all: &'static [ObjectType],
+    /// Editing objects of these types requires to regenerate the global errors of the type
+    global: &'static [ObjectType],
+    /// The types of the errors reported by the global error generators of the type
+    global_errors: &'static [InfraErrorTypeLabel],
+}
+
+const ERROR_DEPENDENCIES: [ErrorDependencies; 6] = [
+    // Buffer stops are checked against the topology and the other buffer stops of their track
+    ErrorDependencies {
+        obj_type: ObjectType::BufferStop,
+        all: &[
+            ObjectType::BufferStop,
+            ObjectType::Switch,
+            ObjectType::SwitchType,
+            ObjectType::TrackSection,
+        ],
+        global: &[],
+        global_errors: &[InfraErrorTypeLabel::MissingBufferStop],
+    },
+    ErrorDependencies {
+        obj_type: ObjectType::Electrification,
+        all: &[],
+        // Gaps are followed along the topology, across track sections
+        global: &[
+            ObjectType::Electrification,
+            ObjectType::Switch,
+            ObjectType::SwitchType,
+            ObjectType::TrackSection,
+        ],
+        global_errors: &[
+            InfraErrorTypeLabel::OverlappingElectrifications,
+            InfraErrorTypeLabel::ShortElectrificationGap,
+        ],
+    },
+    // Routes are checked along their whole path, and the tracks covered by no route are only
+    // known once every route is checked
+    ErrorDependencies {
+        obj_type: ObjectType::Route,
+        all: &[
+            ObjectType::BufferStop,
+            ObjectType::Detector,
+            ObjectType::Route,
+            ObjectType::Switch,
+            ObjectType::SwitchType,
+            ObjectType::TrackSection,
+        ],
+        global: &[],
+        global_errors: &[InfraErrorTypeLabel::MissingRoute],
+    },
+    ErrorDependencies {
+        obj_type: ObjectType::Signal,
+        all: &[],
+        global: &[ObjectType::Detector, ObjectType::Signal],
+        global_errors: &[InfraErrorTypeLabel::MissingDetector],
+    },
+    ErrorDependencies {
+        obj_type: ObjectType::SpeedSection,
+        all: &[],
+        global: &[ObjectType::SpeedSection],
+        global_errors: &[InfraErrorTypeLabel::OverlappingSpeedSections],
+    },
+    // Switches are checked against each other and against their switch type
+    ErrorDependencies {
+        obj_type: ObjectType::Switch,
+        all: &[ObjectType::Switch, ObjectType::SwitchType],
+        global: &[],
+        global_errors: &[],
+    },
+];
+
+/// The errors to regenerate after an edition
+#[derive(Debug, Default)]
+pub struct ErrorScope {
+    /// Object types whose errors are all regenerated
+    all: HashSet<ObjectType>,
+    /// Object types whose global errors are regenerated
+    global: HashSet<ObjectType>,
+    /// Ids of the objects whose object errors are regenerated, by type
+    objects: HashMap<ObjectType, HashSet<String>>,
+}
+
+impl ErrorScope {
+    /// Regenerates every error of the infra
+    pub fn full() -> Self {
+        Self {
+            all: ObjectType::iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Computes the errors to regenerate once the operations are applied to the infra cache
+    ///
+    /// The errors generated before the edition tell which objects were related to the edited
+    /// ones, since deleted objects and the previous state of updated ones are no longer known.
+    pub fn from_operations<'a>(
+        operations: &[CacheOperation],
+        infra_cache: &InfraCache,
+        current_errors: impl IntoIterator<Item = &'a InfraError>,
+    ) -> Self {
+        let edited: HashSet<ObjectRef> = operations
+            .iter()
+            .map(|operation| match operation {
+                CacheOperation::Create(object) | CacheOperation::Update(object) => object.get_ref(),
+                CacheOperation::Delete(obj_ref) => obj_ref.clone(),
+            })
+            .collect();
+        let edited_types: HashSet<ObjectType> =
+            edited.iter().map(|obj_ref| obj_ref.obj_type).collect();
+
+        let mut scope = Self::default();
+        for dependencies in ERROR_DEPENDENCIES.iter() {
+            let depends_on = |types: &[ObjectType]| types.iter().any(|t| edited_types.contains(t));
+            if depends_on(dependencies.all) {
+                scope.all.insert(dependencies.obj_type);
+            }
+            if depends_on(dependencies.global) {
+                scope.global.insert(dependencies.obj_type);
+            }
+        }
+
+        // Objects with errors referring to an edited object, and objects an edited switch
+        // overlapped, whose view of the topology changes
+        let mut related = edited.clone();
+        for error in current_errors {
+            let Some(reference) = error.sub_type.reference() else {
+                continue;
+            };
+            let error_ref = error.get_ref();
+            if edited.contains(reference) {
+                related.insert(error_ref);
+            } else if error_ref.obj_type == ObjectType::Switch && edited.contains(&error_ref) {
+                related.insert(reference.clone());
+            }
+        }
+        if edited_types.contains(&ObjectType::SwitchType) {
+            for switch in infra_cache.switches().values() {
+                let switch = switch.unwrap_switch();
+                let switch_type = ObjectRef::new(ObjectType::SwitchType, &switch.switch_type);
+                if edited.contains(&switch_type) {
+                    related.insert(switch.get_ref());
+                }
+            }
+        }
+
+        // The objects of a track section are checked against its length and its ends
+        let mut tracks = HashSet::new();
+        for obj_ref in related.iter() {
+            match obj_ref.obj_type {
+                ObjectType::TrackSection => {
+                    tracks.insert(obj_ref.obj_id.clone());
+                }
+                ObjectType::BufferStop | ObjectType::Switch => {
+                    if let Some(object) = infra_cache
+                        .get_objects_by_type(obj_ref.obj_type)
+                        .get(&obj_ref.obj_id)
+                    {
+                        tracks.extend(object.get_track_referenced_id().into_iter().cloned());
+                    }
+                }
+                _ => (),
+            }
+        }
+        for track in tracks {
+            if let Some(refs) = infra_cache.track_sections_refs.get(&track) {
+                related.extend(refs.iter().cloned());
+            }
+        }
+
+        for obj_ref in related {
+            scope
+                .objects
+                .entry(obj_ref.obj_type)
+                .or_default()
+                .insert(obj_ref.obj_id);
+        }
+        for dependencies in ERROR_DEPENDENCIES.iter() {
+            if dependencies.all.contains(&dependencies.obj_type)
+                && scope.objects.contains_key(&dependencies.obj_type)
+            {
+                scope.all.insert(dependencies.obj_type);
+            }
+        }
+        scope
+    }
+
+    /// Whether all the errors of the type are regenerated
+    pub fn regenerates_all(&self, obj_type: ObjectType) -> bool {
+        self.all.contains(&obj_type)
+    }
+
+    /// Whether the global errors of the type are regenerated
+    pub fn regenerates_global(&self, obj_type: ObjectType) -> bool {
+        self.regenerates_all(obj_type) || self.global.contains(&obj_type)
+    }
+
+    /// Ids of the objects of the type whose errors are regenerated, if not all of them
+    pub fn objects(&self, obj_type: ObjectType) -> impl Iterator<Item = &String> {
+        self.objects.get(&obj_type).into_iter().flatten()
+    }
+
+    /// Whether an error is regenerated, and must be replaced by the regenerated ones
+    pub fn contains(&self, error: &InfraError) -> bool {
+        let label = InfraErrorTypeLabel::from(&error.sub_type);
+        if let Some(dependencies) = ERROR_DEPENDENCIES
+            .iter()
+            .find(|dependencies| dependencies.global_errors.contains(&label))
+        {
+            return self.regenerates_global(dependencies.obj_type);
+        }
+        self.regenerates_all(error.obj_type)
+            || self
+                .objects
+                .get(&error.obj_type)
+                .is_some_and(|obj_ids| obj_ids.contains(&error.obj_id))
+    }
+}
@@ -1,16 +1,30 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use super::GlobalErrorGenerator;
 use super::NoContext;
 use crate::generated_data::error::ObjectErrorGenerator;
 use crate::generated_data::infra_error::InfraError;
+use crate::infra_cache::object_cache::SignalCache;
 use crate::infra_cache::Graph;
 use crate::infra_cache::InfraCache;
 use crate::infra_cache::ObjectCache;
+use editoast_schemas::infra::Direction;
+use editoast_schemas::primitives::OSRDObject;
 use editoast_schemas::primitives::ObjectRef;
 use editoast_schemas::primitives::ObjectType;
 
-pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 2] = [
+pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 3] = [
     ObjectErrorGenerator::new(1, check_invalid_ref),
     ObjectErrorGenerator::new(2, check_out_of_range),
+    ObjectErrorGenerator::new(3, check_too_close),
 ];
+pub const GLOBAL_GENERATORS: [GlobalErrorGenerator<NoContext>; 1] =
+    [GlobalErrorGenerator::new(check_missing_detectors)];
+
+/// Signals closer than this distance (in meters) to a switch or a buffer stop are reported
+const MIN_OBSTACLE_DISTANCE: f64 = 20.0;
 
 /// Retrieve invalid refs for signals
 pub fn check_invalid_ref(
@@ -51,14 +65,110 @@ pub fn check_out_of_range(
     }
 }
 
+/// Retrieve signals too close to a switch or a buffer stop of their track section
+pub fn check_too_close(
+    signal: &ObjectCache,
+    infra_cache: &InfraCache,
+    graph: &Graph,
+) -> Vec<InfraError> {
+    let signal = signal.unwrap_signal();
+    let track_cache = infra_cache
+        .track_sections()
+        .get(&signal.track)
+        .unwrap()
+        .unwrap_track_section();
+
+    let mut obstacles = vec![];
+    for (track_endpoint, distance) in [
+        (track_cache.get_begin(), signal.position),
+        (track_cache.get_end(), track_cache.length - signal.position),
+    ] {
+        // Links only connect two track sections, they are not an obstacle
+        if let Some(switch) = graph
+            .get_switch(&track_endpoint)
+            .filter(|switch| switch.switch_type != "link")
+        {
+            obstacles.push((switch.get_ref(), distance));
+        }
+    }
+    let buffer_stops = infra_cache
+        .track_sections_refs
+        .get(&signal.track)
+        .into_iter()
+        .flatten()
+        .filter(|obj_ref| obj_ref.obj_type == ObjectType::BufferStop)
+        .filter_map(|obj_ref| infra_cache.buffer_stops().get(&obj_ref.obj_id));
+    for buffer_stop in buffer_stops {
+        let buffer_stop = buffer_stop.unwrap_buffer_stop();
+        let distance = (buffer_stop.position - signal.position).abs();
+        obstacles.push((buffer_stop.get_ref(), distance));
+    }
+
+    obstacles
+        .into_iter()
+        .filter(|(_, distance)| *distance < MIN_OBSTACLE_DISTANCE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(obstacle, distance)| InfraError::new_signal_too_close(signal, obstacle, distance))
+        .into_iter()
+        .collect()
+}
+
+/// Check that consecutive signals of a track section, facing the same direction,
+/// are separated by a detector
+pub fn check_missing_detectors(infra_cache: &InfraCache, _: &Graph) -> Vec<InfraError> {
+    let mut signals_by_track: HashMap<(&String, Direction), Vec<&SignalCache>> = HashMap::new();
+    for signal in infra_cache.signals().values() {
+        let signal = signal.unwrap_signal();
+        signals_by_track
+            .entry((&signal.track, signal.direction.0))
+            .or_default()
+            .push(signal);
+    }
+    let mut detectors_by_track: HashMap<&String, Vec<f64>> = HashMap::new();
+    for detector in infra_cache.detectors().values() {
+        let detector = detector.unwrap_detector();
+        detectors_by_track
+            .entry(&detector.track)
+            .or_default()
+            .push(detector.position);
+    }
+
+    let mut errors = vec![];
+    for ((track, direction), mut signals) in signals_by_track {
+        // Sort the signals in the order a train meets them
+        signals.sort_by(|a, b| a.position.total_cmp(&b.position));
+        if direction == Direction::StopToStart {
+            signals.reverse();
+        }
+        let detectors = detectors_by_track.get(track);
+        for (previous, signal) in signals.into_iter().tuple_windows() {
+            let begin = previous.position.min(signal.position);
+            let end = previous.position.max(signal.position);
+            let has_detector = detectors
+                .into_iter()
+                .flatten()
+                .any(|position| (begin..=end).contains(position));
+            if !has_detector {
+                errors.push(InfraError::new_missing_detector(signal, &previous.obj_id));
+            }
+        }
+    }
+    errors
+}
+
 #[cfg(test)]
 mod tests {
+    use diesel_json::Json as DieselJson;
+
     use super::check_invalid_ref;
+    use super::check_missing_detectors;
     use super::check_out_of_range;
+    use super::check_too_close;
     use super::InfraError;
     use crate::infra_cache::tests::create_signal_cache;
     use crate::infra_cache::tests::create_small_infra_cache;
     use crate::infra_cache::Graph;
+    use editoast_schemas::infra::Direction;
     use editoast_schemas::primitives::ObjectRef;
     use editoast_schemas::primitives::ObjectType;
 
@@ -92,4 +202,41 @@ mod tests {
         let infra_error = InfraError::new_out_of_range(&signal, "position", 530., [0.0, 500.]);
         assert_eq!(infra_error, errors[0]);
     }
+
+    #[test]
+    fn too_close() {
+        let mut infra_cache = create_small_infra_cache();
+        // The switch is at the end of the track section B, which is 500m long
+        let signal = create_signal_cache("S_error", "B", 490.);
+        infra_cache.add(signal.clone()).unwrap();
+        let errors = check_too_close(
+            &signal.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+        );
+        assert_eq!(1, errors.len());
+        let obj_ref = ObjectRef::new(ObjectType::Switch, "switch");
+        let infra_error = InfraError::new_signal_too_close(&signal, obj_ref, 10.);
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn missing_detector() {
+        let mut infra_cache = create_small_infra_cache();
+        // The detector D1 is at 250m on the track section B
+        for (id, position) in [("S1", 100.), ("S2", 300.), ("S3", 400.)] {
+            infra_cache
+                .add(create_signal_cache(id, "B", position))
+                .unwrap();
+        }
+        let mut opposite = create_signal_cache("S4", "B", 450.);
+        opposite.direction = DieselJson(Direction::StopToStart);
+        infra_cache.add(opposite).unwrap();
+
+        let errors = check_missing_detectors(&infra_cache, &Graph::load(&infra_cache));
+        assert_eq!(1, errors.len());
+        let signal = create_signal_cache("S3", "B", 400.);
+        let infra_error = InfraError::new_missing_detector(&signal, "S2");
+        assert_eq!(infra_error, errors[0]);
+    }
 }
@@ -16,13 +16,17 @@ use editoast_schemas::primitives::OSRDIdentified;
 use editoast_schemas::primitives::ObjectRef;
 use editoast_schemas::primitives::ObjectType;
 
-pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 2] = [
+pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 3] = [
     ObjectErrorGenerator::new(1, check_empty),
+    ObjectErrorGenerator::new(1, check_implausible_speeds),
     ObjectErrorGenerator::new(2, check_speed_section_track_ranges),
 ];
 pub const GLOBAL_GENERATORS: [GlobalErrorGenerator<NoContext>; 1] =
     [GlobalErrorGenerator::new(check_overlapping)];
 
+/// Speed limits above this value (in meters per second, 400 km/h) are reported
+const MAX_PLAUSIBLE_SPEED: f64 = 400. / 3.6;
+
 /// Check if a track section has empty speed section
 pub fn check_empty(speed_section: &ObjectCache, _: &InfraCache, _: &Graph) -> Vec<InfraError> {
     let speed_section = speed_section.unwrap_speed_section();
@@ -33,6 +37,28 @@ pub fn check_empty(speed_section: &ObjectCache, _: &InfraCache, _: &Graph) -> Ve
     }
 }
 
+/// Retrieve speed limits which are not positive or unreasonably high
+pub fn check_implausible_speeds(
+    speed_section: &ObjectCache,
+    _: &InfraCache,
+    _: &Graph,
+) -> Vec<InfraError> {
+    let speed_section = speed_section.unwrap_speed_section();
+    let default_speed = speed_section
+        .speed_limit
+        .iter()
+        .map(|speed| ("speed_limit".to_owned(), speed.0));
+    let speeds_by_tag = speed_section
+        .speed_limit_by_tag
+        .iter()
+        .map(|(tag, speed)| (format!("speed_limit_by_tag.{tag}"), speed.0));
+    default_speed
+        .chain(speeds_by_tag)
+        .filter(|(_, speed)| *speed <= 0. || *speed > MAX_PLAUSIBLE_SPEED)
+        .map(|(field, speed)| InfraError::new_implausible_speed(speed_section, field, speed))
+        .collect()
+}
+
 /// Retrieve invalid refs and out of range errors for speed sections
 pub fn check_speed_section_track_ranges(
     speed_section: &ObjectCache,
@@ -144,6 +170,9 @@ pub fn check_overlapping(infra_cache: &InfraCache, _: &Graph) -> Vec<InfraError>
 
 #[cfg(test)]
 mod tests {
+    use rstest::rstest;
+
+    use super::check_implausible_speeds;
     use super::check_speed_section_track_ranges;
     use super::InfraError;
     use crate::generated_data::error::speed_sections::check_overlapping;
@@ -189,6 +218,29 @@ mod tests {
         assert_eq!(infra_error, errors[0]);
     }
 
+    #[rstest]
+    #[case(42., false)]
+    #[case(0., true)]
+    #[case(300., true)]
+    fn implausible_speed(#[case] speed: f64, #[case] error: bool) {
+        let infra_cache = create_small_infra_cache();
+        let mut speed_section = create_speed_section_cache("SP_error", vec![("A", 20., 220.)]);
+        speed_section.speed_limit = Some(Speed(speed));
+        let errors = check_implausible_speeds(
+            &speed_section.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+        );
+        if error {
+            assert_eq!(errors.len(), 1);
+            let infra_error =
+                InfraError::new_implausible_speed(&speed_section, "speed_limit", speed);
+            assert_eq!(infra_error, errors[0]);
+        } else {
+            assert_eq!(errors.len(), 0);
+        }
+    }
+
     #[test]
     fn overlapping_default() {
         let mut infra_cache = create_small_infra_cache();
@@ -5,11 +5,18 @@ use crate::infra_cache::Graph;
 use crate::infra_cache::InfraCache;
 use crate::infra_cache::ObjectCache;
 
-pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 2] = [
+pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 3] = [
     ObjectErrorGenerator::new(1, check_slope_out_of_range),
     ObjectErrorGenerator::new(1, check_curve_out_of_range),
+    ObjectErrorGenerator::new(1, check_geometry_length),
 ];
 
+/// Geometries this many times longer or shorter than the declared length are reported
+const MAX_GEOMETRY_LENGTH_RATIO: f64 = 3.0;
+/// Geometries closer than this distance (in meters) to the declared length are not reported,
+/// whatever their ratio, since short track sections are often drawn schematically
+const MAX_GEOMETRY_LENGTH_DIFFERENCE: f64 = 500.0;
+
 /// Retrieve slopes out of range
 pub fn check_slope_out_of_range(track: &ObjectCache, _: &InfraCache, _: &Graph) -> Vec<InfraError> {
     let track = track.unwrap_track_section();
@@ -50,11 +57,31 @@ pub fn check_curve_out_of_range(track: &ObjectCache, _: &InfraCache, _: &Graph)
     errors
 }
 
+/// Retrieve track sections whose geometry length is far from their declared length
+pub fn check_geometry_length(track: &ObjectCache, _: &InfraCache, _: &Graph) -> Vec<InfraError> {
+    let track = track.unwrap_track_section();
+    // Empty geometries are not worth comparing
+    if track.geo_length <= 0.0 || track.length <= 0.0 {
+        return vec![];
+    }
+    let ratio = track.geo_length.max(track.length) / track.geo_length.min(track.length);
+    let difference = (track.geo_length - track.length).abs();
+    if ratio > MAX_GEOMETRY_LENGTH_RATIO && difference > MAX_GEOMETRY_LENGTH_DIFFERENCE {
+        vec![InfraError::new_geometry_length_mismatch(
+            track,
+            track.geo_length,
+        )]
+    } else {
+        vec![]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
 
     use super::check_curve_out_of_range;
+    use super::check_geometry_length;
     use super::check_slope_out_of_range;
     use super::InfraError;
     use crate::infra_cache::tests::create_small_infra_cache;
@@ -114,4 +141,28 @@ mod tests {
             assert_eq!(errors.len(), 0);
         }
     }
+
+    #[rstest]
+    #[case(1000., 0., false)]
+    #[case(1000., 2500., false)]
+    #[case(1000., 200., true)]
+    #[case(1000., 4000., true)]
+    #[case(50., 360., false)]
+    fn geometry_length(#[case] length: f64, #[case] geo_length: f64, #[case] error: bool) {
+        let infra_cache = create_small_infra_cache();
+        let mut track = create_track_section_cache("S_error", length);
+        track.geo_length = geo_length;
+        let errors = check_geometry_length(
+            &track.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+        );
+        if error {
+            assert_eq!(errors.len(), 1);
+            let infra_error = InfraError::new_geometry_length_mismatch(&track, geo_length);
+            assert_eq!(infra_error, errors[0]);
+        } else {
+            assert_eq!(errors.len(), 0);
+        }
+    }
 }
@@ -24,7 +24,8 @@ use diesel_async::RunQueryDsl;
 use electrification::ElectrificationLayer;
 pub use error::generate_infra_errors;
 pub use error::infra_error;
-use error::ErrorLayer;
+pub use error::rules;
+pub use error::ErrorLayer;
 use neutral_section::NeutralSectionLayer;
 use neutral_sign::NeutralSignLayer;
 use operational_point::OperationalPointLayer;
@@ -34,6 +34,7 @@ use editoast_schemas::infra::SingleSlipSwitch;
 use editoast_schemas::infra::SpeedSection;
 use editoast_schemas::infra::SwitchType;
 use editoast_schemas::infra::TrackEndpoint;
+use editoast_schemas::infra::TrackSection;
 use editoast_schemas::infra::Waypoint;
 use editoast_schemas::primitives::OSRDIdentified;
 use editoast_schemas::primitives::OSRDObject;
@@ -287,6 +288,7 @@ impl From<TrackQueryable> for TrackSectionCache {
         let geo: Geometry =
             serde_json::from_str(&track.geo).expect("invalid track section geometry");
         Self {
+            geo_length: TrackSection::line_length(&geo),
             obj_id: track.obj_id,
             length: track.length,
             curves: serde_json::from_str(&track.curves).unwrap(),
@@ -444,7 +446,7 @@ impl InfraCache {
 
         // Load signal tracks references
         sql_query(
-            "SELECT obj_id, data->>'track' AS track, (data->>'position')::float AS position, data->'logical_signals' as logical_signals FROM infra_object_signal WHERE infra_id = $1")
+            "SELECT obj_id, data->>'track' AS track, (data->>'position')::float AS position, data->'direction' AS direction, data->'logical_signals' as logical_signals FROM infra_object_signal WHERE infra_id = $1")
         .bind::<BigInt, _>(infra_id)
         .load::<SignalCache>(conn.write().await.deref_mut()).await?.into_iter().try_for_each(|signal|
             infra_cache.add(signal)
@@ -567,6 +569,38 @@ impl InfraCache {
             .unwrap_or_default()
     }
 
+    /// The geographic extent of the given objects, which is the union of the bounding boxes
+    /// of their track sections
+    ///
+    /// Objects missing from the cache, or not located on track sections, are ignored.
+    pub fn geographic_extent<'a>(
+        &self,
+        objects: impl IntoIterator<Item = &'a ObjectRef>,
+    ) -> BoundingBox {
+        let mut extent = BoundingBox::default();
+        for obj_ref in objects {
+            let Some(object) = self
+                .get_objects_by_type(obj_ref.obj_type)
+                .get(&obj_ref.obj_id)
+            else {
+                continue;
+            };
+            let tracks = match object {
+                ObjectCache::TrackSection(track) => vec![track],
+                object => object
+                    .get_track_referenced_id()
+                    .into_iter()
+                    .filter_map(|track_id| self.track_sections().get(track_id))
+                    .map(ObjectCache::unwrap_track_section)
+                    .collect(),
+            };
+            for track in tracks {
+                extent.union(&track.bbox_geo);
+            }
+        }
+        extent
+    }
+
     /// Apply delete operation to the infra cache
     pub fn apply_delete(&mut self, object_ref: &ObjectRef) -> Result<()> {
         let obj_cache = self.objects[object_ref.obj_type]
@@ -897,6 +931,7 @@ pub mod tests {
     use crate::infra_cache::SwitchCache;
     use crate::models::fixtures::create_empty_infra;
     use crate::models::fixtures::create_infra_object;
+    use diesel_json::Json as DieselJson;
     use editoast_models::DbConnectionPoolV2;
     use editoast_schemas::infra::ApplicableDirections;
     use editoast_schemas::infra::ApplicableDirectionsTrackRange;
@@ -916,6 +951,8 @@ pub mod tests {
     use editoast_schemas::primitives::Identifier;
     use editoast_schemas::primitives::NonBlankString;
     use editoast_schemas::primitives::OSRDIdentified;
+    use editoast_schemas::primitives::ObjectRef;
+    use editoast_schemas::primitives::ObjectType;
 
     #[rstest]
     async fn load_track_section() {
@@ -1089,6 +1126,37 @@ pub mod tests {
         assert_eq!(refs.get("InvalidRef").unwrap().len(), 1);
     }
 
+    #[test]
+    fn geographic_extent() {
+        let mut infra_cache = InfraCache::default();
+        for (id, bbox_geo) in [
+            ("A", BoundingBox((0., 0.), (1., 1.))),
+            ("B", BoundingBox((2., 2.), (3., 3.))),
+        ] {
+            infra_cache
+                .add(TrackSectionCache {
+                    bbox_geo,
+                    ..create_track_section_cache(id, 500.)
+                })
+                .unwrap();
+        }
+        infra_cache
+            .add(create_signal_cache("S1", "B", 10.))
+            .unwrap();
+
+        let signal = ObjectRef::new(ObjectType::Signal, "S1");
+        let unknown = ObjectRef::new(ObjectType::Signal, "unknown");
+        assert_eq!(
+            infra_cache.geographic_extent([&signal, &unknown]),
+            BoundingBox((2., 2.), (3., 3.))
+        );
+        let track = ObjectRef::new(ObjectType::TrackSection, "A");
+        assert_eq!(
+            infra_cache.geographic_extent([&signal, &track]),
+            BoundingBox((0., 0.), (3., 3.))
+        );
+    }
+
     pub fn create_track_section_cache<T: AsRef<str>>(obj_id: T, length: f64) -> TrackSectionCache {
         TrackSectionCache {
             obj_id: obj_id.as_ref().into(),
@@ -1111,6 +1179,7 @@ pub mod tests {
             obj_id: obj_id.as_ref().into(),
             track: track.as_ref().into(),
             position,
+            direction: DieselJson(Direction::StartToStop),
             logical_signals: Default::default(),
         }
     }
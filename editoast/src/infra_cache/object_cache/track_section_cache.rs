@@ -26,6 +26,9 @@ pub struct TrackSectionCache {
     pub curves: Vec<Curve>,
     #[derivative(Hash = "ignore", PartialEq = "ignore")]
     pub bbox_geo: BoundingBox,
+    /// The length of the geometry, which may differ from the declared `length`
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    pub geo_length: f64,
 }
 
 impl OSRDTyped for TrackSectionCache {
@@ -60,6 +63,7 @@ impl From<TrackSection> for TrackSectionCache {
     fn from(track: TrackSection) -> Self {
         TrackSectionCache {
             bbox_geo: track.geo_bbox(),
+            geo_length: track.geo_length(),
             obj_id: track.id.0,
             length: track.length,
             curves: track.curves,
@@ -7,8 +7,11 @@ use std::ops::Deref as _;
 use editoast_derive::EditoastError;
 use editoast_schemas::primitives::OSRDObject as _;
 use json_patch::Patch;
+use json_patch::PatchOperation;
+use json_patch::TestOperation;
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::json;
 use thiserror::Error;
 pub use update::UpdateOperation;
 use utoipa::ToSchema;
@@ -137,6 +140,15 @@ impl CacheOperation {
 }
 
 impl Operation {
+    /// The object the operation applies to
+    pub fn get_ref(&self) -> ObjectRef {
+        match self {
+            Operation::Create(railjson_object) => railjson_object.get_ref(),
+            Operation::Update(update) => ObjectRef::new(update.obj_type, &update.obj_id),
+            Operation::Delete(deletion) => deletion.clone().into(),
+        }
+    }
+
     pub async fn apply(
         &self,
         infra_id: i64,
@@ -157,6 +169,57 @@ impl Operation {
             }
         }
     }
+
+    /// Applies the operation and computes the operation undoing it
+    ///
+    /// The inverse of an update starts with a `test` of the whole object against the
+    /// result of the update. Applying it fails if the object was modified in the meantime,
+    /// instead of silently overwriting the newer changes.
+    pub async fn apply_and_invert(
+        &self,
+        infra_id: i64,
+        conn: &mut DbConnection,
+    ) -> Result<(Option<InfraObject>, Operation)> {
+        match self {
+            Operation::Create(railjson_object) => {
+                let railjson = self.apply(infra_id, conn).await?;
+                let inverse = Operation::Delete(railjson_object.get_ref().into());
+                Ok((railjson, inverse))
+            }
+            Operation::Update(update) => {
+                let previous_data =
+                    update::get_object_data(infra_id, update.obj_type, &update.obj_id, conn)
+                        .await?;
+                let railjson = self.apply(infra_id, conn).await?;
+                let mut data = previous_data.clone();
+                json_patch::patch(&mut data, &update.railjson_patch)?;
+                let mut railjson_patch = Patch(vec![PatchOperation::Test(TestOperation {
+                    value: data.clone(),
+                    ..Default::default()
+                })]);
+                railjson_patch
+                    .0
+                    .extend(json_patch::diff(&data, &previous_data).0);
+                let inverse = Operation::Update(UpdateOperation {
+                    obj_id: update.obj_id.clone(),
+                    obj_type: update.obj_type,
+                    railjson_patch,
+                });
+                Ok((railjson, inverse))
+            }
+            Operation::Delete(deletion) => {
+                let previous_data =
+                    update::get_object_data(infra_id, deletion.obj_type, &deletion.obj_id, conn)
+                        .await?;
+                let previous_object: InfraObject = serde_json::from_value(json!({
+                    "railjson": previous_data,
+                    "obj_type": deletion.obj_type.to_string(),
+                }))?;
+                let railjson = self.apply(infra_id, conn).await?;
+                Ok((railjson, Operation::Create(Box::new(previous_object))))
+            }
+        }
+    }
 }
 
 pub fn patch_infra_object(infra_object: &InfraObject, json_patch: &Patch) -> Result<InfraObject> {
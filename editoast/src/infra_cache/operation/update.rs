@@ -35,25 +35,8 @@ pub struct UpdateOperation {
 impl UpdateOperation {
     pub async fn apply(&self, infra_id: i64, conn: &mut DbConnection) -> Result<InfraObject> {
         // Load object
-
-        let mut obj: DataObject = match sql_query(format!(
-            "SELECT data FROM {} WHERE infra_id = $1 AND obj_id = $2",
-            get_table(&self.obj_type)
-        ))
-        .bind::<BigInt, _>(infra_id)
-        .bind::<Text, _>(&self.obj_id)
-        .get_result(conn.write().await.deref_mut())
-        .await
-        {
-            Ok(obj) => obj,
-            Err(DieselError::NotFound) => {
-                return Err(OperationError::ObjectNotFound {
-                    obj_id: self.obj_id.clone(),
-                    infra_id,
-                }
-                .into())
-            }
-            Err(err) => return Err(err.into()),
+        let mut obj = DataObject {
+            data: get_object_data(infra_id, self.obj_type, &self.obj_id, conn).await?,
         };
 
         // Apply and check patch
@@ -81,6 +64,32 @@ impl UpdateOperation {
     }
 }
 
+/// Loads the RailJSON data of an infra object, as stored in the database
+pub(super) async fn get_object_data(
+    infra_id: i64,
+    obj_type: ObjectType,
+    obj_id: &str,
+    conn: &mut DbConnection,
+) -> Result<Value> {
+    match sql_query(format!(
+        "SELECT data FROM {} WHERE infra_id = $1 AND obj_id = $2",
+        get_table(&obj_type)
+    ))
+    .bind::<BigInt, _>(infra_id)
+    .bind::<Text, _>(obj_id)
+    .get_result::<DataObject>(conn.write().await.deref_mut())
+    .await
+    {
+        Ok(obj) => Ok(obj.data),
+        Err(DieselError::NotFound) => Err(OperationError::ObjectNotFound {
+            obj_id: obj_id.to_owned(),
+            infra_id,
+        }
+        .into()),
+        Err(err) => Err(err.into()),
+    }
+}
+
 #[derive(QueryableByName)]
 struct DataObject {
     #[diesel(sql_type = Jsonb)]
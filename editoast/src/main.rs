@@ -8,6 +8,7 @@ mod generated_data;
 mod infra_cache;
 mod map;
 mod models;
+mod simulation_store;
 mod valkey_utils;
 mod views;
 
@@ -23,6 +24,8 @@ use client::roles;
 use client::roles::RolesCommand;
 use client::runserver::runserver;
 use client::search_commands::*;
+use client::simulation_store_commands::*;
+use client::stdcm_commands::*;
 use client::stdcm_search_env_commands::handle_stdcm_search_env_command;
 use client::timetables_commands::*;
 use client::user;
@@ -128,6 +131,7 @@ async fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
             .await?;
 
     let valkey_config = client.valkey_config;
+    let simulation_store_config = client.simulation_store_config;
 
     match client.color {
         Color::Never => colored::control::set_override(false),
@@ -136,9 +140,11 @@ async fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
     }
 
     match client.command {
-        Commands::Runserver(args) => runserver(args, pg_config, valkey_config)
-            .await
-            .map_err(Into::into),
+        Commands::Runserver(args) => {
+            runserver(args, pg_config, valkey_config, simulation_store_config)
+                .await
+                .map_err(Into::into)
+        }
         Commands::ImportRollingStock(args) => import_rolling_stock(args, db_pool.into()).await,
         Commands::ImportTowedRollingStock(args) => {
             import_towed_rolling_stock(args, db_pool.into()).await
@@ -169,10 +175,13 @@ async fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
         Commands::Infra(subcommand) => match subcommand {
             InfraCommands::Clone(args) => clone_infra(args, db_pool.into()).await,
             InfraCommands::Clear(args) => clear_infra(args, db_pool.into(), valkey_config).await,
+            InfraCommands::Diff(args) => diff_infras(args, db_pool.into()).await,
             InfraCommands::Generate(args) => {
                 generate_infra(args, db_pool.into(), valkey_config).await
             }
             InfraCommands::ImportRailjson(args) => import_railjson(args, db_pool.into()).await,
+            InfraCommands::ExportTiles(args) => export_tiles(args, db_pool.into()).await,
+            InfraCommands::ExportGeo(args) => export_geo(args, db_pool.into()).await,
         },
         Commands::Timetables(subcommand) => match subcommand {
             TimetablesCommands::Import(args) => trains_import(args, db_pool.into()).await,
@@ -181,6 +190,12 @@ async fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
         Commands::STDCMSearchEnv(subcommand) => {
             handle_stdcm_search_env_command(subcommand, db_pool).await
         }
+        Commands::Stdcm(subcommand) => match subcommand {
+            StdcmCommands::Batch(args) => {
+                stdcm_batch(args, db_pool.into(), valkey_config, simulation_store_config).await
+            }
+            StdcmCommands::Replay(args) => stdcm_replay(args, db_pool.into()).await,
+        },
         Commands::Roles(roles_command) => match roles_command {
             RolesCommand::ListRoles => {
                 roles::list_roles();
@@ -233,6 +248,14 @@ async fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
                 .await
                 .map_err(Into::into)
         }
+        Commands::SimulationStore(subcommand) => match subcommand {
+            SimulationStoreCommands::Warm(args) => {
+                warm_simulation_store(args, db_pool.into(), simulation_store_config).await
+            }
+            SimulationStoreCommands::Invalidate(args) => {
+                invalidate_simulation_store(args, db_pool.into(), simulation_store_config).await
+            }
+        },
     }
 }
 
@@ -0,0 +1,510 @@
+//! Archives of pre-rendered vector tiles
+//!
+//! Two single file formats are supported:
+//!
+//! - [MBTiles](https://github.com/mapbox/mbtiles-spec), an SQLite database,
+//! - [PMTiles](https://github.com/protomaps/PMTiles/blob/main/spec/v3/spec.md) (version 3),
+//!   which can be served as a static file.
+//!
+//! In both formats, tiles are stored gzip compressed.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use editoast_schemas::primitives::BoundingBox;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::json;
+use sha1::Digest;
+use sha1::Sha1;
+
+use super::Tile;
+
+/// Description of the tiles of an archive
+#[derive(Debug, Clone)]
+pub struct TilesetMetadata {
+    pub name: String,
+    pub attribution: String,
+    /// The area covered by the tiles, in `(longitude, latitude)`
+    pub bounds: BoundingBox,
+    pub min_zoom: u64,
+    pub max_zoom: u64,
+    /// The names of the layers of the tiles
+    pub vector_layers: Vec<String>,
+}
+
+impl TilesetMetadata {
+    fn vector_layers_json(&self) -> serde_json::Value {
+        let vector_layers: Vec<_> = self
+            .vector_layers
+            .iter()
+            .map(|layer| json!({ "id": layer, "fields": {} }))
+            .collect();
+        json!({ "vector_layers": vector_layers })
+    }
+
+    fn center(&self) -> (f64, f64) {
+        let BoundingBox((west, south), (east, north)) = &self.bounds;
+        ((west + east) / 2., (south + north) / 2.)
+    }
+}
+
+/// An archive file being written
+pub enum TileArchive {
+    MbTiles(MbTilesWriter),
+    PmTiles(PmTilesWriter),
+}
+
+impl TileArchive {
+    /// Adds a tile, given as an uncompressed MVT
+    pub fn add_tile(&mut self, tile: &Tile, mvt: &[u8]) -> anyhow::Result<()> {
+        let data = gzip(mvt)?;
+        match self {
+            Self::MbTiles(writer) => writer.add_tile(tile, &data),
+            Self::PmTiles(writer) => writer.add_tile(tile, &data),
+        }
+    }
+
+    /// Writes the metadata and completes the archive
+    pub fn finish(self, metadata: &TilesetMetadata) -> anyhow::Result<()> {
+        match self {
+            Self::MbTiles(writer) => writer.finish(metadata),
+            Self::PmTiles(writer) => writer.finish(metadata),
+        }
+    }
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Writes an MBTiles archive, in a single transaction
+pub struct MbTilesWriter {
+    connection: rusqlite::Connection,
+}
+
+impl MbTilesWriter {
+    /// Creates the archive, replacing any existing file
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let connection = rusqlite::Connection::open(path)?;
+        connection.execute_batch(
+            "
+            CREATE TABLE metadata (name TEXT, value TEXT);
+            CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);
+            CREATE UNIQUE INDEX tile_index ON tiles (zoom_level, tile_column, tile_row);
+            BEGIN;
+            ",
+        )?;
+        Ok(Self { connection })
+    }
+
+    fn add_tile(&mut self, tile: &Tile, data: &[u8]) -> anyhow::Result<()> {
+        // MBTiles rows follow the TMS scheme, numbered from the south
+        let tile_row = (1_i64 << tile.z) - 1 - tile.y as i64;
+        self.connection.execute(
+            "INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![tile.z as i64, tile.x as i64, tile_row, data],
+        )?;
+        Ok(())
+    }
+
+    fn finish(self, metadata: &TilesetMetadata) -> anyhow::Result<()> {
+        let BoundingBox((west, south), (east, north)) = &metadata.bounds;
+        let (center_longitude, center_latitude) = metadata.center();
+        let entries = [
+            ("name", metadata.name.clone()),
+            ("format", "pbf".to_owned()),
+            ("attribution", metadata.attribution.clone()),
+            ("bounds", format!("{west},{south},{east},{north}")),
+            (
+                "center",
+                format!("{center_longitude},{center_latitude},{}", metadata.min_zoom),
+            ),
+            ("minzoom", metadata.min_zoom.to_string()),
+            ("maxzoom", metadata.max_zoom.to_string()),
+            ("json", metadata.vector_layers_json().to_string()),
+        ];
+        for (name, value) in entries {
+            self.connection.execute(
+                "INSERT INTO metadata (name, value) VALUES (?1, ?2)",
+                rusqlite::params![name, value],
+            )?;
+        }
+        self.connection.execute_batch("COMMIT;")?;
+        Ok(())
+    }
+}
+
+const PMTILES_HEADER_LENGTH: usize = 127;
+/// The header and the root directory must fit in the first 16 KiB of the archive
+const PMTILES_ROOT_MAX_LENGTH: usize = 16_384 - PMTILES_HEADER_LENGTH;
+
+/// An entry of a PMTiles directory
+///
+/// Entries of tiles point to the tile data section, entries with a `run_length` of 0 point to
+/// a leaf directory.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    tile_id: u64,
+    offset: u64,
+    length: u64,
+    run_length: u64,
+}
+
+/// Writes a PMTiles archive
+///
+/// Since the directories come before the tile data, the tile data is first written to a
+/// temporary file next to the archive. Identical tiles are only stored once.
+pub struct PmTilesWriter {
+    path: PathBuf,
+    data_path: PathBuf,
+    data: BufWriter<File>,
+    data_length: u64,
+    entries: Vec<Entry>,
+    contents: HashMap<Vec<u8>, (u64, u64)>,
+}
+
+impl PmTilesWriter {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let mut data_path = path.as_os_str().to_owned();
+        data_path.push(".part");
+        let data_path = PathBuf::from(data_path);
+        Ok(Self {
+            path: path.to_owned(),
+            data: BufWriter::new(File::create(&data_path)?),
+            data_path,
+            data_length: 0,
+            entries: vec![],
+            contents: HashMap::new(),
+        })
+    }
+
+    fn add_tile(&mut self, tile: &Tile, data: &[u8]) -> anyhow::Result<()> {
+        let hash = Sha1::digest(data).to_vec();
+        let (offset, length) = match self.contents.get(&hash) {
+            Some(location) => *location,
+            None => {
+                let location = (self.data_length, data.len() as u64);
+                self.data.write_all(data)?;
+                self.data_length += data.len() as u64;
+                self.contents.insert(hash, location);
+                location
+            }
+        };
+        self.entries.push(Entry {
+            tile_id: tile_id(tile),
+            offset,
+            length,
+            run_length: 1,
+        });
+        Ok(())
+    }
+
+    fn finish(self, metadata: &TilesetMetadata) -> anyhow::Result<()> {
+        let Self {
+            path,
+            data_path,
+            data,
+            data_length,
+            mut entries,
+            contents,
+        } = self;
+        data.into_inner().map_err(|error| error.into_error())?;
+
+        entries.sort_by_key(|entry| entry.tile_id);
+        let entries = merge_runs(entries);
+        let (root, leaves) = build_directories(&entries);
+        let metadata_json = gzip(
+            json!({
+                "name": metadata.name,
+                "attribution": metadata.attribution,
+                "vector_layers": metadata.vector_layers_json()["vector_layers"],
+            })
+            .to_string()
+            .as_bytes(),
+        )?;
+
+        let root_offset = PMTILES_HEADER_LENGTH as u64;
+        let metadata_offset = root_offset + root.len() as u64;
+        let leaves_offset = metadata_offset + metadata_json.len() as u64;
+        let data_offset = leaves_offset + leaves.len() as u64;
+
+        let mut header = Vec::with_capacity(PMTILES_HEADER_LENGTH);
+        header.extend_from_slice(b"PMTiles");
+        header.push(3);
+        for value in [
+            root_offset,
+            root.len() as u64,
+            metadata_offset,
+            metadata_json.len() as u64,
+            leaves_offset,
+            leaves.len() as u64,
+            data_offset,
+            data_length,
+            entries.iter().map(|entry| entry.run_length).sum(),
+            entries.len() as u64,
+            contents.len() as u64,
+        ] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        let BoundingBox((west, south), (east, north)) = &metadata.bounds;
+        let (center_longitude, center_latitude) = metadata.center();
+        // Not clustered, gzip internal and tile compressions, MVT tiles
+        header.extend_from_slice(&[0, 2, 2, 1]);
+        header.extend_from_slice(&[metadata.min_zoom as u8, metadata.max_zoom as u8]);
+        for coordinate in [west, south, east, north] {
+            header.extend_from_slice(&e7(*coordinate).to_le_bytes());
+        }
+        header.push(metadata.min_zoom as u8);
+        for coordinate in [center_longitude, center_latitude] {
+            header.extend_from_slice(&e7(coordinate).to_le_bytes());
+        }
+        assert_eq!(header.len(), PMTILES_HEADER_LENGTH);
+
+        let mut archive = BufWriter::new(File::create(&path)?);
+        archive.write_all(&header)?;
+        archive.write_all(&root)?;
+        archive.write_all(&metadata_json)?;
+        archive.write_all(&leaves)?;
+        std::io::copy(&mut File::open(&data_path)?, &mut archive)?;
+        archive.flush()?;
+        std::fs::remove_file(data_path)?;
+        Ok(())
+    }
+}
+
+/// Coordinates are stored as integers, in units of 10⁻⁷ degrees
+fn e7(coordinate: f64) -> i32 {
+    (coordinate * 10_000_000.).round() as i32
+}
+
+/// The position of the tile along the Hilbert curves of the successive zoom levels
+fn tile_id(tile: &Tile) -> u64 {
+    let Tile { mut x, mut y, z } = *tile;
+    let tiles_of_lower_zooms = ((1_u64 << (2 * z)) - 1) / 3;
+    let n = 1_u64 << z;
+    let mut position = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = u64::from(x & s > 0);
+        let ry = u64::from(y & s > 0);
+        position += s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    tiles_of_lower_zooms + position
+}
+
+/// Merges the entries of consecutive tiles sharing the same content, entries must be sorted
+fn merge_runs(entries: Vec<Entry>) -> Vec<Entry> {
+    let mut merged: Vec<Entry> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match merged.last_mut() {
+            Some(last)
+                if last.tile_id + last.run_length == entry.tile_id
+                    && last.offset == entry.offset
+                    && last.length == entry.length =>
+            {
+                last.run_length += 1;
+            }
+            _ => merged.push(entry),
+        }
+    }
+    merged
+}
+
+/// Builds the gzip compressed root directory and leaf directories
+///
+/// Leaf directories are only used when the entries don't fit in the root directory.
+fn build_directories(entries: &[Entry]) -> (Vec<u8>, Vec<u8>) {
+    let root = gzip(&serialize_directory(entries)).expect("writing to memory can't fail");
+    if root.len() <= PMTILES_ROOT_MAX_LENGTH {
+        return (root, vec![]);
+    }
+    let mut leaf_size = 4096;
+    loop {
+        let mut leaves = vec![];
+        let mut root_entries = vec![];
+        for chunk in entries.chunks(leaf_size) {
+            let leaf = gzip(&serialize_directory(chunk)).expect("writing to memory can't fail");
+            root_entries.push(Entry {
+                tile_id: chunk[0].tile_id,
+                offset: leaves.len() as u64,
+                length: leaf.len() as u64,
+                run_length: 0,
+            });
+            leaves.extend(leaf);
+        }
+        let root = gzip(&serialize_directory(&root_entries)).expect("writing to memory can't fail");
+        if root.len() <= PMTILES_ROOT_MAX_LENGTH {
+            return (root, leaves);
+        }
+        leaf_size *= 2;
+    }
+}
+
+fn serialize_directory(entries: &[Entry]) -> Vec<u8> {
+    let mut buffer = vec![];
+    write_varint(&mut buffer, entries.len() as u64);
+    let mut last_id = 0;
+    for entry in entries {
+        write_varint(&mut buffer, entry.tile_id - last_id);
+        last_id = entry.tile_id;
+    }
+    for entry in entries {
+        write_varint(&mut buffer, entry.run_length);
+    }
+    for entry in entries {
+        write_varint(&mut buffer, entry.length);
+    }
+    let mut previous: Option<&Entry> = None;
+    for entry in entries {
+        // Offsets following the previous entry are stored as 0, others are shifted by one
+        match previous {
+            Some(previous) if entry.offset == previous.offset + previous.length => {
+                write_varint(&mut buffer, 0)
+            }
+            _ => write_varint(&mut buffer, entry.offset + 1),
+        }
+        previous = Some(entry);
+    }
+    buffer
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn metadata() -> TilesetMetadata {
+        TilesetMetadata {
+            name: "small_infra".to_owned(),
+            attribution: String::new(),
+            bounds: BoundingBox((2.3, 48.8), (2.4, 48.9)),
+            min_zoom: 1,
+            max_zoom: 2,
+            vector_layers: vec!["track_sections".to_owned()],
+        }
+    }
+
+    #[test]
+    fn tile_ids_follow_hilbert_curves() {
+        let ids: Vec<_> = [
+            (0, 0, 0),
+            (1, 0, 0),
+            (1, 0, 1),
+            (1, 1, 1),
+            (1, 1, 0),
+            (2, 0, 0),
+        ]
+        .into_iter()
+        .map(|(z, x, y)| tile_id(&Tile { x, y, z }))
+        .collect();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(tile_id(&Tile { x: 3, y: 0, z: 2 }), 20);
+    }
+
+    #[test]
+    fn serialize_directory_with_runs() {
+        let entries = merge_runs(vec![
+            Entry {
+                tile_id: 1,
+                offset: 0,
+                length: 10,
+                run_length: 1,
+            },
+            Entry {
+                tile_id: 2,
+                offset: 0,
+                length: 10,
+                run_length: 1,
+            },
+            Entry {
+                tile_id: 3,
+                offset: 10,
+                length: 300,
+                run_length: 1,
+            },
+        ]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            serialize_directory(&entries),
+            vec![2, 1, 2, 2, 1, 10, 172, 2, 1, 0]
+        );
+    }
+
+    #[test]
+    fn write_pmtiles_archive() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tiles.pmtiles");
+        let mut archive = TileArchive::PmTiles(PmTilesWriter::create(&path).unwrap());
+        archive
+            .add_tile(&Tile { x: 1, y: 0, z: 1 }, b"tile")
+            .unwrap();
+        archive
+            .add_tile(&Tile { x: 0, y: 0, z: 1 }, b"tile")
+            .unwrap();
+        archive.finish(&metadata()).unwrap();
+
+        let content = std::fs::read(&path).unwrap();
+        assert_eq!(&content[..8], b"PMTiles\x03");
+        let counter =
+            |offset: usize| u64::from_le_bytes(content[offset..offset + 8].try_into().unwrap());
+        // Addressed tiles, tile entries and tile contents
+        assert_eq!((counter(72), counter(80), counter(88)), (2, 2, 1));
+        assert!(!directory.path().join("tiles.pmtiles.part").exists());
+    }
+
+    #[test]
+    fn write_mbtiles_archive() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tiles.mbtiles");
+        let mut archive = TileArchive::MbTiles(MbTilesWriter::create(&path).unwrap());
+        archive
+            .add_tile(&Tile { x: 1, y: 0, z: 1 }, b"tile")
+            .unwrap();
+        archive.finish(&metadata()).unwrap();
+
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        let tile_row: i64 = connection
+            .query_row(
+                "SELECT tile_row FROM tiles WHERE zoom_level = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(tile_row, 1);
+        let format: String = connection
+            .query_row(
+                "SELECT value FROM metadata WHERE name = 'format'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(format, "pbf");
+    }
+}
@@ -1,13 +1,34 @@
+use std::f64::consts::PI;
+
+use editoast_schemas::primitives::BoundingBox;
+
 use crate::client::get_app_version;
 
 /// Web mercator coordinates
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Tile {
     pub x: u64,
     pub y: u64,
     pub z: u64,
 }
 
+impl Tile {
+    /// The tiles of a zoom level covering a bounding box, given in `(longitude, latitude)`
+    pub fn covering(bbox: &BoundingBox, z: u64) -> impl Iterator<Item = Tile> {
+        let n = 1_u64 << z;
+        let to_index = |position: f64| ((position * n as f64).floor().max(0.) as u64).min(n - 1);
+        let column = |longitude: f64| to_index((longitude + 180.) / 360.);
+        let row = |latitude: f64| to_index((1. - latitude.to_radians().tan().asinh() / PI) / 2.);
+        let (columns, rows) = if bbox.is_valid() {
+            let BoundingBox((west, south), (east, north)) = bbox;
+            (column(*west)..=column(*east), row(*north)..=row(*south))
+        } else {
+            (1..=0, 1..=0)
+        };
+        columns.flat_map(move |x| rows.clone().map(move |y| Tile { x, y, z }))
+    }
+}
+
 pub fn get_layer_cache_prefix(layer_name: &str, infra_id: i64) -> String {
     let version = get_app_version().unwrap_or("default".into());
     format!("editoast.{version}.layer.{layer_name}.infra_{infra_id}")
@@ -32,6 +53,8 @@ pub fn get_cache_tile_key(view_prefix: &str, tile: &Tile) -> String {
 #[cfg(test)]
 mod tests {
 
+    use editoast_schemas::primitives::BoundingBox;
+
     use super::get_cache_tile_key;
     use super::get_layer_cache_prefix;
     use super::get_view_cache_prefix;
@@ -63,4 +86,34 @@ mod tests {
             "editoast.default.layer.track_sections.infra_1.tile/3/1/2"
         );
     }
+
+    #[test]
+    fn test_tiles_covering() {
+        let bbox = BoundingBox((2.3, 48.8), (2.4, 48.9));
+        assert_eq!(
+            Tile::covering(&bbox, 1).collect::<Vec<_>>(),
+            vec![Tile { x: 1, y: 0, z: 1 }]
+        );
+        let tiles: Vec<_> = Tile::covering(&bbox, 12)
+            .map(|tile| (tile.x, tile.y))
+            .collect();
+        assert_eq!(
+            tiles,
+            vec![
+                (2074, 1408),
+                (2074, 1409),
+                (2074, 1410),
+                (2075, 1408),
+                (2075, 1409),
+                (2075, 1410)
+            ]
+        );
+        // Bounding boxes outside of the projection are clamped to the border tiles
+        let bbox = BoundingBox((-190., 86.), (-179.9, 89.));
+        assert_eq!(
+            Tile::covering(&bbox, 2).collect::<Vec<_>>(),
+            vec![Tile { x: 0, y: 0, z: 2 }]
+        );
+        assert_eq!(Tile::covering(&BoundingBox::default(), 12).count(), 0);
+    }
 }
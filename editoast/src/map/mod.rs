@@ -1,11 +1,17 @@
+mod archive;
 mod layer_cache;
 mod layers;
 
+use editoast_schemas::primitives::BoundingBox;
 pub use layers::Layer;
 pub use layers::MapLayers;
 pub use layers::View;
 use redis::AsyncCommands;
 
+pub use self::archive::MbTilesWriter;
+pub use self::archive::PmTilesWriter;
+pub use self::archive::TileArchive;
+pub use self::archive::TilesetMetadata;
 pub use self::layer_cache::get_cache_tile_key;
 pub use self::layer_cache::get_layer_cache_prefix;
 pub use self::layer_cache::get_view_cache_prefix;
@@ -13,6 +19,9 @@ pub use self::layer_cache::Tile;
 use crate::error::Result;
 use crate::ValkeyConnection;
 
+/// Beyond this number of cached tiles covering an area, a layer is invalidated as a whole
+const MAX_INVALIDATED_TILES: usize = 10_000;
+
 /// Invalidates layer cache for a specific infra and view if provided
 ///
 /// # Arguments
@@ -36,6 +45,72 @@ async fn invalidate_full_layer_cache(
     Ok(number_of_deleted_keys)
 }
 
+/// Invalidates the cached tiles of a layer intersecting a bounding box
+///
+/// The keys of the tiles covering the bounding box are computed for every view and zoom level.
+/// If the bounding box is covered by too many tiles, the layer is invalidated as a whole.
+///
+/// # Arguments
+///
+/// * `valkey` - Pool to use to connect to the valkey
+/// * `infra_id` - Infra on which the layer must be invalidated
+/// * `layer_name` - Layer to invalidate
+/// * `layer` - Description of the layer, listing its views
+/// * `bbox` - Area to invalidate, in `(longitude, latitude)`
+/// * `max_zoom` - Highest zoom level of the cached tiles
+///
+/// Returns the number of deleted keys
+async fn invalidate_layer_cache_in_bbox(
+    valkey: &mut ValkeyConnection,
+    infra_id: i64,
+    layer_name: &str,
+    layer: &Layer,
+    bbox: &BoundingBox,
+    max_zoom: u64,
+) -> Result<u64> {
+    let tile_keys: Vec<String> = layer
+        .views
+        .keys()
+        .flat_map(move |view_name| {
+            let view_prefix = get_view_cache_prefix(layer_name, infra_id, view_name);
+            (0..=max_zoom)
+                .flat_map(move |z| Tile::covering(bbox, z))
+                .map(move |tile| get_cache_tile_key(&view_prefix, &tile))
+        })
+        .take(MAX_INVALIDATED_TILES + 1)
+        .collect();
+    if tile_keys.len() > MAX_INVALIDATED_TILES {
+        return invalidate_full_layer_cache(valkey, infra_id, layer_name).await;
+    }
+    if tile_keys.is_empty() {
+        return Ok(0);
+    }
+    let number_of_deleted_keys = valkey.del(tile_keys).await?;
+    Ok(number_of_deleted_keys)
+}
+
+/// Invalidates the cached tiles of map layers of a specific infra intersecting a bounding box
+///
+/// # Arguments
+///
+/// * `valkey` - Pool to use to connect to the valkey
+/// * `layers` - Layers to invalidate, with their name
+/// * `infra_id` - Infra to on which layers must be invalidated
+/// * `bbox` - Area to invalidate, in `(longitude, latitude)`
+/// * `max_zoom` - Highest zoom level of the cached tiles
+pub async fn invalidate_bbox(
+    valkey: &mut ValkeyConnection,
+    layers: &[(&String, &Layer)],
+    infra_id: i64,
+    bbox: &BoundingBox,
+    max_zoom: u64,
+) -> Result<()> {
+    for (layer_name, layer) in layers {
+        invalidate_layer_cache_in_bbox(valkey, infra_id, layer_name, layer, bbox, max_zoom).await?;
+    }
+    Ok(())
+}
+
 /// Invalidates all map layers of a specific infra
 ///
 /// # Arguments
@@ -1,6 +1,9 @@
+mod diff;
 pub mod errors;
+pub mod geo_export;
 mod object_queryable;
 mod railjson_data;
+mod rebase;
 mod route_from_waypoint_result;
 mod speed_limit_tags;
 mod split_track_section_with_data;
@@ -26,7 +29,10 @@ use tracing::debug;
 use tracing::error;
 use uuid::Uuid;
 
+pub use diff::InfraDiff;
 pub use object_queryable::ObjectQueryable;
+pub use rebase::Rebase;
+pub use rebase::RebaseConflict;
 
 use crate::error::Result;
 use crate::generated_data;
@@ -45,7 +51,9 @@ use editoast_schemas::primitives::ObjectType;
 
 editoast_common::schemas! {
     Infra,
+    diff::schemas(),
     object_queryable::schemas(),
+    rebase::schemas(),
 }
 
 /// The default version of a newly created infrastructure
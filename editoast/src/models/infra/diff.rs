@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+
+use editoast_derive::EditoastError;
+use editoast_models::DbConnection;
+use editoast_schemas::infra::InfraObject;
+use editoast_schemas::primitives::OSRDIdentified as _;
+use editoast_schemas::primitives::OSRDObject as _;
+use editoast_schemas::primitives::ObjectType;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+use strum::IntoEnumIterator;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use super::Infra;
+use crate::error::Result;
+use crate::infra_cache::operation::DeleteOperation;
+use crate::infra_cache::operation::Operation;
+use crate::infra_cache::operation::UpdateOperation;
+use crate::models::infra_edit::InfraEdit;
+
+editoast_common::schemas! {
+    InfraDiff,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "infra:diff")]
+pub enum InfraDiffError {
+    #[error("Version '{version}' is not a version of infra '{infra_id}' (current version: '{current_version}')")]
+    InvalidVersion {
+        infra_id: i64,
+        version: i64,
+        current_version: i64,
+    },
+    #[error(
+        "The history of infra '{infra_id}' is incomplete, version '{version}' cannot be rebuilt"
+    )]
+    HistoryUnavailable { infra_id: i64, version: i64 },
+}
+
+/// The objects that differ between two infras
+///
+/// Applying the diff to the first infra, as an edition batch, yields the second one.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct InfraDiff {
+    /// Objects only present in the second infra
+    pub added: Vec<InfraObject>,
+    /// Objects only present in the first infra
+    #[schema(inline)]
+    pub removed: Vec<DeleteOperation>,
+    /// Objects present in both infras with a different content
+    #[schema(inline)]
+    pub modified: Vec<UpdateOperation>,
+}
+
+impl InfraDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// Converts the diff into an edition batch: removals first, then additions and modifications
+    pub fn into_operations(self) -> Vec<Operation> {
+        let removed = self.removed.into_iter().map(Operation::Delete);
+        let added = self
+            .added
+            .into_iter()
+            .map(|object| Operation::Create(Box::new(object)));
+        let modified = self.modified.into_iter().map(Operation::Update);
+        removed.chain(added).chain(modified).collect()
+    }
+
+    fn extend_with(
+        &mut self,
+        obj_type: ObjectType,
+        mut base: BTreeMap<String, Value>,
+        other: BTreeMap<String, Value>,
+    ) -> Result<()> {
+        for (obj_id, other_data) in other {
+            match base.remove(&obj_id) {
+                None => self.added.push(infra_object(obj_type, other_data)?),
+                Some(base_data) if base_data != other_data => {
+                    self.modified.push(UpdateOperation {
+                        obj_id,
+                        obj_type,
+                        railjson_patch: json_patch::diff(&base_data, &other_data),
+                    });
+                }
+                Some(_) => (),
+            }
+        }
+        self.removed.extend(
+            base.into_keys()
+                .map(|obj_id| DeleteOperation { obj_id, obj_type }),
+        );
+        Ok(())
+    }
+}
+
+/// Builds an infra object from its RailJSON data
+pub(super) fn infra_object(obj_type: ObjectType, data: Value) -> Result<InfraObject> {
+    let object = serde_json::from_value(json!({
+        "railjson": data,
+        "obj_type": obj_type.to_string(),
+    }))?;
+    Ok(object)
+}
+
+/// Undoes editions on the objects of a given type, by applying their inverse operations in order
+fn undo_edits(
+    objects: &mut BTreeMap<String, Value>,
+    obj_type: ObjectType,
+    edits: &[InfraEdit],
+) -> std::result::Result<(), json_patch::PatchError> {
+    let operations = edits.iter().flat_map(|edit| &edit.inverse_operations);
+    for operation in operations {
+        match operation {
+            Operation::Create(object) if object.get_type() == obj_type => {
+                objects.insert(object.get_id().clone(), object.get_data());
+            }
+            Operation::Update(update) if update.obj_type == obj_type => {
+                if let Some(data) = objects.get_mut(&update.obj_id) {
+                    json_patch::patch(data, &update.railjson_patch)?;
+                }
+            }
+            Operation::Delete(delete) if delete.obj_type == obj_type => {
+                objects.remove(&delete.obj_id);
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+impl Infra {
+    /// Compares the objects of two infras, for every object type
+    ///
+    /// Each infra is taken at its current version, or at a previous one if given. A previous
+    /// version is rebuilt by undoing the editions recorded in the history of the infra since then,
+    /// which allows to compare an infra with one of its previous versions.
+    pub async fn diff(
+        &self,
+        conn: &mut DbConnection,
+        version: Option<i64>,
+        other: &Infra,
+        other_version: Option<i64>,
+    ) -> Result<InfraDiff> {
+        let edits = self.edits_since(conn, version).await?;
+        let other_edits = other.edits_since(conn, other_version).await?;
+        let mut diff = InfraDiff::default();
+        for obj_type in ObjectType::iter() {
+            let mut base = Self::get_objects_data(conn, self.id, &obj_type).await?;
+            undo_edits(&mut base, obj_type, &edits).map_err(|_| {
+                InfraDiffError::HistoryUnavailable {
+                    infra_id: self.id,
+                    version: version.unwrap_or_default(),
+                }
+            })?;
+            let mut other_objects = Self::get_objects_data(conn, other.id, &obj_type).await?;
+            undo_edits(&mut other_objects, obj_type, &other_edits).map_err(|_| {
+                InfraDiffError::HistoryUnavailable {
+                    infra_id: other.id,
+                    version: other_version.unwrap_or_default(),
+                }
+            })?;
+            diff.extend_with(obj_type, base, other_objects)?;
+        }
+        Ok(diff)
+    }
+
+    /// Returns the editions to undo to rebuild a previous version of the infra, the latest first
+    async fn edits_since(
+        &self,
+        conn: &mut DbConnection,
+        version: Option<i64>,
+    ) -> Result<Vec<InfraEdit>> {
+        let Some(version) = version else {
+            return Ok(vec![]);
+        };
+        let current_version: i64 = self
+            .version
+            .parse()
+            .expect("Cannot convert version into an Integer");
+        if version < 0 || version > current_version {
+            return Err(InfraDiffError::InvalidVersion {
+                infra_id: self.id,
+                version,
+                current_version,
+            }
+            .into());
+        }
+        // Every version since the requested one must have been reached through a recorded edition
+        let edits = InfraEdit::list_after_version(conn, self.id, version).await?;
+        if edits.len() as i64 != current_version - version {
+            return Err(InfraDiffError::HistoryUnavailable {
+                infra_id: self.id,
+                version,
+            }
+            .into());
+        }
+        Ok(edits)
+    }
+
+    /// Loads the RailJSON data of all the objects of a given type, indexed by id
+    pub(super) async fn get_objects_data(
+        conn: &mut DbConnection,
+        infra_id: i64,
+        object_type: &ObjectType,
+    ) -> Result<BTreeMap<String, Value>> {
+        Self::get_railjson(conn, infra_id, object_type)
+            .await?
+            .into_iter()
+            .map(|data| {
+                let data: Value = serde_json::from_str(&data.railjson)?;
+                let obj_id = data["id"].as_str().unwrap_or_default().to_owned();
+                Ok((obj_id, data))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use json_patch::Patch;
+    use json_patch::PatchOperation;
+    use json_patch::ReplaceOperation;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::models::fixtures::create_small_infra;
+    use editoast_models::DbConnectionPoolV2;
+
+    #[rstest]
+    async fn diff_of_a_modified_clone_replays_onto_the_original() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let conn = &mut db_pool.get_ok();
+        let infra = create_small_infra(conn).await;
+        let clone = infra.clone(conn, "diff_clone".to_owned()).await.unwrap();
+
+        assert!(infra
+            .diff(conn, None, &clone, None)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let operations = [
+            Operation::Update(UpdateOperation {
+                obj_id: "TA0".to_owned(),
+                obj_type: ObjectType::TrackSection,
+                railjson_patch: Patch(vec![PatchOperation::Replace(ReplaceOperation {
+                    path: "/length".parse().unwrap(),
+                    value: json!(1234.0),
+                })]),
+            }),
+            Operation::Delete(DeleteOperation {
+                obj_id: "SA0".to_owned(),
+                obj_type: ObjectType::Signal,
+            }),
+        ];
+        for operation in &operations {
+            operation.apply(clone.id, conn).await.unwrap();
+        }
+
+        let diff = infra.diff(conn, None, &clone, None).await.unwrap();
+        assert!(diff.added.is_empty());
+        assert_eq!(
+            diff.removed,
+            vec![DeleteOperation {
+                obj_id: "SA0".to_owned(),
+                obj_type: ObjectType::Signal,
+            }]
+        );
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].obj_id, "TA0");
+
+        // The inverse diff restores the clone
+        let inverse = clone.diff(conn, None, &infra, None).await.unwrap();
+        assert_eq!(inverse.added.len(), 1);
+        for operation in inverse.into_operations() {
+            operation.apply(clone.id, conn).await.unwrap();
+        }
+        assert!(infra
+            .diff(conn, None, &clone, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
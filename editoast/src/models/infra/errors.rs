@@ -5,6 +5,7 @@ use serde::Deserialize;
 use super::Infra;
 use crate::error::Result;
 use crate::generated_data::infra_error::{InfraError, InfraErrorTypeLabel};
+use crate::generated_data::rules::Severity;
 use crate::models::pagination::load_for_pagination;
 use editoast_models::DbConnection;
 
@@ -17,12 +18,23 @@ pub enum Level {
     All,
 }
 
+impl Level {
+    /// Whether errors of the given severity belong to this level
+    pub fn includes(&self, severity: Severity) -> bool {
+        match self {
+            Level::Warnings => severity != Severity::Error,
+            Level::Errors => severity == Severity::Error,
+            Level::All => true,
+        }
+    }
+}
+
 impl Infra {
+    /// Lists the errors of the infra which have one of the given types
     pub async fn get_paginated_errors(
         &self,
         conn: &mut DbConnection,
-        level: Level,
-        error_type: Option<InfraErrorTypeLabel>,
+        error_types: &[InfraErrorTypeLabel],
         object_id: Option<Identifier>,
         page: u64,
         page_size: u64,
@@ -38,18 +50,10 @@ impl Infra {
             Box::new(sql::<Bool>("TRUE"))
         }
 
-        let level_filter: Filter = match level {
-            Level::Warnings => Box::new(sql::<Text>("information->>'is_warning'").eq("true")),
-            Level::Errors => Box::new(sql::<Text>("information->>'is_warning'").eq("false")),
-            Level::All => sql_true(),
-        };
-        let error_type_filter: Filter = error_type
-            .as_ref()
-            .map(|ty| ty.as_ref())
-            .map(|ty| -> Filter {
-                Box::new(sql::<Text>("information->>'error_type'").eq(ty.to_owned()))
-            })
-            .unwrap_or_else(sql_true);
+        let error_types = error_types
+            .iter()
+            .map(|ty| ty.as_ref().to_owned())
+            .collect::<Vec<_>>();
         let object_id_filter: Filter = object_id
             .map(|id| id.0)
             .map(|id| -> Filter { Box::new(sql::<Text>("information->>'obj_id'").eq(id)) })
@@ -58,8 +62,7 @@ impl Infra {
         let query = dsl::infra_layer_error
             .select(dsl::information)
             .filter(dsl::infra_id.eq(self.id))
-            .filter(level_filter)
-            .filter(error_type_filter)
+            .filter(sql::<Text>("information->>'error_type'").eq_any(error_types))
             .filter(object_id_filter);
 
         #[derive(QueryableByName)]
@@ -0,0 +1,520 @@
+//! Export of the infra objects along with their generated geometry
+//!
+//! Objects are exported as GeoJSON feature collections or as a GeoPackage, one layer per object
+//! type. Their attributes are flattened into the feature properties, as in the map tiles.
+
+use std::collections::BTreeMap;
+use std::ops::DerefMut;
+
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use diesel_async::RunQueryDsl;
+use editoast_models::DbConnection;
+use editoast_schemas::primitives::ObjectType;
+use geos::geojson::feature::Id;
+use geos::geojson::Feature;
+use geos::geojson::FeatureCollection;
+use geos::geojson::Geometry;
+use geos::geojson::JsonObject;
+use geos::geojson::Value as GeoJsonValue;
+use serde_json::Value as JsonValue;
+use strum::IntoEnumIterator;
+
+use super::Infra;
+use super::ObjectQueryable;
+use crate::error::Result;
+use crate::models::get_geometry_layer_table;
+use crate::models::get_table;
+
+/// The object types having a generated geometry, which can be exported
+pub fn exported_object_types() -> impl Iterator<Item = ObjectType> {
+    ObjectType::iter().filter(|object_type| get_geometry_layer_table(object_type).is_some())
+}
+
+/// The name of the exported layer of an object type, such as `track_section`
+pub fn export_layer_name(object_type: ObjectType) -> Option<&'static str> {
+    get_geometry_layer_table(&object_type)
+        .map(|table| table.strip_prefix("infra_layer_").unwrap_or(table))
+}
+
+impl Infra {
+    /// Retrieves all the objects of a type along with their geometry, in WGS84
+    ///
+    /// Objects spread over several rows of the layer table, such as operational points with
+    /// several parts, get a collection of their geometries. Object types without geometry
+    /// have no objects to export.
+    pub async fn get_objects_with_geometry(
+        &self,
+        conn: &mut DbConnection,
+        object_type: ObjectType,
+    ) -> Result<Vec<ObjectQueryable>> {
+        let Some(layer_table) = get_geometry_layer_table(&object_type) else {
+            return Ok(vec![]);
+        };
+        let query = format!(
+            "
+            SELECT
+                object_table.obj_id AS obj_id,
+                object_table.data AS railjson,
+                ST_AsGeoJSON(ST_Transform(
+                    CASE WHEN COUNT(*) = 1
+                        THEN (array_agg(geometry_table.geographic))[1]
+                        ELSE ST_Collect(geometry_table.geographic)
+                    END,
+                    4326
+                ))::jsonb AS geographic
+            FROM {} AS object_table
+            INNER JOIN {layer_table} AS geometry_table ON object_table.obj_id = geometry_table.obj_id AND object_table.infra_id = geometry_table.infra_id
+            WHERE object_table.infra_id = $1
+            GROUP BY object_table.obj_id, object_table.data
+            ORDER BY object_table.obj_id
+            ",
+            get_table(&object_type),
+        );
+        let objects = sql_query(query)
+            .bind::<BigInt, _>(self.id)
+            .load::<ObjectQueryable>(conn.write().await.deref_mut())
+            .await?;
+        Ok(objects)
+    }
+}
+
+/// Flattens the attributes of an object into feature properties
+///
+/// Nested attributes are named after their path joined by `_`, arrays are kept as JSON strings
+/// and null values are dropped. The geometry of track sections is left out since it is the
+/// geometry of the feature.
+pub fn flatten_properties(railjson: JsonValue) -> JsonObject {
+    fn flatten(properties: &mut JsonObject, name: String, value: JsonValue) {
+        match value {
+            JsonValue::Null => (),
+            JsonValue::Object(attributes) => {
+                for (key, value) in attributes {
+                    let key = if name.is_empty() {
+                        key
+                    } else {
+                        format!("{name}_{key}")
+                    };
+                    flatten(properties, key, value);
+                }
+            }
+            JsonValue::Array(values) => {
+                properties.insert(
+                    name,
+                    JsonValue::String(JsonValue::Array(values).to_string()),
+                );
+            }
+            value => {
+                properties.insert(name, value);
+            }
+        }
+    }
+
+    let mut railjson = railjson;
+    if let Some(attributes) = railjson.as_object_mut() {
+        attributes.remove("geo");
+    }
+    let mut properties = JsonObject::new();
+    flatten(&mut properties, String::new(), railjson);
+    properties
+}
+
+/// Builds a GeoJSON feature collection of the objects, identified by their id
+pub fn into_feature_collection(objects: Vec<ObjectQueryable>) -> FeatureCollection {
+    let features = objects
+        .into_iter()
+        .map(|object| Feature {
+            bbox: None,
+            geometry: object.geographic.map(|geometry| geometry.0),
+            id: Some(Id::String(object.obj_id)),
+            properties: Some(flatten_properties(object.railjson)),
+            foreign_members: None,
+        })
+        .collect();
+    FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    }
+}
+
+const WGS84_SRS_ID: i32 = 4326;
+
+/// Writes the layers into a GeoPackage, returned as the content of the file
+///
+/// Each layer becomes a feature table with a `geom` column and one column per property.
+pub fn write_geopackage(layers: Vec<(&str, FeatureCollection)>) -> Result<Vec<u8>> {
+    let connection = rusqlite::Connection::open_in_memory()?;
+    connection.execute_batch(&format!(
+        "
+        PRAGMA application_id = {application_id};
+        PRAGMA user_version = 10300;
+        CREATE TABLE gpkg_spatial_ref_sys (
+            srs_name TEXT NOT NULL,
+            srs_id INTEGER NOT NULL PRIMARY KEY,
+            organization TEXT NOT NULL,
+            organization_coordsys_id INTEGER NOT NULL,
+            definition TEXT NOT NULL,
+            description TEXT
+        );
+        CREATE TABLE gpkg_contents (
+            table_name TEXT NOT NULL PRIMARY KEY,
+            data_type TEXT NOT NULL,
+            identifier TEXT UNIQUE,
+            description TEXT DEFAULT '',
+            last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+            min_x DOUBLE,
+            min_y DOUBLE,
+            max_x DOUBLE,
+            max_y DOUBLE,
+            srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+        );
+        CREATE TABLE gpkg_geometry_columns (
+            table_name TEXT NOT NULL REFERENCES gpkg_contents(table_name),
+            column_name TEXT NOT NULL,
+            geometry_type_name TEXT NOT NULL,
+            srs_id INTEGER NOT NULL REFERENCES gpkg_spatial_ref_sys(srs_id),
+            z TINYINT NOT NULL,
+            m TINYINT NOT NULL,
+            CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name)
+        );
+        INSERT INTO gpkg_spatial_ref_sys VALUES
+            ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', NULL),
+            ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', NULL),
+            ('WGS 84 geodetic', {WGS84_SRS_ID}, 'EPSG', 4326, '{WGS84_DEFINITION}', NULL);
+        ",
+        // "GPKG" in ASCII
+        application_id = 0x4750_4B47,
+    ))?;
+
+    for (table_name, collection) in layers {
+        write_geopackage_layer(&connection, table_name, collection)?;
+    }
+
+    let content = connection.serialize(rusqlite::DatabaseName::Main)?;
+    Ok(content.to_vec())
+}
+
+const WGS84_DEFINITION: &str = r#"GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563,AUTHORITY["EPSG","7030"]],AUTHORITY["EPSG","6326"]],PRIMEM["Greenwich",0,AUTHORITY["EPSG","8901"]],UNIT["degree",0.0174532925199433,AUTHORITY["EPSG","9122"]],AUTHORITY["EPSG","4326"]]"#;
+
+fn write_geopackage_layer(
+    connection: &rusqlite::Connection,
+    table_name: &str,
+    collection: FeatureCollection,
+) -> Result<()> {
+    // The columns are the properties of all the features, typed after their first value
+    let mut columns = BTreeMap::new();
+    for properties in collection.features.iter().flat_map(|f| f.properties.iter()) {
+        for (name, value) in properties {
+            columns.entry(name.clone()).or_insert(match value {
+                JsonValue::Bool(_) => "BOOLEAN",
+                JsonValue::Number(number) if number.is_f64() => "DOUBLE",
+                JsonValue::Number(_) => "INTEGER",
+                _ => "TEXT",
+            });
+        }
+    }
+    columns.remove("id");
+    let column_definitions: String = columns
+        .iter()
+        .map(|(name, column_type)| format!(", \"{name}\" {column_type}"))
+        .collect();
+    connection.execute_batch(&format!(
+        "CREATE TABLE \"{table_name}\" (fid INTEGER PRIMARY KEY AUTOINCREMENT, geom GEOMETRY, id TEXT{column_definitions});"
+    ))?;
+
+    let column_names: Vec<&String> = columns.keys().collect();
+    let insert = format!(
+        "INSERT INTO \"{table_name}\" (geom, id{}) VALUES (?1, ?2{})",
+        column_names
+            .iter()
+            .map(|name| format!(", \"{name}\""))
+            .collect::<String>(),
+        (0..column_names.len())
+            .map(|index| format!(", ?{}", index + 3))
+            .collect::<String>(),
+    );
+    let mut statement = connection.prepare(&insert)?;
+    let mut extent: Option<Envelope> = None;
+    for feature in collection.features {
+        let geometry = feature.geometry.as_ref().map(|geometry| {
+            let envelope = Envelope::of(&geometry.value);
+            if let Some(envelope) = &envelope {
+                extent = Some(match extent.take() {
+                    Some(extent) => extent.union(envelope),
+                    None => envelope.clone(),
+                });
+            }
+            geopackage_binary(geometry, envelope)
+        });
+        let id = match feature.id {
+            Some(Id::String(id)) => Some(id),
+            Some(Id::Number(id)) => Some(id.to_string()),
+            None => None,
+        };
+        let properties = feature.properties.unwrap_or_default();
+        let mut values: Vec<rusqlite::types::Value> = vec![geometry.into(), id.into()];
+        values.extend(column_names.iter().map(|name| match properties.get(*name) {
+            Some(JsonValue::Bool(value)) => (*value).into(),
+            Some(JsonValue::Number(number)) => match number.as_i64() {
+                Some(value) => value.into(),
+                None => number.as_f64().into(),
+            },
+            Some(JsonValue::String(value)) => value.clone().into(),
+            Some(value) => value.to_string().into(),
+            None => rusqlite::types::Value::Null,
+        }));
+        statement.execute(rusqlite::params_from_iter(values))?;
+    }
+
+    let extent = extent.unwrap_or_default();
+    connection.execute(
+        "INSERT INTO gpkg_contents (table_name, data_type, identifier, min_x, min_y, max_x, max_y, srs_id) VALUES (?1, 'features', ?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+            table_name,
+            extent.min_x,
+            extent.min_y,
+            extent.max_x,
+            extent.max_y,
+            WGS84_SRS_ID
+        ],
+    )?;
+    connection.execute(
+        "INSERT INTO gpkg_geometry_columns VALUES (?1, 'geom', 'GEOMETRY', ?2, 0, 0)",
+        rusqlite::params![table_name, WGS84_SRS_ID],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Envelope {
+    min_x: f64,
+    max_x: f64,
+    min_y: f64,
+    max_y: f64,
+}
+
+impl Envelope {
+    /// The envelope of a geometry, if it has any position
+    fn of(geometry: &GeoJsonValue) -> Option<Self> {
+        let mut positions = vec![];
+        collect_positions(geometry, &mut positions);
+        let (first, others) = positions.split_first()?;
+        let mut envelope = Self {
+            min_x: first[0],
+            max_x: first[0],
+            min_y: first[1],
+            max_y: first[1],
+        };
+        for position in others {
+            envelope.min_x = envelope.min_x.min(position[0]);
+            envelope.max_x = envelope.max_x.max(position[0]);
+            envelope.min_y = envelope.min_y.min(position[1]);
+            envelope.max_y = envelope.max_y.max(position[1]);
+        }
+        Some(envelope)
+    }
+
+    fn union(self, other: &Self) -> Self {
+        Self {
+            min_x: self.min_x.min(other.min_x),
+            max_x: self.max_x.max(other.max_x),
+            min_y: self.min_y.min(other.min_y),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+}
+
+fn collect_positions<'a>(geometry: &'a GeoJsonValue, positions: &mut Vec<&'a Vec<f64>>) {
+    match geometry {
+        GeoJsonValue::Point(point) => positions.push(point),
+        GeoJsonValue::MultiPoint(points) | GeoJsonValue::LineString(points) => {
+            positions.extend(points)
+        }
+        GeoJsonValue::MultiLineString(lines) | GeoJsonValue::Polygon(lines) => {
+            positions.extend(lines.iter().flatten())
+        }
+        GeoJsonValue::MultiPolygon(polygons) => {
+            positions.extend(polygons.iter().flatten().flatten())
+        }
+        GeoJsonValue::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                collect_positions(&geometry.value, positions);
+            }
+        }
+    }
+}
+
+/// Encodes a geometry in the GeoPackage binary format: a header followed by its WKB
+fn geopackage_binary(geometry: &Geometry, envelope: Option<Envelope>) -> Vec<u8> {
+    let mut buffer = b"GP".to_vec();
+    buffer.push(0);
+    // Little endian, with an xy envelope unless the geometry is empty
+    buffer.push(match envelope {
+        Some(_) => 0b0000_0011,
+        None => 0b0001_0001,
+    });
+    buffer.extend_from_slice(&WGS84_SRS_ID.to_le_bytes());
+    if let Some(envelope) = envelope {
+        for value in [
+            envelope.min_x,
+            envelope.max_x,
+            envelope.min_y,
+            envelope.max_y,
+        ] {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    write_wkb(&mut buffer, &geometry.value);
+    buffer
+}
+
+/// Writes a geometry as little endian 2D WKB
+fn write_wkb(buffer: &mut Vec<u8>, geometry: &GeoJsonValue) {
+    fn write_header(buffer: &mut Vec<u8>, geometry_type: u32) {
+        buffer.push(1);
+        buffer.extend_from_slice(&geometry_type.to_le_bytes());
+    }
+    fn write_count(buffer: &mut Vec<u8>, count: usize) {
+        buffer.extend_from_slice(&(count as u32).to_le_bytes());
+    }
+    fn write_positions(buffer: &mut Vec<u8>, positions: &[Vec<f64>]) {
+        write_count(buffer, positions.len());
+        for position in positions {
+            buffer.extend_from_slice(&position[0].to_le_bytes());
+            buffer.extend_from_slice(&position[1].to_le_bytes());
+        }
+    }
+
+    match geometry {
+        GeoJsonValue::Point(point) => {
+            write_header(buffer, 1);
+            buffer.extend_from_slice(&point[0].to_le_bytes());
+            buffer.extend_from_slice(&point[1].to_le_bytes());
+        }
+        GeoJsonValue::LineString(points) => {
+            write_header(buffer, 2);
+            write_positions(buffer, points);
+        }
+        GeoJsonValue::Polygon(rings) => {
+            write_header(buffer, 3);
+            write_count(buffer, rings.len());
+            for ring in rings {
+                write_positions(buffer, ring);
+            }
+        }
+        GeoJsonValue::MultiPoint(points) => {
+            write_header(buffer, 4);
+            write_count(buffer, points.len());
+            for point in points {
+                write_wkb(buffer, &GeoJsonValue::Point(point.clone()));
+            }
+        }
+        GeoJsonValue::MultiLineString(lines) => {
+            write_header(buffer, 5);
+            write_count(buffer, lines.len());
+            for line in lines {
+                write_wkb(buffer, &GeoJsonValue::LineString(line.clone()));
+            }
+        }
+        GeoJsonValue::MultiPolygon(polygons) => {
+            write_header(buffer, 6);
+            write_count(buffer, polygons.len());
+            for polygon in polygons {
+                write_wkb(buffer, &GeoJsonValue::Polygon(polygon.clone()));
+            }
+        }
+        GeoJsonValue::GeometryCollection(geometries) => {
+            write_header(buffer, 7);
+            write_count(buffer, geometries.len());
+            for geometry in geometries {
+                write_wkb(buffer, &geometry.value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::infra_cache::InfraCache;
+    use crate::models::fixtures::create_small_infra;
+    use editoast_models::DbConnectionPoolV2;
+
+    #[test]
+    fn flatten_nested_attributes() {
+        let properties = flatten_properties(json!({
+            "id": "TA0",
+            "length": 2000.0,
+            "geo": { "type": "LineString", "coordinates": [] },
+            "extensions": { "sncf": { "line_code": 1, "track_name": "V1" }, "source": null },
+            "curves": [{ "begin": 0.0, "end": 10.0, "radius": 500.0 }],
+        }));
+        assert_eq!(
+            JsonValue::Object(properties),
+            json!({
+                "id": "TA0",
+                "length": 2000.0,
+                "extensions_sncf_line_code": 1,
+                "extensions_sncf_track_name": "V1",
+                "curves": r#"[{"begin":0.0,"end":10.0,"radius":500.0}]"#,
+            })
+        );
+    }
+
+    #[test]
+    fn encode_geopackage_geometry() {
+        let geometry = Geometry::new(GeoJsonValue::Point(vec![1.0, 2.0]));
+        let envelope = Envelope::of(&geometry.value);
+        let binary = geopackage_binary(&geometry, envelope);
+        // Header with an envelope, then the WKB point
+        assert_eq!(binary.len(), 8 + 32 + 21);
+        assert_eq!(&binary[..4], b"GP\x00\x03");
+        assert_eq!(&binary[40..45], &[1, 1, 0, 0, 0]);
+        assert_eq!(&binary[45..53], &1.0_f64.to_le_bytes());
+    }
+
+    #[rstest]
+    async fn export_small_infra_objects() {
+        let db_pool = Arc::new(DbConnectionPoolV2::for_tests());
+        let conn = &mut db_pool.get_ok();
+        let mut infra = create_small_infra(conn).await;
+        let infra_cache = InfraCache::load(conn, &infra).await.unwrap();
+        infra
+            .refresh(db_pool.clone(), true, &infra_cache)
+            .await
+            .unwrap();
+
+        let tracks = infra
+            .get_objects_with_geometry(conn, ObjectType::TrackSection)
+            .await
+            .unwrap();
+        assert!(!tracks.is_empty());
+        assert!(tracks.iter().all(|track| matches!(
+            track.geographic.as_ref().map(|geometry| &geometry.0.value),
+            Some(GeoJsonValue::LineString(_))
+        )));
+        let routes = infra
+            .get_objects_with_geometry(conn, ObjectType::Route)
+            .await
+            .unwrap();
+        assert!(routes.is_empty());
+
+        let track_count = tracks.len();
+        let content =
+            write_geopackage(vec![("track_section", into_feature_collection(tracks))]).unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), content).unwrap();
+        let connection = rusqlite::Connection::open(file.path()).unwrap();
+        let count: usize = connection
+            .query_row("SELECT COUNT(*) FROM track_section", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, track_count);
+    }
+}
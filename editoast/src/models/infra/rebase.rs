@@ -0,0 +1,186 @@
+use std::collections::BTreeSet;
+
+use editoast_models::DbConnection;
+use editoast_schemas::primitives::ObjectType;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use strum::IntoEnumIterator;
+use utoipa::ToSchema;
+
+use super::diff::infra_object;
+use super::Infra;
+use crate::error::Result;
+use crate::infra_cache::operation::DeleteOperation;
+use crate::infra_cache::operation::Operation;
+use crate::infra_cache::operation::UpdateOperation;
+
+editoast_common::schemas! {
+    RebaseConflict,
+}
+
+/// An object modified both in a variant and in the infra it is rebased onto
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RebaseConflict {
+    pub obj_id: String,
+    pub obj_type: ObjectType,
+    /// The object in the variant, `null` if the variant removed it
+    #[schema(value_type = Option<Object>)]
+    pub variant: Option<Value>,
+    /// The object in the infra rebased onto, `null` if it was removed there
+    #[schema(value_type = Option<Object>)]
+    pub upstream: Option<Value>,
+}
+
+/// The result of a rebase of a variant onto another infra
+#[derive(Debug, Default)]
+pub struct Rebase {
+    /// The operations replaying the changes of the variant, to apply onto the new base
+    pub operations: Vec<Operation>,
+    /// The objects changed on both sides in a different way, which are left as they are upstream
+    pub conflicts: Vec<RebaseConflict>,
+}
+
+impl Rebase {
+    fn extend_with(
+        &mut self,
+        obj_type: ObjectType,
+        base: Option<Value>,
+        variant: Option<Value>,
+        upstream: Option<Value>,
+        obj_id: String,
+    ) -> Result<()> {
+        if variant == base || variant == upstream {
+            // Nothing to replay: either the variant did not touch the object, or upstream
+            // already made the same change
+            return Ok(());
+        }
+        if upstream != base {
+            self.conflicts.push(RebaseConflict {
+                obj_id,
+                obj_type,
+                variant,
+                upstream,
+            });
+            return Ok(());
+        }
+        let operation = match (upstream, variant) {
+            (None, Some(variant)) => Operation::Create(Box::new(infra_object(obj_type, variant)?)),
+            (Some(_), None) => Operation::Delete(DeleteOperation { obj_id, obj_type }),
+            (Some(upstream), Some(variant)) => Operation::Update(UpdateOperation {
+                obj_id,
+                obj_type,
+                railjson_patch: json_patch::diff(&upstream, &variant),
+            }),
+            (None, None) => unreachable!("the object is absent from both sides"),
+        };
+        self.operations.push(operation);
+        Ok(())
+    }
+}
+
+impl Infra {
+    /// Computes how to replay onto `onto_id` the changes made in `variant_id` since `base_id`
+    ///
+    /// `base_id` is the infra the variant was cloned from, in the state it was when cloned.
+    /// Objects changed differently in the variant and in `onto_id` are reported as conflicts.
+    pub async fn rebase(
+        conn: &mut DbConnection,
+        base_id: i64,
+        variant_id: i64,
+        onto_id: i64,
+    ) -> Result<Rebase> {
+        let mut rebase = Rebase::default();
+        for obj_type in ObjectType::iter() {
+            let mut base = Self::get_objects_data(conn, base_id, &obj_type).await?;
+            let mut variant = Self::get_objects_data(conn, variant_id, &obj_type).await?;
+            let mut upstream = Self::get_objects_data(conn, onto_id, &obj_type).await?;
+            let obj_ids: BTreeSet<_> = base
+                .keys()
+                .chain(variant.keys())
+                .chain(upstream.keys())
+                .cloned()
+                .collect();
+            for obj_id in obj_ids {
+                rebase.extend_with(
+                    obj_type,
+                    base.remove(&obj_id),
+                    variant.remove(&obj_id),
+                    upstream.remove(&obj_id),
+                    obj_id,
+                )?;
+            }
+        }
+        Ok(rebase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use json_patch::Patch;
+    use json_patch::PatchOperation;
+    use json_patch::ReplaceOperation;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::fixtures::create_small_infra;
+    use editoast_models::DbConnectionPoolV2;
+
+    fn set_track_length(obj_id: &str, length: f64) -> Operation {
+        Operation::Update(UpdateOperation {
+            obj_id: obj_id.to_owned(),
+            obj_type: ObjectType::TrackSection,
+            railjson_patch: Patch(vec![PatchOperation::Replace(ReplaceOperation {
+                path: "/length".parse().unwrap(),
+                value: json!(length),
+            })]),
+        })
+    }
+
+    #[rstest]
+    async fn rebase_replays_variant_changes_and_reports_conflicts() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let conn = &mut db_pool.get_ok();
+        let base = create_small_infra(conn).await;
+        let variant = base.clone(conn, "rebase_variant".to_owned()).await.unwrap();
+        let upstream = base
+            .clone(conn, "rebase_upstream".to_owned())
+            .await
+            .unwrap();
+
+        for operation in [
+            set_track_length("TA0", 1234.0),
+            set_track_length("TA1", 1234.0),
+            set_track_length("TA2", 1234.0),
+        ] {
+            operation.apply(variant.id, conn).await.unwrap();
+        }
+        for operation in [
+            set_track_length("TA1", 1234.0),
+            set_track_length("TA2", 4321.0),
+            set_track_length("TA3", 4321.0),
+        ] {
+            operation.apply(upstream.id, conn).await.unwrap();
+        }
+
+        let rebase = Infra::rebase(conn, base.id, variant.id, upstream.id)
+            .await
+            .unwrap();
+
+        // TA0 is only changed in the variant, TA1 is changed the same way on both sides
+        assert_eq!(rebase.operations.len(), 1);
+        let Operation::Update(update) = &rebase.operations[0] else {
+            panic!("expected an update operation");
+        };
+        assert_eq!(update.obj_id, "TA0");
+
+        // TA2 is changed differently on both sides
+        assert_eq!(rebase.conflicts.len(), 1);
+        let conflict = &rebase.conflicts[0];
+        assert_eq!(conflict.obj_id, "TA2");
+        assert_eq!(conflict.variant.as_ref().unwrap()["length"], 1234.0);
+        assert_eq!(conflict.upstream.as_ref().unwrap()["length"], 4321.0);
+    }
+}
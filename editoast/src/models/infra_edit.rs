@@ -0,0 +1,107 @@
+use std::ops::DerefMut;
+
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::ExpressionMethods;
+use diesel::QueryDsl;
+use diesel_async::RunQueryDsl;
+use editoast_derive::Model;
+use editoast_models::tables::infra_edit::dsl;
+use editoast_models::DbConnection;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::Result;
+use crate::infra_cache::operation::Operation;
+use crate::models::prelude::*;
+
+editoast_common::schemas! {
+    InfraEdit,
+}
+
+/// A batch of operations applied to an infra, as recorded in its edition history
+///
+/// The inverse operations are computed while the batch is applied. Applying them
+/// in order restores the objects to the state they had before the edition.
+#[derive(Clone, Debug, Serialize, Deserialize, Model, ToSchema)]
+#[model(table = editoast_models::tables::infra_edit)]
+#[model(gen(ops = crd, list))]
+pub struct InfraEdit {
+    pub id: i64,
+    pub infra_id: i64,
+    /// The version of the infra reached once the edition is applied
+    pub version: i64,
+    /// The user who applied the edition, if known
+    pub author_id: Option<i64>,
+    pub created: DateTime<Utc>,
+    #[model(json)]
+    pub operations: Vec<Operation>,
+    #[model(json)]
+    pub inverse_operations: Vec<Operation>,
+    /// The edition this one reverts, if it was produced by a revert
+    pub reverted_edit_id: Option<i64>,
+}
+
+impl InfraEdit {
+    /// Returns the editions of an infra that are more recent than the given version,
+    /// the latest one first
+    pub async fn list_after_version(
+        conn: &mut DbConnection,
+        infra_id: i64,
+        version: i64,
+    ) -> Result<Vec<InfraEdit>> {
+        let edits = dsl::infra_edit
+            .filter(dsl::infra_id.eq(infra_id))
+            .filter(dsl::version.gt(version))
+            .order(dsl::version.desc())
+            .load(conn.write().await.deref_mut())
+            .await?
+            .into_iter()
+            .map(Self::from_row)
+            .collect();
+        Ok(edits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::infra_cache::operation::DeleteOperation;
+    use crate::models::fixtures::create_empty_infra;
+    use editoast_models::DbConnectionPoolV2;
+    use editoast_schemas::primitives::ObjectType;
+
+    #[rstest]
+    async fn list_after_version_returns_latest_first() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let infra = create_empty_infra(&mut db_pool.get_ok()).await;
+        let operation = Operation::Delete(DeleteOperation {
+            obj_id: "track".to_string(),
+            obj_type: ObjectType::TrackSection,
+        });
+
+        for version in 1..=3 {
+            InfraEdit::changeset()
+                .infra_id(infra.id)
+                .version(version)
+                .operations(vec![operation.clone()])
+                .inverse_operations(vec![])
+                .create(&mut db_pool.get_ok())
+                .await
+                .expect("Failed to create infra edit");
+        }
+
+        let edits = InfraEdit::list_after_version(&mut db_pool.get_ok(), infra.id, 1)
+            .await
+            .expect("Failed to list infra edits");
+
+        assert_eq!(
+            edits.iter().map(|edit| edit.version).collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+    }
+}
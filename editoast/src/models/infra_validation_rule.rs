@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::ops::DerefMut;
+use std::str::FromStr;
+
+use diesel::ExpressionMethods;
+use diesel::QueryDsl;
+use diesel_async::RunQueryDsl;
+use editoast_derive::Model;
+use editoast_models::tables::infra_validation_rule::dsl;
+use editoast_models::DbConnection;
+
+use crate::error::Result;
+use crate::generated_data::infra_error::InfraErrorTypeLabel;
+use crate::generated_data::rules::RuleSettings;
+use crate::models::prelude::*;
+
+/// The settings of a validation rule overridden for a specific infra
+#[derive(Debug, Clone, Model)]
+#[model(table = editoast_models::tables::infra_validation_rule)]
+#[model(preferred = (infra_id, rule_id))]
+#[model(gen(ops = crud))]
+pub struct InfraValidationRule {
+    pub id: i64,
+    pub infra_id: i64,
+    pub rule_id: String,
+    #[model(json)]
+    pub settings: RuleSettings,
+}
+
+impl InfraValidationRule {
+    /// Loads the rule settings overridden for an infra, indexed by rule
+    ///
+    /// Overrides of rules which no longer exist are ignored.
+    pub async fn list_overrides(
+        conn: &mut DbConnection,
+        infra_id: i64,
+    ) -> Result<HashMap<InfraErrorTypeLabel, RuleSettings>> {
+        let overrides = dsl::infra_validation_rule
+            .filter(dsl::infra_id.eq(infra_id))
+            .load(conn.write().await.deref_mut())
+            .await?
+            .into_iter()
+            .map(Self::from_row)
+            .filter_map(|rule| {
+                let rule_id = InfraErrorTypeLabel::from_str(&rule.rule_id).ok()?;
+                Some((rule_id, rule.settings))
+            })
+            .collect();
+        Ok(overrides)
+    }
+
+    /// Overrides the settings of a rule for an infra, empty settings remove the override
+    ///
+    /// The override is upserted in a single statement, so concurrent calls do not conflict.
+    pub async fn set_override(
+        conn: &mut DbConnection,
+        infra_id: i64,
+        rule_id: InfraErrorTypeLabel,
+        settings: RuleSettings,
+    ) -> Result<()> {
+        let rule_id = rule_id.as_ref().to_owned();
+        if settings.is_empty() {
+            Self::delete_static(conn, (infra_id, rule_id)).await?;
+            return Ok(());
+        }
+        let settings = serde_json::to_value(settings)?;
+        diesel::insert_into(dsl::infra_validation_rule)
+            .values((
+                dsl::infra_id.eq(infra_id),
+                dsl::rule_id.eq(&rule_id),
+                dsl::settings.eq(&settings),
+            ))
+            .on_conflict((dsl::infra_id, dsl::rule_id))
+            .do_update()
+            .set(dsl::settings.eq(&settings))
+            .execute(conn.write().await.deref_mut())
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::generated_data::rules::Severity;
+    use crate::models::fixtures::create_empty_infra;
+    use editoast_models::DbConnectionPoolV2;
+
+    #[rstest]
+    async fn set_override_upserts_and_removes_settings() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let conn = &mut db_pool.get_ok();
+        let infra = create_empty_infra(conn).await;
+        let rule_id = InfraErrorTypeLabel::MissingRoute;
+
+        for severity in [Severity::Info, Severity::Error] {
+            let settings = RuleSettings {
+                enabled: None,
+                severity: Some(severity),
+            };
+            InfraValidationRule::set_override(conn, infra.id, rule_id, settings.clone())
+                .await
+                .unwrap();
+            let overrides = InfraValidationRule::list_overrides(conn, infra.id)
+                .await
+                .unwrap();
+            assert_eq!(overrides, HashMap::from([(rule_id, settings)]));
+        }
+
+        InfraValidationRule::set_override(conn, infra.id, rule_id, RuleSettings::default())
+            .await
+            .unwrap();
+        let overrides = InfraValidationRule::list_overrides(conn, infra.id)
+            .await
+            .unwrap();
+        assert!(overrides.is_empty());
+    }
+}
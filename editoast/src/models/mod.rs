@@ -4,7 +4,9 @@ pub mod electrical_profiles;
 #[cfg(test)]
 pub mod fixtures;
 pub mod infra;
+pub mod infra_edit;
 pub mod infra_objects;
+pub mod infra_validation_rule;
 pub mod layers;
 pub mod macro_node;
 pub mod stdcm_log;
@@ -26,6 +28,7 @@ pub mod temporary_speed_limits;
 pub mod timetable;
 pub mod towed_rolling_stock;
 pub mod train_schedule;
+pub mod train_service;
 pub mod work_schedules;
 
 pub use prelude::*;
@@ -42,10 +45,12 @@ pub use tags::Tags;
 
 editoast_common::schemas! {
     infra::schemas(),
+    infra_edit::schemas(),
     projects::schemas(),
     rolling_stock_model::schemas(),
     stdcm_log::schemas(),
     tags::schemas(),
+    timetable::schemas(),
 }
 
 #[cfg(test)]
@@ -1,13 +1,17 @@
+use std::ops::DerefMut;
+
 use chrono::DateTime;
 use chrono::Utc;
 use editoast_derive::Model;
 use editoast_models::DbConnection;
+use itertools::Itertools;
 use serde::Deserialize;
 use serde::Serialize;
 use utoipa::ToSchema;
 
 use crate::core::stdcm::Request;
 use crate::core::stdcm::Response;
+use crate::error::Result;
 use crate::models::prelude::*;
 
 editoast_common::schemas! {
@@ -29,6 +33,8 @@ pub struct StdcmLog {
     pub response: Response,
     pub created: DateTime<Utc>,
     pub user_id: Option<i64>,
+    /// The rolling stock of the searched train, unknown for the oldest logs
+    pub rolling_stock_name: Option<String>,
 }
 
 impl StdcmLog {
@@ -38,14 +44,34 @@ impl StdcmLog {
         request: Request,
         response: Response,
         user_id: Option<i64>,
+        rolling_stock_name: String,
     ) {
         let stdcm_log_changeset = StdcmLog::changeset()
             .trace_id(trace_id)
             .request(request)
             .response(response.clone())
-            .user_id(user_id);
+            .user_id(user_id)
+            .rolling_stock_name(Some(rolling_stock_name));
         if let Err(e) = stdcm_log_changeset.create(&mut conn).await {
             tracing::error!("Failed during log operation: {e}");
         }
     }
+
+    /// The logs created in the given time range, bounds included, oldest first
+    pub async fn created_between(
+        conn: &mut DbConnection,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<StdcmLog>> {
+        use diesel::prelude::*;
+        use diesel_async::RunQueryDsl;
+        use editoast_models::tables::stdcm_logs::dsl;
+
+        let stdcm_logs = dsl::stdcm_logs
+            .filter(dsl::created.between(from, to))
+            .order_by(dsl::id)
+            .load::<Row<StdcmLog>>(conn.write().await.deref_mut())
+            .await?;
+        Ok(stdcm_logs.into_iter().map_into().collect())
+    }
 }
@@ -1,5 +1,6 @@
 use chrono::DateTime;
 use chrono::Utc;
+use diesel::BoolExpressionMethods;
 use diesel::ExpressionMethods;
 use diesel::QueryDsl;
 use diesel_async::RunQueryDsl;
@@ -10,17 +11,20 @@ use std::ops::DerefMut;
 use utoipa::ToSchema;
 
 use crate::error::Result;
-use crate::models::prelude::{Create, Row};
+use crate::models::prelude::Row;
 
 #[cfg(test)]
 use serde::Deserialize;
 
 #[derive(Debug, Clone, Model, ToSchema, Serialize)]
 #[model(table = editoast_models::tables::stdcm_search_environment)]
-#[model(gen(ops = crd, list))]
+#[model(gen(ops = crud, list))]
 #[cfg_attr(test, derive(Deserialize, PartialEq), model(changeset(derive(Clone))))]
 pub struct StdcmSearchEnvironment {
     pub id: i64,
+    /// Unique name used to select the search environment
+    #[model(identifier)]
+    pub name: String,
     pub infra_id: i64,
     #[schema(nullable = false)]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -48,6 +52,118 @@ impl StdcmSearchEnvironment {
             .ok()
     }
 
+    /// List all the search environments, the latest first
+    pub async fn list_latest_first(conn: &mut DbConnection) -> Result<Vec<Self>> {
+        use editoast_models::tables::stdcm_search_environment::dsl::*;
+        let search_envs = stdcm_search_environment
+            .order_by((search_window_end.desc(), search_window_begin.asc()))
+            .load::<Row<StdcmSearchEnvironment>>(conn.write().await.deref_mut())
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        Ok(search_envs)
+    }
+
+    /// List the search environments a user may search paths in, the latest first
+    ///
+    /// A search environment granted to no subject is open to everyone. Otherwise it is only open
+    /// to the subjects it is granted to, either the user or one of their groups.
+    pub async fn list_accessible(
+        conn: &mut DbConnection,
+        user_id: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use editoast_models::tables::authn_group_membership::dsl as membership;
+        use editoast_models::tables::stdcm_search_environment;
+        use editoast_models::tables::stdcm_search_environment_access::dsl as access;
+
+        let mut subject_ids = vec![];
+        if let Some(user_id) = user_id {
+            subject_ids = membership::authn_group_membership
+                .select(membership::group)
+                .filter(membership::user.eq(user_id))
+                .load::<i64>(conn.write().await.deref_mut())
+                .await?;
+            subject_ids.push(user_id);
+        }
+
+        let search_envs = stdcm_search_environment::table
+            .left_join(access::stdcm_search_environment_access)
+            .select(stdcm_search_environment::all_columns)
+            .distinct()
+            .filter(
+                access::subject_id
+                    .is_null()
+                    .or(access::subject_id.eq_any(subject_ids)),
+            )
+            .order_by((
+                stdcm_search_environment::search_window_end.desc(),
+                stdcm_search_environment::search_window_begin.asc(),
+            ))
+            .load::<Row<StdcmSearchEnvironment>>(conn.write().await.deref_mut())
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        Ok(search_envs)
+    }
+
+    /// Whether a user may search paths in this search environment
+    ///
+    /// See [StdcmSearchEnvironment::list_accessible].
+    pub async fn is_accessible_by(
+        &self,
+        conn: &mut DbConnection,
+        user_id: Option<i64>,
+    ) -> Result<bool> {
+        Ok(Self::list_accessible(conn, user_id)
+            .await?
+            .iter()
+            .any(|search_env| search_env.id == self.id))
+    }
+
+    /// The subjects (users or groups) this search environment is granted to
+    pub async fn granted_subjects(&self, conn: &mut DbConnection) -> Result<Vec<i64>> {
+        use editoast_models::tables::stdcm_search_environment_access::dsl::*;
+        let subject_ids = stdcm_search_environment_access
+            .select(subject_id)
+            .filter(search_environment_id.eq(self.id))
+            .order_by(subject_id)
+            .load(conn.write().await.deref_mut())
+            .await?;
+        Ok(subject_ids)
+    }
+
+    /// Restrict this search environment to some subjects, in addition to the ones already granted
+    pub async fn grant(&self, conn: &mut DbConnection, subject_ids: &[i64]) -> Result<()> {
+        use editoast_models::tables::stdcm_search_environment_access::dsl::*;
+        let grants: Vec<_> = subject_ids
+            .iter()
+            .map(|subject| (search_environment_id.eq(self.id), subject_id.eq(*subject)))
+            .collect();
+        diesel::insert_into(stdcm_search_environment_access)
+            .values(grants)
+            .on_conflict_do_nothing()
+            .execute(conn.write().await.deref_mut())
+            .await?;
+        Ok(())
+    }
+
+    /// Revoke the access of some subjects to this search environment
+    ///
+    /// Once all the grants are revoked, the search environment is open to everyone again.
+    pub async fn revoke(&self, conn: &mut DbConnection, subject_ids: &[i64]) -> Result<()> {
+        use editoast_models::tables::stdcm_search_environment_access::dsl::*;
+        diesel::delete(
+            stdcm_search_environment_access
+                .filter(search_environment_id.eq(self.id))
+                .filter(subject_id.eq_any(subject_ids)),
+        )
+        .execute(conn.write().await.deref_mut())
+        .await?;
+        Ok(())
+    }
+
     pub async fn delete_all(conn: &mut DbConnection) -> Result<()> {
         use editoast_models::tables::stdcm_search_environment::dsl::*;
         diesel::delete(stdcm_search_environment)
@@ -58,9 +174,19 @@ impl StdcmSearchEnvironment {
 }
 
 impl StdcmSearchEnvironmentChangeset {
+    /// Create the search environment, or replace the one with the same name
+    ///
+    /// A replaced search environment keeps its id and the subjects it is granted to.
     pub async fn overwrite(self, conn: &mut DbConnection) -> Result<StdcmSearchEnvironment> {
-        StdcmSearchEnvironment::delete_all(conn).await?;
-        self.create(conn).await
+        use editoast_models::tables::stdcm_search_environment::dsl;
+        let search_env = diesel::insert_into(dsl::stdcm_search_environment)
+            .values(&self)
+            .on_conflict(dsl::name)
+            .do_update()
+            .set(&self)
+            .get_result::<Row<StdcmSearchEnvironment>>(conn.write().await.deref_mut())
+            .await?;
+        Ok(search_env.into())
     }
 }
 
@@ -72,6 +198,7 @@ pub mod tests {
     use rstest::rstest;
 
     use super::*;
+    use crate::models::auth::PgAuthDriver;
     use crate::models::electrical_profiles::ElectricalProfileSet;
     use crate::models::fixtures::{
         create_electrical_profile_set, create_empty_infra, create_temporary_speed_limit_group,
@@ -81,7 +208,10 @@ pub mod tests {
     use crate::models::timetable::Timetable;
     use crate::models::work_schedules::WorkScheduleGroup;
     use crate::models::Infra;
-    use crate::models::{Count, Model, SelectionSettings};
+    use crate::models::{Count, Create, Exists, Model, SelectionSettings};
+    use editoast_authz::authorizer::StorageDriver;
+    use editoast_authz::authorizer::UserInfo;
+    use editoast_authz::BuiltinRole;
     use editoast_models::DbConnectionPoolV2;
 
     pub async fn stdcm_search_env_fixtures(
@@ -124,6 +254,7 @@ pub mod tests {
         ) = stdcm_search_env_fixtures(&mut db_pool.get_ok()).await;
 
         let changeset_1 = StdcmSearchEnvironment::changeset()
+            .name("test_overwrite".to_owned())
             .infra_id(infra.id)
             .electrical_profile_set_id(Some(electrical_profile_set.id))
             .work_schedule_group_id(Some(work_schedule_group.id))
@@ -140,7 +271,7 @@ pub mod tests {
             .search_window_begin(begin)
             .search_window_end(end);
 
-        changeset_1
+        let created = changeset_1
             .create(&mut db_pool.get_ok())
             .await
             .expect("Failed to create first search environment");
@@ -152,7 +283,7 @@ pub mod tests {
             initial_env_count + 1
         );
 
-        let _ = changeset_2
+        let overwritten = changeset_2
             .overwrite(&mut db_pool.get_ok())
             .await
             .expect("Failed to overwrite search environment");
@@ -161,15 +292,95 @@ pub mod tests {
             StdcmSearchEnvironment::count(&mut db_pool.get_ok(), SelectionSettings::new())
                 .await
                 .expect("Failed to count"),
-            1
+            initial_env_count + 1
         );
+        assert_eq!(overwritten.id, created.id);
+        assert_eq!(overwritten.search_window_begin, begin);
+        assert_eq!(overwritten.search_window_end, end);
+    }
 
-        let result = StdcmSearchEnvironment::retrieve_latest(&mut db_pool.get_ok())
+    #[rstest]
+    async fn test_overwrite_keeps_other_names() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let (infra, timetable, ..) = stdcm_search_env_fixtures(&mut db_pool.get_ok()).await;
+
+        let north = StdcmSearchEnvironment::changeset()
+            .name("north".to_owned())
+            .infra_id(infra.id)
+            .timetable_id(timetable.id)
+            .search_window_begin(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+            .search_window_end(Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap());
+        let south = north.clone().name("south".to_owned());
+
+        let north = north
+            .overwrite(&mut db_pool.get_ok())
             .await
-            .expect("Failed to retrieve latest search environment");
+            .expect("Failed to create north search environment");
+        let south = south
+            .overwrite(&mut db_pool.get_ok())
+            .await
+            .expect("Failed to create south search environment");
 
-        assert_eq!(result.search_window_begin, begin);
-        assert_eq!(result.search_window_end, end);
+        assert_ne!(north.id, south.id);
+        assert!(
+            StdcmSearchEnvironment::exists(&mut db_pool.get_ok(), "north".to_owned())
+                .await
+                .expect("Failed to check the search environment")
+        );
+    }
+
+    #[rstest]
+    async fn search_environments_are_restricted_to_granted_subjects() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let (infra, timetable, ..) = stdcm_search_env_fixtures(&mut db_pool.get_ok()).await;
+        let driver = PgAuthDriver::<BuiltinRole>::new(db_pool.clone().into());
+        let mut user_ids = vec![];
+        for identity in ["granted_user", "other_user"] {
+            let user = UserInfo {
+                identity: identity.to_owned(),
+                name: identity.to_owned(),
+            };
+            let user_id = driver
+                .ensure_user(&user)
+                .await
+                .expect("Failed to create user");
+            user_ids.push(user_id);
+        }
+        let (granted_user, other_user) = (user_ids[0], user_ids[1]);
+
+        let search_env = StdcmSearchEnvironment::changeset()
+            .name("restricted".to_owned())
+            .infra_id(infra.id)
+            .timetable_id(timetable.id)
+            .search_window_begin(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+            .search_window_end(Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap())
+            .create(&mut db_pool.get_ok())
+            .await
+            .expect("Failed to create search environment");
+        let conn = &mut db_pool.get_ok();
+        assert!(search_env
+            .is_accessible_by(conn, Some(other_user))
+            .await
+            .unwrap());
+
+        search_env.grant(conn, &[granted_user]).await.unwrap();
+
+        assert!(search_env
+            .is_accessible_by(conn, Some(granted_user))
+            .await
+            .unwrap());
+        assert!(!search_env
+            .is_accessible_by(conn, Some(other_user))
+            .await
+            .unwrap());
+        assert!(!search_env.is_accessible_by(conn, None).await.unwrap());
+
+        search_env.revoke(conn, &[granted_user]).await.unwrap();
+
+        assert!(search_env
+            .is_accessible_by(conn, Some(other_user))
+            .await
+            .unwrap());
     }
 
     #[rstest]
@@ -187,6 +398,7 @@ pub mod tests {
         ) = stdcm_search_env_fixtures(&mut db_pool.get_ok()).await;
 
         let too_old = StdcmSearchEnvironment::changeset()
+            .name("too_old".to_owned())
             .infra_id(infra.id)
             .electrical_profile_set_id(Some(electrical_profile_set.id))
             .work_schedule_group_id(Some(work_schedule_group.id))
@@ -197,6 +409,7 @@ pub mod tests {
 
         let too_young = too_old
             .clone()
+            .name("too_young".to_owned())
             .search_window_begin(Utc.with_ymd_and_hms(2024, 1, 16, 0, 0, 0).unwrap())
             .search_window_end(Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap());
 
@@ -205,10 +418,12 @@ pub mod tests {
 
         let the_best = too_old
             .clone()
+            .name("the_best".to_owned())
             .search_window_begin(begin)
             .search_window_end(end);
 
-        for changeset in [too_old, too_young.clone(), the_best, too_young] {
+        let too_young_again = too_young.clone().name("too_young_again".to_owned());
+        for changeset in [too_old, too_young, the_best, too_young_again] {
             changeset
                 .create(&mut db_pool.get_ok())
                 .await
@@ -1,3 +1,5 @@
+pub mod exchange;
+
 use chrono::DateTime;
 use chrono::Utc;
 use diesel::prelude::*;
@@ -6,17 +8,23 @@ use diesel::sql_types::Array;
 use diesel::sql_types::BigInt;
 use diesel_async::RunQueryDsl;
 use futures_util::stream::TryStreamExt;
+use std::collections::HashMap;
 use std::ops::DerefMut;
 
 use crate::error::Result;
 use crate::models::prelude::*;
 use crate::models::train_schedule::TrainSchedule;
+use crate::models::train_service::TrainService;
 use crate::models::Identifiable;
 use crate::models::{DeleteStatic, Retrieve};
 use crate::Exists;
 use editoast_models::tables::timetable::dsl;
 use editoast_models::DbConnection;
 
+editoast_common::schemas! {
+    exchange::schemas(),
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Queryable, Identifiable)]
 #[diesel(table_name = editoast_models::tables::timetable)]
 #[cfg_attr(test, derive(serde::Deserialize))]
@@ -35,6 +43,19 @@ impl Timetable {
             .map_err(Into::into)
     }
 
+    /// Lock the timetable until the end of the current transaction
+    ///
+    /// Concurrent transactions locking the same timetable wait for this one to end.
+    pub async fn lock(conn: &mut DbConnection, timetable_id: i64) -> Result<Option<Timetable>> {
+        dsl::timetable
+            .filter(dsl::id.eq(timetable_id))
+            .for_update()
+            .get_result::<Timetable>(conn.write().await.deref_mut())
+            .await
+            .optional()
+            .map_err(Into::into)
+    }
+
     pub async fn trains_count(timetable_id: i64, conn: &mut DbConnection) -> Result<i64> {
         use editoast_models::tables::train_schedule::dsl;
 
@@ -60,6 +81,45 @@ impl Timetable {
             .map_err(Into::into)
     }
 
+    /// Copies the timetable with its train schedules and train services
+    ///
+    /// The copied train schedules of a train service are attached to the copy of the service.
+    pub async fn duplicate(&self, conn: &mut DbConnection) -> Result<Timetable> {
+        let timetable = Timetable::create(conn).await?;
+
+        let mut train_service_ids = HashMap::new();
+        for train_service in TrainService::list_by_timetable(conn, self.id).await? {
+            let train_service_id = train_service.id;
+            let copy = Changeset::<TrainService>::from(train_service)
+                .timetable_id(timetable.id)
+                .create(conn)
+                .await?;
+            train_service_ids.insert(train_service_id, copy.id);
+        }
+
+        let timetable_id = self.id;
+        let train_schedules = TrainSchedule::list(
+            conn,
+            SelectionSettings::new().filter(move || TrainSchedule::TIMETABLE_ID.eq(timetable_id)),
+        )
+        .await?;
+        let changesets = train_schedules.into_iter().map(|train_schedule| {
+            let train_service_id = train_schedule
+                .train_service_id
+                .and_then(|id| train_service_ids.get(&id).copied());
+            Changeset::<TrainSchedule>::from(train_schedule)
+                .timetable_id(timetable.id)
+                .train_service_id(train_service_id)
+        });
+        let _: Vec<_> = TrainSchedule::create_batch(conn, changesets).await?;
+
+        Ok(timetable)
+    }
+
+    /// The train schedules starting before the given time
+    ///
+    /// The trains with an operating calendar are always included, their start time only giving
+    /// their time of day.
     pub async fn schedules_before_date(
         self,
         conn: &mut DbConnection,
@@ -70,7 +130,7 @@ impl Timetable {
         use editoast_models::tables::train_schedule::dsl;
 
         let train_schedules = dsl::train_schedule
-            .filter(dsl::start_time.le(time))
+            .filter(dsl::start_time.le(time).or(dsl::calendar.is_not_null()))
             .filter(dsl::timetable_id.eq(self.id))
             .load_stream::<Row<TrainSchedule>>(conn.write().await.deref_mut())
             .await?
@@ -0,0 +1,788 @@
+//! Exchange of timetables with other tools, as railML 3 or GTFS files
+//!
+//! Train schedules are first converted into an [ExchangeTimetable], independent of the file
+//! format. Its stops are operational points, identified by UIC code or by trigram and secondary
+//! code, so that they can be matched against the operational points of another infra.
+//!
+//! The operating calendars of the trains are exchanged in the days of the file, those of the
+//! first departure in its timezone. They are shifted to and from the UTC days of the train
+//! schedules by the number of days between the two at the first departure.
+
+pub mod gtfs;
+pub mod railml;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::DerefMut;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use chrono_tz::Tz;
+use diesel::sql_query;
+use diesel::sql_types::Array;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Double;
+use diesel::sql_types::Text;
+use diesel::QueryableByName;
+use diesel_async::RunQueryDsl;
+use editoast_models::DbConnection;
+use editoast_schemas::primitives::NonBlankString;
+use editoast_schemas::primitives::PositiveDuration;
+use editoast_schemas::train_schedule::OperatingCalendar;
+use editoast_schemas::train_schedule::OperationalPointIdentifier;
+use editoast_schemas::train_schedule::OperationalPointReference;
+use editoast_schemas::train_schedule::PathItem;
+use editoast_schemas::train_schedule::PathItemLocation;
+use editoast_schemas::train_schedule::ScheduleItem;
+use editoast_schemas::train_schedule::TrainScheduleBase;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::error::Result;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::OperationalPointModel;
+use crate::views::path::path_item_cache::PathItemCache;
+
+editoast_common::schemas! {
+    ExchangeFormat,
+    UnmatchedStop,
+}
+
+/// The maximum number of trains read from a file
+pub const MAX_IMPORTED_TRAINS: usize = 10_000;
+/// The maximum number of days an imported calendar spans, when given as dates
+pub const MAX_CALENDAR_DAYS: usize = 3_660;
+
+#[derive(Debug, Error)]
+pub enum ExchangeFileError {
+    #[error("invalid XML: {0}")]
+    XmlRead(#[from] quick_xml::DeError),
+    #[error("could not write XML: {0}")]
+    XmlWrite(#[from] quick_xml::SeError),
+    #[error("invalid CSV: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("invalid ZIP archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("more than {max} trains")]
+    TooManyTrains { max: usize },
+    #[error("service '{service}' spans more than {max} days")]
+    TooLongCalendar { service: String, max: usize },
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// A file format timetables are exchanged in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExchangeFormat {
+    /// A railML 3.2 XML document
+    Railml,
+    /// A zipped GTFS feed
+    Gtfs,
+}
+
+impl ExchangeFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExchangeFormat::Railml => "application/xml",
+            ExchangeFormat::Gtfs => "application/zip",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExchangeFormat::Railml => "xml",
+            ExchangeFormat::Gtfs => "zip",
+        }
+    }
+
+    pub fn write(&self, timetable: &ExchangeTimetable) -> Result<Vec<u8>, ExchangeFileError> {
+        match self {
+            ExchangeFormat::Railml => railml::write(timetable).map(String::into_bytes),
+            ExchangeFormat::Gtfs => gtfs::write(timetable),
+        }
+    }
+
+    /// Reads a timetable, keeping only the trains running on `date` if given
+    pub fn read(
+        &self,
+        data: &[u8],
+        date: Option<NaiveDate>,
+    ) -> Result<ExchangeTimetable, ExchangeFileError> {
+        match self {
+            ExchangeFormat::Railml => {
+                let xml = std::str::from_utf8(data)
+                    .map_err(|_| ExchangeFileError::Invalid("not UTF-8".to_owned()))?;
+                railml::read(xml, date)
+            }
+            ExchangeFormat::Gtfs => gtfs::read(data, date),
+        }
+    }
+}
+
+/// A timetable as exchanged with other tools
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeTimetable {
+    /// The timezone the times and the running days of the file are given in
+    pub timezone: Tz,
+    pub stops: Vec<ExchangeStop>,
+    pub trains: Vec<ExchangeTrain>,
+}
+
+/// The day on which a train departing at `time` runs, in a timezone
+pub fn service_day(timezone: Tz, time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&timezone).date_naive()
+}
+
+/// The instant the times of a service day are counted from, in a timezone
+///
+/// As in GTFS, it is noon minus 12 hours, so that daylight saving time changes, which happen
+/// at night, do not shift the times of the day.
+pub fn service_day_start(timezone: Tz, day: NaiveDate) -> DateTime<Utc> {
+    let noon = day.and_hms_opt(12, 0, 0).expect("noon is a valid time");
+    let noon = timezone
+        .from_local_datetime(&noon)
+        .earliest()
+        .expect("noon is never skipped by a timezone");
+    noon.with_timezone(&Utc) - Duration::hours(12)
+}
+
+/// The first day of a calendar and the number of days it spans
+fn calendar_span(calendar: &OperatingCalendar) -> (NaiveDate, usize) {
+    match calendar {
+        OperatingCalendar::Period {
+            start_date,
+            end_date,
+            ..
+        } => {
+            let day_count = (*end_date - *start_date).num_days() + 1;
+            (*start_date, usize::try_from(day_count).unwrap_or_default())
+        }
+        OperatingCalendar::Bitmap { start_date, days } => (*start_date, days.len()),
+    }
+}
+
+/// The running days of a calendar, in chronological order
+fn running_days(calendar: &OperatingCalendar) -> impl Iterator<Item = NaiveDate> + '_ {
+    let (start_date, day_count) = calendar_span(calendar);
+    start_date
+        .iter_days()
+        .take(day_count)
+        .filter(|day| calendar.runs_on(*day))
+}
+
+/// The first running day of a calendar, along with the calendar unless it is the only one
+///
+/// Returns `None` if the calendar has no running day.
+fn first_running_day(
+    calendar: OperatingCalendar,
+) -> Option<(NaiveDate, Option<OperatingCalendar>)> {
+    let mut days = running_days(&calendar);
+    let first_day = days.next()?;
+    let several_days = days.next().is_some();
+    Some((first_day, several_days.then_some(calendar)))
+}
+
+/// Moves the running days of a calendar by a number of days
+fn shift_calendar(calendar: OperatingCalendar, offset: i64) -> OperatingCalendar {
+    let shift = |day: NaiveDate| {
+        day.checked_add_signed(Duration::days(offset))
+            .unwrap_or(if offset < 0 {
+                NaiveDate::MIN
+            } else {
+                NaiveDate::MAX
+            })
+    };
+    match calendar {
+        OperatingCalendar::Period {
+            start_date,
+            end_date,
+            weekdays,
+        } => {
+            let mut shifted_weekdays = weekdays.clone().into_bytes();
+            for (weekday, running) in weekdays.bytes().enumerate() {
+                shifted_weekdays[(weekday as i64 + offset).rem_euclid(7) as usize] = running;
+            }
+            OperatingCalendar::Period {
+                start_date: shift(start_date),
+                end_date: shift(end_date),
+                weekdays: String::from_utf8(shifted_weekdays).expect("weekdays are ASCII"),
+            }
+        }
+        OperatingCalendar::Bitmap { start_date, days } => OperatingCalendar::Bitmap {
+            start_date: shift(start_date),
+            days,
+        },
+    }
+}
+
+/// A stop of an exchanged timetable
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExchangeStop {
+    /// Identifier of the stop in the file
+    pub id: String,
+    pub name: Option<String>,
+    pub uic: Option<u32>,
+    pub trigram: Option<String>,
+    pub secondary_code: Option<String>,
+    /// Object id of the operational point in the exporting infra
+    pub operational_point: Option<String>,
+    /// Longitude and latitude, in WGS84
+    pub coordinates: Option<(f64, f64)>,
+}
+
+impl ExchangeStop {
+    /// The operational point of the stop, identified by UIC code, by trigram or by object id
+    pub fn identifier(&self) -> Option<OperationalPointIdentifier> {
+        let secondary_code = self.secondary_code.clone();
+        if let Some(uic) = self.uic {
+            return Some(OperationalPointIdentifier::OperationalPointUic {
+                uic,
+                secondary_code,
+            });
+        }
+        if let Some(trigram) = self.trigram.as_ref().filter(|t| !t.trim().is_empty()) {
+            return Some(OperationalPointIdentifier::OperationalPointDescription {
+                trigram: NonBlankString(trigram.clone()),
+                secondary_code,
+            });
+        }
+        self.operational_point
+            .as_ref()
+            .filter(|id| (1..256).contains(&id.len()))
+            .map(|id| OperationalPointIdentifier::OperationalPointId {
+                operational_point: id.as_str().into(),
+            })
+    }
+}
+
+/// A train of an exchanged timetable
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeTrain {
+    /// Identifier of the train in the file
+    pub id: String,
+    pub name: String,
+    /// The departure time on the first running day
+    pub start_time: DateTime<Utc>,
+    /// The running days in the days of the file, the train only runs on the day of its start
+    /// time if not set
+    pub calendar: Option<OperatingCalendar>,
+    pub calls: Vec<Call>,
+}
+
+/// A stop or a passing point of a train
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub stop_id: String,
+    /// Arrival time, from the start time of the train
+    pub arrival: Option<Duration>,
+    /// Departure time, from the start time of the train
+    pub departure: Option<Duration>,
+    /// Whether the train stops, passing times are given as both arrival and departure otherwise
+    pub stops: bool,
+}
+
+/// A stop of an exchanged timetable missing from the infra
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UnmatchedStop {
+    /// Identifier of the stop in the file, or its operational point reference on export
+    pub id: String,
+    pub name: Option<String>,
+    /// The names of the trains calling at the stop
+    pub train_names: Vec<String>,
+}
+
+impl UnmatchedStop {
+    fn add_train(&mut self, train_name: &str) {
+        if self.train_names.last().map(String::as_str) != Some(train_name) {
+            self.train_names.push(train_name.to_owned());
+        }
+    }
+}
+
+/// A readable key of an operational point reference, such as `87271007/BV` or `PNO`
+fn identifier_key(identifier: &OperationalPointIdentifier) -> String {
+    let (code, secondary_code) = match identifier {
+        OperationalPointIdentifier::OperationalPointId { operational_point } => {
+            (operational_point.0.clone(), None)
+        }
+        OperationalPointIdentifier::OperationalPointDescription {
+            trigram,
+            secondary_code,
+        } => (trigram.0.clone(), secondary_code.as_ref()),
+        OperationalPointIdentifier::OperationalPointUic {
+            uic,
+            secondary_code,
+        } => (uic.to_string(), secondary_code.as_ref()),
+    };
+    match secondary_code {
+        Some(secondary_code) => format!("{code}/{secondary_code}"),
+        None => code,
+    }
+}
+
+fn operational_point_reference(reference: OperationalPointIdentifier) -> PathItemLocation {
+    PathItemLocation::OperationalPointReference(OperationalPointReference {
+        reference,
+        track_reference: None,
+    })
+}
+
+#[derive(QueryableByName)]
+struct OperationalPointCoordinates {
+    #[diesel(sql_type = Text)]
+    obj_id: String,
+    #[diesel(sql_type = Double)]
+    lon: f64,
+    #[diesel(sql_type = Double)]
+    lat: f64,
+}
+
+/// The coordinates of operational points, at the centroid of their parts
+async fn operational_point_coordinates(
+    conn: &mut DbConnection,
+    infra_id: i64,
+    obj_ids: Vec<String>,
+) -> Result<HashMap<String, (f64, f64)>> {
+    let coordinates = sql_query(
+        "SELECT obj_id, ST_X(centroid) AS lon, ST_Y(centroid) AS lat
+        FROM (
+            SELECT obj_id, ST_Transform(ST_Centroid(ST_Collect(geographic)), 4326) AS centroid
+            FROM infra_layer_operational_point
+            WHERE infra_id = $1 AND obj_id = ANY($2)
+            GROUP BY obj_id
+        ) AS operational_point",
+    )
+    .bind::<BigInt, _>(infra_id)
+    .bind::<Array<Text>, _>(obj_ids)
+    .load::<OperationalPointCoordinates>(conn.write().await.deref_mut())
+    .await?;
+    Ok(coordinates
+        .into_iter()
+        .map(|op| (op.obj_id, (op.lon, op.lat)))
+        .collect())
+}
+
+impl ExchangeStop {
+    fn from_operational_point(
+        id: String,
+        op: &OperationalPointModel,
+        secondary_code: Option<String>,
+    ) -> Self {
+        let sncf = op.extensions.sncf.as_ref();
+        let identifier = op.extensions.identifier.as_ref();
+        Self {
+            id,
+            name: identifier
+                .map(|identifier| identifier.name.0.clone())
+                .or_else(|| sncf.map(|sncf| sncf.ch_long_label.0.clone())),
+            uic: identifier.and_then(|identifier| u32::try_from(identifier.uic).ok()),
+            trigram: sncf.map(|sncf| sncf.trigram.clone()),
+            secondary_code: secondary_code.or_else(|| sncf.map(|sncf| sncf.ch.clone())),
+            operational_point: Some(op.obj_id.clone()),
+            coordinates: None,
+        }
+    }
+}
+
+impl ExchangeTimetable {
+    /// Converts train schedules, looking up their stops in an infra
+    ///
+    /// Only the path items referencing an operational point are exchanged. Stops are given the
+    /// name, codes and coordinates of the operational point they reference, the ones missing
+    /// from the infra are returned with the trains calling at them. The times and the running
+    /// days are written in the given timezone, trains whose calendar has no running day are
+    /// left out.
+    pub async fn from_train_schedules(
+        conn: &mut DbConnection,
+        infra_id: i64,
+        train_schedules: &[TrainSchedule],
+        timezone: Tz,
+    ) -> Result<(ExchangeTimetable, Vec<UnmatchedStop>)> {
+        let locations: Vec<_> = train_schedules
+            .iter()
+            .flat_map(|train| &train.path)
+            .map(|path_item| &path_item.location)
+            .filter(|location| matches!(location, PathItemLocation::OperationalPointReference(_)))
+            .collect();
+        let cache = PathItemCache::load(conn, infra_id, &locations).await?;
+
+        let mut stops: BTreeMap<String, ExchangeStop> = BTreeMap::new();
+        let mut unmatched: BTreeMap<String, UnmatchedStop> = BTreeMap::new();
+        let mut trains = Vec::with_capacity(train_schedules.len());
+        for train in train_schedules {
+            let (start_time, calendar) = match train.calendar.clone() {
+                None => (train.start_time, None),
+                Some(calendar) => {
+                    let Some((first_day, calendar)) = first_running_day(calendar) else {
+                        continue;
+                    };
+                    let start_time = first_day.and_time(train.start_time.time()).and_utc();
+                    let shift = (service_day(timezone, start_time) - first_day).num_days();
+                    let calendar = calendar.map(|calendar| shift_calendar(calendar, shift));
+                    (start_time, calendar)
+                }
+            };
+            let schedule: HashMap<_, _> =
+                train.schedule.iter().map(|item| (&item.at, item)).collect();
+            let mut calls = vec![];
+            for (index, path_item) in train.path.iter().enumerate() {
+                let PathItemLocation::OperationalPointReference(OperationalPointReference {
+                    reference,
+                    ..
+                }) = &path_item.location
+                else {
+                    continue;
+                };
+                let stop_id = identifier_key(reference);
+                if !stops.contains_key(&stop_id) && !unmatched.contains_key(&stop_id) {
+                    match cache.get_from_identifier(reference).first() {
+                        Some(op) => {
+                            let secondary_code = match reference {
+                                OperationalPointIdentifier::OperationalPointId { .. } => None,
+                                OperationalPointIdentifier::OperationalPointDescription {
+                                    secondary_code,
+                                    ..
+                                }
+                                | OperationalPointIdentifier::OperationalPointUic {
+                                    secondary_code,
+                                    ..
+                                } => secondary_code.clone(),
+                            };
+                            let stop = ExchangeStop::from_operational_point(
+                                stop_id.clone(),
+                                op,
+                                secondary_code,
+                            );
+                            stops.insert(stop_id.clone(), stop);
+                        }
+                        None => {
+                            let stop = UnmatchedStop {
+                                id: stop_id.clone(),
+                                name: None,
+                                train_names: vec![],
+                            };
+                            unmatched.insert(stop_id.clone(), stop);
+                        }
+                    }
+                }
+                if let Some(stop) = unmatched.get_mut(&stop_id) {
+                    stop.add_train(&train.train_name);
+                }
+
+                let schedule_item = schedule.get(&path_item.id);
+                let arrival = schedule_item.and_then(|item| item.arrival.as_ref().map(|a| **a));
+                let stop_for = schedule_item.and_then(|item| item.stop_for.as_ref().map(|s| **s));
+                let is_last = index == train.path.len() - 1;
+                let call = if index == 0 {
+                    Call {
+                        stop_id,
+                        arrival: None,
+                        departure: Some(Duration::zero()),
+                        stops: true,
+                    }
+                } else {
+                    Call {
+                        stop_id,
+                        arrival,
+                        departure: match stop_for {
+                            _ if is_last => None,
+                            Some(stop_for) => arrival.map(|arrival| arrival + stop_for),
+                            None => arrival,
+                        },
+                        stops: is_last || stop_for.is_some(),
+                    }
+                };
+                calls.push(call);
+            }
+            trains.push(ExchangeTrain {
+                id: train.id.to_string(),
+                name: train.train_name.clone(),
+                start_time,
+                calendar,
+                calls,
+            });
+        }
+
+        let obj_ids = stops
+            .values()
+            .filter_map(|stop| stop.operational_point.clone())
+            .collect();
+        let coordinates = operational_point_coordinates(conn, infra_id, obj_ids).await?;
+        let stops = stops
+            .into_values()
+            .map(|mut stop| {
+                stop.coordinates = stop
+                    .operational_point
+                    .as_ref()
+                    .and_then(|obj_id| coordinates.get(obj_id).copied());
+                stop
+            })
+            .collect();
+        Ok((
+            ExchangeTimetable {
+                timezone,
+                stops,
+                trains,
+            },
+            unmatched.into_values().collect(),
+        ))
+    }
+
+    /// Converts the trains into train schedules, matching their stops in an infra
+    ///
+    /// Trains calling at a stop missing from the infra are not converted: the stop is
+    /// returned instead, along with the trains calling at it.
+    pub async fn into_train_schedules(
+        self,
+        conn: &mut DbConnection,
+        infra_id: i64,
+        rolling_stock_name: &str,
+    ) -> Result<(Vec<TrainScheduleBase>, Vec<UnmatchedStop>)> {
+        let identifiers: HashMap<_, _> = self
+            .stops
+            .iter()
+            .filter_map(|stop| Some((stop.id.clone(), stop.identifier()?)))
+            .collect();
+        let locations: Vec<_> = identifiers
+            .values()
+            .cloned()
+            .map(operational_point_reference)
+            .collect();
+        let cache =
+            PathItemCache::load(conn, infra_id, &locations.iter().collect::<Vec<_>>()).await?;
+        let matched: HashSet<_> = identifiers
+            .iter()
+            .filter(|(_, identifier)| !cache.get_from_identifier(identifier).is_empty())
+            .map(|(stop_id, _)| stop_id.clone())
+            .collect();
+        let names: HashMap<_, _> = self
+            .stops
+            .iter()
+            .map(|stop| (stop.id.clone(), stop.name.clone()))
+            .collect();
+
+        let mut unmatched: BTreeMap<String, UnmatchedStop> = BTreeMap::new();
+        let mut train_schedules = vec![];
+        for train in self.trains {
+            let unmatched_calls: Vec<_> = train
+                .calls
+                .iter()
+                .filter(|call| !matched.contains(&call.stop_id))
+                .collect();
+            if !unmatched_calls.is_empty() {
+                for call in unmatched_calls {
+                    unmatched
+                        .entry(call.stop_id.clone())
+                        .or_insert_with(|| UnmatchedStop {
+                            id: call.stop_id.clone(),
+                            name: names.get(&call.stop_id).cloned().flatten(),
+                            train_names: vec![],
+                        })
+                        .add_train(&train.name);
+                }
+                continue;
+            }
+
+            let calendar = train.calendar.map(|calendar| {
+                let shift = running_days(&calendar).next().map_or(0, |first_day| {
+                    (train.start_time.date_naive() - first_day).num_days()
+                });
+                shift_calendar(calendar, shift)
+            });
+            let last_index = train.calls.len().saturating_sub(1);
+            let mut path = Vec::with_capacity(train.calls.len());
+            let mut schedule = vec![];
+            for (index, call) in train.calls.into_iter().enumerate() {
+                let id = NonBlankString(index.to_string());
+                if index > 0 {
+                    let arrival = call.arrival.or(call.departure);
+                    let stop_for = match (arrival, call.departure) {
+                        _ if !call.stops => None,
+                        (Some(arrival), Some(departure)) => Some(departure - arrival),
+                        _ if index == last_index => None,
+                        _ => Some(Duration::zero()),
+                    };
+                    let arrival =
+                        arrival.and_then(|arrival| PositiveDuration::try_from(arrival).ok());
+                    let stop_for =
+                        stop_for.and_then(|stop_for| PositiveDuration::try_from(stop_for).ok());
+                    if arrival.is_some() || stop_for.is_some() {
+                        schedule.push(ScheduleItem {
+                            at: id.clone(),
+                            arrival,
+                            stop_for,
+                            ..Default::default()
+                        });
+                    }
+                }
+                path.push(PathItem {
+                    id,
+                    deleted: false,
+                    location: operational_point_reference(identifiers[&call.stop_id].clone()),
+                });
+            }
+            train_schedules.push(TrainScheduleBase {
+                train_name: train.name,
+                rolling_stock_name: rolling_stock_name.to_owned(),
+                start_time: train.start_time,
+                path,
+                schedule,
+                calendar,
+                ..Default::default()
+            });
+        }
+        Ok((train_schedules, unmatched.into_values().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::models::fixtures::create_small_infra;
+    use editoast_models::DbConnectionPoolV2;
+
+    #[test]
+    fn stop_identifier_prefers_uic() {
+        let stop = ExchangeStop {
+            id: "stop".to_owned(),
+            uic: Some(87271007),
+            trigram: Some("PNO".to_owned()),
+            secondary_code: Some("BV".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            stop.identifier(),
+            Some(OperationalPointIdentifier::OperationalPointUic {
+                uic: 87271007,
+                secondary_code: Some("BV".to_owned()),
+            })
+        );
+
+        let stop = ExchangeStop { uic: None, ..stop };
+        assert_eq!(
+            stop.identifier(),
+            Some(OperationalPointIdentifier::OperationalPointDescription {
+                trigram: NonBlankString("PNO".to_owned()),
+                secondary_code: Some("BV".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn shifted_calendar_runs_on_shifted_days() {
+        let calendars = [
+            OperatingCalendar::Period {
+                start_date: NaiveDate::from_ymd_opt(2025, 3, 3).unwrap(),
+                end_date: NaiveDate::from_ymd_opt(2025, 3, 16).unwrap(),
+                weekdays: "1111100".to_owned(),
+            },
+            OperatingCalendar::Bitmap {
+                start_date: NaiveDate::from_ymd_opt(2025, 3, 10).unwrap(),
+                days: "1011".to_owned(),
+            },
+        ];
+        for calendar in calendars {
+            for shift in [-1, 1] {
+                let shifted = shift_calendar(calendar.clone(), shift);
+                for day in NaiveDate::from_ymd_opt(2025, 3, 1)
+                    .unwrap()
+                    .iter_days()
+                    .take(20)
+                {
+                    assert_eq!(
+                        shifted.runs_on(day + Duration::days(shift)),
+                        calendar.runs_on(day)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn first_running_day_drops_single_day_calendars() {
+        let bitmap = |days: &str| OperatingCalendar::Bitmap {
+            start_date: NaiveDate::from_ymd_opt(2025, 3, 10).unwrap(),
+            days: days.to_owned(),
+        };
+        let day = |day| NaiveDate::from_ymd_opt(2025, 3, day).unwrap();
+        assert_eq!(first_running_day(bitmap("0010")), Some((day(12), None)));
+        assert_eq!(
+            first_running_day(bitmap("0101")),
+            Some((day(11), Some(bitmap("0101"))))
+        );
+        assert_eq!(first_running_day(bitmap("000")), None);
+    }
+
+    #[rstest]
+    async fn import_reports_unmatched_stops() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let conn = &mut db_pool.get_ok();
+        let infra = create_small_infra(conn).await;
+
+        let stop = |id: &str, trigram: &str| ExchangeStop {
+            id: id.to_owned(),
+            trigram: Some(trigram.to_owned()),
+            ..Default::default()
+        };
+        let call = |stop_id: &str, departure: i64| Call {
+            stop_id: stop_id.to_owned(),
+            arrival: Some(Duration::minutes(departure)),
+            departure: Some(Duration::minutes(departure)),
+            stops: true,
+        };
+        let start_time = Utc.with_ymd_and_hms(2025, 1, 1, 8, 0, 0).unwrap();
+        let timetable = ExchangeTimetable {
+            timezone: Tz::UTC,
+            stops: vec![stop("a", "WS"), stop("b", "NES"), stop("c", "XXX")],
+            trains: vec![
+                ExchangeTrain {
+                    id: "1".to_owned(),
+                    name: "matched".to_owned(),
+                    start_time,
+                    calendar: None,
+                    calls: vec![call("a", 0), call("b", 20)],
+                },
+                ExchangeTrain {
+                    id: "2".to_owned(),
+                    name: "unmatched".to_owned(),
+                    start_time,
+                    calendar: None,
+                    calls: vec![call("a", 0), call("c", 20)],
+                },
+            ],
+        };
+
+        let (trains, unmatched) = timetable
+            .into_train_schedules(conn, infra.id, "R2D2")
+            .await
+            .unwrap();
+
+        assert_eq!(trains.len(), 1);
+        assert_eq!(trains[0].train_name, "matched");
+        assert_eq!(trains[0].path.len(), 2);
+        assert_eq!(
+            *trains[0].schedule[0].arrival.clone().unwrap(),
+            Duration::minutes(20)
+        );
+        assert_eq!(
+            unmatched,
+            vec![UnmatchedStop {
+                id: "c".to_owned(),
+                name: None,
+                train_names: vec!["unmatched".to_owned()],
+            }]
+        );
+    }
+}
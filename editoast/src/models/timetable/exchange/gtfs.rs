@@ -0,0 +1,787 @@
+//! GTFS feeds
+//!
+//! Stops are identified by their `stop_code`, either a UIC code or a trigram, and their
+//! `platform_code` holds the secondary code of the operational point. Exported feeds have a
+//! route per train. Trains running on a single day share a service per day, the others have
+//! their own service: a regular calendar for validity periods, calendar dates for day bitmaps.
+//! Times are given in the timezone of the agency, from noon minus 12 hours of the service day.
+//!
+//! Only the stops of the trains are part of a feed, passing points are not exported.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Write;
+
+use chrono::Datelike;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use editoast_schemas::train_schedule::OperatingCalendar;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::ZipArchive;
+use zip::ZipWriter;
+
+use super::first_running_day;
+use super::running_days;
+use super::service_day;
+use super::service_day_start;
+use super::Call;
+use super::ExchangeFileError;
+use super::ExchangeStop;
+use super::ExchangeTimetable;
+use super::ExchangeTrain;
+use super::MAX_CALENDAR_DAYS;
+use super::MAX_IMPORTED_TRAINS;
+
+const AGENCY_ID: &str = "osrd";
+/// Route type of railway services
+const RAIL_ROUTE_TYPE: u8 = 2;
+const DATE_FORMAT: &str = "%Y%m%d";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Agency {
+    #[serde(default)]
+    agency_id: Option<String>,
+    agency_name: String,
+    agency_url: String,
+    agency_timezone: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Stop {
+    stop_id: String,
+    #[serde(default)]
+    stop_code: Option<String>,
+    #[serde(default)]
+    stop_name: Option<String>,
+    #[serde(default)]
+    stop_lat: Option<f64>,
+    #[serde(default)]
+    stop_lon: Option<f64>,
+    #[serde(default)]
+    platform_code: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Route {
+    route_id: String,
+    agency_id: &'static str,
+    route_short_name: String,
+    route_type: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Trip {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+    #[serde(default)]
+    trip_short_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Calendar {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    start_date: String,
+    end_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CalendarDate {
+    service_id: String,
+    date: String,
+    /// `1` if the service is added on the date, `2` if it is removed
+    exception_type: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StopTime {
+    trip_id: String,
+    #[serde(default)]
+    arrival_time: Option<String>,
+    #[serde(default)]
+    departure_time: Option<String>,
+    stop_id: String,
+    stop_sequence: u32,
+    /// `0` if the times are approximate or missing
+    #[serde(default)]
+    timepoint: Option<u8>,
+}
+
+/// Formats a time of a service day, which can exceed 24 hours
+fn format_time(time: Duration) -> String {
+    let seconds = time.num_seconds();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+fn parse_time(time: &str) -> Result<Duration, ExchangeFileError> {
+    let invalid = || ExchangeFileError::Invalid(format!("invalid time '{time}'"));
+    let parts = time
+        .trim()
+        .split(':')
+        .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let [hours, minutes, seconds] = parts[..] else {
+        return Err(invalid());
+    };
+    Ok(Duration::hours(hours) + Duration::minutes(minutes) + Duration::seconds(seconds))
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, ExchangeFileError> {
+    NaiveDate::parse_from_str(date.trim(), DATE_FORMAT)
+        .map_err(|_| ExchangeFileError::Invalid(format!("invalid date '{date}'")))
+}
+
+fn write_file<T: Serialize>(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    name: &str,
+    records: impl IntoIterator<Item = T>,
+) -> Result<(), ExchangeFileError> {
+    zip.start_file(name, SimpleFileOptions::default())?;
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in records {
+        writer.serialize(record)?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|error| ExchangeFileError::Io(error.into_error()))?;
+    zip.write_all(&data)?;
+    Ok(())
+}
+
+/// Writes a timetable as a zipped GTFS feed
+pub fn write(timetable: &ExchangeTimetable) -> Result<Vec<u8>, ExchangeFileError> {
+    let stops = timetable.stops.iter().map(|stop| Stop {
+        stop_id: stop.id.clone(),
+        stop_code: stop
+            .uic
+            .map(|uic| uic.to_string())
+            .or_else(|| stop.trigram.clone()),
+        stop_name: Some(stop.name.clone().unwrap_or_else(|| stop.id.clone())),
+        stop_lat: stop.coordinates.map(|(_, lat)| lat),
+        stop_lon: stop.coordinates.map(|(lon, _)| lon),
+        platform_code: stop.secondary_code.clone(),
+    });
+    let routes = timetable.trains.iter().map(|train| Route {
+        route_id: train.id.clone(),
+        agency_id: AGENCY_ID,
+        route_short_name: train.name.clone(),
+        route_type: RAIL_ROUTE_TYPE,
+    });
+    let timezone = timetable.timezone;
+    let service_id = |train: &ExchangeTrain| match train.calendar {
+        None => service_day(timezone, train.start_time)
+            .format(DATE_FORMAT)
+            .to_string(),
+        Some(_) => format!("train_{}", train.id),
+    };
+    let trips = timetable.trains.iter().map(|train| Trip {
+        route_id: train.id.clone(),
+        service_id: service_id(train),
+        trip_id: train.id.clone(),
+        trip_short_name: Some(train.name.clone()),
+    });
+    let mut calendars = vec![];
+    let mut service_dates = BTreeSet::new();
+    for train in &timetable.trains {
+        let service_id = service_id(train);
+        match &train.calendar {
+            None => {
+                service_dates.insert((service_id.clone(), service_id));
+            }
+            Some(OperatingCalendar::Period {
+                start_date,
+                end_date,
+                weekdays,
+            }) => calendars.push(Calendar::new(service_id, *start_date, *end_date, weekdays)),
+            Some(calendar @ OperatingCalendar::Bitmap { .. }) => {
+                service_dates.extend(
+                    running_days(calendar)
+                        .map(|day| (service_id.clone(), day.format(DATE_FORMAT).to_string())),
+                );
+            }
+        }
+    }
+    let calendar_dates = service_dates
+        .into_iter()
+        .map(|(service_id, date)| CalendarDate {
+            service_id,
+            date,
+            exception_type: 1,
+        });
+    let stop_times = timetable.trains.iter().flat_map(|train| {
+        let day = service_day(timezone, train.start_time);
+        let start = train.start_time - service_day_start(timezone, day);
+        train
+            .calls
+            .iter()
+            .filter(|call| call.stops)
+            .enumerate()
+            .map(move |(sequence, call)| {
+                let arrival = call.arrival.or(call.departure);
+                let departure = call.departure.or(call.arrival);
+                StopTime {
+                    trip_id: train.id.clone(),
+                    arrival_time: arrival.map(|arrival| format_time(start + arrival)),
+                    departure_time: departure.map(|departure| format_time(start + departure)),
+                    stop_id: call.stop_id.clone(),
+                    stop_sequence: sequence as u32 + 1,
+                    timepoint: Some(u8::from(arrival.is_some())),
+                }
+            })
+    });
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    write_file(
+        &mut zip,
+        "agency.txt",
+        [Agency {
+            agency_id: Some(AGENCY_ID.to_owned()),
+            agency_name: "OSRD".to_owned(),
+            agency_url: "https://osrd.fr".to_owned(),
+            agency_timezone: timezone.name().to_owned(),
+        }],
+    )?;
+    write_file(&mut zip, "stops.txt", stops)?;
+    write_file(&mut zip, "routes.txt", routes)?;
+    write_file(&mut zip, "trips.txt", trips)?;
+    if !calendars.is_empty() {
+        write_file(&mut zip, "calendar.txt", calendars)?;
+    }
+    write_file(&mut zip, "calendar_dates.txt", calendar_dates)?;
+    write_file(&mut zip, "stop_times.txt", stop_times)?;
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Reads the records of a file of the feed, `None` if the feed does not contain it
+fn read_file<T: DeserializeOwned>(
+    zip: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> Result<Option<Vec<T>>, ExchangeFileError> {
+    let mut file = match zip.by_name(name) {
+        Ok(file) => file,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+    let mut data = vec![];
+    file.read_to_end(&mut data)?;
+    // Feeds are often written with a byte order mark
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&data);
+    let records = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data)
+        .deserialize()
+        .collect::<Result<_, _>>()?;
+    Ok(Some(records))
+}
+
+fn read_required_file<T: DeserializeOwned>(
+    zip: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> Result<Vec<T>, ExchangeFileError> {
+    read_file(zip, name)?
+        .ok_or_else(|| ExchangeFileError::Invalid(format!("the feed has no '{name}' file")))
+}
+
+impl Calendar {
+    fn new(service_id: String, start_date: NaiveDate, end_date: NaiveDate, weekdays: &str) -> Self {
+        let running = |weekday: usize| u8::from(weekdays.as_bytes()[weekday] == b'1');
+        Self {
+            service_id,
+            monday: running(0),
+            tuesday: running(1),
+            wednesday: running(2),
+            thursday: running(3),
+            friday: running(4),
+            saturday: running(5),
+            sunday: running(6),
+            start_date: start_date.format(DATE_FORMAT).to_string(),
+            end_date: end_date.format(DATE_FORMAT).to_string(),
+        }
+    }
+
+    fn weekdays(&self) -> [bool; 7] {
+        [
+            self.monday,
+            self.tuesday,
+            self.wednesday,
+            self.thursday,
+            self.friday,
+            self.saturday,
+            self.sunday,
+        ]
+        .map(|running| running == 1)
+    }
+}
+
+/// The running days of a service, from its regular calendars and their exceptions
+#[derive(Debug, Default)]
+struct Service {
+    /// The validity periods of the regular calendars, with the weekdays they run on
+    periods: Vec<(NaiveDate, NaiveDate, [bool; 7])>,
+    added: BTreeSet<NaiveDate>,
+    removed: BTreeSet<NaiveDate>,
+}
+
+impl Service {
+    fn runs_on(&self, day: NaiveDate) -> bool {
+        let weekday = day.weekday().num_days_from_monday() as usize;
+        !self.removed.contains(&day)
+            && (self.added.contains(&day)
+                || self.periods.iter().any(|(start_date, end_date, weekdays)| {
+                    (*start_date..=*end_date).contains(&day) && weekdays[weekday]
+                }))
+    }
+
+    /// The running days in chronological order
+    ///
+    /// The days are enumerated lazily, so that long periods are not expanded upfront.
+    fn days(&self) -> impl Iterator<Item = NaiveDate> + '_ {
+        let bounds = self
+            .periods
+            .iter()
+            .flat_map(|(start_date, end_date, _)| [*start_date, *end_date])
+            .chain(self.added.iter().copied());
+        let first = bounds.clone().min();
+        let last = bounds.max();
+        first
+            .zip(last)
+            .into_iter()
+            .flat_map(|(first, last)| first.iter_days().take_while(move |day| *day <= last))
+            .filter(move |day| self.runs_on(*day))
+    }
+
+    /// The operating calendar of the service
+    ///
+    /// A single regular calendar is kept as a validity period, the running days are listed as
+    /// a day bitmap otherwise.
+    fn calendar(&self, service_id: &str) -> Result<OperatingCalendar, ExchangeFileError> {
+        if let ([(start_date, end_date, weekdays)], true, true) = (
+            self.periods.as_slice(),
+            self.added.is_empty(),
+            self.removed.is_empty(),
+        ) {
+            return Ok(OperatingCalendar::Period {
+                start_date: *start_date,
+                end_date: *end_date,
+                weekdays: weekdays
+                    .iter()
+                    .map(|&running| if running { '1' } else { '0' })
+                    .collect(),
+            });
+        }
+
+        let mut days = self.days();
+        let Some(start_date) = days.next() else {
+            return Ok(OperatingCalendar::Bitmap {
+                start_date: NaiveDate::default(),
+                days: String::new(),
+            });
+        };
+        let mut bitmap = String::from("1");
+        for day in days {
+            let offset = (day - start_date).num_days() as usize;
+            if offset >= MAX_CALENDAR_DAYS {
+                return Err(ExchangeFileError::TooLongCalendar {
+                    service: service_id.to_owned(),
+                    max: MAX_CALENDAR_DAYS,
+                });
+            }
+            bitmap.extend(std::iter::repeat_n('0', offset - bitmap.len()));
+            bitmap.push('1');
+        }
+        Ok(OperatingCalendar::Bitmap {
+            start_date,
+            days: bitmap,
+        })
+    }
+}
+
+/// The services of the feed, from the regular calendars and their exceptions
+fn services(
+    calendars: Vec<Calendar>,
+    calendar_dates: Vec<CalendarDate>,
+) -> Result<HashMap<String, Service>, ExchangeFileError> {
+    let mut services: HashMap<String, Service> = HashMap::new();
+    for calendar in calendars {
+        let period = (
+            parse_date(&calendar.start_date)?,
+            parse_date(&calendar.end_date)?,
+            calendar.weekdays(),
+        );
+        services
+            .entry(calendar.service_id)
+            .or_default()
+            .periods
+            .push(period);
+    }
+    for calendar_date in calendar_dates {
+        let date = parse_date(&calendar_date.date)?;
+        let service = services.entry(calendar_date.service_id).or_default();
+        match calendar_date.exception_type {
+            1 => {
+                service.removed.remove(&date);
+                service.added.insert(date);
+            }
+            2 => {
+                service.added.remove(&date);
+                service.removed.insert(date);
+            }
+            exception_type => {
+                return Err(ExchangeFileError::Invalid(format!(
+                    "invalid exception type '{exception_type}'"
+                )))
+            }
+        };
+    }
+    Ok(services)
+}
+
+/// The timezone of the feed, the one of its agencies
+fn timezone(agencies: &[Agency]) -> Result<Tz, ExchangeFileError> {
+    let Some(agency) = agencies.first() else {
+        return Err(ExchangeFileError::Invalid(
+            "the feed has no agency".to_owned(),
+        ));
+    };
+    agency.agency_timezone.parse().map_err(|_| {
+        ExchangeFileError::Invalid(format!("unknown timezone '{}'", agency.agency_timezone))
+    })
+}
+
+/// Reads a zipped GTFS feed, keeping only the trips running on `date` if given
+///
+/// The trips are read as a train running on the days of their service, or only on `date`.
+/// Days and times are given in the timezone of the agency.
+pub fn read(data: &[u8], date: Option<NaiveDate>) -> Result<ExchangeTimetable, ExchangeFileError> {
+    let mut zip = ZipArchive::new(Cursor::new(data))?;
+    let agencies: Vec<Agency> = read_required_file(&mut zip, "agency.txt")?;
+    let timezone = timezone(&agencies)?;
+    let stops: Vec<Stop> = read_required_file(&mut zip, "stops.txt")?;
+    let trips: Vec<Trip> = read_required_file(&mut zip, "trips.txt")?;
+    let stop_times: Vec<StopTime> = read_required_file(&mut zip, "stop_times.txt")?;
+    let calendars: Vec<Calendar> = read_file(&mut zip, "calendar.txt")?.unwrap_or_default();
+    let calendar_dates: Vec<CalendarDate> =
+        read_file(&mut zip, "calendar_dates.txt")?.unwrap_or_default();
+    let services = services(calendars, calendar_dates)?;
+    // The first running day of each service, with its calendar unless it runs on that day only
+    let service_days = services
+        .iter()
+        .map(|(service_id, service)| {
+            let running_day = match date {
+                Some(date) => service.runs_on(date).then_some((date, None)),
+                None => first_running_day(service.calendar(service_id)?),
+            };
+            Ok((service_id.as_str(), running_day))
+        })
+        .collect::<Result<HashMap<_, _>, ExchangeFileError>>()?;
+
+    let stops = stops
+        .into_iter()
+        .map(|stop| {
+            let code = stop.stop_code.filter(|code| !code.is_empty());
+            let uic = code.as_ref().and_then(|code| code.parse().ok());
+            ExchangeStop {
+                id: stop.stop_id,
+                name: stop.stop_name,
+                uic,
+                trigram: code.filter(|_| uic.is_none()),
+                secondary_code: stop.platform_code.filter(|code| !code.is_empty()),
+                operational_point: None,
+                coordinates: stop.stop_lon.zip(stop.stop_lat),
+            }
+        })
+        .collect();
+
+    let mut trip_stop_times: HashMap<String, Vec<StopTime>> = HashMap::new();
+    for stop_time in stop_times {
+        trip_stop_times
+            .entry(stop_time.trip_id.clone())
+            .or_default()
+            .push(stop_time);
+    }
+
+    let mut trains = vec![];
+    for trip in trips {
+        let mut stop_times = trip_stop_times.remove(&trip.trip_id).unwrap_or_default();
+        stop_times.sort_by_key(|stop_time| stop_time.stop_sequence);
+        let mut times = Vec::with_capacity(stop_times.len());
+        for stop_time in &stop_times {
+            let parse = |time: &Option<String>| {
+                time.as_deref()
+                    .filter(|time| !time.is_empty())
+                    .map(parse_time)
+                    .transpose()
+            };
+            times.push((
+                parse(&stop_time.arrival_time)?,
+                parse(&stop_time.departure_time)?,
+            ));
+        }
+        let Some(start) = times
+            .first()
+            .and_then(|(arrival, departure)| departure.or(*arrival))
+        else {
+            return Err(ExchangeFileError::Invalid(format!(
+                "trip '{}' has no departure time",
+                trip.trip_id
+            )));
+        };
+        let calls: Vec<_> = stop_times
+            .into_iter()
+            .zip(times)
+            .map(|(stop_time, (arrival, departure))| Call {
+                stop_id: stop_time.stop_id,
+                arrival: arrival.map(|arrival| arrival - start),
+                departure: departure.map(|departure| departure - start),
+                stops: true,
+            })
+            .collect();
+
+        let Some((day, calendar)) = service_days
+            .get(trip.service_id.as_str())
+            .cloned()
+            .flatten()
+        else {
+            continue;
+        };
+        if trains.len() == MAX_IMPORTED_TRAINS {
+            return Err(ExchangeFileError::TooManyTrains {
+                max: MAX_IMPORTED_TRAINS,
+            });
+        }
+        trains.push(ExchangeTrain {
+            name: trip
+                .trip_short_name
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| trip.trip_id.clone()),
+            id: trip.trip_id,
+            start_time: service_day_start(timezone, day) + start,
+            calendar,
+            calls,
+        });
+    }
+
+    Ok(ExchangeTimetable {
+        timezone,
+        stops,
+        trains,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use chrono::TimeZone;
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_times_after_midnight() {
+        assert_eq!(
+            parse_time("25:03:10").unwrap(),
+            Duration::hours(25) + Duration::minutes(3) + Duration::seconds(10)
+        );
+        assert_eq!(format_time(parse_time("7:05:00").unwrap()), "07:05:00");
+        assert!(parse_time("7:05").is_err());
+    }
+
+    fn train(start_time: DateTime<Utc>) -> ExchangeTrain {
+        let call = |stop_id: &str, departure: i64| Call {
+            stop_id: stop_id.to_owned(),
+            arrival: Some(Duration::minutes(departure)),
+            departure: Some(Duration::minutes(departure)),
+            stops: true,
+        };
+        ExchangeTrain {
+            id: "42".to_owned(),
+            name: "train 42".to_owned(),
+            start_time,
+            calendar: None,
+            calls: vec![call("AAA", 0), call("BBB", 45)],
+        }
+    }
+
+    #[test]
+    fn write_and_read_feed() {
+        let stop = |id: &str| ExchangeStop {
+            id: id.to_owned(),
+            name: Some(format!("Station {id}")),
+            trigram: Some(id.to_owned()),
+            secondary_code: Some("BV".to_owned()),
+            coordinates: Some((2.35, 48.85)),
+            ..Default::default()
+        };
+        let call = |stop_id: &str, arrival: Option<i64>, departure: Option<i64>, stops| Call {
+            stop_id: stop_id.to_owned(),
+            arrival: arrival.map(Duration::minutes),
+            departure: departure.map(Duration::minutes),
+            stops,
+        };
+        let timetable = ExchangeTimetable {
+            timezone: Tz::UTC,
+            stops: vec![stop("AAA"), stop("BBB"), stop("CCC")],
+            trains: vec![ExchangeTrain {
+                id: "42".to_owned(),
+                name: "train 42".to_owned(),
+                start_time: Utc.with_ymd_and_hms(2025, 1, 1, 23, 30, 0).unwrap(),
+                calendar: None,
+                calls: vec![
+                    call("AAA", None, Some(0), true),
+                    call("BBB", Some(10), Some(10), false),
+                    call("CCC", Some(45), Some(50), true),
+                ],
+            }],
+        };
+
+        let feed = write(&timetable).unwrap();
+        let read_timetable = read(&feed, None).unwrap();
+
+        assert_eq!(read_timetable.stops, timetable.stops);
+        let train = &read_timetable.trains[0];
+        assert_eq!(train.name, "train 42");
+        assert_eq!(train.start_time, timetable.trains[0].start_time);
+        assert_eq!(
+            train.calls,
+            vec![
+                call("AAA", Some(0), Some(0), true),
+                call("CCC", Some(45), Some(50), true),
+            ]
+        );
+        let next_day = NaiveDate::from_ymd_opt(2025, 1, 2);
+        assert!(read(&feed, next_day).unwrap().trains.is_empty());
+    }
+
+    #[test]
+    fn write_and_read_feed_in_agency_timezone() {
+        let stop = |id: &str| ExchangeStop {
+            id: id.to_owned(),
+            trigram: Some(id.to_owned()),
+            ..Default::default()
+        };
+        // 00:30 on January 2nd in Paris
+        let start_time = Utc.with_ymd_and_hms(2025, 1, 1, 23, 30, 0).unwrap();
+        let timetable = ExchangeTimetable {
+            timezone: Tz::Europe__Paris,
+            stops: vec![stop("AAA"), stop("BBB")],
+            trains: vec![train(start_time)],
+        };
+
+        let feed = write(&timetable).unwrap();
+        let mut zip = ZipArchive::new(Cursor::new(feed.as_slice())).unwrap();
+        let agencies: Vec<Agency> = read_required_file(&mut zip, "agency.txt").unwrap();
+        assert_eq!(agencies[0].agency_timezone, "Europe/Paris");
+        let stop_times: Vec<StopTime> = read_required_file(&mut zip, "stop_times.txt").unwrap();
+        assert_eq!(stop_times[0].departure_time.as_deref(), Some("00:30:00"));
+
+        let read_timetable = read(&feed, None).unwrap();
+        assert_eq!(read_timetable.timezone, Tz::Europe__Paris);
+        assert_eq!(read_timetable.trains[0].start_time, start_time);
+        assert_eq!(read_timetable.trains[0].id, "42");
+        let next_day = NaiveDate::from_ymd_opt(2025, 1, 2);
+        assert_eq!(read(&feed, next_day).unwrap().trains.len(), 1);
+        let previous_day = NaiveDate::from_ymd_opt(2025, 1, 1);
+        assert!(read(&feed, previous_day).unwrap().trains.is_empty());
+    }
+
+    #[test]
+    fn write_and_read_feed_with_calendars() {
+        let stop = |id: &str| ExchangeStop {
+            id: id.to_owned(),
+            trigram: Some(id.to_owned()),
+            ..Default::default()
+        };
+        let period = OperatingCalendar::Period {
+            start_date: NaiveDate::from_ymd_opt(2025, 3, 3).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2025, 3, 30).unwrap(),
+            weekdays: "1111100".to_owned(),
+        };
+        let bitmap = OperatingCalendar::Bitmap {
+            start_date: NaiveDate::from_ymd_opt(2025, 3, 10).unwrap(),
+            days: "1011".to_owned(),
+        };
+        let working_days = ExchangeTrain {
+            id: "1".to_owned(),
+            calendar: Some(period.clone()),
+            ..train(Utc.with_ymd_and_hms(2025, 3, 3, 7, 0, 0).unwrap())
+        };
+        let some_days = ExchangeTrain {
+            id: "2".to_owned(),
+            calendar: Some(bitmap.clone()),
+            ..train(Utc.with_ymd_and_hms(2025, 3, 10, 7, 0, 0).unwrap())
+        };
+        let timetable = ExchangeTimetable {
+            timezone: Tz::Europe__Paris,
+            stops: vec![stop("AAA"), stop("BBB")],
+            trains: vec![working_days, some_days],
+        };
+
+        let feed = write(&timetable).unwrap();
+        let mut zip = ZipArchive::new(Cursor::new(feed.as_slice())).unwrap();
+        let calendars: Vec<Calendar> = read_required_file(&mut zip, "calendar.txt").unwrap();
+        assert_eq!(calendars.len(), 1);
+        assert_eq!(
+            calendars[0].weekdays(),
+            [true, true, true, true, true, false, false]
+        );
+        let calendar_dates: Vec<CalendarDate> =
+            read_required_file(&mut zip, "calendar_dates.txt").unwrap();
+        assert_eq!(calendar_dates.len(), 3);
+
+        let read_timetable = read(&feed, None).unwrap();
+        assert_eq!(read_timetable.trains.len(), 2);
+        assert_eq!(read_timetable.trains[0].calendar, Some(period));
+        assert_eq!(read_timetable.trains[1].calendar, Some(bitmap));
+        assert_eq!(
+            read_timetable.trains[1].start_time,
+            timetable.trains[1].start_time
+        );
+
+        // Only the trains running on the day are read, without their calendar
+        let tuesday = NaiveDate::from_ymd_opt(2025, 3, 11);
+        let read_timetable = read(&feed, tuesday).unwrap();
+        assert_eq!(read_timetable.trains.len(), 1);
+        assert_eq!(read_timetable.trains[0].calendar, None);
+        assert_eq!(
+            read_timetable.trains[0].start_time,
+            Utc.with_ymd_and_hms(2025, 3, 11, 7, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn read_services_lazily() {
+        let service = Service {
+            periods: vec![(
+                NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+                NaiveDate::MAX,
+                [true, true, true, true, true, false, false],
+            )],
+            added: BTreeSet::from([NaiveDate::from_ymd_opt(2025, 1, 4).unwrap()]),
+            removed: BTreeSet::from([NaiveDate::from_ymd_opt(2025, 1, 2).unwrap()]),
+        };
+        let days: Vec<_> = service.days().take(4).map(|day| day.day()).collect();
+        assert_eq!(days, vec![1, 3, 4, 6]);
+        let saturday = NaiveDate::from_ymd_opt(2025, 1, 11).unwrap();
+        assert!(!service.runs_on(saturday));
+    }
+}
@@ -0,0 +1,719 @@
+//! railML 3.2 timetables
+//!
+//! Only a subset of the schema is supported: the operational points of the functional
+//! infrastructure with their designators, and the operational trains with their base itinerary
+//! and operating period. Times are local times followed by their UTC offset, such as
+//! `08:00:00+01:00`, and are read as UTC times when they have no offset. Operating periods are
+//! written with a bitmask, and read as running every day between their bounds without one.
+//!
+//! A stop is a point with an arrival time, or the first point of the itinerary. Passing points
+//! only have a departure time.
+
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::Offset;
+use chrono::Utc;
+use chrono_tz::Tz;
+use editoast_schemas::train_schedule::OperatingCalendar;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::calendar_span;
+use super::first_running_day;
+use super::service_day;
+use super::Call;
+use super::ExchangeFileError;
+use super::ExchangeStop;
+use super::ExchangeTimetable;
+use super::ExchangeTrain;
+use super::MAX_IMPORTED_TRAINS;
+
+const NAMESPACE: &str = "https://www.railml.org/schemas/3.2";
+const VERSION: &str = "3.2";
+
+/// Designator registers of the operational points
+const UIC_REGISTER: &str = "UIC";
+const TRIGRAM_REGISTER: &str = "TRIGRAM";
+const SECONDARY_CODE_REGISTER: &str = "SECONDARY_CODE";
+const OPERATIONAL_POINT_REGISTER: &str = "OSRD";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const TIME_FORMAT: &str = "%H:%M:%S";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename = "railML")]
+struct RailMl {
+    #[serde(rename = "@xmlns", default)]
+    xmlns: String,
+    #[serde(rename = "@version", default)]
+    version: String,
+    #[serde(default)]
+    infrastructure: Infrastructure,
+    timetable: Timetable,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Infrastructure {
+    #[serde(rename = "@id", default)]
+    id: String,
+    #[serde(rename = "functionalInfrastructure", default)]
+    functional_infrastructure: FunctionalInfrastructure,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FunctionalInfrastructure {
+    #[serde(rename = "operationalPoints", default)]
+    operational_points: OperationalPoints,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OperationalPoints {
+    #[serde(rename = "operationalPoint", default)]
+    operational_points: Vec<OperationalPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OperationalPoint {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    name: Vec<Name>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    designator: Vec<Designator>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Name {
+    #[serde(rename = "@name")]
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Designator {
+    #[serde(rename = "@register")]
+    register: String,
+    #[serde(rename = "@entry")]
+    entry: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Timetable {
+    #[serde(rename = "@id", default)]
+    id: String,
+    #[serde(rename = "operatingPeriods", default)]
+    operating_periods: OperatingPeriods,
+    #[serde(rename = "baseItineraries", default)]
+    base_itineraries: BaseItineraries,
+    #[serde(rename = "operationalTrains", default)]
+    operational_trains: OperationalTrains,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OperatingPeriods {
+    #[serde(rename = "operatingPeriod", default)]
+    operating_periods: Vec<OperatingPeriod>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OperatingPeriod {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@startDate")]
+    start_date: String,
+    #[serde(rename = "@endDate")]
+    end_date: String,
+    /// A character per day from the start date, `1` if the train runs
+    #[serde(rename = "@bitmask", default, skip_serializing_if = "Option::is_none")]
+    bitmask: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BaseItineraries {
+    #[serde(rename = "baseItinerary", default)]
+    base_itineraries: Vec<BaseItinerary>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BaseItinerary {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "baseItineraryPoint", default)]
+    points: Vec<BaseItineraryPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BaseItineraryPoint {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@seq")]
+    seq: u32,
+    #[serde(rename = "@locationRef")]
+    location_ref: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arrival: Option<Time>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    departure: Option<Time>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Time {
+    #[serde(rename = "@time")]
+    time: String,
+    #[serde(rename = "@dayOffset", default)]
+    day_offset: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OperationalTrains {
+    #[serde(rename = "operationalTrain", default)]
+    operational_trains: Vec<OperationalTrain>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OperationalTrain {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@name", default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(rename = "operationalTrainVariant", default)]
+    variants: Vec<OperationalTrainVariant>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OperationalTrainVariant {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@itineraryRef")]
+    itinerary_ref: String,
+    #[serde(rename = "@operatingPeriodRef")]
+    operating_period_ref: String,
+}
+
+impl Time {
+    /// The local time of an instant in a timezone, with its UTC offset
+    fn new(time: DateTime<Utc>, timezone: Tz, operating_day: NaiveDate) -> Self {
+        let time = time.with_timezone(&timezone);
+        let utc_offset = time.offset().fix().local_minus_utc();
+        let sign = if utc_offset < 0 { '-' } else { '+' };
+        let utc_offset = utc_offset.abs() / 60;
+        Self {
+            time: format!(
+                "{}{sign}{:02}:{:02}",
+                time.format(TIME_FORMAT),
+                utc_offset / 60,
+                utc_offset % 60
+            ),
+            day_offset: (time.date_naive() - operating_day).num_days(),
+        }
+    }
+
+    /// The offset from midnight UTC of the operating day
+    fn offset(&self) -> Result<Duration, ExchangeFileError> {
+        let invalid = || ExchangeFileError::Invalid(format!("invalid time '{}'", self.time));
+        let (time, utc_offset) = match self.time.split_at_checked(8) {
+            Some((time, "" | "Z")) => (time, Duration::zero()),
+            Some((time, utc_offset)) => (time, parse_utc_offset(utc_offset).ok_or_else(invalid)?),
+            None => return Err(invalid()),
+        };
+        let time = NaiveTime::parse_from_str(time, TIME_FORMAT).map_err(|_| invalid())?;
+        Ok(time - NaiveTime::MIN + Duration::days(self.day_offset) - utc_offset)
+    }
+}
+
+/// Parses a UTC offset such as `+01:00`
+fn parse_utc_offset(utc_offset: &str) -> Option<Duration> {
+    let (sign, utc_offset) = match utc_offset.split_at_checked(1)? {
+        ("+", utc_offset) => (1, utc_offset),
+        ("-", utc_offset) => (-1, utc_offset),
+        _ => return None,
+    };
+    let (hours, minutes) = utc_offset.split_once(':')?;
+    let minutes = hours.parse::<i64>().ok()? * 60 + minutes.parse::<i64>().ok()?;
+    Some(Duration::minutes(sign * minutes))
+}
+
+fn designator(register: &str, entry: impl ToString) -> Designator {
+    Designator {
+        register: register.to_owned(),
+        entry: entry.to_string(),
+    }
+}
+
+/// Writes a timetable as a railML document
+pub fn write(timetable: &ExchangeTimetable) -> Result<String, ExchangeFileError> {
+    let location_refs: HashMap<_, _> = timetable
+        .stops
+        .iter()
+        .enumerate()
+        .map(|(index, stop)| (stop.id.as_str(), format!("op_{index}")))
+        .collect();
+    let operational_points = timetable
+        .stops
+        .iter()
+        .map(|stop| {
+            let designators = [
+                stop.uic.map(|uic| designator(UIC_REGISTER, uic)),
+                stop.trigram
+                    .as_ref()
+                    .map(|trigram| designator(TRIGRAM_REGISTER, trigram)),
+                stop.secondary_code
+                    .as_ref()
+                    .map(|code| designator(SECONDARY_CODE_REGISTER, code)),
+                stop.operational_point
+                    .as_ref()
+                    .map(|id| designator(OPERATIONAL_POINT_REGISTER, id)),
+            ];
+            OperationalPoint {
+                id: location_refs[stop.id.as_str()].clone(),
+                name: stop
+                    .name
+                    .iter()
+                    .map(|name| Name { name: name.clone() })
+                    .collect(),
+                designator: designators.into_iter().flatten().collect(),
+            }
+        })
+        .collect();
+
+    let mut operating_periods: Vec<OperatingPeriod> = vec![];
+    let mut base_itineraries = vec![];
+    let mut operational_trains = vec![];
+    for (index, train) in timetable.trains.iter().enumerate() {
+        let date = service_day(timetable.timezone, train.start_time);
+        let calendar = train
+            .calendar
+            .clone()
+            .unwrap_or_else(|| OperatingCalendar::Bitmap {
+                start_date: date,
+                days: "1".to_owned(),
+            });
+        let operating_period = OperatingPeriod::new(format!("opp_{index}"), &calendar);
+        let operating_period_ref = match operating_periods.iter().find(|period| {
+            (&period.start_date, &period.end_date, &period.bitmask)
+                == (
+                    &operating_period.start_date,
+                    &operating_period.end_date,
+                    &operating_period.bitmask,
+                )
+        }) {
+            Some(period) => period.id.clone(),
+            None => {
+                let id = operating_period.id.clone();
+                operating_periods.push(operating_period);
+                id
+            }
+        };
+
+        let time =
+            |offset: Duration| Time::new(train.start_time + offset, timetable.timezone, date);
+        let itinerary_id = format!("bi_{index}");
+        let points = train
+            .calls
+            .iter()
+            .enumerate()
+            .map(|(seq, call)| BaseItineraryPoint {
+                id: format!("{itinerary_id}_{seq}"),
+                seq: seq as u32 + 1,
+                location_ref: location_refs
+                    .get(call.stop_id.as_str())
+                    .cloned()
+                    .unwrap_or_else(|| call.stop_id.clone()),
+                arrival: call.arrival.filter(|_| call.stops).map(time),
+                departure: call.departure.map(time),
+            })
+            .collect();
+        base_itineraries.push(BaseItinerary {
+            id: itinerary_id.clone(),
+            points,
+        });
+        operational_trains.push(OperationalTrain {
+            id: format!("ot_{index}"),
+            name: Some(train.name.clone()),
+            variants: vec![OperationalTrainVariant {
+                id: format!("otv_{index}"),
+                itinerary_ref: itinerary_id,
+                operating_period_ref,
+            }],
+        });
+    }
+
+    let railml = RailMl {
+        xmlns: NAMESPACE.to_owned(),
+        version: VERSION.to_owned(),
+        infrastructure: Infrastructure {
+            id: "infrastructure".to_owned(),
+            functional_infrastructure: FunctionalInfrastructure {
+                operational_points: OperationalPoints { operational_points },
+            },
+        },
+        timetable: Timetable {
+            id: "timetable".to_owned(),
+            operating_periods: OperatingPeriods { operating_periods },
+            base_itineraries: BaseItineraries { base_itineraries },
+            operational_trains: OperationalTrains { operational_trains },
+        },
+    };
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let mut serializer = quick_xml::se::Serializer::new(&mut xml);
+    serializer.indent(' ', 2);
+    railml.serialize(serializer)?;
+    Ok(xml)
+}
+
+impl OperatingPeriod {
+    /// The period spanned by a calendar, with a bitmask of its running days
+    fn new(id: String, calendar: &OperatingCalendar) -> Self {
+        let (start_date, day_count) = calendar_span(calendar);
+        let days = start_date.iter_days().take(day_count);
+        let end_date = days.clone().last().unwrap_or(start_date);
+        Self {
+            id,
+            start_date: start_date.format(DATE_FORMAT).to_string(),
+            end_date: end_date.format(DATE_FORMAT).to_string(),
+            bitmask: Some(
+                days.map(|day| if calendar.runs_on(day) { '1' } else { '0' })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The running days of the period, all days between its bounds if it has no bitmask
+    fn calendar(&self) -> Result<OperatingCalendar, ExchangeFileError> {
+        let parse = |date: &str| {
+            NaiveDate::parse_from_str(date, DATE_FORMAT)
+                .map_err(|_| ExchangeFileError::Invalid(format!("invalid date '{date}'")))
+        };
+        let start_date = parse(&self.start_date)?;
+        let end_date = parse(&self.end_date)?;
+        if end_date < start_date {
+            return Err(ExchangeFileError::Invalid(format!(
+                "operating period '{}' ends before it starts",
+                self.id
+            )));
+        }
+        Ok(match &self.bitmask {
+            Some(bitmask) => {
+                let day_count = (end_date - start_date).num_days() as usize + 1;
+                OperatingCalendar::Bitmap {
+                    start_date,
+                    days: bitmask
+                        .chars()
+                        .take(day_count)
+                        .map(|running| if running == '1' { '1' } else { '0' })
+                        .collect(),
+                }
+            }
+            None => OperatingCalendar::Period {
+                start_date,
+                end_date,
+                weekdays: "1111111".to_owned(),
+            },
+        })
+    }
+}
+
+impl OperationalPoint {
+    fn designator(&self, register: &str) -> Option<String> {
+        self.designator
+            .iter()
+            .find(|designator| designator.register == register)
+            .map(|designator| designator.entry.clone())
+    }
+}
+
+/// Reads a railML document, keeping only the trains running on `date` if given
+///
+/// Each variant of the operational trains is read as a train running on the days of its
+/// operating period, or only on `date`. The times are converted to UTC using their offsets.
+pub fn read(xml: &str, date: Option<NaiveDate>) -> Result<ExchangeTimetable, ExchangeFileError> {
+    let railml: RailMl = quick_xml::de::from_str(xml)?;
+
+    let stops = railml
+        .infrastructure
+        .functional_infrastructure
+        .operational_points
+        .operational_points
+        .iter()
+        .map(|op| ExchangeStop {
+            id: op.id.clone(),
+            name: op.name.first().map(|name| name.name.clone()),
+            uic: op.designator(UIC_REGISTER).and_then(|uic| uic.parse().ok()),
+            trigram: op.designator(TRIGRAM_REGISTER),
+            secondary_code: op.designator(SECONDARY_CODE_REGISTER),
+            operational_point: op.designator(OPERATIONAL_POINT_REGISTER),
+            coordinates: None,
+        })
+        .collect();
+
+    let timetable = railml.timetable;
+    let itineraries: HashMap<_, _> = timetable
+        .base_itineraries
+        .base_itineraries
+        .into_iter()
+        .map(|itinerary| (itinerary.id.clone(), itinerary))
+        .collect();
+    let operating_periods: HashMap<_, _> = timetable
+        .operating_periods
+        .operating_periods
+        .iter()
+        .map(|period| (period.id.as_str(), period))
+        .collect();
+
+    let mut trains = vec![];
+    for train in timetable.operational_trains.operational_trains {
+        for variant in &train.variants {
+            let itinerary = itineraries.get(&variant.itinerary_ref).ok_or_else(|| {
+                ExchangeFileError::Invalid(format!(
+                    "unknown base itinerary '{}'",
+                    variant.itinerary_ref
+                ))
+            })?;
+            let period = operating_periods
+                .get(variant.operating_period_ref.as_str())
+                .ok_or_else(|| {
+                    ExchangeFileError::Invalid(format!(
+                        "unknown operating period '{}'",
+                        variant.operating_period_ref
+                    ))
+                })?;
+
+            let mut points: Vec<_> = itinerary.points.iter().collect();
+            points.sort_by_key(|point| point.seq);
+            let mut times = Vec::with_capacity(points.len());
+            for point in &points {
+                let arrival = point.arrival.as_ref().map(Time::offset).transpose()?;
+                let departure = point.departure.as_ref().map(Time::offset).transpose()?;
+                times.push((arrival, departure));
+            }
+            let Some(start) = times
+                .first()
+                .and_then(|(arrival, departure)| departure.or(*arrival))
+            else {
+                return Err(ExchangeFileError::Invalid(format!(
+                    "train '{}' has no departure time",
+                    train.id
+                )));
+            };
+            let calls: Vec<_> = points
+                .iter()
+                .zip(times)
+                .enumerate()
+                .map(|(index, (point, (arrival, departure)))| {
+                    let stops = index == 0 || arrival.is_some();
+                    Call {
+                        stop_id: point.location_ref.clone(),
+                        arrival: arrival.or(departure.filter(|_| !stops)).map(|a| a - start),
+                        departure: departure.map(|departure| departure - start),
+                        stops,
+                    }
+                })
+                .collect();
+
+            let calendar = period.calendar()?;
+            let running_day = match date {
+                Some(date) => calendar.runs_on(date).then_some((date, None)),
+                None => first_running_day(calendar),
+            };
+            let Some((day, calendar)) = running_day else {
+                continue;
+            };
+            if trains.len() == MAX_IMPORTED_TRAINS {
+                return Err(ExchangeFileError::TooManyTrains {
+                    max: MAX_IMPORTED_TRAINS,
+                });
+            }
+            trains.push(ExchangeTrain {
+                id: variant.id.clone(),
+                name: train.name.clone().unwrap_or_else(|| train.id.clone()),
+                start_time: day.and_time(NaiveTime::MIN).and_utc() + start,
+                calendar,
+                calls,
+            });
+        }
+    }
+
+    Ok(ExchangeTimetable {
+        timezone: Tz::UTC,
+        stops,
+        trains,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn write_and_read_timetable() {
+        let stop = |id: &str, uic: u32| ExchangeStop {
+            id: id.to_owned(),
+            name: Some(format!("Station {id}")),
+            uic: Some(uic),
+            trigram: Some(id.to_uppercase()),
+            secondary_code: Some("BV".to_owned()),
+            ..Default::default()
+        };
+        let timetable = ExchangeTimetable {
+            timezone: Tz::UTC,
+            stops: vec![stop("abc", 1), stop("def", 2), stop("ghi", 3)],
+            trains: vec![ExchangeTrain {
+                id: "train".to_owned(),
+                name: "night train".to_owned(),
+                start_time: Utc.with_ymd_and_hms(2025, 1, 1, 23, 30, 0).unwrap(),
+                calendar: None,
+                calls: vec![
+                    Call {
+                        stop_id: "abc".to_owned(),
+                        arrival: None,
+                        departure: Some(Duration::zero()),
+                        stops: true,
+                    },
+                    Call {
+                        stop_id: "def".to_owned(),
+                        arrival: Some(Duration::minutes(20)),
+                        departure: Some(Duration::minutes(20)),
+                        stops: false,
+                    },
+                    Call {
+                        stop_id: "ghi".to_owned(),
+                        arrival: Some(Duration::minutes(45)),
+                        departure: None,
+                        stops: true,
+                    },
+                ],
+            }],
+        };
+
+        let xml = write(&timetable).unwrap();
+        assert!(xml.contains(r#"time="00:15:00+00:00" dayOffset="1""#));
+
+        let read_timetable = read(&xml, None).unwrap();
+        let stop_ids: Vec<_> = read_timetable.stops.iter().map(|s| s.id.clone()).collect();
+        assert_eq!(stop_ids, vec!["op_0", "op_1", "op_2"]);
+        assert_eq!(read_timetable.stops[1].uic, Some(2));
+        assert_eq!(read_timetable.stops[1].trigram.as_deref(), Some("DEF"));
+        let train = &read_timetable.trains[0];
+        assert_eq!(train.name, "night train");
+        assert_eq!(train.start_time, timetable.trains[0].start_time);
+        let calls: Vec<_> = train
+            .calls
+            .iter()
+            .map(|call| (call.arrival, call.departure, call.stops))
+            .collect();
+        let expected: Vec<_> = timetable.trains[0]
+            .calls
+            .iter()
+            .map(|call| (call.arrival, call.departure, call.stops))
+            .collect();
+        assert_eq!(calls, expected);
+
+        assert!(read(&xml, NaiveDate::from_ymd_opt(2025, 1, 2))
+            .unwrap()
+            .trains
+            .is_empty());
+    }
+
+    #[test]
+    fn write_and_read_timetable_with_utc_offsets() {
+        let call = |stop_id: &str, departure: i64| Call {
+            stop_id: stop_id.to_owned(),
+            arrival: (departure > 0).then(|| Duration::minutes(departure)),
+            departure: Some(Duration::minutes(departure)),
+            stops: true,
+        };
+        // 00:30 on January 2nd in Paris
+        let start_time = Utc.with_ymd_and_hms(2025, 1, 1, 23, 30, 0).unwrap();
+        let timetable = ExchangeTimetable {
+            timezone: Tz::Europe__Paris,
+            stops: vec![],
+            trains: vec![ExchangeTrain {
+                id: "train".to_owned(),
+                name: "train".to_owned(),
+                start_time,
+                calendar: None,
+                calls: vec![call("abc", 0), call("def", 20)],
+            }],
+        };
+
+        let xml = write(&timetable).unwrap();
+        assert!(xml.contains(r#"time="00:30:00+01:00" dayOffset="0""#));
+        assert!(xml.contains(r#"startDate="2025-01-02""#));
+
+        let read_timetable = read(&xml, NaiveDate::from_ymd_opt(2025, 1, 2)).unwrap();
+        assert_eq!(read_timetable.trains[0].start_time, start_time);
+        assert_eq!(
+            read_timetable.trains[0].calls[1].arrival,
+            Some(Duration::minutes(20))
+        );
+    }
+
+    #[test]
+    fn write_and_read_operating_periods() {
+        let call = |stop_id: &str, departure: i64| Call {
+            stop_id: stop_id.to_owned(),
+            arrival: (departure > 0).then(|| Duration::minutes(departure)),
+            departure: Some(Duration::minutes(departure)),
+            stops: true,
+        };
+        let calendar = OperatingCalendar::Period {
+            start_date: NaiveDate::from_ymd_opt(2025, 3, 3).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2025, 3, 9).unwrap(),
+            weekdays: "1111100".to_owned(),
+        };
+        let train = |id: &str| ExchangeTrain {
+            id: id.to_owned(),
+            name: id.to_owned(),
+            start_time: Utc.with_ymd_and_hms(2025, 3, 3, 7, 0, 0).unwrap(),
+            calendar: Some(calendar.clone()),
+            calls: vec![call("abc", 0), call("def", 20)],
+        };
+        let timetable = ExchangeTimetable {
+            timezone: Tz::Europe__Paris,
+            stops: vec![],
+            trains: vec![train("first"), train("second")],
+        };
+
+        let xml = write(&timetable).unwrap();
+        assert!(xml.contains(r#"startDate="2025-03-03" endDate="2025-03-09" bitmask="1111100""#));
+        assert_eq!(xml.matches("<operatingPeriod ").count(), 1);
+
+        let read_timetable = read(&xml, None).unwrap();
+        let train = &read_timetable.trains[0];
+        assert_eq!(train.start_time, timetable.trains[0].start_time);
+        let read_calendar = train.calendar.as_ref().unwrap();
+        for day in NaiveDate::from_ymd_opt(2025, 3, 1)
+            .unwrap()
+            .iter_days()
+            .take(10)
+        {
+            assert_eq!(read_calendar.runs_on(day), calendar.runs_on(day));
+        }
+
+        let saturday = NaiveDate::from_ymd_opt(2025, 3, 8);
+        assert!(read(&xml, saturday).unwrap().trains.is_empty());
+    }
+
+    #[test]
+    fn read_times_with_and_without_utc_offset() {
+        let time = |time: &str| Time {
+            time: time.to_owned(),
+            day_offset: 0,
+        };
+        assert_eq!(time("08:00:00").offset().unwrap(), Duration::hours(8));
+        assert_eq!(time("08:00:00Z").offset().unwrap(), Duration::hours(8));
+        assert_eq!(time("08:00:00+01:00").offset().unwrap(), Duration::hours(7));
+        assert_eq!(
+            time("08:00:00-03:30").offset().unwrap(),
+            Duration::minutes(11 * 60 + 30)
+        );
+        assert!(time("08:00:00+1").offset().is_err());
+    }
+}
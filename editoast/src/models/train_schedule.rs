@@ -1,9 +1,11 @@
 use chrono::DateTime;
+use chrono::NaiveDate;
 use chrono::Utc;
 use editoast_derive::Model;
 use editoast_schemas::train_schedule::Comfort;
 use editoast_schemas::train_schedule::Distribution;
 use editoast_schemas::train_schedule::Margins;
+use editoast_schemas::train_schedule::OperatingCalendar;
 use editoast_schemas::train_schedule::PathItem;
 use editoast_schemas::train_schedule::PowerRestrictionItem;
 use editoast_schemas::train_schedule::ScheduleItem;
@@ -38,6 +40,32 @@ pub struct TrainSchedule {
     pub power_restrictions: Vec<PowerRestrictionItem>,
     #[model(json)]
     pub options: TrainScheduleOptions,
+    /// The train service this schedule is an occurrence of
+    pub train_service_id: Option<i64>,
+    #[model(json)]
+    pub calendar: Option<OperatingCalendar>,
+}
+
+impl TrainSchedule {
+    /// Whether the train runs on the given day (in UTC)
+    ///
+    /// A train without an operating calendar only runs on the day of its start time.
+    pub fn runs_on(&self, day: NaiveDate) -> bool {
+        match &self.calendar {
+            Some(calendar) => calendar.runs_on(day),
+            None => self.start_time.date_naive() == day,
+        }
+    }
+
+    /// The train as it runs on the given day, its start time moved to that day
+    ///
+    /// Returns `None` if the train does not run that day.
+    pub fn on_day(&self, day: NaiveDate) -> Option<TrainSchedule> {
+        self.runs_on(day).then(|| TrainSchedule {
+            start_time: day.and_time(self.start_time.time()).and_utc(),
+            ..self.clone()
+        })
+    }
 }
 
 impl From<TrainScheduleBase> for TrainScheduleChangeset {
@@ -56,6 +84,7 @@ impl From<TrainScheduleBase> for TrainScheduleChangeset {
             speed_limit_tag,
             power_restrictions,
             options,
+            calendar,
         }: TrainScheduleBase,
     ) -> Self {
         TrainSchedule::changeset()
@@ -72,5 +101,6 @@ impl From<TrainScheduleBase> for TrainScheduleChangeset {
             .start_time(start_time)
             .train_name(train_name)
             .options(options)
+            .calendar(calendar)
     }
 }
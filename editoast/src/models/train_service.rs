@@ -0,0 +1,170 @@
+use std::ops::DerefMut;
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use editoast_derive::Model;
+use editoast_models::DbConnection;
+use editoast_schemas::train_schedule::Recurrence;
+use editoast_schemas::train_schedule::TrainScheduleBase;
+use futures_util::stream::TryStreamExt;
+
+use crate::error::Result;
+use crate::models::prelude::*;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::train_schedule::TrainScheduleChangeset;
+
+/// A base train schedule repeated at a regular interval
+///
+/// The service is either only simulated virtually, or expanded into a train schedule per
+/// occurrence. Expanded occurrences are regenerated from the pattern whenever it is edited.
+#[derive(Debug, Clone, Model)]
+#[model(table = editoast_models::tables::train_service)]
+#[model(gen(ops = crud))]
+pub struct TrainService {
+    pub id: i64,
+    pub timetable_id: i64,
+    #[model(json)]
+    pub base: TrainScheduleBase,
+    #[model(json)]
+    pub recurrence: Recurrence,
+    pub expanded: bool,
+}
+
+impl TrainService {
+    /// The train services of a timetable, sorted by id
+    pub async fn list_by_timetable(
+        conn: &mut DbConnection,
+        timetable_id: i64,
+    ) -> Result<Vec<TrainService>> {
+        use editoast_models::tables::train_service::dsl;
+
+        dsl::train_service
+            .filter(dsl::timetable_id.eq(timetable_id))
+            .order_by(dsl::id)
+            .load_stream::<Row<TrainService>>(conn.write().await.deref_mut())
+            .await?
+            .map_ok(Into::into)
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    /// The base schedule of each occurrence, with its own start time
+    pub fn occurrences(&self) -> impl Iterator<Item = TrainScheduleBase> + '_ {
+        self.recurrence
+            .departures(self.base.start_time)
+            .map(|start_time| TrainScheduleBase {
+                start_time,
+                ..self.base.clone()
+            })
+    }
+
+    /// The train schedules expanded from the service, sorted by start time
+    pub async fn expanded_trains(&self, conn: &mut DbConnection) -> Result<Vec<TrainSchedule>> {
+        use editoast_models::tables::train_schedule::dsl;
+
+        dsl::train_schedule
+            .filter(dsl::train_service_id.eq(self.id))
+            .order_by((dsl::start_time, dsl::id))
+            .load_stream::<Row<TrainSchedule>>(conn.write().await.deref_mut())
+            .await?
+            .map_ok(Into::into)
+            .try_collect()
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Synchronizes the expanded train schedules with the pattern of the service
+    ///
+    /// The existing train schedules are updated in order of start time to keep their ids, the
+    /// extra ones are deleted and the missing ones created. All of them are deleted if the
+    /// service is not expanded.
+    pub async fn sync_expanded_trains(
+        &self,
+        conn: &mut DbConnection,
+    ) -> Result<Vec<TrainSchedule>> {
+        let existing = self.expanded_trains(conn).await?;
+        let changeset = |occurrence: TrainScheduleBase| {
+            TrainScheduleChangeset::from(occurrence)
+                .timetable_id(self.timetable_id)
+                .train_service_id(Some(self.id))
+        };
+        let mut occurrences: Vec<TrainScheduleBase> = if self.expanded {
+            self.occurrences().collect()
+        } else {
+            vec![]
+        };
+
+        let missing = occurrences.split_off(existing.len().min(occurrences.len()));
+        let to_delete: Vec<_> = existing
+            .iter()
+            .skip(occurrences.len())
+            .map(|train| train.id)
+            .collect();
+        let mut to_create: Vec<_> = missing.into_iter().map(changeset).collect();
+        let mut trains = Vec::with_capacity(occurrences.len() + to_create.len());
+        for (train, occurrence) in existing.iter().zip(occurrences) {
+            match changeset(occurrence.clone()).update(conn, train.id).await? {
+                Some(train) => trains.push(train),
+                // The train schedule was deleted since it was retrieved, it is created again
+                None => to_create.push(changeset(occurrence)),
+            }
+        }
+        TrainSchedule::delete_batch(conn, to_delete).await?;
+        let created: Vec<_> = TrainSchedule::create_batch(conn, to_create).await?;
+        trains.extend(created);
+        Ok(trains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::models::fixtures::create_timetable;
+    use crate::models::fixtures::simple_train_schedule_base;
+    use editoast_models::DbConnectionPoolV2;
+    use editoast_schemas::primitives::PositiveDuration;
+
+    #[rstest]
+    async fn sync_expanded_trains_keeps_ids() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let conn = &mut db_pool.get_ok();
+        let timetable = create_timetable(conn).await;
+        let base = simple_train_schedule_base();
+        let recurrence = Recurrence {
+            interval: PositiveDuration::try_from(Duration::hours(1)).unwrap(),
+            last_departure: base.start_time + Duration::hours(3),
+            exception_days: vec![],
+        };
+        let mut service = TrainService::changeset()
+            .timetable_id(timetable.id)
+            .base(base.clone())
+            .recurrence(recurrence)
+            .expanded(true)
+            .create(conn)
+            .await
+            .expect("Failed to create train service");
+
+        let trains = service.sync_expanded_trains(conn).await.unwrap();
+        assert_eq!(trains.len(), 4);
+        assert_eq!(trains[3].start_time, base.start_time + Duration::hours(3));
+
+        service.recurrence.last_departure = base.start_time + Duration::hours(1);
+        service.base.train_name = "renamed".to_owned();
+        let synced = service.sync_expanded_trains(conn).await.unwrap();
+        assert_eq!(
+            synced.iter().map(|train| train.id).collect::<Vec<_>>(),
+            trains[..2].iter().map(|train| train.id).collect::<Vec<_>>()
+        );
+        assert!(synced.iter().all(|train| train.train_name == "renamed"));
+        assert_eq!(service.expanded_trains(conn).await.unwrap().len(), 2);
+
+        service.expanded = false;
+        assert!(service.sync_expanded_trains(conn).await.unwrap().is_empty());
+        assert!(service.expanded_trains(conn).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,454 @@
+//! Persistent storage of simulation results
+//!
+//! Simulation results are first cached in Valkey, which may evict them at any time. A
+//! [SimulationResultStore] keeps them around as a second-level cache, so that a flushed Valkey
+//! doesn't mean re-simulating every train of a timetable.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::ops::DerefMut;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use editoast_models::db_connection_pool::DatabasePoolError;
+use editoast_models::tables::simulation_result::dsl;
+use editoast_models::DbConnectionPoolV2;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use tracing::debug;
+use tracing::warn;
+
+use crate::core::simulation::SimulationResponse;
+
+/// Maximum number of results inserted in a single query
+const INSERT_CHUNK_SIZE: usize = 1_000;
+
+#[derive(Debug, Error)]
+pub enum SimulationStoreError {
+    #[error(transparent)]
+    DatabasePool(#[from] DatabasePoolError),
+    #[error(transparent)]
+    Database(#[from] diesel::result::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, SimulationStoreError>;
+
+/// Identifies a simulation result along with the inputs it depends on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationResultKey {
+    /// Hash of the simulation request, also used as the Valkey key
+    pub hash: String,
+    pub infra_id: i64,
+    pub infra_version: String,
+    pub rolling_stock_name: String,
+    pub electrical_profile_set_id: Option<i64>,
+}
+
+/// The stored simulation results to discard when one of their inputs changes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Invalidation {
+    /// Results computed on any version of an infra
+    Infra { infra_id: i64 },
+    /// Results computed with a rolling stock
+    RollingStock { name: String },
+    /// Results computed with an electrical profile set
+    ElectricalProfileSet { electrical_profile_set_id: i64 },
+}
+
+impl Invalidation {
+    fn matches(&self, key: &SimulationResultKey) -> bool {
+        match self {
+            Invalidation::Infra { infra_id } => key.infra_id == *infra_id,
+            Invalidation::RollingStock { name } => key.rolling_stock_name == *name,
+            Invalidation::ElectricalProfileSet {
+                electrical_profile_set_id,
+            } => key.electrical_profile_set_id == Some(*electrical_profile_set_id),
+        }
+    }
+}
+
+#[async_trait]
+pub trait SimulationResultStore: Send + Sync {
+    /// Fetch the stored results, in the same order as the given keys
+    async fn get_bulk(
+        &self,
+        keys: &[&SimulationResultKey],
+    ) -> Result<Vec<Option<SimulationResponse>>>;
+
+    /// Store the given results, keeping the existing ones
+    async fn set_bulk(&self, results: &[(&SimulationResultKey, &SimulationResponse)])
+        -> Result<()>;
+
+    /// Discard stored results, returning how many were removed
+    async fn invalidate(&self, invalidation: &Invalidation) -> Result<u64>;
+}
+
+impl dyn SimulationResultStore {
+    /// Discard stored results after one of their inputs changed
+    ///
+    /// Results are keyed by a hash of their inputs, so they are never served stale: this only
+    /// reclaims the space taken by results that can't be hit anymore. Failures are thus logged
+    /// instead of failing the change that triggered the invalidation.
+    pub async fn invalidate_or_warn(&self, invalidation: Invalidation) {
+        match self.invalidate(&invalidation).await {
+            Ok(deleted) => debug!(?invalidation, deleted, "Invalidated stored simulations"),
+            Err(error) => warn!(%error, ?invalidation, "Could not invalidate stored simulations"),
+        }
+    }
+}
+
+/// Which [SimulationResultStore] backs the simulation cache
+#[derive(Debug, Clone, Default)]
+pub enum SimulationStoreConfig {
+    /// Only Valkey caches simulation results
+    #[default]
+    Disabled,
+    /// Results are stored in the `simulation_result` table
+    Postgres,
+    /// Results are stored as JSON files in a directory
+    Filesystem { root: PathBuf },
+}
+
+impl SimulationStoreConfig {
+    pub fn build(self, db_pool: Arc<DbConnectionPoolV2>) -> Arc<dyn SimulationResultStore> {
+        match self {
+            SimulationStoreConfig::Disabled => Arc::new(DisabledStore),
+            SimulationStoreConfig::Postgres => Arc::new(PostgresStore { db_pool }),
+            SimulationStoreConfig::Filesystem { root } => Arc::new(FilesystemStore { root }),
+        }
+    }
+}
+
+/// A store that never keeps anything
+pub struct DisabledStore;
+
+#[async_trait]
+impl SimulationResultStore for DisabledStore {
+    async fn get_bulk(
+        &self,
+        keys: &[&SimulationResultKey],
+    ) -> Result<Vec<Option<SimulationResponse>>> {
+        Ok(vec![None; keys.len()])
+    }
+
+    async fn set_bulk(
+        &self,
+        _results: &[(&SimulationResultKey, &SimulationResponse)],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn invalidate(&self, _invalidation: &Invalidation) -> Result<u64> {
+        Ok(0)
+    }
+}
+
+/// Stores the results in the `simulation_result` table
+///
+/// Results are removed along with their infra or electrical profile set.
+pub struct PostgresStore {
+    db_pool: Arc<DbConnectionPoolV2>,
+}
+
+#[async_trait]
+impl SimulationResultStore for PostgresStore {
+    async fn get_bulk(
+        &self,
+        keys: &[&SimulationResultKey],
+    ) -> Result<Vec<Option<SimulationResponse>>> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let conn = &mut self.db_pool.get().await?;
+        let rows: Vec<(String, serde_json::Value)> = dsl::simulation_result
+            .select((dsl::hash, dsl::result))
+            .filter(dsl::hash.eq_any(keys.iter().map(|key| &key.hash)))
+            .load(conn.write().await.deref_mut())
+            .await?;
+        let mut results: HashMap<_, _> = rows.into_iter().collect();
+        // A result that can't be read anymore is treated as missing
+        Ok(keys
+            .iter()
+            .map(|key| {
+                results
+                    .remove(&key.hash)
+                    .and_then(|result| serde_json::from_value(result).ok())
+            })
+            .collect())
+    }
+
+    async fn set_bulk(
+        &self,
+        results: &[(&SimulationResultKey, &SimulationResponse)],
+    ) -> Result<()> {
+        let conn = &mut self.db_pool.get().await?;
+        for chunk in results.chunks(INSERT_CHUNK_SIZE) {
+            let values = chunk
+                .iter()
+                .map(|(key, result)| {
+                    Ok((
+                        dsl::hash.eq(&key.hash),
+                        dsl::infra_id.eq(key.infra_id),
+                        dsl::infra_version.eq(&key.infra_version),
+                        dsl::rolling_stock_name.eq(&key.rolling_stock_name),
+                        dsl::electrical_profile_set_id.eq(key.electrical_profile_set_id),
+                        dsl::result.eq(serde_json::to_value(result)?),
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            diesel::insert_into(dsl::simulation_result)
+                .values(values)
+                .on_conflict(dsl::hash)
+                .do_nothing()
+                .execute(conn.write().await.deref_mut())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn invalidate(&self, invalidation: &Invalidation) -> Result<u64> {
+        let conn = &mut self.db_pool.get().await?;
+        let query = diesel::delete(dsl::simulation_result);
+        let deleted = match invalidation {
+            Invalidation::Infra { infra_id } => {
+                query
+                    .filter(dsl::infra_id.eq(infra_id))
+                    .execute(conn.write().await.deref_mut())
+                    .await?
+            }
+            Invalidation::RollingStock { name } => {
+                query
+                    .filter(dsl::rolling_stock_name.eq(name))
+                    .execute(conn.write().await.deref_mut())
+                    .await?
+            }
+            Invalidation::ElectricalProfileSet {
+                electrical_profile_set_id,
+            } => {
+                query
+                    .filter(dsl::electrical_profile_set_id.eq(electrical_profile_set_id))
+                    .execute(conn.write().await.deref_mut())
+                    .await?
+            }
+        };
+        Ok(deleted as u64)
+    }
+}
+
+/// Stores each result as a JSON file, in a directory per infra
+pub struct FilesystemStore {
+    root: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredResult {
+    key: SimulationResultKey,
+    result: SimulationResponse,
+}
+
+impl FilesystemStore {
+    fn infra_dir(&self, infra_id: i64) -> PathBuf {
+        self.root.join(infra_id.to_string())
+    }
+
+    fn path(&self, key: &SimulationResultKey) -> PathBuf {
+        let file_name: String = key
+            .hash
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect();
+        self.infra_dir(key.infra_id)
+            .join(format!("{file_name}.json"))
+    }
+
+    async fn read(&self, key: &SimulationResultKey) -> Result<Option<SimulationResponse>> {
+        let data = match tokio::fs::read(self.path(key)).await {
+            Ok(data) => data,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        // A result that can't be read anymore is treated as missing
+        Ok(serde_json::from_slice::<StoredResult>(&data)
+            .ok()
+            .filter(|stored| stored.key == *key)
+            .map(|stored| stored.result))
+    }
+
+    /// Remove the files of a directory whose key matches the invalidation
+    async fn invalidate_dir(&self, dir: PathBuf, invalidation: &Invalidation) -> Result<u64> {
+        let mut deleted = 0;
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            // Skip the files being written
+            if entry.path().extension() != Some("json".as_ref()) {
+                continue;
+            }
+            let data = tokio::fs::read(entry.path()).await?;
+            let matches = serde_json::from_slice::<StoredResult>(&data)
+                .map(|stored| invalidation.matches(&stored.key))
+                .unwrap_or(true);
+            if matches {
+                tokio::fs::remove_file(entry.path()).await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl SimulationResultStore for FilesystemStore {
+    async fn get_bulk(
+        &self,
+        keys: &[&SimulationResultKey],
+    ) -> Result<Vec<Option<SimulationResponse>>> {
+        let mut results = Vec::with_capacity(keys.len());
+        for key in keys {
+            results.push(self.read(key).await?);
+        }
+        Ok(results)
+    }
+
+    async fn set_bulk(
+        &self,
+        results: &[(&SimulationResultKey, &SimulationResponse)],
+    ) -> Result<()> {
+        for (key, result) in results {
+            let path = self.path(key);
+            tokio::fs::create_dir_all(self.infra_dir(key.infra_id)).await?;
+            let data = serde_json::to_vec(&StoredResult {
+                key: (*key).clone(),
+                result: (*result).clone(),
+            })?;
+            // Write then rename, so that a concurrent read never sees a partial file
+            let tmp_path = path.with_extension("json.tmp");
+            tokio::fs::write(&tmp_path, data).await?;
+            tokio::fs::rename(tmp_path, path).await?;
+        }
+        Ok(())
+    }
+
+    async fn invalidate(&self, invalidation: &Invalidation) -> Result<u64> {
+        if let Invalidation::Infra { infra_id } = invalidation {
+            let dir = self.infra_dir(*infra_id);
+            if !tokio::fs::try_exists(&dir).await? {
+                return Ok(0);
+            }
+            let mut deleted = 0;
+            let mut entries = tokio::fs::read_dir(&dir).await?;
+            while entries.next_entry().await?.is_some() {
+                deleted += 1;
+            }
+            tokio::fs::remove_dir_all(dir).await?;
+            return Ok(deleted);
+        }
+        if !tokio::fs::try_exists(&self.root).await? {
+            return Ok(0);
+        }
+        let mut deleted = 0;
+        let mut entries = tokio::fs::read_dir(&self.root).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                deleted += self.invalidate_dir(entry.path(), invalidation).await?;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::models::fixtures::create_electrical_profile_set;
+    use crate::models::fixtures::create_empty_infra;
+
+    fn key(
+        hash: &str,
+        infra_id: i64,
+        electrical_profile_set_id: Option<i64>,
+    ) -> SimulationResultKey {
+        SimulationResultKey {
+            hash: hash.to_owned(),
+            infra_id,
+            infra_version: "1".to_owned(),
+            rolling_stock_name: "R2D2".to_owned(),
+            electrical_profile_set_id,
+        }
+    }
+
+    async fn check_store(store: &dyn SimulationResultStore, infra_id: i64, profile_set_id: i64) {
+        let first = key("simulation_dev.1.1.42", infra_id, None);
+        let second = key("simulation_dev.1.1.43", infra_id, Some(profile_set_id));
+        let result = SimulationResponse::default();
+        store
+            .set_bulk(&[(&first, &result), (&second, &result)])
+            .await
+            .unwrap();
+        let stored = store.get_bulk(&[&second, &first]).await.unwrap();
+        assert_eq!(stored, vec![Some(result.clone()), Some(result.clone())]);
+
+        let deleted = store
+            .invalidate(&Invalidation::ElectricalProfileSet {
+                electrical_profile_set_id: profile_set_id,
+            })
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        let stored = store.get_bulk(&[&first, &second]).await.unwrap();
+        assert_eq!(stored, vec![Some(result), None]);
+
+        let deleted = store
+            .invalidate(&Invalidation::RollingStock {
+                name: "R2D2".to_owned(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert_eq!(store.get_bulk(&[&first]).await.unwrap(), vec![None]);
+    }
+
+    #[rstest]
+    async fn postgres_store() {
+        let db_pool = Arc::new(DbConnectionPoolV2::for_tests());
+        let infra = create_empty_infra(&mut db_pool.get_ok()).await;
+        let profile_set = create_electrical_profile_set(&mut db_pool.get_ok()).await;
+        let store = SimulationStoreConfig::Postgres.build(db_pool);
+        check_store(store.as_ref(), infra.id, profile_set.id).await;
+    }
+
+    #[rstest]
+    async fn filesystem_store() {
+        let root = tempfile::tempdir().unwrap();
+        let store = SimulationStoreConfig::Filesystem {
+            root: root.path().to_owned(),
+        }
+        .build(Arc::new(DbConnectionPoolV2::for_tests()));
+        check_store(store.as_ref(), 1, 2).await;
+
+        let other_infra = key("simulation_dev.2.1.42", 2, None);
+        store
+            .set_bulk(&[(&other_infra, &SimulationResponse::default())])
+            .await
+            .unwrap();
+        let deleted = store
+            .invalidate(&Invalidation::Infra { infra_id: 2 })
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert!(!root.path().join("2").exists());
+    }
+}
@@ -25,6 +25,8 @@ use crate::models::Create;
 use crate::models::DeleteStatic;
 use crate::models::Model;
 use crate::models::Retrieve;
+use crate::simulation_store::Invalidation;
+use crate::AppState;
 
 crate::routes! {
     "/electrical_profile_set" => {
@@ -154,7 +156,11 @@ async fn get_level_order(
     )
 )]
 async fn delete(
-    State(db_pool): State<DbConnectionPoolV2>,
+    State(AppState {
+        db_pool,
+        simulation_store,
+        ..
+    }): State<AppState>,
     Extension(auth): AuthenticationExt,
     Path(electrical_profile_set_id): Path<i64>,
 ) -> Result<impl IntoResponse> {
@@ -168,6 +174,11 @@ async fn delete(
     let conn = &mut db_pool.get().await?;
     let deleted = ElectricalProfileSet::delete_static(conn, electrical_profile_set_id).await?;
     if deleted {
+        simulation_store
+            .invalidate_or_warn(Invalidation::ElectricalProfileSet {
+                electrical_profile_set_id,
+            })
+            .await;
         Ok(StatusCode::NO_CONTENT)
     } else {
         Ok(StatusCode::NOT_FOUND)
@@ -350,6 +350,7 @@ mod tests {
     use crate::views::test_app::TestAppBuilder;
     use editoast_schemas::infra::ApplicableDirectionsTrackRange;
     use editoast_schemas::infra::Detector;
+    use editoast_schemas::infra::Direction;
     use editoast_schemas::infra::Electrification;
     use editoast_schemas::infra::Endpoint;
     use editoast_schemas::infra::InfraObject;
@@ -494,7 +495,13 @@ mod tests {
 
     #[test]
     fn test_invalid_ref_signal_fix() {
-        let signal = SignalCache::new("SA0".to_string(), "TA1".to_string(), 0.0, vec![]);
+        let signal = SignalCache::new(
+            "SA0".to_string(),
+            "TA1".to_string(),
+            0.0,
+            Direction::StartToStop,
+            vec![],
+        );
         let error = InfraError::new_invalid_reference(
             &signal,
             "track",
@@ -517,7 +524,13 @@ mod tests {
 
     #[rstest::rstest]
     async fn test_wrong_invalid_ref_signal_fix() {
-        let signal = SignalCache::new("SA0".to_string(), "TA1".to_string(), 0.0, vec![]);
+        let signal = SignalCache::new(
+            "SA0".to_string(),
+            "TA1".to_string(),
+            0.0,
+            Direction::StartToStop,
+            vec![],
+        );
         let error = InfraError::new_invalid_reference(
             &signal,
             "track",
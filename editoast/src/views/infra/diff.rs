@@ -0,0 +1,175 @@
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::Extension;
+use editoast_authz::BuiltinRole;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::InfraApiError;
+use super::InfraIdParam;
+use crate::error::Result;
+use crate::models::infra::InfraDiff;
+use crate::models::prelude::*;
+use crate::models::Infra;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use editoast_models::DbConnectionPoolV2;
+
+crate::routes! {
+    "/diff/{other_infra_id}" => get_diff,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[allow(unused)]
+struct OtherInfraIdParam {
+    /// The infra to compare with
+    other_infra_id: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DiffVersionQueryParams {
+    /// A previous version of the first infra to compare from, its current version if omitted
+    from_version: Option<i64>,
+    /// A previous version of the second infra to compare to, its current version if omitted
+    to_version: Option<i64>,
+}
+
+/// Compare the objects of two infras, or of two versions of an infra
+///
+/// The diff lists the changes leading from the first infra to the second one.
+/// Modified objects come with a JSON patch, so the diff can be replayed onto another infra
+/// as an edition batch.
+///
+/// A previous version of an infra is rebuilt from its edition history, so it can only be
+/// compared if every edition since then was recorded. Both infras can be the same one.
+#[utoipa::path(
+    get, path = "",
+    tag = "infra",
+    params(InfraIdParam, OtherInfraIdParam, DiffVersionQueryParams),
+    responses(
+        (status = 200, body = InfraDiff, description = "The differences between the two infras"),
+        (status = 400, description = "A version cannot be rebuilt from the edition history"),
+        (status = 404, description = "One of the infras was not found"),
+    )
+)]
+async fn get_diff(
+    Path((infra_id, other_infra_id)): Path<(i64, i64)>,
+    Query(DiffVersionQueryParams {
+        from_version,
+        to_version,
+    }): Query<DiffVersionQueryParams>,
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+) -> Result<Json<InfraDiff>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let infra =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let other_infra = Infra::retrieve_or_fail(conn, other_infra_id, || InfraApiError::NotFound {
+        infra_id: other_infra_id,
+    })
+    .await?;
+
+    let diff = infra
+        .diff(conn, from_version, &other_infra, to_version)
+        .await?;
+    Ok(Json(diff))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::fixtures::create_empty_infra;
+    use crate::models::fixtures::create_small_infra;
+    use crate::views::test_app::TestAppBuilder;
+
+    #[rstest]
+    async fn diff_with_an_empty_infra_removes_everything() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let small_infra = create_small_infra(&mut db_pool.get_ok()).await;
+        let empty_infra = create_empty_infra(&mut db_pool.get_ok()).await;
+
+        let request =
+            app.get(format!("/infra/{}/diff/{}", small_infra.id, empty_infra.id).as_str());
+        let diff: InfraDiff = app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        assert!(diff.added.is_empty());
+        assert!(diff.modified.is_empty());
+        assert!(!diff.removed.is_empty());
+
+        let request =
+            app.get(format!("/infra/{}/diff/{}", empty_infra.id, small_infra.id).as_str());
+        let inverse: InfraDiff = app.fetch(request).assert_status(StatusCode::OK).json_into();
+        assert_eq!(inverse.added.len(), diff.removed.len());
+    }
+
+    #[rstest]
+    async fn diff_with_a_previous_version_lists_the_editions() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let small_infra = create_small_infra(&mut db_pool.get_ok()).await;
+        let initial_version: i64 = small_infra.version.parse().unwrap();
+
+        let request = app
+            .post(format!("/infra/{}/", small_infra.id).as_str())
+            .json(&json!([{
+                "operation_type": "UPDATE",
+                "obj_type": "TrackSection",
+                "obj_id": "TA0",
+                "railjson_patch": [
+                    { "op": "replace", "path": "/length", "value": 1234 }
+                ]
+            }]));
+        app.fetch(request).assert_status(StatusCode::OK);
+
+        let request = app.get(
+            format!(
+                "/infra/{0}/diff/{0}?from_version={initial_version}",
+                small_infra.id
+            )
+            .as_str(),
+        );
+        let diff: InfraDiff = app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].obj_id, "TA0");
+
+        let request = app.get(
+            format!(
+                "/infra/{0}/diff/{0}?from_version={1}",
+                small_infra.id,
+                initial_version + 2
+            )
+            .as_str(),
+        );
+        app.fetch(request).assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[rstest]
+    async fn diff_with_a_missing_infra_returns_404() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let empty_infra = create_empty_infra(&mut db_pool.get_ok()).await;
+
+        let request = app.get(format!("/infra/{}/diff/123456789", empty_infra.id).as_str());
+        app.fetch(request).assert_status(StatusCode::NOT_FOUND);
+    }
+}
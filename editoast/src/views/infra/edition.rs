@@ -12,6 +12,7 @@ use editoast_schemas::infra::Switch;
 use editoast_schemas::infra::TrackEndpoint;
 use editoast_schemas::infra::TrackOffset;
 use editoast_schemas::infra::TrackSection;
+use editoast_schemas::primitives::BoundingBox;
 use editoast_schemas::primitives::Identifier;
 use editoast_schemas::primitives::OSRDIdentified;
 use editoast_schemas::primitives::ObjectType;
@@ -26,6 +27,8 @@ use uuid::Uuid;
 
 use crate::error::Result;
 use crate::generated_data;
+use crate::generated_data::ErrorLayer;
+use crate::generated_data::GeneratedData as _;
 use crate::infra_cache::object_cache::OperationalPointPartCache;
 use crate::infra_cache::operation::CacheOperation;
 use crate::infra_cache::operation::DeleteOperation;
@@ -34,13 +37,17 @@ use crate::infra_cache::operation::UpdateOperation;
 use crate::infra_cache::InfraCache;
 use crate::infra_cache::ObjectCache;
 use crate::map;
+use crate::map::MapLayers;
+use crate::models::infra_edit::InfraEdit;
 use crate::models::prelude::*;
 use crate::models::Infra;
+use crate::simulation_store::Invalidation;
 use crate::views::infra::InfraApiError;
 use crate::views::infra::InfraIdParam;
 use crate::views::AuthenticationExt;
 use crate::views::AuthorizationError;
 use crate::AppState;
+use crate::ValkeyConnection;
 use editoast_models::DbConnection;
 use editoast_schemas::infra::InfraObject;
 
@@ -71,10 +78,12 @@ crate::routes! {
 async fn edit(
     Path(InfraIdParam { infra_id }): Path<InfraIdParam>,
     State(AppState {
+        config,
         db_pool,
         infra_caches,
         valkey,
         map_layers,
+        simulation_store,
         ..
     }): State<AppState>,
     Extension(auth): AuthenticationExt,
@@ -87,6 +96,7 @@ async fn edit(
     if !authorized {
         return Err(AuthorizationError::Forbidden.into());
     }
+    let author_id = auth.user_id();
 
     // TODO: lock for update
     let mut infra = Infra::retrieve_or_fail(&mut db_pool.get().await?, infra_id, || {
@@ -95,21 +105,29 @@ async fn edit(
     .await?;
     let mut infra_cache =
         InfraCache::get_or_load_mut(&mut db_pool.get().await?, &infra_caches, &infra).await?;
-    let operation_results = apply_edit(
+    let mut extent = edited_extent(&operations, &infra_cache);
+    let (operation_results, _) = apply_edit(
         &mut db_pool.get().await?,
         &mut infra,
         &operations,
         &mut infra_cache,
+        InfraEdit::changeset().author_id(author_id),
     )
     .await?;
+    extent.union(&edited_extent(&operations, &infra_cache));
 
     let mut conn = valkey.get_connection().await?;
-    map::invalidate_all(
+    invalidate_edited_tiles(
         &mut conn,
-        &map_layers.layers.keys().cloned().collect(),
+        &map_layers,
         infra_id,
+        &extent,
+        config.map_layers_max_zoom,
     )
     .await?;
+    simulation_store
+        .invalidate_or_warn(Invalidation::Infra { infra_id })
+        .await;
 
     Ok(Json(operation_results))
 }
@@ -126,10 +144,12 @@ async fn edit(
 pub async fn split_track_section(
     Path(InfraIdParam { infra_id }): Path<InfraIdParam>,
     State(AppState {
+        config,
         db_pool,
         infra_caches,
         valkey,
         map_layers,
+        simulation_store,
         ..
     }): State<AppState>,
     Extension(auth): AuthenticationExt,
@@ -142,6 +162,7 @@ pub async fn split_track_section(
     if !authorized {
         return Err(AuthorizationError::Forbidden.into());
     }
+    let author_id = auth.user_id();
 
     info!(
         track_id = payload.track.as_str(),
@@ -327,20 +348,28 @@ pub async fn split_track_section(
     }));
 
     // Apply operations
+    let mut extent = edited_extent(&operations, &infra_cache);
     apply_edit(
         &mut db_pool.get().await?,
         &mut infra,
         &operations,
         &mut infra_cache,
+        InfraEdit::changeset().author_id(author_id),
     )
     .await?;
+    extent.union(&edited_extent(&operations, &infra_cache));
     let mut conn = valkey.get_connection().await?;
-    map::invalidate_all(
+    invalidate_edited_tiles(
         &mut conn,
-        &map_layers.layers.keys().cloned().collect(),
+        &map_layers,
         infra_id,
+        &extent,
+        config.map_layers_max_zoom,
     )
     .await?;
+    simulation_store
+        .invalidate_or_warn(Invalidation::Infra { infra_id })
+        .await;
 
     // Return the result
     Ok(Json(
@@ -836,12 +865,50 @@ fn get_split_patch_operations_for_ranges(
     patch_operations
 }
 
-async fn apply_edit(
+/// The geographic extent of the objects edited by the operations, in the state of the infra cache
+pub(in crate::views::infra) fn edited_extent(
+    operations: &[Operation],
+    infra_cache: &InfraCache,
+) -> BoundingBox {
+    let objects = operations.iter().map(Operation::get_ref).collect_vec();
+    infra_cache.geographic_extent(&objects)
+}
+
+/// Invalidates the cached map tiles displaying the edited objects
+///
+/// `extent` must cover the edited objects both before and after the edition. Errors may be
+/// reported far from the edited objects, for instance on a track section no longer covered
+/// by any route, so the error layer is invalidated as a whole.
+pub(in crate::views::infra) async fn invalidate_edited_tiles(
+    valkey: &mut ValkeyConnection,
+    map_layers: &MapLayers,
+    infra_id: i64,
+    extent: &BoundingBox,
+    max_zoom: u8,
+) -> Result<()> {
+    let (error_layers, layers): (Vec<_>, Vec<_>) = map_layers
+        .layers
+        .iter()
+        .partition(|(_, layer)| layer.table_name == ErrorLayer::table_name());
+    let error_layers = error_layers
+        .into_iter()
+        .map(|(name, _)| name.clone())
+        .collect();
+    map::invalidate_all(valkey, &error_layers, infra_id).await?;
+    map::invalidate_bbox(valkey, &layers, infra_id, extent, max_zoom as u64).await
+}
+
+/// Applies a batch of operations to an infra and records it in the infra edition history
+///
+/// The given `edit` is completed with the operations, their inverse and the new version
+/// of the infra before being saved.
+pub(in crate::views::infra) async fn apply_edit(
     connection: &mut DbConnection,
     infra: &mut Infra,
     operations: &[Operation],
     infra_cache: &mut InfraCache,
-) -> Result<Vec<InfraObject>> {
+    edit: Changeset<InfraEdit>,
+) -> Result<(Vec<InfraObject>, InfraEdit)> {
     let infra_id = infra.id;
     // Check if the infra is locked
     if infra.locked {
@@ -855,8 +922,12 @@ async fn apply_edit(
             Box::pin(async move {
                 let mut railjsons = vec![];
                 let mut cache_operations = vec![];
+                let mut inverse_operations = vec![];
                 for operation in operations {
-                    let railjson = operation.apply(infra_id, &mut conn.clone()).await?;
+                    let (railjson, inverse_operation) = operation
+                        .apply_and_invert(infra_id, &mut conn.clone())
+                        .await?;
+                    inverse_operations.push(inverse_operation);
                     match (operation, railjson) {
                         (Operation::Create(_), Some(railjson)) => {
                             railjsons.push(railjson.clone());
@@ -878,6 +949,22 @@ async fn apply_edit(
 
                 // Bump version
                 infra.bump_version(&mut conn.clone()).await?;
+
+                // Record the edition, undoing it means applying the inverse operations in reverse order
+                inverse_operations.reverse();
+                let edit = edit
+                    .infra_id(infra_id)
+                    .version(
+                        infra
+                            .version
+                            .parse()
+                            .expect("Cannot convert version into an Integer"),
+                    )
+                    .operations(operations.to_vec())
+                    .inverse_operations(inverse_operations)
+                    .create(&mut conn.clone())
+                    .await?;
+
                 // Apply operations to infra cache
                 infra_cache.apply_operations(&cache_operations)?;
 
@@ -894,7 +981,7 @@ async fn apply_edit(
                 // Bump infra generated version to the infra version
                 infra.bump_generated_version(&mut conn.clone()).await?;
 
-                Ok(railjsons)
+                Ok((railjsons, edit))
             })
         })
         .await
@@ -1055,13 +1142,23 @@ pub mod tests {
             }),
         ]
         .to_vec();
-        let result: Vec<InfraObject> =
-            apply_edit(conn, &mut small_infra, &operations, &mut infra_cache)
-                .await
-                .unwrap();
+        let (result, edit) = apply_edit(
+            conn,
+            &mut small_infra,
+            &operations,
+            &mut infra_cache,
+            InfraEdit::changeset(),
+        )
+        .await
+        .unwrap();
 
         // Check that the updated track has the new length
         assert_eq!(1234.0, result[0].get_data()["length"]);
+
+        // Check that the edition has been recorded
+        assert_eq!(edit.infra_id, small_infra.id);
+        assert_eq!(edit.version.to_string(), small_infra.version);
+        assert_eq!(edit.inverse_operations.len(), 1);
     }
 
     #[rstest]
@@ -1101,7 +1198,14 @@ pub mod tests {
             }),
         ]
         .to_vec();
-        let result = apply_edit(conn, &mut small_infra, &operations, &mut infra_cache).await;
+        let result = apply_edit(
+            conn,
+            &mut small_infra,
+            &operations,
+            &mut infra_cache,
+            InfraEdit::changeset(),
+        )
+        .await;
 
         // Check that we have an error
         assert!(result.is_err());
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use axum::extract::Json;
@@ -15,7 +16,11 @@ use thiserror::Error;
 use crate::error::Result;
 use crate::generated_data::infra_error::InfraError;
 use crate::generated_data::infra_error::InfraErrorTypeLabel;
+use crate::generated_data::rules::RuleSettings;
+use crate::generated_data::rules::Severity;
+use crate::generated_data::rules::ValidationRule;
 use crate::models::infra::errors::Level;
+use crate::models::infra_validation_rule::InfraValidationRule;
 use crate::models::prelude::*;
 use crate::models::Infra;
 use crate::views::infra::InfraIdParam;
@@ -23,12 +28,19 @@ use crate::views::pagination::PaginationQueryParams;
 use crate::views::pagination::PaginationStats;
 use crate::views::AuthenticationExt;
 use crate::views::AuthorizationError;
-use editoast_models::DbConnectionPoolV2;
+use crate::AppState;
+use editoast_models::DbConnection;
 
 use super::InfraApiError;
 
 crate::routes! {
-    "/errors" => list_errors,
+    "/errors" => {
+        list_errors,
+        "/rules" => {
+            list_rules,
+            "/{rule_id}" => update_rule,
+        },
+    },
 }
 
 #[derive(Debug, Clone, Deserialize, utoipa::IntoParams)]
@@ -38,7 +50,9 @@ struct ErrorListQueryParams {
     #[serde(default)]
     #[param(inline)]
     level: Level,
-    /// The type of error to filter on
+    /// Filter errors of a given severity
+    severity: Option<Severity>,
+    /// The type of error to filter on, which is also the id of the rule reporting it
     #[param(value_type = Option<InfraErrorTypeLabel>)]
     error_type: Option<String>,
     /// Filter errors and warnings related to a given object
@@ -59,6 +73,18 @@ pub(in crate::views) struct ErrorListResponse {
 #[cfg_attr(test, derive(Deserialize, PartialEq))]
 pub(in crate::views) struct InfraErrorResponse {
     pub(in crate::views) information: InfraError,
+    /// The severity of the error, as configured for the rule reporting it
+    pub(in crate::views) severity: Severity,
+}
+
+/// Resolves the effective settings of the validation rules of an infra
+async fn infra_rules(
+    conn: &mut DbConnection,
+    app_state: &AppState,
+    infra_id: i64,
+) -> Result<Vec<ValidationRule>> {
+    let overrides = InfraValidationRule::list_overrides(conn, infra_id).await?;
+    Ok(app_state.config.validation_rules.resolve(&overrides))
 }
 
 /// A paginated list of errors related to an infra
@@ -71,12 +97,13 @@ pub(in crate::views) struct InfraErrorResponse {
      ),
  )]
 async fn list_errors(
-    State(db_pool): State<DbConnectionPoolV2>,
+    State(app_state): State<AppState>,
     Extension(auth): AuthenticationExt,
     Path(InfraIdParam { infra_id }): Path<InfraIdParam>,
     Query(pagination_params): Query<PaginationQueryParams>,
     Query(ErrorListQueryParams {
         level,
+        severity,
         error_type,
         object_id,
     }): Query<ErrorListQueryParams>,
@@ -101,26 +128,124 @@ async fn list_errors(
         None => None,
     };
 
-    let conn = &mut db_pool.get().await?;
+    let conn = &mut app_state.db_pool.get().await?;
     let infra =
         Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
 
+    // Disabled rules, and rules whose severity does not match the filters, are left out
+    let severities: HashMap<_, _> = infra_rules(conn, &app_state, infra_id)
+        .await?
+        .into_iter()
+        .filter(|rule| rule.matches(severity) && level.includes(rule.severity))
+        .filter(|rule| error_type.is_none_or(|error_type| error_type == rule.id))
+        .map(|rule| (rule.id, rule.severity))
+        .collect();
+    let error_types = severities.keys().copied().collect::<Vec<_>>();
+
     let (results, total_count) = infra
-        .get_paginated_errors(conn, level, error_type, object_id, page, page_size)
+        .get_paginated_errors(conn, &error_types, object_id, page, page_size)
         .await?;
     let results = results
         .into_iter()
-        .map(|information| InfraErrorResponse { information })
+        .map(|information| {
+            let severity = severities[&InfraErrorTypeLabel::from(&information.sub_type)];
+            InfraErrorResponse {
+                information,
+                severity,
+            }
+        })
         .collect::<Vec<_>>();
     let stats = PaginationStats::new(results.len() as u64, total_count, page, page_size);
     Ok(Json(ErrorListResponse { stats, results }))
 }
 
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+#[allow(unused)]
+struct RuleIdParam {
+    /// The id of a validation rule
+    #[param(value_type = InfraErrorTypeLabel)]
+    rule_id: String,
+}
+
+/// List the validation rules of an infra, with their effective settings
+///
+/// The settings of a rule are its defaults, overridden by the server configuration,
+/// then by the settings specific to the infra.
+#[utoipa::path(
+    get, path = "",
+    tag = "infra",
+    params(InfraIdParam),
+    responses(
+        (status = 200, body = Vec<ValidationRule>, description = "The validation rules of the infra"),
+    ),
+)]
+async fn list_rules(
+    State(app_state): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Path(InfraIdParam { infra_id }): Path<InfraIdParam>,
+) -> Result<Json<Vec<ValidationRule>>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut app_state.db_pool.get().await?;
+    Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    Ok(Json(infra_rules(conn, &app_state, infra_id).await?))
+}
+
+/// Override the settings of a validation rule for an infra
+///
+/// Unset fields fall back to the server configuration. Sending empty settings removes the override.
+#[utoipa::path(
+    put, path = "",
+    tag = "infra",
+    params(InfraIdParam, RuleIdParam),
+    request_body = RuleSettings,
+    responses(
+        (status = 200, body = ValidationRule, description = "The rule with its effective settings"),
+        (status = 404, description = "The infra or the rule was not found"),
+    ),
+)]
+async fn update_rule(
+    State(app_state): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Path((infra_id, rule_id)): Path<(i64, String)>,
+    Json(settings): Json<RuleSettings>,
+) -> Result<Json<ValidationRule>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let rule_id = InfraErrorTypeLabel::from_str(&rule_id)
+        .map_err(|_| ListErrorsErrors::UnknownRule { rule_id })?;
+
+    let conn = &mut app_state.db_pool.get().await?;
+    Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    InfraValidationRule::set_override(conn, infra_id, rule_id, settings).await?;
+    let rule = infra_rules(conn, &app_state, infra_id)
+        .await?
+        .into_iter()
+        .find(|rule| rule.id == rule_id)
+        .expect("every rule should be resolved");
+    Ok(Json(rule))
+}
+
 #[derive(Debug, Error, EditoastError)]
 #[editoast_error(base_id = "infra:errors")]
 enum ListErrorsErrors {
     #[error("Wrong Error type provided")]
     WrongErrorTypeProvided,
+    #[error("Unknown validation rule '{rule_id}'")]
+    #[editoast_error(status = 404)]
+    UnknownRule { rule_id: String },
 }
 
 #[cfg(test)]
@@ -128,8 +253,11 @@ pub(in crate::views) async fn query_errors(
     conn: &mut editoast_models::DbConnection,
     infra: &Infra,
 ) -> (Vec<InfraError>, u64) {
+    use strum::IntoEnumIterator;
+
+    let error_types = InfraErrorTypeLabel::iter().collect::<Vec<_>>();
     infra
-        .get_paginated_errors(conn, Level::All, None, None, 1, 10000)
+        .get_paginated_errors(conn, &error_types, None, 1, 10000)
         .await
         .expect("errors should be fetched successfully")
 }
@@ -137,8 +265,11 @@ pub(in crate::views) async fn query_errors(
 #[cfg(test)]
 mod tests {
     use axum::http::StatusCode;
+    use pretty_assertions::assert_eq;
     use rstest::rstest;
+    use serde_json::json;
 
+    use super::*;
     use crate::models::fixtures::create_empty_infra;
     use crate::views::test_app::TestAppBuilder;
 
@@ -160,4 +291,40 @@ mod tests {
         );
         app.fetch(req).assert_status(StatusCode::OK);
     }
+
+    #[rstest]
+    async fn update_rule_overrides_settings_for_the_infra() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let empty_infra = create_empty_infra(&mut db_pool.get_ok()).await;
+
+        let request = app
+            .put(format!("/infra/{}/errors/rules/missing_route", empty_infra.id).as_str())
+            .json(&json!({ "enabled": false }));
+        let rule: ValidationRule = app.fetch(request).assert_status(StatusCode::OK).json_into();
+        assert!(!rule.enabled);
+        assert_eq!(rule.severity, Severity::Warning);
+
+        let request = app.get(format!("/infra/{}/errors/rules", empty_infra.id).as_str());
+        let rules: Vec<ValidationRule> =
+            app.fetch(request).assert_status(StatusCode::OK).json_into();
+        let disabled = rules
+            .into_iter()
+            .filter(|rule| !rule.enabled)
+            .map(|rule| rule.id)
+            .collect::<Vec<_>>();
+        assert_eq!(disabled, vec![InfraErrorTypeLabel::MissingRoute]);
+    }
+
+    #[rstest]
+    async fn update_unknown_rule_returns_404() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let empty_infra = create_empty_infra(&mut db_pool.get_ok()).await;
+
+        let request = app
+            .put(format!("/infra/{}/errors/rules/not_a_rule", empty_infra.id).as_str())
+            .json(&json!({ "enabled": false }));
+        app.fetch(request).assert_status(StatusCode::NOT_FOUND);
+    }
 }
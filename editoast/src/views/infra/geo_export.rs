@@ -0,0 +1,182 @@
+use axum::extract::Path;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::Extension;
+use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use editoast_models::DbConnectionPoolV2;
+use editoast_schemas::primitives::ObjectType;
+use thiserror::Error;
+
+use super::InfraApiError;
+use super::InfraIdParam;
+use crate::error::Result;
+use crate::models::infra::geo_export::export_layer_name;
+use crate::models::infra::geo_export::exported_object_types;
+use crate::models::infra::geo_export::into_feature_collection;
+use crate::models::infra::geo_export::write_geopackage;
+use crate::models::prelude::*;
+use crate::models::Infra;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+
+crate::routes! {
+    "/geojson/{object_type}" => get_geojson,
+    "/geopackage" => get_geopackage,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "infra:geo_export")]
+enum GeoExportError {
+    #[error("Objects of type '{object_type}' have no geometry")]
+    #[editoast_error(status = 400)]
+    NoGeometry { object_type: ObjectType },
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+struct GeoJsonExportParams {
+    /// An existing infra ID
+    infra_id: i64,
+    /// The type of the objects to export
+    object_type: ObjectType,
+}
+
+/// Export the objects of a type with their geometry, as a GeoJSON feature collection
+///
+/// The attributes of the objects are flattened into the properties of the features.
+#[utoipa::path(
+    get, path = "",
+    tag = "infra",
+    params(GeoJsonExportParams),
+    responses(
+        (status = 200, description = "The objects as a GeoJSON feature collection", content_type = "application/geo+json", body = Object),
+        (status = 400, description = "The objects of this type have no geometry"),
+        (status = 404, description = "The infra was not found"),
+    )
+)]
+async fn get_geojson(
+    Path(GeoJsonExportParams {
+        infra_id,
+        object_type,
+    }): Path<GeoJsonExportParams>,
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+) -> Result<impl IntoResponse> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    if export_layer_name(object_type).is_none() {
+        return Err(GeoExportError::NoGeometry { object_type }.into());
+    }
+    let conn = &mut db_pool.get().await?;
+    let infra =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let objects = infra.get_objects_with_geometry(conn, object_type).await?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/geo+json")],
+        serde_json::to_string(&into_feature_collection(objects))?,
+    ))
+}
+
+/// Export the objects with their geometry as a GeoPackage, with a layer per object type
+///
+/// The attributes of the objects are flattened into the columns of the layers.
+#[utoipa::path(
+    get, path = "",
+    tag = "infra",
+    params(InfraIdParam),
+    responses(
+        (status = 200, description = "The GeoPackage file", content_type = "application/geopackage+sqlite3", body = Vec<u8>),
+        (status = 404, description = "The infra was not found"),
+    )
+)]
+async fn get_geopackage(
+    Path(InfraIdParam { infra_id }): Path<InfraIdParam>,
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+) -> Result<impl IntoResponse> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let infra =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let mut layers = vec![];
+    for object_type in exported_object_types() {
+        let objects = infra.get_objects_with_geometry(conn, object_type).await?;
+        let layer_name = export_layer_name(object_type).expect("exported types have a layer");
+        layers.push((layer_name, into_feature_collection(objects)));
+    }
+    let geopackage = write_geopackage(layers)?;
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "application/geopackage+sqlite3".to_owned(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"infra_{infra_id}.gpkg\""),
+            ),
+        ],
+        geopackage,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::Value as JsonValue;
+
+    use crate::infra_cache::InfraCache;
+    use crate::models::fixtures::create_small_infra;
+    use crate::views::test_app::TestAppBuilder;
+
+    #[rstest]
+    async fn export_signals_as_geojson() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let mut infra = create_small_infra(&mut db_pool.get_ok()).await;
+        let infra_cache = InfraCache::load(&mut db_pool.get_ok(), &infra)
+            .await
+            .unwrap();
+        infra
+            .refresh(db_pool.clone(), true, &infra_cache)
+            .await
+            .unwrap();
+
+        let request = app.get(format!("/infra/{}/geojson/Signal", infra.id).as_str());
+        let collection: JsonValue = app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        assert_eq!(collection["type"], "FeatureCollection");
+        let feature = &collection["features"][0];
+        assert_eq!(feature["geometry"]["type"], "Point");
+        assert_eq!(feature["properties"]["id"], feature["id"]);
+        assert!(feature["properties"]["track"].is_string());
+    }
+
+    #[rstest]
+    async fn export_routes_as_geojson_fails() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let infra = create_small_infra(&mut db_pool.get_ok()).await;
+
+        let request = app.get(format!("/infra/{}/geojson/Route", infra.id).as_str());
+        app.fetch(request).assert_status(StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,407 @@
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::Extension;
+use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use super::edition::apply_edit;
+use super::edition::edited_extent;
+use super::edition::invalidate_edited_tiles;
+use super::InfraApiError;
+use super::InfraIdParam;
+use crate::error::Result;
+use crate::infra_cache::operation::Operation;
+use crate::infra_cache::InfraCache;
+use crate::models::infra_edit::InfraEdit;
+use crate::models::prelude::*;
+use crate::models::Infra;
+use crate::simulation_store::Invalidation;
+use crate::views::pagination::PaginatedList as _;
+use crate::views::pagination::PaginationQueryParams;
+use crate::views::pagination::PaginationStats;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+
+crate::routes! {
+    "/history" => {
+        list_history,
+        "/restore" => restore,
+        "/{edit_id}/revert" => revert,
+    },
+}
+
+editoast_common::schemas! {
+    RestoreForm,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "infra:history")]
+enum InfraHistoryError {
+    #[error("Edition '{edit_id}' could not be found in the history of infra '{infra_id}'")]
+    #[editoast_error(status = 404)]
+    EditNotFound { infra_id: i64, edit_id: i64 },
+    #[error("Version '{version}' is not a previous version of the infra (current version: '{current_version}')")]
+    InvalidVersion { version: i64, current_version: i64 },
+    #[error(
+        "The history of infra '{infra_id}' is incomplete, version '{version}' cannot be restored"
+    )]
+    HistoryUnavailable { infra_id: i64, version: i64 },
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(test, derive(Deserialize))]
+struct InfraHistoryResponse {
+    results: Vec<InfraEdit>,
+    #[serde(flatten)]
+    stats: PaginationStats,
+}
+
+/// List the editions applied to an infra, the latest one first
+#[utoipa::path(
+    get, path = "",
+    tag = "infra",
+    params(InfraIdParam, PaginationQueryParams),
+    responses(
+        (status = 200, body = inline(InfraHistoryResponse), description = "The edition history of the infra"),
+        (status = 404, description = "The infra was not found"),
+    )
+)]
+async fn list_history(
+    Path(InfraIdParam { infra_id }): Path<InfraIdParam>,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Query(pagination_params): Query<PaginationQueryParams>,
+) -> Result<Json<InfraHistoryResponse>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+
+    let settings = pagination_params
+        .validate(100)?
+        .into_selection_settings()
+        .filter(move || InfraEdit::INFRA_ID.eq(infra_id))
+        .order_by(|| InfraEdit::VERSION.desc());
+    let (results, stats) = InfraEdit::list_paginated(conn, settings).await?;
+
+    Ok(Json(InfraHistoryResponse { results, stats }))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[allow(unused)]
+struct EditIdParam {
+    /// An infra edition ID
+    edit_id: i64,
+}
+
+/// Revert an edition of an infra
+///
+/// The inverse operations of the edition are applied as a new edition, which is recorded
+/// in the history as well. Reverting a revert therefore redoes the original edition.
+///
+/// The revert is rejected if the objects it touches have been modified since.
+#[utoipa::path(
+    post, path = "",
+    tag = "infra",
+    params(InfraIdParam, EditIdParam),
+    responses(
+        (status = 200, body = InfraEdit, description = "The edition recorded by the revert"),
+        (status = 404, description = "The infra or the edition was not found"),
+    )
+)]
+async fn revert(
+    Path((infra_id, edit_id)): Path<(i64, i64)>,
+    State(AppState {
+        config,
+        db_pool,
+        infra_caches,
+        valkey,
+        map_layers,
+        simulation_store,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+) -> Result<Json<InfraEdit>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+    let author_id = auth.user_id();
+
+    let conn = &mut db_pool.get().await?;
+    let mut infra =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let reverted_edit = InfraEdit::retrieve(conn, edit_id)
+        .await?
+        .filter(|edit| edit.infra_id == infra_id)
+        .ok_or(InfraHistoryError::EditNotFound { infra_id, edit_id })?;
+
+    let mut infra_cache = InfraCache::get_or_load_mut(conn, &infra_caches, &infra).await?;
+    let mut extent = edited_extent(&reverted_edit.inverse_operations, &infra_cache);
+    let (_, edit) = apply_edit(
+        conn,
+        &mut infra,
+        &reverted_edit.inverse_operations,
+        &mut infra_cache,
+        InfraEdit::changeset()
+            .author_id(author_id)
+            .reverted_edit_id(Some(edit_id)),
+    )
+    .await?;
+    extent.union(&edited_extent(
+        &reverted_edit.inverse_operations,
+        &infra_cache,
+    ));
+
+    let mut valkey_conn = valkey.get_connection().await?;
+    invalidate_edited_tiles(
+        &mut valkey_conn,
+        &map_layers,
+        infra_id,
+        &extent,
+        config.map_layers_max_zoom,
+    )
+    .await?;
+    simulation_store
+        .invalidate_or_warn(Invalidation::Infra { infra_id })
+        .await;
+
+    Ok(Json(edit))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[cfg_attr(test, derive(Serialize))]
+struct RestoreForm {
+    /// The version of the infra to restore
+    version: i64,
+}
+
+/// Restore an infra to a previous version
+///
+/// Every edition applied since the requested version is undone at once. The restoration
+/// is recorded in the history as a single new edition, so it can itself be reverted.
+#[utoipa::path(
+    post, path = "",
+    tag = "infra",
+    params(InfraIdParam),
+    request_body = RestoreForm,
+    responses(
+        (status = 200, body = InfraEdit, description = "The edition recorded by the restoration"),
+        (status = 404, description = "The infra was not found"),
+    )
+)]
+async fn restore(
+    Path(InfraIdParam { infra_id }): Path<InfraIdParam>,
+    State(AppState {
+        config,
+        db_pool,
+        infra_caches,
+        valkey,
+        map_layers,
+        simulation_store,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Json(RestoreForm { version }): Json<RestoreForm>,
+) -> Result<Json<InfraEdit>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+    let author_id = auth.user_id();
+
+    let conn = &mut db_pool.get().await?;
+    let mut infra =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let current_version: i64 = infra
+        .version
+        .parse()
+        .expect("Cannot convert version into an Integer");
+    if version < 0 || version >= current_version {
+        return Err(InfraHistoryError::InvalidVersion {
+            version,
+            current_version,
+        }
+        .into());
+    }
+
+    // Every version between the requested one and the current one must have been reached
+    // through a recorded edition, otherwise the infra cannot be reliably restored
+    let edits = InfraEdit::list_after_version(conn, infra_id, version).await?;
+    if edits.len() as i64 != current_version - version {
+        return Err(InfraHistoryError::HistoryUnavailable { infra_id, version }.into());
+    }
+    let operations: Vec<Operation> = edits
+        .into_iter()
+        .flat_map(|edit| edit.inverse_operations)
+        .collect();
+
+    let mut infra_cache = InfraCache::get_or_load_mut(conn, &infra_caches, &infra).await?;
+    let mut extent = edited_extent(&operations, &infra_cache);
+    let (_, edit) = apply_edit(
+        conn,
+        &mut infra,
+        &operations,
+        &mut infra_cache,
+        InfraEdit::changeset().author_id(author_id),
+    )
+    .await?;
+    extent.union(&edited_extent(&operations, &infra_cache));
+
+    let mut valkey_conn = valkey.get_connection().await?;
+    invalidate_edited_tiles(
+        &mut valkey_conn,
+        &map_layers,
+        infra_id,
+        &extent,
+        config.map_layers_max_zoom,
+    )
+    .await?;
+    simulation_store
+        .invalidate_or_warn(Invalidation::Infra { infra_id })
+        .await;
+
+    Ok(Json(edit))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use editoast_schemas::primitives::ObjectType;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::fixtures::create_small_infra;
+    use crate::views::test_app::TestAppBuilder;
+
+    fn update_track_length(length: u64) -> serde_json::Value {
+        json!([{
+            "operation_type": "UPDATE",
+            "obj_type": "TrackSection",
+            "obj_id": "TA0",
+            "railjson_patch": [
+                { "op": "replace", "path": "/length", "value": length }
+            ]
+        }])
+    }
+
+    async fn track_length(db_pool: &editoast_models::DbConnectionPoolV2, infra: &Infra) -> f64 {
+        let objects = infra
+            .get_objects(
+                &mut db_pool.get_ok(),
+                ObjectType::TrackSection,
+                &vec!["TA0".to_string()],
+            )
+            .await
+            .unwrap();
+        objects[0].railjson["length"].as_f64().unwrap()
+    }
+
+    #[rstest]
+    async fn revert_edit_restores_previous_object() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let small_infra = create_small_infra(&mut db_pool.get_ok()).await;
+
+        let request = app
+            .post(format!("/infra/{}/", small_infra.id).as_str())
+            .json(&update_track_length(1234));
+        app.fetch(request).assert_status(StatusCode::OK);
+        assert_eq!(track_length(&db_pool, &small_infra).await, 1234.0);
+
+        let request = app.get(format!("/infra/{}/history", small_infra.id).as_str());
+        let history: InfraHistoryResponse =
+            app.fetch(request).assert_status(StatusCode::OK).json_into();
+        assert_eq!(history.results.len(), 1);
+        let edit_id = history.results[0].id;
+
+        let request =
+            app.post(format!("/infra/{}/history/{edit_id}/revert", small_infra.id).as_str());
+        let revert: InfraEdit = app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        assert_eq!(revert.reverted_edit_id, Some(edit_id));
+        assert_eq!(track_length(&db_pool, &small_infra).await, 2000.0);
+    }
+
+    #[rstest]
+    async fn restore_undoes_all_later_edits() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let small_infra = create_small_infra(&mut db_pool.get_ok()).await;
+        let initial_version: i64 = small_infra.version.parse().unwrap();
+
+        for length in [1234, 4321] {
+            let request = app
+                .post(format!("/infra/{}/", small_infra.id).as_str())
+                .json(&update_track_length(length));
+            app.fetch(request).assert_status(StatusCode::OK);
+        }
+
+        let request = app
+            .post(format!("/infra/{}/history/restore", small_infra.id).as_str())
+            .json(&RestoreForm {
+                version: initial_version,
+            });
+        let restoration: InfraEdit = app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        assert_eq!(restoration.version, initial_version + 3);
+        assert_eq!(track_length(&db_pool, &small_infra).await, 2000.0);
+    }
+
+    #[rstest]
+    async fn restore_fails_without_recorded_history() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let small_infra = create_small_infra(&mut db_pool.get_ok()).await;
+        let initial_version: i64 = small_infra.version.parse().unwrap();
+
+        // The infra is edited without recording the edition between two recorded ones
+        let request = app
+            .post(format!("/infra/{}/", small_infra.id).as_str())
+            .json(&update_track_length(1234));
+        app.fetch(request).assert_status(StatusCode::OK);
+        let mut infra = Infra::retrieve(&mut db_pool.get_ok(), small_infra.id)
+            .await
+            .unwrap()
+            .expect("Infra not found");
+        infra.bump_version(&mut db_pool.get_ok()).await.unwrap();
+        let request = app
+            .post(format!("/infra/{}/", small_infra.id).as_str())
+            .json(&update_track_length(4321));
+        app.fetch(request).assert_status(StatusCode::OK);
+
+        let request = app
+            .post(format!("/infra/{}/history/restore", small_infra.id).as_str())
+            .json(&RestoreForm {
+                version: initial_version,
+            });
+        let error: serde_json::Value = app
+            .fetch(request)
+            .assert_status(StatusCode::BAD_REQUEST)
+            .json_into();
+        assert_eq!(error["type"], "editoast:infra:history:HistoryUnavailable");
+        assert_eq!(track_length(&db_pool, &small_infra).await, 4321.0);
+    }
+}
@@ -1,12 +1,16 @@
 mod attached;
 mod auto_fixes;
 mod delimited_area;
+mod diff;
 mod edition;
 mod errors;
+mod geo_export;
+mod history;
 mod lines;
 mod objects;
 mod pathfinding;
 mod railjson;
+mod rebase;
 mod routes;
 
 use axum::extract::Json;
@@ -38,6 +42,7 @@ use crate::infra_cache::ObjectCache;
 use crate::map;
 use crate::models::prelude::*;
 use crate::models::Infra;
+use crate::simulation_store::Invalidation;
 use crate::views::pagination::PaginatedList as _;
 use crate::views::pagination::PaginationQueryParams;
 use crate::views::AuthorizationError;
@@ -60,7 +65,11 @@ crate::routes! {
             &pathfinding,
             &attached,
             &edition,
+            &diff,
             &errors,
+            &geo_export,
+            &history,
+            &rebase,
             &delimited_area,
 
             get,
@@ -80,6 +89,8 @@ crate::routes! {
 editoast_common::schemas! {
     pathfinding::schemas(),
     delimited_area::schemas(),
+    history::schemas(),
+    rebase::schemas(),
     InfraState,
     InfraWithState,
 }
@@ -427,6 +438,7 @@ async fn delete(
     State(AppState {
         db_pool,
         infra_caches,
+        simulation_store,
         ..
     }): State<AppState>,
     Extension(auth): AuthenticationExt,
@@ -442,6 +454,9 @@ async fn delete(
 
     if Infra::fast_delete_static(db_pool.get().await?, infra_id).await? {
         infra_caches.remove(&infra_id);
+        simulation_store
+            .invalidate_or_warn(Invalidation::Infra { infra_id })
+            .await;
         Ok(StatusCode::NO_CONTENT)
     } else {
         Ok(StatusCode::NOT_FOUND)
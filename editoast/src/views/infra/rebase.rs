@@ -0,0 +1,187 @@
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::State;
+use axum::Extension;
+use editoast_authz::BuiltinRole;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::edition::apply_edit;
+use super::InfraApiError;
+use super::InfraIdParam;
+use crate::error::Result;
+use crate::infra_cache::InfraCache;
+use crate::models::infra::RebaseConflict;
+use crate::models::infra_edit::InfraEdit;
+use crate::models::prelude::*;
+use crate::models::Infra;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+
+crate::routes! {
+    "/rebase" => rebase,
+}
+
+editoast_common::schemas! {
+    RebaseForm,
+    RebaseResponse,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[cfg_attr(test, derive(Serialize))]
+struct RebaseForm {
+    /// The infra the variant was cloned from, as it was when cloned
+    base_id: i64,
+    /// The infra to replay the changes of the variant onto
+    onto_id: i64,
+    /// The name of the rebased infra, defaults to the name of the variant
+    name: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[cfg_attr(test, derive(Deserialize))]
+struct RebaseResponse {
+    /// The rebased infra
+    infra: Infra,
+    /// The objects changed on both sides, left as they are in the infra rebased onto
+    conflicts: Vec<RebaseConflict>,
+}
+
+/// Rebase an infra variant onto another infra
+///
+/// The changes made in the variant since it was cloned from `base_id` are replayed onto
+/// a clone of `onto_id`, which becomes the rebased infra. The variant itself is left untouched.
+///
+/// Objects changed differently in the variant and in `onto_id` are not replayed and are
+/// reported as conflicts.
+#[utoipa::path(
+    post, path = "",
+    tag = "infra",
+    params(InfraIdParam),
+    request_body = RebaseForm,
+    responses(
+        (status = 200, body = RebaseResponse, description = "The rebased infra and the conflicts"),
+        (status = 404, description = "One of the infras was not found"),
+    )
+)]
+async fn rebase(
+    Path(InfraIdParam { infra_id }): Path<InfraIdParam>,
+    State(AppState {
+        db_pool,
+        infra_caches,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Json(RebaseForm {
+        base_id,
+        onto_id,
+        name,
+    }): Json<RebaseForm>,
+) -> Result<Json<RebaseResponse>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+    let author_id = auth.user_id();
+
+    let conn = &mut db_pool.get().await?;
+    let variant =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    Infra::retrieve_or_fail(conn, base_id, || InfraApiError::NotFound {
+        infra_id: base_id,
+    })
+    .await?;
+    let onto = Infra::retrieve_or_fail(conn, onto_id, || InfraApiError::NotFound {
+        infra_id: onto_id,
+    })
+    .await?;
+
+    let rebase = Infra::rebase(conn, base_id, infra_id, onto_id).await?;
+
+    let mut infra = onto
+        .clone(conn, name.unwrap_or_else(|| variant.name.clone()))
+        .await?;
+    if !rebase.operations.is_empty() {
+        let mut infra_cache = InfraCache::get_or_load_mut(conn, &infra_caches, &infra).await?;
+        let result = apply_edit(
+            conn,
+            &mut infra,
+            &rebase.operations,
+            &mut infra_cache,
+            InfraEdit::changeset().author_id(author_id),
+        )
+        .await;
+        drop(infra_cache);
+        if let Err(error) = result {
+            // Do not leave a half rebased infra behind
+            infra_caches.remove(&infra.id);
+            Infra::fast_delete_static(db_pool.get().await?, infra.id).await?;
+            return Err(error);
+        }
+    }
+
+    Ok(Json(RebaseResponse {
+        infra,
+        conflicts: rebase.conflicts,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::fixtures::create_small_infra;
+    use crate::views::test_app::TestAppBuilder;
+
+    #[rstest]
+    async fn rebase_replays_variant_edits_onto_new_base() {
+        let app = TestAppBuilder::default_app();
+        let db_pool = app.db_pool();
+        let base = create_small_infra(&mut db_pool.get_ok()).await;
+        let variant = base
+            .clone(&mut db_pool.get_ok(), "rebase_variant".to_owned())
+            .await
+            .unwrap();
+        let onto = create_small_infra(&mut db_pool.get_ok()).await;
+
+        let request = app
+            .post(format!("/infra/{}/", variant.id).as_str())
+            .json(&json!([{
+                "operation_type": "DELETE",
+                "obj_type": "Signal",
+                "obj_id": "SA0",
+            }]));
+        app.fetch(request).assert_status(StatusCode::OK);
+
+        let request = app
+            .post(format!("/infra/{}/rebase", variant.id).as_str())
+            .json(&RebaseForm {
+                base_id: base.id,
+                onto_id: onto.id,
+                name: None,
+            });
+        let response: RebaseResponse = app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        assert!(response.conflicts.is_empty());
+        assert_eq!(response.infra.name, variant.name);
+        let signals = response
+            .infra
+            .get_objects(
+                &mut db_pool.get_ok(),
+                editoast_schemas::primitives::ObjectType::Signal,
+                &vec!["SA0".to_owned()],
+            )
+            .await
+            .unwrap();
+        assert!(signals.is_empty());
+    }
+}
@@ -20,6 +20,7 @@ pub mod study;
 pub mod temporary_speed_limits;
 pub mod timetable;
 pub mod train_schedule;
+pub mod train_service;
 pub mod work_schedules;
 
 #[cfg(test)]
@@ -70,6 +71,7 @@ use url::Url;
 use utoipa::ToSchema;
 
 use crate::client::get_app_version;
+use crate::core::http_client;
 use crate::core::mq_client;
 use crate::core::version::CoreVersionRequest;
 use crate::core::AsCoreRequest;
@@ -79,12 +81,15 @@ use crate::core::{self};
 use crate::error::Result;
 use crate::error::{self};
 use crate::generated_data;
+use crate::generated_data::rules::ValidationRulesConfig;
 use crate::generated_data::speed_limit_tags_config::SpeedLimitTagIds;
 use crate::infra_cache::operation;
 use crate::infra_cache::InfraCache;
 use crate::map::MapLayers;
 use crate::models;
 use crate::models::auth::PgAuthDriver;
+use crate::simulation_store::SimulationResultStore;
+use crate::simulation_store::SimulationStoreConfig;
 use crate::valkey_utils::ValkeyConfig;
 use crate::ValkeyClient;
 
@@ -110,6 +115,7 @@ crate::routes! {
     &work_schedules,
     &temporary_speed_limits,
     &train_schedule,
+    &train_service,
     &timetable,
     &path,
     &stdcm_logs,
@@ -141,6 +147,7 @@ editoast_common::schemas! {
     search::schemas(),
     stdcm_search_environment::schemas(),
     train_schedule::schemas(),
+    train_service::schemas(),
     timetable::schemas(),
     work_schedules::schemas(),
     stdcm_logs::schemas(),
@@ -186,6 +193,17 @@ impl Authentication {
             }
         }
     }
+
+    /// Returns the id of the user who issued the request, if it is known.
+    /// Superuser stubs (used when authorization is disabled) have no meaningful id.
+    pub fn user_id(&self) -> Option<i64> {
+        match self {
+            Authentication::Authenticated(authorizer) if !authorizer.is_superuser_stub() => {
+                Some(authorizer.user_id())
+            }
+            _ => None,
+        }
+    }
 }
 
 pub type AuthenticationExt = axum::extract::Extension<Authentication>;
@@ -346,6 +364,9 @@ pub struct CoreConfig {
     pub timeout: Duration,
     pub single_worker: bool,
     pub num_channels: usize,
+    /// If set, requests are sent to this core worker over HTTP instead of the message queue
+    pub url: Option<Url>,
+    pub retries: u8,
 }
 
 pub struct OsrdyneConfig {
@@ -370,6 +391,8 @@ pub struct ServerConfig {
     pub postgres_config: PostgresConfig,
     pub osrdyne_config: OsrdyneConfig,
     pub valkey_config: ValkeyConfig,
+    pub validation_rules: ValidationRulesConfig,
+    pub simulation_store: SimulationStoreConfig,
 }
 
 pub struct Server {
@@ -385,6 +408,7 @@ pub struct AppState {
     pub config: Arc<ServerConfig>,
     pub db_pool: Arc<DbConnectionPoolV2>,
     pub valkey: Arc<ValkeyClient>,
+    pub simulation_store: Arc<dyn SimulationResultStore>,
     pub infra_caches: Arc<DashMap<i64, InfraCache>>,
     pub map_layers: Arc<MapLayers>,
     pub speed_limit_tag_ids: Arc<SpeedLimitTagIds>,
@@ -422,6 +446,9 @@ impl AppState {
             Arc::new(pool)
         };
 
+        // Setup the persistent simulation result store
+        let simulation_store = config.simulation_store.clone().build(db_pool.clone());
+
         // Setup infra cache map
         let infra_caches = DashMap::<i64, InfraCache>::default().into();
 
@@ -434,15 +461,27 @@ impl AppState {
                 timeout,
                 single_worker,
                 num_channels,
+                url,
+                retries,
             } = config.osrdyne_config.core.clone();
-            let options = mq_client::Options {
-                uri: config.osrdyne_config.mq_url.clone(),
-                worker_pool_identifier: "core".to_owned(),
-                timeout: timeout.num_seconds() as u64,
-                single_worker,
-                num_channels,
-            };
-            CoreClient::new_mq(options).await?.into()
+            match url {
+                Some(url) => CoreClient::new_http(http_client::Options {
+                    url,
+                    timeout: timeout.num_seconds() as u64,
+                    retries,
+                })?
+                .into(),
+                None => {
+                    let options = mq_client::Options {
+                        uri: config.osrdyne_config.mq_url.clone(),
+                        worker_pool_identifier: "core".to_owned(),
+                        timeout: timeout.num_seconds() as u64,
+                        single_worker,
+                        num_channels,
+                    };
+                    CoreClient::new_mq(options).await?.into()
+                }
+            }
         };
 
         let osrdyne_client = Arc::new(OsrdyneClient::new(
@@ -452,6 +491,7 @@ impl AppState {
         Ok(Self {
             valkey,
             db_pool,
+            simulation_store,
             infra_caches,
             core_client,
             osrdyne_client,
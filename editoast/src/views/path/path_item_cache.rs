@@ -97,6 +97,38 @@ impl PathItemCache {
         self.uic_to_ops.get(&uic)
     }
 
+    /// Get the operational points matching an identifier, filtered by secondary code
+    pub fn get_from_identifier(
+        &self,
+        identifier: &OperationalPointIdentifier,
+    ) -> Vec<OperationalPointModel> {
+        match identifier {
+            OperationalPointIdentifier::OperationalPointId { operational_point } => self
+                .get_from_id(&operational_point.0)
+                .cloned()
+                .into_iter()
+                .collect(),
+            OperationalPointIdentifier::OperationalPointDescription {
+                trigram,
+                secondary_code,
+            } => secondary_code_filter(
+                secondary_code,
+                self.get_from_trigram(&trigram.0)
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            OperationalPointIdentifier::OperationalPointUic {
+                uic,
+                secondary_code,
+            } => secondary_code_filter(
+                secondary_code,
+                self.get_from_uic(i64::from(*uic))
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+
     /// Check if a track exists
     pub fn track_exists(&self, track: &str) -> bool {
         self.existing_track_ids.contains(track)
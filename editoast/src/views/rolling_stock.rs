@@ -41,8 +41,10 @@ use crate::models::rolling_stock_model::ScenarioReference;
 use crate::models::Document;
 use crate::models::RollingStockModel;
 use crate::models::RollingStockSeparatedImageModel;
+use crate::simulation_store::Invalidation;
 use crate::views::AuthenticationExt;
 use crate::views::AuthorizationError;
+use crate::AppState;
 
 crate::routes! {
     "/rolling_stock" => {
@@ -349,7 +351,11 @@ async fn create(
     )
 )]
 async fn update(
-    State(db_pool): State<DbConnectionPoolV2>,
+    State(AppState {
+        db_pool,
+        simulation_store,
+        ..
+    }): State<AppState>,
     Extension(auth): AuthenticationExt,
     Path(rolling_stock_id): Path<i64>,
     Json(rolling_stock_form): Json<RollingStockForm>,
@@ -364,7 +370,7 @@ async fn update(
     rolling_stock_form.validate()?;
     let name = rolling_stock_form.name.clone();
 
-    let new_rolling_stock = db_pool
+    let (previous_rolling_stock, new_rolling_stock) = db_pool
         .get()
         .await?
         .transaction::<_, InternalError, _>(|conn| {
@@ -395,12 +401,20 @@ async fn update(
                         .await
                         .map_err(|err| map_diesel_error(err, name))?;
                 }
-                Ok(new_rolling_stock)
+                Ok((previous_rolling_stock, new_rolling_stock))
             }
             .scope_boxed()
         })
         .await?;
 
+    if new_rolling_stock.version != previous_rolling_stock.version {
+        simulation_store
+            .invalidate_or_warn(Invalidation::RollingStock {
+                name: previous_rolling_stock.name,
+            })
+            .await;
+    }
+
     let new_rolling_stock_with_liveries =
         RollingStockWithLiveries::try_fetch(&mut db_pool.get().await?, new_rolling_stock).await?;
 
@@ -428,7 +442,11 @@ struct DeleteRollingStockQueryParams {
     )
 )]
 async fn delete(
-    State(db_pool): State<DbConnectionPoolV2>,
+    State(AppState {
+        db_pool,
+        simulation_store,
+        ..
+    }): State<AppState>,
     Extension(auth): AuthenticationExt,
     Path(rolling_stock_id): Path<i64>,
     Query(DeleteRollingStockQueryParams { force }): Query<DeleteRollingStockQueryParams>,
@@ -450,21 +468,24 @@ async fn delete(
     .await?;
     assert_rolling_stock_unlocked(&rolling_stock)?;
 
-    if force {
-        delete_rolling_stock(conn, rolling_stock_id).await?;
-        return Ok(StatusCode::NO_CONTENT);
+    if !force {
+        let scenarios_using_rs = rolling_stock.get_usage(conn).await?;
+        if !scenarios_using_rs.is_empty() {
+            return Err(RollingStockError::IsUsed {
+                rolling_stock_id,
+                usage: scenarios_using_rs,
+            }
+            .into());
+        }
     }
 
-    let scenarios_using_rs = rolling_stock.get_usage(conn).await?;
-    if scenarios_using_rs.is_empty() {
-        delete_rolling_stock(conn, rolling_stock_id).await?;
-        return Ok(StatusCode::NO_CONTENT);
-    }
-    Err(RollingStockError::IsUsed {
-        rolling_stock_id,
-        usage: scenarios_using_rs,
-    }
-    .into())
+    delete_rolling_stock(conn, rolling_stock_id).await?;
+    simulation_store
+        .invalidate_or_warn(Invalidation::RollingStock {
+            name: rolling_stock.name,
+        })
+        .await;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 async fn delete_rolling_stock(conn: &mut DbConnection, rolling_stock_id: i64) -> Result<()> {
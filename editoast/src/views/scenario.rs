@@ -21,6 +21,7 @@ use utoipa::ToSchema;
 
 use crate::error::InternalError;
 use crate::error::Result;
+use crate::models::macro_node::MacroNode;
 use crate::models::prelude::*;
 use crate::models::scenario::Scenario;
 use crate::models::timetable::Timetable;
@@ -47,6 +48,7 @@ crate::routes! {
             get,
             delete,
             patch,
+            "/clone" => clone,
             &macro_nodes,
         },
     },
@@ -58,6 +60,7 @@ editoast_common::schemas! {
     ScenarioWithDetails,
     ScenarioResponse,
     ScenarioCreateForm,
+    ScenarioCloneForm,
 }
 
 #[derive(IntoParams, Deserialize)]
@@ -419,6 +422,154 @@ async fn patch(
     Ok(Json(scenarios_response))
 }
 
+/// This structure is used by the clone endpoint to duplicate a scenario
+#[derive(Serialize, Deserialize, Derivative, ToSchema)]
+#[derivative(Default)]
+struct ScenarioCloneForm {
+    /// The name of the copy, by default the name of the scenario followed by "(copy)"
+    pub name: Option<String>,
+    /// The study of the copy, by default the study of the scenario
+    pub study_id: Option<i64>,
+    /// The infra of the copy, by default the infra of the scenario
+    pub infra_id: Option<i64>,
+}
+
+/// Duplicate a scenario with its timetable and macro nodes
+///
+/// The train schedules and train services of the timetable are copied. The copy keeps the tags
+/// and the electrical profile set of the scenario, and can be moved to another study or infra.
+#[utoipa::path(
+    post, path = "",
+    tag = "scenarios",
+    params(ProjectIdParam, StudyIdParam, ScenarioIdParam),
+    request_body = ScenarioCloneForm,
+    responses(
+        (status = 200, body = ScenarioResponse, description = "The copy of the scenario"),
+        (status = 404, body = InternalError, description = "The scenario, the target study or the infra was not found"),
+    )
+)]
+async fn clone(
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+    Path(ScenarioPathParam {
+        project_id,
+        study_id,
+        scenario_id,
+    }): Path<ScenarioPathParam>,
+    Json(form): Json<ScenarioCloneForm>,
+) -> Result<Json<ScenarioResponse>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::OpsRead, BuiltinRole::OpsWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let scenarios_response = db_pool
+        .get()
+        .await?
+        .transaction::<_, InternalError, _>(|conn| {
+            async move {
+                // Check if the project, the study and the scenario exist
+                let (project, study) =
+                    check_project_study(&mut conn.clone(), project_id, study_id).await?;
+                let scenario = Scenario::retrieve_or_fail(&mut conn.clone(), scenario_id, || {
+                    ScenarioError::NotFound { scenario_id }
+                })
+                .await?;
+                if scenario.study_id != study_id {
+                    return Err(ScenarioError::NotFound { scenario_id }.into());
+                }
+
+                // Check if the target study exists
+                let (mut project, study) = match form.study_id {
+                    Some(target_study_id) if target_study_id != study_id => {
+                        let study =
+                            Study::retrieve_or_fail(&mut conn.clone(), target_study_id, || {
+                                StudyError::NotFound {
+                                    study_id: target_study_id,
+                                }
+                            })
+                            .await?;
+                        let project_id = study.project_id;
+                        let project =
+                            Project::retrieve_or_fail(&mut conn.clone(), project_id, || {
+                                ProjectError::NotFound { project_id }
+                            })
+                            .await?;
+                        (project, study)
+                    }
+                    _ => (project, study),
+                };
+
+                // Check if the infra exists
+                let infra_id = form.infra_id.unwrap_or(scenario.infra_id);
+                if !Infra::exists(&mut conn.clone(), infra_id).await? {
+                    return Err(ScenarioError::InfraNotFound { infra_id }.into());
+                }
+
+                // Copy the timetable
+                let timetable_id = scenario.timetable_id;
+                let timetable =
+                    Timetable::retrieve_or_fail(&mut conn.clone(), timetable_id, || {
+                        ScenarioError::TimetableNotFound { timetable_id }
+                    })
+                    .await?
+                    .duplicate(&mut conn.clone())
+                    .await?;
+
+                // Copy the scenario
+                let name = form
+                    .name
+                    .unwrap_or_else(|| format!("{} (copy)", scenario.name));
+                let copy = Changeset::<Scenario>::from(scenario)
+                    .name(name)
+                    .creation_date(Utc::now().naive_utc())
+                    .last_modification(Utc::now().naive_utc())
+                    .infra_id(infra_id)
+                    .timetable_id(timetable.id)
+                    .study_id(study.id)
+                    .create(&mut conn.clone())
+                    .await?;
+
+                // Copy the macro nodes
+                let macro_nodes = MacroNode::list(
+                    &mut conn.clone(),
+                    SelectionSettings::new().filter(move || MacroNode::SCENARIO_ID.eq(scenario_id)),
+                )
+                .await?;
+                for macro_node in macro_nodes {
+                    Changeset::<MacroNode>::from(macro_node)
+                        .scenario_id(copy.id)
+                        .create(&mut conn.clone())
+                        .await?;
+                }
+
+                // Update study last_modification field
+                study
+                    .clone()
+                    .update_last_modified(&mut conn.clone())
+                    .await?;
+
+                // Update project last_modification field
+                project.update_last_modified(&mut conn.clone()).await?;
+
+                let scenarios_with_details =
+                    ScenarioWithDetails::from_scenario(copy, &mut conn.clone()).await?;
+
+                let scenarios_response =
+                    ScenarioResponse::new(scenarios_with_details, project, study);
+
+                Ok(scenarios_response)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(Json(scenarios_response))
+}
+
 /// Return a specific scenario
 #[utoipa::path(
     get, path = "",
@@ -532,8 +683,10 @@ mod tests {
     use crate::models::fixtures::create_empty_infra;
     use crate::models::fixtures::create_project;
     use crate::models::fixtures::create_scenario_fixtures_set;
+    use crate::models::fixtures::create_simple_train_schedule;
     use crate::models::fixtures::create_study;
     use crate::models::fixtures::create_timetable;
+    use crate::models::train_schedule::TrainSchedule;
     use crate::views::test_app::TestAppBuilder;
 
     pub fn scenario_url(project_id: i64, study_id: i64, scenario_id: Option<i64>) -> String {
@@ -735,6 +888,87 @@ mod tests {
         assert_eq!(response.scenario.name, study_name);
     }
 
+    #[rstest]
+    async fn clone_scenario() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+
+        let fixtures = create_scenario_fixtures_set(&mut pool.get_ok(), "test_scenario_name").await;
+        let train_schedule =
+            create_simple_train_schedule(&mut pool.get_ok(), fixtures.timetable.id).await;
+        MacroNode::changeset()
+            .scenario_id(fixtures.scenario.id)
+            .position_x(12)
+            .position_y(32)
+            .connection_time(51)
+            .labels(Tags::default())
+            .path_item_key("PATH".to_string())
+            .create(&mut pool.get_ok())
+            .await
+            .expect("Failed to create macro node");
+        let other_study =
+            create_study(&mut pool.get_ok(), "other_study", fixtures.project.id).await;
+
+        let url = scenario_url(
+            fixtures.project.id,
+            fixtures.study.id,
+            Some(fixtures.scenario.id),
+        );
+        let request = app.post(&format!("{url}/clone")).json(&json!({
+            "study_id": other_study.id,
+        }));
+        let response: ScenarioResponse =
+            app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        let copy = response.scenario;
+        assert_ne!(copy.id, fixtures.scenario.id);
+        assert_eq!(copy.name, "test_scenario_name (copy)");
+        assert_eq!(copy.study_id, other_study.id);
+        assert_eq!(copy.infra_id, fixtures.infra.id);
+        assert_eq!(copy.tags, fixtures.scenario.tags);
+        assert_ne!(copy.timetable_id, fixtures.timetable.id);
+        assert_eq!(response.trains_count, 1);
+
+        let copied_trains = TrainSchedule::list(
+            &mut pool.get_ok(),
+            SelectionSettings::new()
+                .filter(move || TrainSchedule::TIMETABLE_ID.eq(copy.timetable_id)),
+        )
+        .await
+        .expect("Failed to list train schedules");
+        assert_eq!(copied_trains[0].train_name, train_schedule.train_name);
+        assert_eq!(copied_trains[0].start_time, train_schedule.start_time);
+
+        let copy_id = copy.id;
+        let copied_nodes = MacroNode::list(
+            &mut pool.get_ok(),
+            SelectionSettings::new().filter(move || MacroNode::SCENARIO_ID.eq(copy_id)),
+        )
+        .await
+        .expect("Failed to list macro nodes");
+        assert_eq!(copied_nodes.len(), 1);
+        assert_eq!(copied_nodes[0].path_item_key, "PATH");
+    }
+
+    #[rstest]
+    async fn clone_scenario_with_unavailable_infra() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+
+        let fixtures = create_scenario_fixtures_set(&mut pool.get_ok(), "test_scenario_name").await;
+
+        let url = scenario_url(
+            fixtures.project.id,
+            fixtures.study.id,
+            Some(fixtures.scenario.id),
+        );
+        let request = app.post(&format!("{url}/clone")).json(&json!({
+            "infra_id": 999999999,
+        }));
+
+        app.fetch(request).assert_status(StatusCode::NOT_FOUND);
+    }
+
     #[rstest]
     async fn delete_scenario() {
         let app = TestAppBuilder::default_app();
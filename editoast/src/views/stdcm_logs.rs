@@ -239,6 +239,10 @@ mod tests {
             total_length: None,
             max_speed: None,
             loading_gauge_type: None,
+            alternative_count: 1,
+            ranking: vec![],
+            search_environment_id: None,
+            search_environment_name: None,
         }
     }
 
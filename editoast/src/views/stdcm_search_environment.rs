@@ -1,4 +1,5 @@
 use axum::extract::Json;
+use axum::extract::Query;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
@@ -7,10 +8,14 @@ use axum::Extension;
 use chrono::DateTime;
 use chrono::Utc;
 use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use editoast_models::DbConnection;
 use editoast_models::DbConnectionPoolV2;
 use serde::de::Error as SerdeError;
 use serde::Deserialize;
 use std::result::Result as StdResult;
+use thiserror::Error;
+use utoipa::IntoParams;
 use utoipa::ToSchema;
 
 #[cfg(test)]
@@ -19,14 +24,19 @@ use serde::Serialize;
 use crate::error::Result;
 use crate::models::stdcm_search_environment::StdcmSearchEnvironment;
 use crate::models::Changeset;
+use crate::views::Authentication;
 use crate::views::AuthenticationExt;
 use crate::views::AuthorizationError;
+use crate::List;
 use crate::Model;
+use crate::Retrieve;
+use crate::SelectionSettings;
 
 crate::routes! {
     "/stdcm/search_environment" => {
         overwrite,
         retrieve_latest,
+        "/list" => list,
     },
 }
 
@@ -35,9 +45,114 @@ editoast_common::schemas! {
     StdcmSearchEnvironment,
 }
 
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "stdcm_search_environment")]
+pub enum StdcmSearchEnvironmentError {
+    #[error("STDCM search environment '{name}' could not be found")]
+    #[editoast_error(status = 404)]
+    NotFound { name: String },
+    #[error("STDCM search environment '{search_environment_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    IdNotFound { search_environment_id: i64 },
+    #[error("STDCM search environment '{name}' does not search paths in timetable '{timetable_id}' on infra '{infra_id}'")]
+    #[editoast_error(status = 400)]
+    Mismatch {
+        name: String,
+        timetable_id: i64,
+        infra_id: i64,
+    },
+}
+
+/// Returns `Forbidden` if the user may not search paths in the search environment
+///
+/// STDCM admins have access to every search environment.
+pub(crate) async fn check_search_environment_access(
+    conn: &mut DbConnection,
+    auth: &Authentication,
+    search_env: &StdcmSearchEnvironment,
+) -> Result<()> {
+    let admin = auth
+        .check_roles([BuiltinRole::StdcmAdmin].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !admin && !search_env.is_accessible_by(conn, auth.user_id()).await? {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+    Ok(())
+}
+
+/// Returns `Forbidden` if the timetable is only used by search environments the user may not
+/// search paths in
+///
+/// Timetables used by no search environment are open to everyone, and STDCM admins have access
+/// to every timetable.
+pub(crate) async fn check_timetable_access(
+    conn: &mut DbConnection,
+    auth: &Authentication,
+    timetable_id: i64,
+) -> Result<()> {
+    let search_envs = StdcmSearchEnvironment::list(
+        conn,
+        SelectionSettings::new()
+            .filter(move || StdcmSearchEnvironment::TIMETABLE_ID.eq(timetable_id)),
+    )
+    .await?;
+    if search_envs.is_empty() {
+        return Ok(());
+    }
+    let admin = auth
+        .check_roles([BuiltinRole::StdcmAdmin].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if admin {
+        return Ok(());
+    }
+    let accessible = StdcmSearchEnvironment::list_accessible(conn, auth.user_id()).await?;
+    if !accessible
+        .iter()
+        .any(|search_env| search_env.timetable_id == timetable_id)
+    {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+    Ok(())
+}
+
+/// Retrieve the search environment selected by id or by name, if any
+///
+/// The user must have access to it, see [check_search_environment_access].
+pub(crate) async fn retrieve_selected_search_environment(
+    conn: &mut DbConnection,
+    auth: &Authentication,
+    search_environment_id: Option<i64>,
+    search_environment_name: Option<String>,
+) -> Result<Option<StdcmSearchEnvironment>> {
+    let search_env = match (search_environment_id, search_environment_name) {
+        (Some(search_environment_id), _) => {
+            StdcmSearchEnvironment::retrieve_or_fail(conn, search_environment_id, || {
+                StdcmSearchEnvironmentError::IdNotFound {
+                    search_environment_id,
+                }
+            })
+            .await?
+        }
+        (None, Some(name)) => {
+            StdcmSearchEnvironment::retrieve_or_fail(conn, name.clone(), || {
+                StdcmSearchEnvironmentError::NotFound { name }
+            })
+            .await?
+        }
+        (None, None) => return Ok(None),
+    };
+    check_search_environment_access(conn, auth, &search_env).await?;
+    Ok(Some(search_env))
+}
+
 #[derive(ToSchema)]
 #[cfg_attr(test, derive(Serialize))]
 struct StdcmSearchEnvironmentCreateForm {
+    /// Creates the search environment, or replaces the one with the same name
+    #[schema(default = "default", required = false)]
+    name: String,
     infra_id: i64,
     electrical_profile_set_id: Option<i64>,
     work_schedule_group_id: Option<i64>,
@@ -47,6 +162,10 @@ struct StdcmSearchEnvironmentCreateForm {
     search_window_end: DateTime<Utc>,
 }
 
+fn default_search_environment_name() -> String {
+    "default".to_owned()
+}
+
 impl<'de> Deserialize<'de> for StdcmSearchEnvironmentCreateForm {
     fn deserialize<D>(deserializer: D) -> StdResult<StdcmSearchEnvironmentCreateForm, D::Error>
     where
@@ -55,6 +174,8 @@ impl<'de> Deserialize<'de> for StdcmSearchEnvironmentCreateForm {
         #[derive(Deserialize)]
         #[serde(deny_unknown_fields)]
         struct Internal {
+            #[serde(default = "default_search_environment_name")]
+            name: String,
             infra_id: i64,
             electrical_profile_set_id: Option<i64>,
             work_schedule_group_id: Option<i64>,
@@ -74,6 +195,7 @@ impl<'de> Deserialize<'de> for StdcmSearchEnvironmentCreateForm {
         }
 
         Ok(StdcmSearchEnvironmentCreateForm {
+            name: internal.name,
             infra_id: internal.infra_id,
             electrical_profile_set_id: internal.electrical_profile_set_id,
             work_schedule_group_id: internal.work_schedule_group_id,
@@ -88,6 +210,7 @@ impl<'de> Deserialize<'de> for StdcmSearchEnvironmentCreateForm {
 impl From<StdcmSearchEnvironmentCreateForm> for Changeset<StdcmSearchEnvironment> {
     fn from(form: StdcmSearchEnvironmentCreateForm) -> Self {
         StdcmSearchEnvironment::changeset()
+            .name(form.name)
             .infra_id(form.infra_id)
             .electrical_profile_set_id(form.electrical_profile_set_id)
             .work_schedule_group_id(form.work_schedule_group_id)
@@ -98,6 +221,9 @@ impl From<StdcmSearchEnvironmentCreateForm> for Changeset<StdcmSearchEnvironment
     }
 }
 
+/// Create a search environment, or replace the one with the same name
+///
+/// A replaced search environment keeps its id and the subjects it is granted to.
 #[utoipa::path(
     post, path = "",
     tag = "stdcm_search_environment",
@@ -124,17 +250,28 @@ async fn overwrite(
     Ok((StatusCode::CREATED, Json(changeset.overwrite(conn).await?)))
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SearchEnvironmentNameQueryParam {
+    /// The name of the search environment, the latest accessible one if omitted
+    name: Option<String>,
+}
+
+/// Retrieve a search environment by name, or the latest one the user has access to
 #[utoipa::path(
     get, path = "",
     tag = "stdcm_search_environment",
+    params(SearchEnvironmentNameQueryParam),
     responses(
         (status = 200, body = StdcmSearchEnvironment),
-        (status = 204, description = "No search environment was created")
+        (status = 204, description = "No search environment was created"),
+        (status = 404, description = "No search environment has this name"),
     )
 )]
 async fn retrieve_latest(
     State(db_pool): State<DbConnectionPoolV2>,
     Extension(auth): AuthenticationExt,
+    Query(SearchEnvironmentNameQueryParam { name }): Query<SearchEnvironmentNameQueryParam>,
 ) -> Result<Response> {
     let authorized = auth
         .check_roles([BuiltinRole::Stdcm].into())
@@ -145,7 +282,28 @@ async fn retrieve_latest(
     }
 
     let conn = &mut db_pool.get().await?;
-    let search_env = StdcmSearchEnvironment::retrieve_latest(conn).await;
+    if let Some(name) = name {
+        let search_env = StdcmSearchEnvironment::retrieve_or_fail(conn, name.clone(), || {
+            StdcmSearchEnvironmentError::NotFound { name }
+        })
+        .await?;
+        check_search_environment_access(conn, &auth, &search_env).await?;
+        return Ok(Json(search_env).into_response());
+    }
+
+    // STDCM admins have access to every search environment
+    let admin = auth
+        .check_roles([BuiltinRole::StdcmAdmin].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    let search_env = if admin {
+        StdcmSearchEnvironment::retrieve_latest(conn).await
+    } else {
+        StdcmSearchEnvironment::list_accessible(conn, auth.user_id())
+            .await?
+            .into_iter()
+            .next()
+    };
     if let Some(search_env) = search_env {
         Ok(Json(search_env).into_response())
     } else {
@@ -154,6 +312,40 @@ async fn retrieve_latest(
     }
 }
 
+/// List the search environments the user has access to, the latest first
+#[utoipa::path(
+    get, path = "",
+    tag = "stdcm_search_environment",
+    responses(
+        (status = 200, body = Vec<StdcmSearchEnvironment>),
+    )
+)]
+async fn list(
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+) -> Result<Json<Vec<StdcmSearchEnvironment>>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::Stdcm].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    // STDCM admins have access to every search environment
+    let admin = auth
+        .check_roles([BuiltinRole::StdcmAdmin].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    let search_envs = if admin {
+        StdcmSearchEnvironment::list_latest_first(conn).await?
+    } else {
+        StdcmSearchEnvironment::list_accessible(conn, auth.user_id()).await?
+    };
+    Ok(Json(search_envs))
+}
+
 #[cfg(test)]
 pub mod tests {
     use axum::http::StatusCode;
@@ -182,6 +374,7 @@ pub mod tests {
         ) = stdcm_search_env_fixtures(&mut pool.get_ok()).await;
 
         let form = StdcmSearchEnvironmentCreateForm {
+            name: "create_stdcm_search_env".to_owned(),
             infra_id: infra.id,
             electrical_profile_set_id: Some(electrical_profile_set.id),
             work_schedule_group_id: Some(work_schedule_group.id),
@@ -230,6 +423,7 @@ pub mod tests {
         let end = Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap();
 
         let _ = StdcmSearchEnvironment::changeset()
+            .name("default".to_owned())
             .infra_id(infra.id)
             .electrical_profile_set_id(Some(electrical_profile_set.id))
             .work_schedule_group_id(Some(work_schedule_group.id))
@@ -254,6 +448,7 @@ pub mod tests {
             stdcm_search_env,
             StdcmSearchEnvironment {
                 id: stdcm_search_env.id,
+                name: "default".to_owned(),
                 infra_id: infra.id,
                 electrical_profile_set_id: Some(electrical_profile_set.id),
                 work_schedule_group_id: Some(work_schedule_group.id),
@@ -265,6 +460,42 @@ pub mod tests {
         );
     }
 
+    #[rstest]
+    async fn retrieve_stdcm_search_env_by_name() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let (infra, timetable, ..) = stdcm_search_env_fixtures(&mut pool.get_ok()).await;
+
+        let changeset = StdcmSearchEnvironment::changeset()
+            .infra_id(infra.id)
+            .timetable_id(timetable.id)
+            .search_window_begin(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+            .search_window_end(Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap());
+        let north = changeset
+            .clone()
+            .name("north".to_owned())
+            .create(&mut pool.get_ok())
+            .await
+            .expect("Failed to create stdcm search environment");
+        changeset
+            .name("south".to_owned())
+            .search_window_end(Utc.with_ymd_and_hms(2024, 2, 15, 0, 0, 0).unwrap())
+            .create(&mut pool.get_ok())
+            .await
+            .expect("Failed to create stdcm search environment");
+
+        let request = app.get("/stdcm/search_environment?name=north");
+
+        let stdcm_search_env = app
+            .fetch(request)
+            .assert_status(StatusCode::OK)
+            .json_into::<StdcmSearchEnvironment>();
+
+        assert_eq!(stdcm_search_env, north);
+        app.fetch(app.get("/stdcm/search_environment?name=west"))
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
     #[rstest]
     async fn retrieve_stdcm_search_env_not_found() {
         // GIVEN
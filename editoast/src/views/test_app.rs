@@ -157,6 +157,8 @@ impl TestAppBuilder {
                     timeout: chrono::Duration::seconds(180),
                     single_worker: false,
                     num_channels: 8,
+                    url: None,
+                    retries: 3,
                 },
             },
             valkey_config: ValkeyConfig {
@@ -164,6 +166,8 @@ impl TestAppBuilder {
                 is_cluster_client: false,
                 valkey_url: Url::parse("redis://localhost:6379").unwrap(),
             },
+            validation_rules: Default::default(),
+            simulation_store: Default::default(),
         };
 
         // Setup tracing
@@ -196,6 +200,9 @@ impl TestAppBuilder {
             "No database pool provided to TestAppBuilder, use Default or provide a database pool",
         ));
 
+        // Setup the persistent simulation result store
+        let simulation_store = config.simulation_store.clone().build(db_pool_v2.clone());
+
         // Setup infra cache map
         let infra_caches = DashMap::<i64, InfraCache>::default().into();
 
@@ -218,6 +225,7 @@ impl TestAppBuilder {
             core_client: core_client.clone(),
             osrdyne_client,
             valkey,
+            simulation_store,
             infra_caches,
             map_layers: Arc::new(MapLayers::default()),
             speed_limit_tag_ids,
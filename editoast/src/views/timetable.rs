@@ -1,3 +1,9 @@
+pub mod capacity;
+pub mod conflict_resolution;
+pub mod cross_conflicts;
+pub mod exchange;
+pub mod robustness;
+pub mod statistics;
 pub mod stdcm;
 
 use std::collections::HashMap;
@@ -9,6 +15,7 @@ use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::Extension;
+use chrono::NaiveDate;
 use derivative::Derivative;
 use editoast_authz::BuiltinRole;
 use editoast_derive::EditoastError;
@@ -31,10 +38,15 @@ use crate::models::timetable::Timetable;
 use crate::models::timetable::TimetableWithTrains;
 use crate::models::train_schedule::TrainSchedule;
 use crate::models::train_schedule::TrainScheduleChangeset;
+use crate::models::train_service::TrainService;
 use crate::models::Infra;
+use crate::views::path::pathfinding::PathfindingResult;
 use crate::views::train_schedule::train_simulation_batch;
+use crate::views::train_schedule::trains_on_day;
 use crate::views::train_schedule::TrainScheduleForm;
 use crate::views::train_schedule::TrainScheduleResult;
+use crate::views::train_service::TrainServiceForm;
+use crate::views::train_service::TrainServiceResult;
 use crate::views::AuthenticationExt;
 use crate::views::AuthorizationError;
 use crate::AppState;
@@ -44,11 +56,21 @@ use editoast_models::DbConnectionPoolV2;
 crate::routes! {
     "/timetable" => {
         post,
+        &cross_conflicts,
         "/{id}" => {
             delete,
             get,
             "/conflicts" => conflicts,
             "/train_schedule" => train_schedule,
+            "/train_service" => {
+                create_train_service,
+                list_train_services,
+            },
+            &capacity,
+            &conflict_resolution,
+            &exchange,
+            &robustness,
+            &statistics,
             &stdcm,
         },
     },
@@ -57,6 +79,12 @@ crate::routes! {
 editoast_common::schemas! {
     TimetableResult,
     TimetableDetailedResult,
+    capacity::schemas(),
+    conflict_resolution::schemas(),
+    cross_conflicts::schemas(),
+    exchange::schemas(),
+    robustness::schemas(),
+    statistics::schemas(),
     stdcm::schemas(),
 }
 
@@ -243,6 +271,90 @@ async fn train_schedule(
     Ok(Json(train_schedule.into_iter().map_into().collect()))
 }
 
+/// Create a train service, repeating a base schedule at a regular interval
+///
+/// If the service is expanded, a train schedule is created for each occurrence.
+#[utoipa::path(
+    post, path = "",
+    tag = "timetable,train_service",
+    params(TimetableIdParam),
+    request_body = TrainServiceForm,
+    responses(
+        (status = 200, description = "The created train service", body = TrainServiceResult),
+        (status = 404, description = "Timetable not found"),
+    )
+)]
+async fn create_train_service(
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+    Path(TimetableIdParam { id: timetable_id }): Path<TimetableIdParam>,
+    Json(train_service_form): Json<TrainServiceForm>,
+) -> Result<Json<TrainServiceResult>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::TimetableWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+    train_service_form.validate()?;
+
+    let conn = &mut db_pool.get().await?;
+    Timetable::retrieve_or_fail(conn, timetable_id, || TimetableError::NotFound {
+        timetable_id,
+    })
+    .await?;
+    conn.transaction(|conn| {
+        Box::pin(async move {
+            let train_service = train_service_form
+                .into_changeset(timetable_id)
+                .create(&mut conn.clone())
+                .await?;
+            let trains = train_service
+                .sync_expanded_trains(&mut conn.clone())
+                .await?;
+            Ok(Json(TrainServiceResult::new(train_service, &trains)))
+        })
+    })
+    .await
+}
+
+/// List the train services of a timetable
+#[utoipa::path(
+    get, path = "",
+    tag = "timetable,train_service",
+    params(TimetableIdParam),
+    responses(
+        (status = 200, description = "The train services of the timetable", body = Vec<TrainServiceResult>),
+        (status = 404, description = "Timetable not found"),
+    )
+)]
+async fn list_train_services(
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+    Path(TimetableIdParam { id: timetable_id }): Path<TimetableIdParam>,
+) -> Result<Json<Vec<TrainServiceResult>>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::TimetableRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    Timetable::retrieve_or_fail(conn, timetable_id, || TimetableError::NotFound {
+        timetable_id,
+    })
+    .await?;
+    let mut results = vec![];
+    for train_service in TrainService::list_by_timetable(conn, timetable_id).await? {
+        let trains = train_service.expanded_trains(conn).await?;
+        results.push(TrainServiceResult::new(train_service, &trains));
+    }
+    Ok(Json(results))
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams, ToSchema)]
 #[into_params(parameter_in = Query)]
 pub struct InfraIdQueryParam {
@@ -255,11 +367,41 @@ pub struct ElectricalProfileSetIdQueryParam {
     electrical_profile_set_id: Option<i64>,
 }
 
+#[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct DayQueryParam {
+    /// Only consider the trains running on this day (in UTC), moved to that day
+    day: Option<NaiveDate>,
+}
+
+/// Gather the requirements of the trains whose simulation succeeded
+fn trains_requirements(
+    trains: &[TrainSchedule],
+    simulations: Vec<(SimulationResponse, PathfindingResult)>,
+) -> HashMap<i64, TrainRequirements> {
+    let mut trains_requirements = HashMap::with_capacity(trains.len());
+    for (train, (sim, _)) in trains.iter().zip(simulations) {
+        let final_output = match sim {
+            SimulationResponse::Success { final_output, .. } => final_output,
+            _ => continue,
+        };
+        trains_requirements.insert(
+            train.id,
+            TrainRequirements {
+                start_time: train.start_time,
+                spacing_requirements: final_output.spacing_requirements,
+                routing_requirements: final_output.routing_requirements,
+            },
+        );
+    }
+    trains_requirements
+}
+
 /// Retrieve the list of conflict of the timetable (invalid trains are ignored)
 #[utoipa::path(
     get, path = "",
     tag = "timetable",
-    params(TimetableIdParam, InfraIdQueryParam, ElectricalProfileSetIdQueryParam),
+    params(TimetableIdParam, InfraIdQueryParam, ElectricalProfileSetIdQueryParam, DayQueryParam),
     responses(
         (status = 200, description = "List of conflict", body = Vec<Conflict>),
     ),
@@ -268,6 +410,7 @@ async fn conflicts(
     State(AppState {
         db_pool,
         valkey: valkey_client,
+        simulation_store,
         core_client,
         ..
     }): State<AppState>,
@@ -277,6 +420,7 @@ async fn conflicts(
     Query(ElectricalProfileSetIdQueryParam {
         electrical_profile_set_id,
     }): Query<ElectricalProfileSetIdQueryParam>,
+    Query(DayQueryParam { day }): Query<DayQueryParam>,
 ) -> Result<Json<Vec<Conflict>>> {
     let authorized = auth
         .check_roles([BuiltinRole::InfraRead, BuiltinRole::TimetableRead].into())
@@ -301,10 +445,12 @@ async fn conflicts(
     let (trains, _): (Vec<_>, _) =
         TrainSchedule::retrieve_batch(&mut db_pool.get().await?, timetable_trains.train_ids)
             .await?;
+    let trains = trains_on_day(trains, day);
 
     let simulations = train_simulation_batch(
         &mut db_pool.get().await?,
         valkey_client.clone(),
+        simulation_store.as_ref(),
         core_client.clone(),
         &trains,
         &infra,
@@ -313,26 +459,10 @@ async fn conflicts(
     .await?;
 
     // 2. Build core request
-    let mut trains_requirements = HashMap::with_capacity(trains.len());
-    for (train, sim) in trains.into_iter().zip(simulations) {
-        let (sim, _) = sim;
-        let final_output = match sim {
-            SimulationResponse::Success { final_output, .. } => final_output,
-            _ => continue,
-        };
-        trains_requirements.insert(
-            train.id,
-            TrainRequirements {
-                start_time: train.start_time,
-                spacing_requirements: final_output.spacing_requirements,
-                routing_requirements: final_output.routing_requirements,
-            },
-        );
-    }
     let conflict_detection_request = ConflictDetectionRequest {
         infra: infra_id,
         expected_version: infra.version,
-        trains_requirements,
+        trains_requirements: trains_requirements(&trains, simulations),
         work_schedules: None,
     };
 
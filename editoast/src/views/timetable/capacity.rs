@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::Extension;
+use chrono::DateTime;
+use chrono::Utc;
+use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use editoast_schemas::infra::TrackOffset;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use super::ElectricalProfileSetIdQueryParam;
+use super::InfraIdQueryParam;
+use super::TimetableError;
+use super::TimetableIdParam;
+use crate::core::conflict_detection::TrainRequirements;
+use crate::core::pathfinding::TrackRange;
+use crate::core::simulation::SimulationResponse;
+use crate::core::simulation::ZoneUpdate;
+use crate::error::Result;
+use crate::infra_cache::InfraCache;
+use crate::infra_cache::ObjectCache;
+use crate::models::prelude::*;
+use crate::models::timetable::TimetableWithTrains;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::Infra;
+use crate::views::path::pathfinding::PathfindingResult;
+use crate::views::path::projection::PathProjection;
+use crate::views::path::projection::TrackLocationFromPath;
+use crate::views::train_schedule::train_simulation_batch;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+use crate::RetrieveBatch;
+
+crate::routes! {
+    "/capacity" => capacity,
+}
+
+editoast_common::schemas! {
+    CapacityForm,
+    LineSectionCapacity,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "timetable:capacity")]
+enum TimetableCapacityError {
+    #[error("The time window must end after it starts")]
+    #[editoast_error(status = 400)]
+    InvalidTimeWindow,
+    #[error("The path must have at least one track section range")]
+    #[editoast_error(status = 400)]
+    EmptyPath,
+    #[error("The range on track section '{track_section}' ends before it begins")]
+    #[editoast_error(status = 400)]
+    InvalidTrackRange { track_section: String },
+    #[error("The path goes through track section '{track_section}' more than once")]
+    #[editoast_error(status = 400)]
+    RepeatedTrackSection { track_section: String },
+}
+
+/// Reject paths that cannot be projected: empty, with reversed ranges or through a track twice
+fn validate_path(track_section_ranges: &[TrackRange]) -> Result<()> {
+    if track_section_ranges.is_empty() {
+        return Err(TimetableCapacityError::EmptyPath.into());
+    }
+    let mut track_sections = HashSet::new();
+    for range in track_section_ranges {
+        let track_section = range.track_section.0.clone();
+        if range.begin > range.end {
+            return Err(TimetableCapacityError::InvalidTrackRange { track_section }.into());
+        }
+        if !track_sections.insert(&range.track_section) {
+            return Err(TimetableCapacityError::RepeatedTrackSection { track_section }.into());
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+struct CapacityForm {
+    /// The path whose capacity is analysed
+    #[schema(min_items = 1)]
+    track_section_ranges: Vec<TrackRange>,
+    /// Start of the time window
+    start_time: DateTime<Utc>,
+    /// End of the time window
+    end_time: DateTime<Utc>,
+}
+
+/// The capacity consumption of a stretch of the path between two operational points
+#[derive(Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+struct LineSectionCapacity {
+    /// The operational point the section starts at, none at the start of the path
+    from_operational_point: Option<String>,
+    /// The operational point the section ends at, none at the end of the path
+    to_operational_point: Option<String>,
+    /// Position of the start of the section on the path in mm
+    start_position: u64,
+    /// Position of the end of the section on the path in mm
+    end_position: u64,
+    /// Number of trains entering the section during the time window
+    train_count: u64,
+    /// Time in ms the section is occupied once the trains are compressed
+    compressed_occupancy: u64,
+    /// Share of the time window taken by the compressed occupancy, in percent
+    capacity_consumption: f64,
+}
+
+/// A zone occupied by a train, located on the analysed path
+#[derive(Debug)]
+struct ZoneOccupancy<'a> {
+    zone: &'a str,
+    /// Position of the middle of the zone on the path in mm
+    position: u64,
+    /// In ms since epoch
+    begin: i64,
+    /// In ms since epoch
+    end: i64,
+}
+
+fn project_position(
+    position: u64,
+    train_path: &PathProjection,
+    path: &PathProjection,
+) -> Option<u64> {
+    match train_path.get_location(position.min(train_path.len())) {
+        TrackLocationFromPath::One(location) => path.get_position(&location),
+        TrackLocationFromPath::Two(location_a, location_b) => path
+            .get_position(&location_a)
+            .or_else(|| path.get_position(&location_b)),
+    }
+}
+
+/// Locate the zones occupied by a train on the analysed path
+///
+/// The spacing and routing requirements of a zone are merged, and the zone is placed at the
+/// middle of its entry and exit by the train. Zones outside of the analysed path are dropped.
+fn zone_occupancies<'a>(
+    requirements: &'a TrainRequirements,
+    zone_updates: &[ZoneUpdate],
+    train_path: &PathProjection,
+    path: &PathProjection,
+) -> Vec<ZoneOccupancy<'a>> {
+    let mut intervals: HashMap<&str, (u64, u64)> = HashMap::new();
+    let spacing = requirements.spacing_requirements.iter().map(|requirement| {
+        (
+            requirement.zone.as_str(),
+            requirement.begin_time,
+            requirement.end_time,
+        )
+    });
+    let routing = requirements
+        .routing_requirements
+        .iter()
+        .flat_map(|requirement| {
+            requirement
+                .zones
+                .iter()
+                .map(|zone| (zone.zone.as_str(), requirement.begin_time, zone.end_time))
+        });
+    for (zone, begin, end) in spacing.chain(routing) {
+        let interval = intervals.entry(zone).or_insert((begin, end));
+        *interval = (interval.0.min(begin), interval.1.max(end));
+    }
+
+    let mut train_positions: HashMap<&str, (Option<u64>, Option<u64>)> = HashMap::new();
+    for update in zone_updates {
+        let positions = train_positions.entry(update.zone.as_str()).or_default();
+        if update.is_entry {
+            positions.0 = Some(update.position);
+        } else {
+            positions.1 = Some(update.position);
+        }
+    }
+
+    let start_time = requirements.start_time.timestamp_millis();
+    intervals
+        .into_iter()
+        .filter_map(|(zone, (begin, end))| {
+            let position = match train_positions.get(zone)? {
+                (Some(entry), Some(exit)) => project_position((entry + exit) / 2, train_path, path)
+                    .or_else(|| project_position(*entry, train_path, path))
+                    .or_else(|| project_position(*exit, train_path, path)),
+                (Some(position), None) | (None, Some(position)) => {
+                    project_position(*position, train_path, path)
+                }
+                (None, None) => None,
+            }?;
+            Some(ZoneOccupancy {
+                zone,
+                position,
+                begin: start_time + begin as i64,
+                end: start_time + end as i64,
+            })
+        })
+        .collect()
+}
+
+/// Compress the occupancies of trains following the UIC 406 method
+///
+/// The trains keep their order and are pushed as early as possible, each zone being claimed only
+/// once the trains before have released it. Returns the duration in ms between the first claim
+/// and the last release. Buffer times and supplements are not added.
+fn compressed_occupancy(mut trains: Vec<Vec<&ZoneOccupancy>>) -> u64 {
+    trains.retain(|occupancies| !occupancies.is_empty());
+    trains.sort_by_key(|occupancies| occupancies.iter().map(|occupancy| occupancy.begin).min());
+
+    // Times are relative to the compressed start of the first train
+    let mut releases: HashMap<&str, i64> = HashMap::new();
+    let mut previous_start = 0;
+    let mut last_release = 0;
+    for occupancies in trains {
+        let origin = occupancies
+            .iter()
+            .map(|occupancy| occupancy.begin)
+            .min()
+            .unwrap_or_default();
+        let start = occupancies
+            .iter()
+            .filter_map(|occupancy| {
+                let release = releases.get(occupancy.zone)?;
+                Some(release - (occupancy.begin - origin))
+            })
+            .fold(previous_start, i64::max);
+        for occupancy in occupancies {
+            let release = start + occupancy.end - origin;
+            let zone_release = releases.entry(occupancy.zone).or_insert(release);
+            *zone_release = (*zone_release).max(release);
+            last_release = last_release.max(release);
+        }
+        previous_start = start;
+    }
+    last_release as u64
+}
+
+/// Split the path at the operational points located on it
+fn line_sections(
+    infra_cache: &InfraCache,
+    path: &PathProjection,
+) -> Vec<(u64, u64, Option<String>, Option<String>)> {
+    let mut operational_points: Vec<_> = infra_cache
+        .operational_points()
+        .values()
+        .map(ObjectCache::unwrap_operational_point)
+        .filter_map(|operational_point| {
+            let position = operational_point
+                .parts
+                .iter()
+                .filter_map(|part| {
+                    let offset = (part.position * 1000.).round() as u64;
+                    path.get_position(&TrackOffset::new(&part.track, offset))
+                })
+                .min()?;
+            Some((position, operational_point.obj_id.clone()))
+        })
+        .collect();
+    operational_points.sort();
+
+    let mut boundaries: Vec<(u64, Option<String>)> = vec![(0, None)];
+    for (position, operational_point) in operational_points {
+        match boundaries.last_mut() {
+            Some((last_position, last)) if *last_position == position => {
+                last.get_or_insert(operational_point);
+            }
+            _ => boundaries.push((position, Some(operational_point))),
+        }
+    }
+    if boundaries
+        .last()
+        .is_some_and(|(position, _)| *position < path.len())
+    {
+        boundaries.push((path.len(), None));
+    }
+    boundaries
+        .windows(2)
+        .map(|bounds| {
+            let ((start, from), (end, to)) = (&bounds[0], &bounds[1]);
+            (*start, *end, from.clone(), to.clone())
+        })
+        .collect()
+}
+
+/// Compute the capacity consumption of a path over a time window following the UIC 406 method
+///
+/// The path is split into line sections at the operational points located on it. In each
+/// section, the blocking times of the trains of the timetable entering it during the time window
+/// are compressed, as if the trains were running as close as possible to each other.
+/// The capacity consumption is the compressed occupancy divided by the duration of the window.
+/// Trains whose simulation fails are ignored.
+#[utoipa::path(
+    post, path = "",
+    tag = "timetable",
+    params(TimetableIdParam, InfraIdQueryParam, ElectricalProfileSetIdQueryParam),
+    request_body = CapacityForm,
+    responses(
+        (status = 200, description = "The capacity consumption of each line section of the path", body = Vec<LineSectionCapacity>),
+        (status = 400, description = "The time window is empty or the path is invalid"),
+        (status = 404, description = "Timetable or infra not found"),
+    ),
+)]
+async fn capacity(
+    State(AppState {
+        db_pool,
+        valkey: valkey_client,
+        simulation_store,
+        core_client,
+        infra_caches,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Path(TimetableIdParam { id: timetable_id }): Path<TimetableIdParam>,
+    Query(InfraIdQueryParam { infra_id }): Query<InfraIdQueryParam>,
+    Query(ElectricalProfileSetIdQueryParam {
+        electrical_profile_set_id,
+    }): Query<ElectricalProfileSetIdQueryParam>,
+    Json(CapacityForm {
+        track_section_ranges,
+        start_time,
+        end_time,
+    }): Json<CapacityForm>,
+) -> Result<Json<Vec<LineSectionCapacity>>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead, BuiltinRole::TimetableRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+    if end_time <= start_time {
+        return Err(TimetableCapacityError::InvalidTimeWindow.into());
+    }
+    validate_path(&track_section_ranges)?;
+
+    let conn = &mut db_pool.get().await?;
+    let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
+        TimetableError::NotFound { timetable_id }
+    })
+    .await?;
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+    let (trains, _): (Vec<_>, _) = TrainSchedule::retrieve_batch(conn, timetable.train_ids).await?;
+
+    let simulations = train_simulation_batch(
+        conn,
+        valkey_client,
+        simulation_store.as_ref(),
+        core_client,
+        &trains,
+        &infra,
+        electrical_profile_set_id,
+    )
+    .await?;
+
+    let mut trains_details = vec![];
+    for (train, (simulation, pathfinding)) in trains.iter().zip(simulations) {
+        let (SimulationResponse::Success { final_output, .. }, PathfindingResult::Success(path)) =
+            (simulation, pathfinding)
+        else {
+            continue;
+        };
+        let requirements = TrainRequirements {
+            start_time: train.start_time,
+            spacing_requirements: final_output.spacing_requirements,
+            routing_requirements: final_output.routing_requirements,
+        };
+        trains_details.push((
+            requirements,
+            final_output.zone_updates,
+            path.track_section_ranges,
+        ));
+    }
+
+    let path = PathProjection::new(&track_section_ranges);
+    let trains_occupancies: Vec<_> = trains_details
+        .iter()
+        .map(|(requirements, zone_updates, train_path)| {
+            let train_path = PathProjection::new(train_path);
+            zone_occupancies(requirements, zone_updates, &train_path, &path)
+        })
+        .collect();
+
+    let infra_cache = InfraCache::get_or_load(conn, &infra_caches, &infra).await?;
+    let window = (end_time - start_time).num_milliseconds();
+    let (start_time, end_time) = (start_time.timestamp_millis(), end_time.timestamp_millis());
+    let sections = line_sections(&infra_cache, &path)
+        .into_iter()
+        .map(|(start_position, end_position, from, to)| {
+            let in_section = |occupancy: &&ZoneOccupancy| {
+                start_position <= occupancy.position
+                    && (occupancy.position < end_position
+                        || (occupancy.position == end_position && end_position == path.len()))
+            };
+            let trains: Vec<Vec<_>> = trains_occupancies
+                .iter()
+                .map(|occupancies| occupancies.iter().filter(in_section).collect::<Vec<_>>())
+                .filter(|occupancies| {
+                    occupancies
+                        .iter()
+                        .map(|occupancy| occupancy.begin)
+                        .min()
+                        .is_some_and(|entry| start_time <= entry && entry < end_time)
+                })
+                .collect();
+            let train_count = trains.len() as u64;
+            let compressed_occupancy = compressed_occupancy(trains);
+            LineSectionCapacity {
+                from_operational_point: from,
+                to_operational_point: to,
+                start_position,
+                end_position,
+                train_count,
+                compressed_occupancy,
+                capacity_consumption: compressed_occupancy as f64 / window as f64 * 100.,
+            }
+        })
+        .collect();
+    Ok(Json(sections))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use editoast_schemas::infra::Direction;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::fixtures::create_small_infra;
+    use crate::models::fixtures::create_timetable;
+    use crate::views::test_app::TestAppBuilder;
+
+    fn occupancy(zone: &str, begin: i64, end: i64) -> ZoneOccupancy {
+        ZoneOccupancy {
+            zone,
+            position: 0,
+            begin,
+            end,
+        }
+    }
+
+    #[test]
+    fn trains_are_compressed() {
+        let first = [occupancy("a", 0, 60_000), occupancy("b", 30_000, 120_000)];
+        let second = [
+            occupancy("a", 600_000, 630_000),
+            occupancy("b", 620_000, 700_000),
+        ];
+        let third = [occupancy("c", 900_000, 960_000)];
+
+        let compressed = compressed_occupancy(vec![
+            third.iter().collect(),
+            first.iter().collect(),
+            second.iter().collect(),
+        ]);
+
+        // The second train starts at 100 s to claim "b" once it is released at 120 s, the third
+        // one shares no zone with the others and starts along with the second
+        assert_eq!(compressed, 200_000);
+    }
+
+    #[rstest]
+    async fn empty_time_window_is_rejected() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+
+        let request = app
+            .post(&format!(
+                "/timetable/{}/capacity?infra_id={}",
+                timetable.id, infra.id
+            ))
+            .json(&json!({
+                "track_section_ranges": [
+                    {"track_section": "TA0", "begin": 0, "end": 1000, "direction": "START_TO_STOP"}
+                ],
+                "start_time": "2025-01-01T10:00:00Z",
+                "end_time": "2025-01-01T08:00:00Z",
+            }));
+
+        app.fetch(request).assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[rstest]
+    async fn path_with_repeated_track_section_is_rejected() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+
+        let request = app
+            .post(&format!(
+                "/timetable/{}/capacity?infra_id={}",
+                timetable.id, infra.id
+            ))
+            .json(&json!({
+                "track_section_ranges": [
+                    {"track_section": "TA0", "begin": 0, "end": 1000, "direction": "START_TO_STOP"},
+                    {"track_section": "TA0", "begin": 0, "end": 1000, "direction": "STOP_TO_START"}
+                ],
+                "start_time": "2025-01-01T08:00:00Z",
+                "end_time": "2025-01-01T10:00:00Z",
+            }));
+
+        let error: serde_json::Value = app
+            .fetch(request)
+            .assert_status(StatusCode::BAD_REQUEST)
+            .json_into();
+        assert_eq!(
+            error["type"],
+            "editoast:timetable:capacity:RepeatedTrackSection"
+        );
+        assert_eq!(error["context"]["track_section"], "TA0");
+    }
+
+    #[test]
+    fn invalid_paths_are_rejected() {
+        let range = |track_section: &str, begin, end| TrackRange {
+            track_section: track_section.into(),
+            begin,
+            end,
+            direction: Direction::StartToStop,
+        };
+        assert!(validate_path(&[]).is_err());
+        assert!(validate_path(&[range("TA0", 1000, 0)]).is_err());
+        assert!(validate_path(&[range("TA0", 0, 1000), range("TA1", 0, 1000)]).is_ok());
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::Extension;
+use chrono::Duration;
+use editoast_authz::BuiltinRole;
+use editoast_models::DbConnection;
+use editoast_schemas::train_schedule::OperationalPointReference;
+use editoast_schemas::train_schedule::PathItemLocation;
+use editoast_schemas::train_schedule::TrackReference;
+use itertools::Itertools;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::DayQueryParam;
+use super::ElectricalProfileSetIdQueryParam;
+use super::InfraIdQueryParam;
+use super::TimetableError;
+use super::TimetableIdParam;
+use crate::core::conflict_detection::Conflict;
+use crate::core::conflict_detection::ConflictDetectionRequest;
+use crate::core::conflict_detection::TrainRequirements;
+use crate::core::simulation::SimulationResponse;
+use crate::core::AsCoreRequest;
+use crate::core::CoreClient;
+use crate::error::Result;
+use crate::models::prelude::*;
+use crate::models::timetable::TimetableWithTrains;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::Infra;
+use crate::simulation_store::DisabledStore;
+use crate::views::path::path_item_cache::PathItemCache;
+use crate::views::path::pathfinding::PathfindingResult;
+use crate::views::train_schedule::train_simulation_batch;
+use crate::views::train_schedule::trains_on_day;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+use crate::RetrieveBatch;
+use crate::ValkeyClient;
+
+crate::routes! {
+    "/conflicts/resolutions" => conflict_resolutions,
+}
+
+editoast_common::schemas! {
+    ConflictResolution,
+    ResolutionProposal,
+    Adjustment,
+}
+
+/// Only the first conflicts of the timetable are looked into, each proposal costs a conflict detection
+const MAX_RESOLVED_CONFLICTS: usize = 10;
+/// Number of alternative tracks tried for each train of a conflict
+const MAX_ALTERNATIVE_TRACKS: usize = 3;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+struct ConflictResolution {
+    conflict: Conflict,
+    /// The proposals which solve the conflict, validated by a new conflict detection
+    proposals: Vec<ResolutionProposal>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+struct ResolutionProposal {
+    /// The train to adjust
+    train_id: i64,
+    adjustment: Adjustment,
+    /// Number of conflicts left in the timetable once the adjustment is applied
+    remaining_conflict_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Adjustment {
+    /// Shift the start time of the train, in ms. A negative shift makes the train leave earlier.
+    Retime { shift: i64 },
+    /// Extend a stop of the train, the following scheduled arrivals are delayed as much
+    ExtendStop {
+        /// The path item of the stop
+        at: String,
+        /// In ms
+        extra_duration: u64,
+    },
+    /// Route the train through another track of an operational point of its path
+    AlternativePath {
+        /// The path item referencing the operational point
+        at: String,
+        track_id: String,
+    },
+}
+
+/// A zone occupied by a train, in ms since epoch
+#[derive(Debug, Clone, Copy)]
+struct Occupancy<'a> {
+    zone: &'a str,
+    begin: i64,
+    end: i64,
+}
+
+fn occupancies(requirements: &TrainRequirements) -> Vec<Occupancy> {
+    let start_time = requirements.start_time.timestamp_millis();
+    let spacing = requirements
+        .spacing_requirements
+        .iter()
+        .map(|requirement| Occupancy {
+            zone: &requirement.zone,
+            begin: start_time + requirement.begin_time as i64,
+            end: start_time + requirement.end_time as i64,
+        });
+    let routing = requirements
+        .routing_requirements
+        .iter()
+        .flat_map(|requirement| {
+            requirement.zones.iter().map(|zone| Occupancy {
+                zone: &zone.zone,
+                begin: start_time + requirement.begin_time as i64,
+                end: start_time + zone.end_time as i64,
+            })
+        });
+    spacing.chain(routing).collect()
+}
+
+/// Find the smallest shift, in whole seconds, freeing the zones `moved` shares with `others`
+///
+/// A delay is positive and an advance negative. The smallest freeing shift always brings an
+/// occupancy of `moved` right after or right before one of `others`, so only these shifts are
+/// tried.
+fn minimum_shift(moved: &[Occupancy], others: &[Occupancy], delay: bool) -> Option<i64> {
+    let overlaps = |shift: i64| {
+        moved.iter().any(|moved| {
+            others.iter().any(|other| {
+                moved.zone == other.zone
+                    && moved.begin + shift < other.end
+                    && other.begin < moved.end + shift
+            })
+        })
+    };
+    let mut shifts: Vec<_> = moved
+        .iter()
+        .flat_map(|moved| {
+            others
+                .iter()
+                .filter(|other| other.zone == moved.zone)
+                .map(move |other| {
+                    if delay {
+                        (other.end - moved.begin + 999).div_euclid(1000) * 1000
+                    } else {
+                        (other.begin - moved.end).div_euclid(1000) * 1000
+                    }
+                })
+        })
+        .filter(|&shift| if delay { shift > 0 } else { shift < 0 })
+        .collect();
+    shifts.sort_by_key(|shift| shift.abs());
+    shifts.into_iter().find(|&shift| !overlaps(shift))
+}
+
+/// Run the conflict detection with the requirements of one train replaced
+struct ConflictValidator<'a> {
+    core_client: Arc<CoreClient>,
+    infra: &'a Infra,
+    requirements: HashMap<i64, TrainRequirements>,
+    conflict_count: usize,
+}
+
+impl ConflictValidator<'_> {
+    /// Return the number of conflicts left if the adjusted train no longer runs into the conflict
+    /// and the timetable has less conflicts than before
+    async fn validate(
+        &self,
+        conflict: &Conflict,
+        train_id: i64,
+        requirements: TrainRequirements,
+    ) -> Result<Option<u64>> {
+        let mut trains_requirements = self.requirements.clone();
+        trains_requirements.insert(train_id, requirements);
+        let conflicts = ConflictDetectionRequest {
+            infra: self.infra.id,
+            expected_version: self.infra.version.clone(),
+            trains_requirements,
+            work_schedules: None,
+        }
+        .fetch(&self.core_client)
+        .await?
+        .conflicts;
+
+        let trains: HashSet<_> = conflict.train_ids.iter().collect();
+        let unsolved = conflicts.iter().any(|remaining| {
+            remaining.conflict_type == conflict.conflict_type
+                && remaining.train_ids.iter().collect::<HashSet<_>>() == trains
+        });
+        if unsolved || conflicts.len() >= self.conflict_count {
+            return Ok(None);
+        }
+        Ok(Some(conflicts.len() as u64))
+    }
+}
+
+/// Delay the train from its last stop before the conflict on, by extending this stop
+fn extend_stop(
+    train: &TrainSchedule,
+    path_item_times: &[u64],
+    moved: &[Occupancy],
+    others: &[Occupancy],
+    conflict: &Conflict,
+) -> Option<(TrainSchedule, Adjustment)> {
+    let start_time = train.start_time.timestamp_millis();
+    let conflict_start = conflict.start_time.timestamp_millis() - start_time;
+    let (index, arrival) = train
+        .schedule
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, schedule_item)| schedule_item.stop_for.is_some())
+        .filter_map(|(index, schedule_item)| {
+            let position = train
+                .path
+                .iter()
+                .position(|path_item| path_item.id == schedule_item.at)?;
+            Some((index, *path_item_times.get(position)? as i64))
+        })
+        .find(|&(_, arrival)| arrival < conflict_start)?;
+
+    let moved: Vec<_> = moved
+        .iter()
+        .filter(|occupancy| occupancy.begin >= start_time + arrival)
+        .copied()
+        .collect();
+    let extra_duration = Duration::milliseconds(minimum_shift(&moved, others, true)?);
+
+    let mut train = train.clone();
+    let stop = &mut train.schedule[index];
+    let stop_for = *stop.stop_for.as_deref()?;
+    stop.stop_for = Some((stop_for + extra_duration).try_into().ok()?);
+    let at = stop.at.to_string();
+    for schedule_item in &mut train.schedule[index + 1..] {
+        if let Some(&arrival) = schedule_item.arrival.as_deref() {
+            schedule_item.arrival = Some((arrival + extra_duration).try_into().ok()?);
+        }
+    }
+    let adjustment = Adjustment::ExtendStop {
+        at,
+        extra_duration: extra_duration.num_milliseconds() as u64,
+    };
+    Some((train, adjustment))
+}
+
+/// Pin the operational points of the path to the tracks the train does not use yet
+fn alternative_paths(
+    train: &TrainSchedule,
+    pathfinding: &PathfindingResult,
+    path_item_cache: &PathItemCache,
+) -> Vec<(TrainSchedule, Adjustment)> {
+    let PathfindingResult::Success(path) = pathfinding else {
+        return vec![];
+    };
+    let used_tracks: HashSet<_> = path
+        .track_section_ranges
+        .iter()
+        .map(|range| range.track_section.as_str())
+        .collect();
+
+    let mut alternatives = vec![];
+    for (index, path_item) in train.path.iter().enumerate() {
+        let PathItemLocation::OperationalPointReference(reference) = &path_item.location else {
+            continue;
+        };
+        let location = PathItemLocation::OperationalPointReference(OperationalPointReference {
+            track_reference: None,
+            ..reference.clone()
+        });
+        let Ok(track_offsets) = path_item_cache.extract_location_from_path_items(&[&location])
+        else {
+            continue;
+        };
+        let tracks = track_offsets
+            .into_iter()
+            .flatten()
+            .map(|track_offset| track_offset.track)
+            .filter(|track| !used_tracks.contains(track.as_str()))
+            .unique();
+        for track_id in tracks {
+            let mut train = train.clone();
+            let PathItemLocation::OperationalPointReference(reference) =
+                &mut train.path[index].location
+            else {
+                unreachable!()
+            };
+            reference.track_reference = Some(TrackReference::Id {
+                track_id: track_id.clone(),
+            });
+            let adjustment = Adjustment::AlternativePath {
+                at: path_item.id.to_string(),
+                track_id: track_id.to_string(),
+            };
+            alternatives.push((train, adjustment));
+        }
+    }
+    alternatives.truncate(MAX_ALTERNATIVE_TRACKS);
+    alternatives
+}
+
+/// Propose adjustments solving the conflicts of the timetable
+///
+/// For each train of a conflict, three adjustments are looked for: retiming the train by the
+/// smallest shift, extending its last stop before the conflict, or routing it through another
+/// track of an operational point of its path. A proposal is returned only if a new conflict
+/// detection shows it solves the conflict without increasing the number of conflicts.
+/// Only the first conflicts of the timetable are looked into.
+#[utoipa::path(
+    get, path = "",
+    tag = "timetable",
+    params(TimetableIdParam, InfraIdQueryParam, ElectricalProfileSetIdQueryParam, DayQueryParam),
+    responses(
+        (status = 200, description = "The conflicts with their resolution proposals", body = Vec<ConflictResolution>),
+        (status = 404, description = "Timetable or infra not found"),
+    ),
+)]
+async fn conflict_resolutions(
+    State(AppState {
+        db_pool,
+        valkey: valkey_client,
+        simulation_store,
+        core_client,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Path(TimetableIdParam { id: timetable_id }): Path<TimetableIdParam>,
+    Query(InfraIdQueryParam { infra_id }): Query<InfraIdQueryParam>,
+    Query(ElectricalProfileSetIdQueryParam {
+        electrical_profile_set_id,
+    }): Query<ElectricalProfileSetIdQueryParam>,
+    Query(DayQueryParam { day }): Query<DayQueryParam>,
+) -> Result<Json<Vec<ConflictResolution>>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead, BuiltinRole::TimetableRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
+        TimetableError::NotFound { timetable_id }
+    })
+    .await?;
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+    let (trains, _): (Vec<_>, _) = TrainSchedule::retrieve_batch(conn, timetable.train_ids).await?;
+    let trains = trains_on_day(trains, day);
+
+    let simulations = train_simulation_batch(
+        conn,
+        valkey_client.clone(),
+        simulation_store.as_ref(),
+        core_client.clone(),
+        &trains,
+        &infra,
+        electrical_profile_set_id,
+    )
+    .await?;
+
+    let mut requirements = HashMap::with_capacity(trains.len());
+    let mut simulated_trains = HashMap::with_capacity(trains.len());
+    for (train, (simulation, pathfinding)) in trains.iter().zip(simulations) {
+        let SimulationResponse::Success { final_output, .. } = simulation else {
+            continue;
+        };
+        requirements.insert(
+            train.id,
+            TrainRequirements {
+                start_time: train.start_time,
+                spacing_requirements: final_output.spacing_requirements,
+                routing_requirements: final_output.routing_requirements,
+            },
+        );
+        simulated_trains.insert(
+            train.id,
+            (
+                train,
+                final_output.report_train.path_item_times,
+                pathfinding,
+            ),
+        );
+    }
+    if requirements.len() < 2 {
+        return Ok(Json(vec![]));
+    }
+
+    let conflicts = ConflictDetectionRequest {
+        infra: infra_id,
+        expected_version: infra.version.clone(),
+        trains_requirements: requirements.clone(),
+        work_schedules: None,
+    }
+    .fetch(&core_client)
+    .await?
+    .conflicts;
+
+    let path_items: Vec<_> = simulated_trains
+        .values()
+        .flat_map(|(train, _, _)| train.path.iter().map(|path_item| &path_item.location))
+        .collect();
+    let path_item_cache = PathItemCache::load(conn, infra_id, &path_items).await?;
+
+    let validator = ConflictValidator {
+        core_client: core_client.clone(),
+        infra: &infra,
+        requirements,
+        conflict_count: conflicts.len(),
+    };
+    let mut resolutions = Vec::with_capacity(conflicts.len());
+    for conflict in conflicts {
+        let proposals = if resolutions.len() < MAX_RESOLVED_CONFLICTS {
+            propose(
+                conn,
+                &conflict,
+                &validator,
+                &simulated_trains,
+                &path_item_cache,
+                valkey_client.clone(),
+                electrical_profile_set_id,
+            )
+            .await?
+        } else {
+            vec![]
+        };
+        resolutions.push(ConflictResolution {
+            conflict,
+            proposals,
+        });
+    }
+    Ok(Json(resolutions))
+}
+
+async fn propose(
+    conn: &mut DbConnection,
+    conflict: &Conflict,
+    validator: &ConflictValidator<'_>,
+    simulated_trains: &HashMap<i64, (&TrainSchedule, Vec<u64>, PathfindingResult)>,
+    path_item_cache: &PathItemCache,
+    valkey_client: Arc<ValkeyClient>,
+    electrical_profile_set_id: Option<i64>,
+) -> Result<Vec<ResolutionProposal>> {
+    let mut proposals = vec![];
+    for &train_id in &conflict.train_ids {
+        let Some((train, path_item_times, pathfinding)) = simulated_trains.get(&train_id) else {
+            continue;
+        };
+        let moved = occupancies(&validator.requirements[&train_id]);
+        let others: Vec<_> = conflict
+            .train_ids
+            .iter()
+            .filter(|&&other| other != train_id)
+            .filter_map(|other| validator.requirements.get(other))
+            .flat_map(occupancies)
+            .collect();
+
+        // Retiming the train does not change its requirements relatively to its start time
+        for delay in [true, false] {
+            let Some(shift) = minimum_shift(&moved, &others, delay) else {
+                continue;
+            };
+            let mut requirements = validator.requirements[&train_id].clone();
+            requirements.start_time += Duration::milliseconds(shift);
+            if let Some(remaining_conflict_count) =
+                validator.validate(conflict, train_id, requirements).await?
+            {
+                proposals.push(ResolutionProposal {
+                    train_id,
+                    adjustment: Adjustment::Retime { shift },
+                    remaining_conflict_count,
+                });
+            }
+        }
+
+        // The other adjustments change the run of the train, which has to be simulated again
+        let (variants, adjustments): (Vec<_>, Vec<_>) =
+            extend_stop(train, path_item_times, &moved, &others, conflict)
+                .into_iter()
+                .chain(alternative_paths(train, pathfinding, path_item_cache))
+                .unzip();
+        if variants.is_empty() {
+            continue;
+        }
+        // Variants are not worth persisting, only the adopted one will be simulated again
+        let simulations = train_simulation_batch(
+            conn,
+            valkey_client.clone(),
+            &DisabledStore,
+            validator.core_client.clone(),
+            &variants,
+            validator.infra,
+            electrical_profile_set_id,
+        )
+        .await?;
+        for ((variant, (simulation, _)), adjustment) in
+            variants.iter().zip(simulations).zip(adjustments)
+        {
+            let SimulationResponse::Success { final_output, .. } = simulation else {
+                continue;
+            };
+            let requirements = TrainRequirements {
+                start_time: variant.start_time,
+                spacing_requirements: final_output.spacing_requirements,
+                routing_requirements: final_output.routing_requirements,
+            };
+            if let Some(remaining_conflict_count) =
+                validator.validate(conflict, train_id, requirements).await?
+            {
+                proposals.push(ResolutionProposal {
+                    train_id,
+                    adjustment,
+                    remaining_conflict_count,
+                });
+            }
+        }
+    }
+    Ok(proposals)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::models::fixtures::create_small_infra;
+    use crate::models::fixtures::create_timetable;
+    use crate::views::test_app::TestAppBuilder;
+
+    fn occupancy(zone: &str, begin: i64, end: i64) -> Occupancy {
+        Occupancy { zone, begin, end }
+    }
+
+    #[rstest]
+    #[case::delay(true, Some(61_000))]
+    #[case::advance(false, Some(-110_000))]
+    fn minimum_shift_frees_the_shared_zones(#[case] delay: bool, #[case] expected: Option<i64>) {
+        let moved = [
+            occupancy("a", 100_000, 160_000),
+            occupancy("b", 150_000, 200_000),
+        ];
+        let others = [
+            occupancy("a", 50_000, 70_000),
+            occupancy("b", 130_000, 210_500),
+            occupancy("c", 0, 1_000_000),
+        ];
+
+        assert_eq!(minimum_shift(&moved, &others, delay), expected);
+    }
+
+    #[rstest]
+    async fn resolutions_of_an_empty_timetable() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+
+        let request = app.get(&format!(
+            "/timetable/{}/conflicts/resolutions?infra_id={}",
+            timetable.id, infra.id
+        ));
+        let resolutions: Vec<ConflictResolution> =
+            app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        assert!(resolutions.is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use axum::extract::Json;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::Extension;
+use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use super::trains_requirements;
+use super::DayQueryParam;
+use super::ElectricalProfileSetIdQueryParam;
+use super::InfraIdQueryParam;
+use super::TimetableError;
+use crate::core::conflict_detection::Conflict;
+use crate::core::conflict_detection::ConflictDetectionRequest;
+use crate::core::AsCoreRequest;
+use crate::error::Result;
+use crate::models::prelude::*;
+use crate::models::timetable::TimetableWithTrains;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::Infra;
+use crate::views::train_schedule::train_simulation_batch;
+use crate::views::train_schedule::trains_on_day;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+
+crate::routes! {
+    "/conflicts" => cross_timetable_conflicts,
+}
+
+editoast_common::schemas! {
+    CrossTimetableConflictsForm,
+    TimetableConflict,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "timetable:conflicts")]
+enum CrossTimetableConflictsError {
+    #[error("At least two timetables, or a timetable and some train schedules, are required")]
+    #[editoast_error(status = 400)]
+    NothingToCompare,
+    #[error("{number} train schedule(s) could not be found")]
+    #[editoast_error(status = 404)]
+    TrainSchedulesNotFound { number: usize },
+}
+
+/// The trains checked against each other
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+struct CrossTimetableConflictsForm {
+    /// All the trains of these timetables are checked
+    timetable_ids: Vec<i64>,
+    /// Train schedules from any timetable, checked along with the timetables
+    #[serde(default)]
+    train_ids: Vec<i64>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+struct TimetableConflict {
+    conflict: Conflict,
+    /// The timetables of the trains involved in the conflict
+    timetable_ids: Vec<i64>,
+}
+
+/// Tag a conflict with the timetables its trains come from
+fn tag_conflict(conflict: Conflict, train_timetables: &HashMap<i64, i64>) -> TimetableConflict {
+    let timetable_ids: BTreeSet<_> = conflict
+        .train_ids
+        .iter()
+        .filter_map(|train_id| train_timetables.get(train_id))
+        .copied()
+        .collect();
+    TimetableConflict {
+        conflict,
+        timetable_ids: timetable_ids.into_iter().collect(),
+    }
+}
+
+/// Detect the conflicts between the trains of several timetables
+///
+/// The trains of the given timetables, and the given train schedules, are checked against each
+/// other as if they were part of the same timetable. Trains whose simulation fails are ignored.
+#[utoipa::path(
+    post, path = "",
+    tag = "timetable",
+    params(InfraIdQueryParam, ElectricalProfileSetIdQueryParam, DayQueryParam),
+    request_body = CrossTimetableConflictsForm,
+    responses(
+        (status = 200, description = "The conflicts tagged with their timetables", body = Vec<TimetableConflict>),
+        (status = 400, description = "Less than two sets of trains were given"),
+        (status = 404, description = "Timetable, train schedule or infra not found"),
+    ),
+)]
+async fn cross_timetable_conflicts(
+    State(AppState {
+        db_pool,
+        valkey: valkey_client,
+        simulation_store,
+        core_client,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Query(InfraIdQueryParam { infra_id }): Query<InfraIdQueryParam>,
+    Query(ElectricalProfileSetIdQueryParam {
+        electrical_profile_set_id,
+    }): Query<ElectricalProfileSetIdQueryParam>,
+    Query(DayQueryParam { day }): Query<DayQueryParam>,
+    Json(CrossTimetableConflictsForm {
+        timetable_ids,
+        train_ids,
+    }): Json<CrossTimetableConflictsForm>,
+) -> Result<Json<Vec<TimetableConflict>>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead, BuiltinRole::TimetableRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let timetable_ids: BTreeSet<_> = timetable_ids.into_iter().collect();
+    if timetable_ids.is_empty() || (timetable_ids.len() == 1 && train_ids.is_empty()) {
+        return Err(CrossTimetableConflictsError::NothingToCompare.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let mut all_train_ids: BTreeSet<_> = train_ids.into_iter().collect();
+    for timetable_id in timetable_ids {
+        let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
+            TimetableError::NotFound { timetable_id }
+        })
+        .await?;
+        all_train_ids.extend(timetable.train_ids);
+    }
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+    let trains: Vec<TrainSchedule> =
+        TrainSchedule::retrieve_batch_or_fail(conn, all_train_ids, |missing| {
+            CrossTimetableConflictsError::TrainSchedulesNotFound {
+                number: missing.len(),
+            }
+        })
+        .await?;
+    let trains = trains_on_day(trains, day);
+
+    let simulations = train_simulation_batch(
+        conn,
+        valkey_client,
+        simulation_store.as_ref(),
+        core_client.clone(),
+        &trains,
+        &infra,
+        electrical_profile_set_id,
+    )
+    .await?;
+    let conflicts = ConflictDetectionRequest {
+        infra: infra_id,
+        expected_version: infra.version,
+        trains_requirements: trains_requirements(&trains, simulations),
+        work_schedules: None,
+    }
+    .fetch(&core_client)
+    .await?
+    .conflicts;
+
+    let train_timetables: HashMap<_, _> = trains
+        .iter()
+        .map(|train| (train.id, train.timetable_id))
+        .collect();
+    Ok(Json(
+        conflicts
+            .into_iter()
+            .map(|conflict| tag_conflict(conflict, &train_timetables))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use chrono::DateTime;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::core::conflict_detection::ConflictType;
+    use crate::models::fixtures::create_small_infra;
+    use crate::models::fixtures::create_timetable;
+    use crate::views::test_app::TestAppBuilder;
+
+    #[test]
+    fn conflicts_are_tagged_with_their_timetables() {
+        let conflict = Conflict {
+            train_ids: vec![3, 1, 2],
+            work_schedule_ids: vec![],
+            start_time: DateTime::default(),
+            end_time: DateTime::default(),
+            conflict_type: ConflictType::Spacing,
+            requirements: vec![],
+        };
+        let train_timetables = HashMap::from([(1, 20), (2, 10), (3, 20)]);
+
+        let tagged = tag_conflict(conflict, &train_timetables);
+
+        assert_eq!(tagged.timetable_ids, vec![10, 20]);
+    }
+
+    #[rstest]
+    async fn a_single_timetable_is_rejected() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+
+        let request = app
+            .post(&format!("/timetable/conflicts?infra_id={}", infra.id))
+            .json(&json!({"timetable_ids": [timetable.id, timetable.id]}));
+
+        app.fetch(request).assert_status(StatusCode::BAD_REQUEST);
+    }
+}
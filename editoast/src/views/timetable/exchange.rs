@@ -0,0 +1,368 @@
+use axum::body::Bytes;
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::Extension;
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use editoast_models::DbConnectionPoolV2;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use super::TimetableError;
+use crate::error::Result;
+use crate::models::prelude::*;
+use crate::models::timetable::exchange::ExchangeFileError;
+use crate::models::timetable::exchange::ExchangeFormat;
+use crate::models::timetable::exchange::ExchangeTimetable;
+use crate::models::timetable::exchange::UnmatchedStop;
+use crate::models::timetable::TimetableWithTrains;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::Infra;
+use crate::views::train_schedule::TrainScheduleForm;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::RetrieveBatch;
+
+crate::routes! {
+    "/export/{format}" => export,
+    "/import/{format}" => import,
+}
+
+editoast_common::schemas! {
+    TimetableImportReport,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "timetable:exchange")]
+pub enum TimetableExchangeError {
+    #[error("Invalid timetable file: {message}")]
+    #[editoast_error(status = 400)]
+    InvalidFile { message: String },
+    #[error("{} stops of the timetable could not be found in the infra", stops.len())]
+    #[editoast_error(status = 400)]
+    UnmatchedStops { stops: Vec<UnmatchedStop> },
+    #[error("Unknown timezone '{timezone}'")]
+    #[editoast_error(status = 400)]
+    UnknownTimezone { timezone: String },
+}
+
+impl From<ExchangeFileError> for TimetableExchangeError {
+    fn from(error: ExchangeFileError) -> Self {
+        Self::InvalidFile {
+            message: error.to_string(),
+        }
+    }
+}
+
+#[derive(IntoParams, Deserialize)]
+struct TimetableExchangeParams {
+    /// A timetable ID
+    id: i64,
+    format: ExchangeFormat,
+}
+
+#[derive(IntoParams, Deserialize)]
+#[into_params(parameter_in = Query)]
+struct ExportQueryParams {
+    /// The infra the stops are looked up in
+    infra_id: i64,
+    /// The IANA timezone the times and the running days are written in, UTC by default
+    timezone: Option<String>,
+}
+
+#[derive(IntoParams, Deserialize)]
+#[into_params(parameter_in = Query)]
+struct ImportQueryParams {
+    /// The infra the stops are matched in
+    infra_id: i64,
+    /// The rolling stock of the imported trains
+    rolling_stock_name: String,
+    /// Only import the trains running on this day
+    date: Option<NaiveDate>,
+}
+
+/// Result of a timetable import
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+struct TimetableImportReport {
+    /// The created train schedules
+    train_ids: Vec<i64>,
+    /// The stops missing from the infra, the trains calling at them were not imported
+    unmatched_stops: Vec<UnmatchedStop>,
+}
+
+/// Export the train schedules of a timetable as railML 3 or GTFS
+///
+/// Stops are given the name, codes and coordinates of their operational point in the infra.
+/// Only the path items referencing an operational point are exported.
+#[utoipa::path(
+    get, path = "",
+    tag = "timetable",
+    params(TimetableExchangeParams, ExportQueryParams),
+    responses(
+        (status = 200, description = "The railML document or the zipped GTFS feed", body = Vec<u8>),
+        (status = 400, description = "Some stops could not be found in the infra"),
+        (status = 404, description = "Timetable or infra not found"),
+    ),
+)]
+async fn export(
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+    Path(TimetableExchangeParams {
+        id: timetable_id,
+        format,
+    }): Path<TimetableExchangeParams>,
+    Query(ExportQueryParams { infra_id, timezone }): Query<ExportQueryParams>,
+) -> Result<impl IntoResponse> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead, BuiltinRole::TimetableRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let timezone: Tz = match timezone {
+        Some(timezone) => timezone
+            .parse()
+            .map_err(|_| TimetableExchangeError::UnknownTimezone { timezone })?,
+        None => Tz::UTC,
+    };
+
+    let conn = &mut db_pool.get().await?;
+    let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
+        TimetableError::NotFound { timetable_id }
+    })
+    .await?;
+    Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+    let (trains, _): (Vec<_>, _) = TrainSchedule::retrieve_batch(conn, timetable.train_ids).await?;
+
+    let (exchange_timetable, unmatched_stops) =
+        ExchangeTimetable::from_train_schedules(conn, infra_id, &trains, timezone).await?;
+    if !unmatched_stops.is_empty() {
+        return Err(TimetableExchangeError::UnmatchedStops {
+            stops: unmatched_stops,
+        }
+        .into());
+    }
+    let data = format
+        .write(&exchange_timetable)
+        .map_err(TimetableExchangeError::from)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"timetable_{timetable_id}.{}\"",
+                    format.extension()
+                ),
+            ),
+        ],
+        data,
+    ))
+}
+
+/// Import train schedules into a timetable from railML 3 or GTFS
+///
+/// Stops are matched with the operational points of the infra by UIC code, or by trigram and
+/// secondary code. Trains calling at a stop missing from the infra are not imported and the
+/// stop is reported instead.
+#[utoipa::path(
+    post, path = "",
+    tag = "timetable",
+    params(TimetableExchangeParams, ImportQueryParams),
+    request_body = Vec<u8>,
+    responses(
+        (status = 200, description = "The created train schedules and the unmatched stops", body = TimetableImportReport),
+        (status = 400, description = "The file could not be read"),
+        (status = 404, description = "Timetable or infra not found"),
+    ),
+)]
+async fn import(
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+    Path(TimetableExchangeParams {
+        id: timetable_id,
+        format,
+    }): Path<TimetableExchangeParams>,
+    Query(ImportQueryParams {
+        infra_id,
+        rolling_stock_name,
+        date,
+    }): Query<ImportQueryParams>,
+    body: Bytes,
+) -> Result<Json<TimetableImportReport>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead, BuiltinRole::TimetableWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || TimetableError::NotFound {
+        timetable_id,
+    })
+    .await?;
+    Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+
+    let exchange_timetable = format
+        .read(&body, date)
+        .map_err(TimetableExchangeError::from)?;
+    let (train_schedules, unmatched_stops) = exchange_timetable
+        .into_train_schedules(conn, infra_id, &rolling_stock_name)
+        .await?;
+    let changesets: Vec<Changeset<TrainSchedule>> = train_schedules
+        .into_iter()
+        .map(|train_schedule| {
+            TrainScheduleForm {
+                timetable_id: Some(timetable_id),
+                train_schedule,
+            }
+            .into()
+        })
+        .collect();
+    let trains: Vec<_> = TrainSchedule::create_batch(conn, changesets).await?;
+
+    Ok(Json(TimetableImportReport {
+        train_ids: trains.into_iter().map(|train| train.id).collect(),
+        unmatched_stops,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use chrono::TimeZone;
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::fixtures::create_small_infra;
+    use crate::models::fixtures::create_timetable;
+    use crate::views::test_app::TestAppBuilder;
+    use editoast_schemas::train_schedule::TrainScheduleBase;
+
+    fn train_schedule(trigrams: &[&str]) -> TrainScheduleBase {
+        let path: Vec<_> = trigrams
+            .iter()
+            .enumerate()
+            .map(|(index, trigram)| json!({"id": index.to_string(), "trigram": trigram}))
+            .collect();
+        serde_json::from_value(json!({
+            "train_name": "train",
+            "rolling_stock_name": "R2D2",
+            "start_time": "2025-01-01T08:00:00Z",
+            "path": path,
+            "schedule": [
+                {"at": "1", "arrival": "PT10M", "stop_for": "PT1M"},
+                {"at": "2", "arrival": "PT30M"},
+            ],
+            "constraint_distribution": "STANDARD",
+        }))
+        .unwrap()
+    }
+
+    #[rstest]
+    async fn export_and_import_gtfs() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+        let changeset: Changeset<TrainSchedule> = TrainScheduleForm {
+            timetable_id: Some(timetable.id),
+            train_schedule: train_schedule(&["WS", "MWS", "NES"]),
+        }
+        .into();
+        changeset.create(&mut pool.get_ok()).await.unwrap();
+
+        let request = app.get(&format!(
+            "/timetable/{}/export/gtfs?infra_id={}",
+            timetable.id, infra.id
+        ));
+        let feed = app.fetch(request).assert_status(StatusCode::OK).bytes();
+
+        let imported_timetable = create_timetable(&mut pool.get_ok()).await;
+        let request = app
+            .post(&format!(
+                "/timetable/{}/import/gtfs?infra_id={}&rolling_stock_name=R2D2",
+                imported_timetable.id, infra.id
+            ))
+            .bytes(feed.into());
+        let report: TimetableImportReport =
+            app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        assert!(report.unmatched_stops.is_empty());
+        let train = TrainSchedule::retrieve(&mut pool.get_ok(), report.train_ids[0])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(train.path.len(), 3);
+        assert_eq!(train.schedule.len(), 2);
+        assert_eq!(
+            train.start_time,
+            Utc.with_ymd_and_hms(2025, 1, 1, 8, 0, 0).unwrap()
+        );
+    }
+
+    #[rstest]
+    async fn export_with_unknown_timezone_fails() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+
+        let request = app.get(&format!(
+            "/timetable/{}/export/gtfs?infra_id={}&timezone=Mars/Olympus",
+            timetable.id, infra.id
+        ));
+        let error: serde_json::Value = app
+            .fetch(request)
+            .assert_status(StatusCode::BAD_REQUEST)
+            .json_into();
+        assert_eq!(error["context"]["timezone"], "Mars/Olympus");
+    }
+
+    #[rstest]
+    async fn export_with_unmatched_stops_fails() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+        let changeset: Changeset<TrainSchedule> = TrainScheduleForm {
+            timetable_id: Some(timetable.id),
+            train_schedule: train_schedule(&["WS", "XXX", "NES"]),
+        }
+        .into();
+        changeset.create(&mut pool.get_ok()).await.unwrap();
+
+        let request = app.get(&format!(
+            "/timetable/{}/export/railml?infra_id={}",
+            timetable.id, infra.id
+        ));
+        let error: serde_json::Value = app
+            .fetch(request)
+            .assert_status(StatusCode::BAD_REQUEST)
+            .json_into();
+        assert_eq!(error["context"]["stops"][0]["id"], "XXX");
+    }
+}
@@ -0,0 +1,723 @@
+use std::collections::HashMap;
+
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::Extension;
+use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use super::ElectricalProfileSetIdQueryParam;
+use super::InfraIdQueryParam;
+use super::TimetableError;
+use super::TimetableIdParam;
+use crate::core::conflict_detection::ConflictDetectionRequest;
+use crate::core::conflict_detection::TrainRequirements;
+use crate::core::simulation::ReportTrain;
+use crate::core::simulation::SimulationResponse;
+use crate::core::AsCoreRequest;
+use crate::error::Result;
+use crate::models::prelude::*;
+use crate::models::timetable::TimetableWithTrains;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::Infra;
+use crate::views::train_schedule::train_simulation_batch;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+use crate::RetrieveBatch;
+
+crate::routes! {
+    "/robustness" => robustness,
+}
+
+editoast_common::schemas! {
+    RobustnessForm,
+    PrimaryDelay,
+    DelayDistribution,
+    RobustnessReport,
+    TrainDelays,
+    ScheduleItemDelay,
+}
+
+/// Each scenario costs a conflict detection
+const MAX_SCENARIO_COUNT: u32 = 20;
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "timetable:robustness")]
+enum TimetableRobustnessError {
+    #[error("Train '{train_id}' is not part of the timetable or could not be simulated")]
+    #[editoast_error(status = 400)]
+    UnknownTrain { train_id: i64 },
+    #[error("The number of scenarios must be between 1 and {max}")]
+    #[editoast_error(status = 400)]
+    InvalidScenarioCount { max: u32 },
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+struct RobustnessForm {
+    primary_delays: Vec<PrimaryDelay>,
+    /// Seed of the random draws, the same seed always gives the same report
+    #[serde(default)]
+    seed: u64,
+    /// Number of scenarios drawn, the reported delays are their mean
+    #[serde(default = "default_scenario_count")]
+    #[schema(minimum = 1, maximum = 20)]
+    scenario_count: u32,
+}
+
+fn default_scenario_count() -> u32 {
+    1
+}
+
+/// A delay of a train at its departure
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+struct PrimaryDelay {
+    train_id: i64,
+    delay: DelayDistribution,
+}
+
+/// Durations are in ms
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+enum DelayDistribution {
+    /// The same delay in every scenario
+    Fixed { duration: u64 },
+    /// A delay drawn uniformly between two bounds
+    Uniform { min: u64, max: u64 },
+    /// A delay drawn from an exponential distribution, the usual model of primary delays
+    Exponential { mean: u64 },
+}
+
+impl DelayDistribution {
+    fn sample(&self, rng: &mut StdRng) -> u64 {
+        match *self {
+            DelayDistribution::Fixed { duration } => duration,
+            DelayDistribution::Uniform { min, max } => rng.gen_range(min.min(max)..=max.max(min)),
+            DelayDistribution::Exponential { mean } => {
+                let draw: f64 = rng.gen();
+                (-(mean as f64) * (1. - draw).ln()).round() as u64
+            }
+        }
+    }
+}
+
+/// Delays are in ms and averaged over the scenarios
+#[derive(Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+struct RobustnessReport {
+    trains: Vec<TrainDelays>,
+    /// Sum of the primary delays
+    total_primary_delay: u64,
+    /// Sum of the delays passed on from train to train
+    total_secondary_delay: u64,
+    /// Share of the delays which are not passed on to other trains, from 0 to 1
+    robustness_score: f64,
+    /// Number of conflicts once the delays are applied
+    conflict_count: f64,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+struct TrainDelays {
+    train_id: i64,
+    primary_delay: u64,
+    /// The largest delay caused by the other trains
+    secondary_delay: u64,
+    /// The delay at each schedule item of the train
+    arrival_delays: Vec<ScheduleItemDelay>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+struct ScheduleItemDelay {
+    at: String,
+    delay: u64,
+}
+
+/// Linear interpolation of `ys` at `x`, `xs` being sorted
+fn interpolate(xs: &[u64], ys: &[u64], x: u64) -> u64 {
+    let index = xs.partition_point(|&value| value < x);
+    if index == 0 {
+        return ys.first().copied().unwrap_or_default();
+    }
+    if index == xs.len() {
+        return ys.last().copied().unwrap_or_default();
+    }
+    let (x_start, x_end) = (xs[index - 1], xs[index]);
+    let (y_start, y_end) = (ys[index - 1] as f64, ys[index] as f64);
+    let ratio = (x - x_start) as f64 / (x_end - x_start) as f64;
+    (y_start + ratio * (y_end - y_start)).round() as u64
+}
+
+/// The time the train can make up until `time` by running without its regularity margins
+fn recovery_at(base: &ReportTrain, report: &ReportTrain, time: u64) -> u64 {
+    let position = interpolate(&report.times, &report.positions, time);
+    time.saturating_sub(interpolate(&base.positions, &base.times, position))
+}
+
+#[derive(Debug)]
+enum CheckpointKind<'a> {
+    /// The train claims a zone until `end`, in ms since its departure
+    Zone {
+        zone: &'a str,
+        end: u64,
+    },
+    Arrival {
+        at: &'a str,
+    },
+}
+
+#[derive(Debug)]
+struct Checkpoint<'a> {
+    /// In ms since the departure of the train
+    time: u64,
+    /// The time the train can make up between its departure and this checkpoint
+    recovery: u64,
+    kind: CheckpointKind<'a>,
+}
+
+/// A train as seen by the delay propagation, its checkpoints ordered by time
+#[derive(Debug)]
+struct PropagationTrain<'a> {
+    train_id: i64,
+    /// In ms since epoch
+    start_time: i64,
+    checkpoints: Vec<Checkpoint<'a>>,
+}
+
+impl<'a> PropagationTrain<'a> {
+    fn new(
+        train: &'a TrainSchedule,
+        requirements: &'a TrainRequirements,
+        base: &ReportTrain,
+        report: &ReportTrain,
+    ) -> Self {
+        let zones = requirements.spacing_requirements.iter().map(|requirement| {
+            (
+                requirement.begin_time,
+                CheckpointKind::Zone {
+                    zone: &requirement.zone,
+                    end: requirement.end_time,
+                },
+            )
+        });
+        let arrivals = train.schedule.iter().filter_map(|schedule_item| {
+            let index = train
+                .path
+                .iter()
+                .position(|path_item| path_item.id == schedule_item.at)?;
+            let time = *report.path_item_times.get(index)?;
+            Some((
+                time,
+                CheckpointKind::Arrival {
+                    at: &schedule_item.at,
+                },
+            ))
+        });
+        let mut checkpoints: Vec<_> = zones
+            .chain(arrivals)
+            .map(|(time, kind)| Checkpoint {
+                time,
+                recovery: recovery_at(base, report, time),
+                kind,
+            })
+            .collect();
+        checkpoints.sort_by_key(|checkpoint| checkpoint.time);
+        // A recovered delay is not lost again
+        let mut recovery = 0;
+        for checkpoint in &mut checkpoints {
+            recovery = recovery.max(checkpoint.recovery);
+            checkpoint.recovery = recovery;
+        }
+        Self {
+            train_id: train.id,
+            start_time: requirements.start_time.timestamp_millis(),
+            checkpoints,
+        }
+    }
+}
+
+/// Compute the delay of the trains at each of their checkpoints
+///
+/// A train passes its delay on to the next train claiming the same zone, minus the buffer time
+/// between them. A train makes up its delay as much as its regularity margins allow.
+/// The trains keep their order on each zone.
+fn propagate(trains: &[PropagationTrain], primary_delays: &[u64]) -> Vec<Vec<u64>> {
+    // The previous claim of each zone claim, with the buffer time in between
+    let mut claims: HashMap<&str, Vec<(i64, i64, usize, usize)>> = HashMap::new();
+    for (train_index, train) in trains.iter().enumerate() {
+        for (index, checkpoint) in train.checkpoints.iter().enumerate() {
+            if let CheckpointKind::Zone { zone, end } = checkpoint.kind {
+                let begin = train.start_time + checkpoint.time as i64;
+                let end = train.start_time + end as i64;
+                claims
+                    .entry(zone)
+                    .or_default()
+                    .push((begin, end, train_index, index));
+            }
+        }
+    }
+    let mut previous_claims: HashMap<(usize, usize), Vec<(usize, usize, u64)>> = HashMap::new();
+    for claims in claims.values_mut() {
+        claims.sort();
+        for pair in claims.windows(2) {
+            let (_, previous_end, previous_train, previous_index) = pair[0];
+            let (begin, _, train, index) = pair[1];
+            if previous_train == train {
+                continue;
+            }
+            let buffer = (begin - previous_end).max(0) as u64;
+            previous_claims.entry((train, index)).or_default().push((
+                previous_train,
+                previous_index,
+                buffer,
+            ));
+        }
+    }
+
+    let mut delays: Vec<Vec<u64>> = trains
+        .iter()
+        .map(|train| vec![0; train.checkpoints.len()])
+        .collect();
+    // Delays only grow, they are stable after at most one pass per train
+    for _ in 0..=trains.len() {
+        let mut changed = false;
+        for (train_index, train) in trains.iter().enumerate() {
+            let mut delay = primary_delays[train_index];
+            let mut recovery = 0;
+            for (index, checkpoint) in train.checkpoints.iter().enumerate() {
+                delay = delay.saturating_sub(checkpoint.recovery - recovery);
+                recovery = checkpoint.recovery;
+                for &(previous_train, previous_index, buffer) in previous_claims
+                    .get(&(train_index, index))
+                    .into_iter()
+                    .flatten()
+                {
+                    delay =
+                        delay.max(delays[previous_train][previous_index].saturating_sub(buffer));
+                }
+                if delays[train_index][index] != delay {
+                    changed = true;
+                    delays[train_index][index] = delay;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    delays
+}
+
+/// The delays of the trains if they did not pass them on
+fn propagate_alone(trains: &[PropagationTrain], primary_delays: &[u64]) -> Vec<Vec<u64>> {
+    trains
+        .iter()
+        .zip(primary_delays)
+        .map(|(train, &primary_delay)| {
+            propagate(std::slice::from_ref(train), &[primary_delay]).remove(0)
+        })
+        .collect()
+}
+
+/// The delay of the train at `time`, given its delays at its checkpoints
+fn delay_at(train: &PropagationTrain, delays: &[u64], primary_delay: u64, time: u64) -> u64 {
+    let index = train
+        .checkpoints
+        .partition_point(|checkpoint| checkpoint.time <= time);
+    if index == 0 {
+        primary_delay
+    } else {
+        delays[index - 1]
+    }
+}
+
+/// Delay the requirements of a train
+fn delayed_requirements(
+    requirements: &TrainRequirements,
+    train: &PropagationTrain,
+    delays: &[u64],
+    primary_delay: u64,
+) -> TrainRequirements {
+    let mut requirements = requirements.clone();
+    for requirement in &mut requirements.spacing_requirements {
+        let delay = delay_at(train, delays, primary_delay, requirement.begin_time);
+        requirement.begin_time += delay;
+        requirement.end_time += delay;
+    }
+    for requirement in &mut requirements.routing_requirements {
+        let delay = delay_at(train, delays, primary_delay, requirement.begin_time);
+        requirement.begin_time += delay;
+        for zone in &mut requirement.zones {
+            zone.end_time += delay;
+        }
+    }
+    requirements
+}
+
+/// Propagate primary delays through the timetable
+///
+/// The primary delays are drawn for each scenario from their distribution, with a seeded random
+/// generator. Each delay is passed on to the trains following the delayed one on the same zones,
+/// minus the buffer time between their blocking times, and is made up as much as the regularity
+/// margins of the trains allow. Conflict detection is run on the delayed timetable of each
+/// scenario.
+///
+/// The robustness score is the share of the delays which are not passed on to other trains:
+/// 1 means no train is delayed by another one.
+#[utoipa::path(
+    post, path = "",
+    tag = "timetable",
+    params(TimetableIdParam, InfraIdQueryParam, ElectricalProfileSetIdQueryParam),
+    request_body = RobustnessForm,
+    responses(
+        (status = 200, description = "The delays of the trains and the robustness of the timetable", body = RobustnessReport),
+        (status = 400, description = "A delayed train is not part of the timetable"),
+        (status = 404, description = "Timetable or infra not found"),
+    ),
+)]
+async fn robustness(
+    State(AppState {
+        db_pool,
+        valkey: valkey_client,
+        simulation_store,
+        core_client,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Path(TimetableIdParam { id: timetable_id }): Path<TimetableIdParam>,
+    Query(InfraIdQueryParam { infra_id }): Query<InfraIdQueryParam>,
+    Query(ElectricalProfileSetIdQueryParam {
+        electrical_profile_set_id,
+    }): Query<ElectricalProfileSetIdQueryParam>,
+    Json(RobustnessForm {
+        primary_delays,
+        seed,
+        scenario_count,
+    }): Json<RobustnessForm>,
+) -> Result<Json<RobustnessReport>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead, BuiltinRole::TimetableRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+    if !(1..=MAX_SCENARIO_COUNT).contains(&scenario_count) {
+        return Err(TimetableRobustnessError::InvalidScenarioCount {
+            max: MAX_SCENARIO_COUNT,
+        }
+        .into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
+        TimetableError::NotFound { timetable_id }
+    })
+    .await?;
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+    let (trains, _): (Vec<_>, _) = TrainSchedule::retrieve_batch(conn, timetable.train_ids).await?;
+
+    let simulations = train_simulation_batch(
+        conn,
+        valkey_client,
+        simulation_store.as_ref(),
+        core_client.clone(),
+        &trains,
+        &infra,
+        electrical_profile_set_id,
+    )
+    .await?;
+
+    let mut simulated_trains = vec![];
+    for (train, (simulation, _)) in trains.iter().zip(simulations) {
+        let SimulationResponse::Success {
+            base, final_output, ..
+        } = simulation
+        else {
+            continue;
+        };
+        let requirements = TrainRequirements {
+            start_time: train.start_time,
+            spacing_requirements: final_output.spacing_requirements,
+            routing_requirements: final_output.routing_requirements,
+        };
+        simulated_trains.push((train, requirements, base, final_output.report_train));
+    }
+    let propagation_trains: Vec<_> = simulated_trains
+        .iter()
+        .map(|(train, requirements, base, report)| {
+            PropagationTrain::new(train, requirements, base, report)
+        })
+        .collect();
+    let train_indexes: HashMap<_, _> = propagation_trains
+        .iter()
+        .enumerate()
+        .map(|(index, train)| (train.train_id, index))
+        .collect();
+    let mut delayed_trains = Vec::with_capacity(primary_delays.len());
+    for PrimaryDelay { train_id, delay } in primary_delays {
+        let Some(&index) = train_indexes.get(&train_id) else {
+            return Err(TimetableRobustnessError::UnknownTrain { train_id }.into());
+        };
+        delayed_trains.push((index, delay));
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut primary_sums = vec![0; propagation_trains.len()];
+    let mut secondary_sums = vec![0; propagation_trains.len()];
+    let mut arrival_sums: Vec<Vec<u64>> = propagation_trains
+        .iter()
+        .map(|train| vec![0; train.checkpoints.len()])
+        .collect();
+    let mut conflict_count = 0;
+    for _ in 0..scenario_count {
+        let mut primary_delays = vec![0; propagation_trains.len()];
+        for (index, distribution) in &delayed_trains {
+            primary_delays[*index] += distribution.sample(&mut rng);
+        }
+        let delays = propagate(&propagation_trains, &primary_delays);
+        let own_delays = propagate_alone(&propagation_trains, &primary_delays);
+
+        let mut trains_requirements = HashMap::with_capacity(propagation_trains.len());
+        for (index, train) in propagation_trains.iter().enumerate() {
+            primary_sums[index] += primary_delays[index];
+            secondary_sums[index] += delays[index]
+                .iter()
+                .zip(&own_delays[index])
+                .map(|(delay, own_delay)| delay - own_delay)
+                .max()
+                .unwrap_or_default();
+            for (sum, delay) in arrival_sums[index].iter_mut().zip(&delays[index]) {
+                *sum += delay;
+            }
+            trains_requirements.insert(
+                train.train_id,
+                delayed_requirements(
+                    &simulated_trains[index].1,
+                    train,
+                    &delays[index],
+                    primary_delays[index],
+                ),
+            );
+        }
+        if trains_requirements.len() > 1 {
+            conflict_count += ConflictDetectionRequest {
+                infra: infra_id,
+                expected_version: infra.version.clone(),
+                trains_requirements,
+                work_schedules: None,
+            }
+            .fetch(&core_client)
+            .await?
+            .conflicts
+            .len();
+        }
+    }
+
+    let scenario_count = scenario_count as u64;
+    let mut trains: Vec<_> = propagation_trains
+        .iter()
+        .enumerate()
+        .map(|(index, train)| TrainDelays {
+            train_id: train.train_id,
+            primary_delay: primary_sums[index] / scenario_count,
+            secondary_delay: secondary_sums[index] / scenario_count,
+            arrival_delays: train
+                .checkpoints
+                .iter()
+                .zip(&arrival_sums[index])
+                .filter_map(|(checkpoint, sum)| match checkpoint.kind {
+                    CheckpointKind::Arrival { at } => Some(ScheduleItemDelay {
+                        at: at.to_owned(),
+                        delay: sum / scenario_count,
+                    }),
+                    CheckpointKind::Zone { .. } => None,
+                })
+                .collect(),
+        })
+        .collect();
+    trains.sort_by_key(|train| train.train_id);
+    let total_primary_delay = primary_sums.iter().sum::<u64>() / scenario_count;
+    let total_secondary_delay = secondary_sums.iter().sum::<u64>() / scenario_count;
+    let total_delay = total_primary_delay + total_secondary_delay;
+    Ok(Json(RobustnessReport {
+        trains,
+        total_primary_delay,
+        total_secondary_delay,
+        robustness_score: if total_delay > 0 {
+            total_primary_delay as f64 / total_delay as f64
+        } else {
+            1.
+        },
+        conflict_count: conflict_count as f64 / scenario_count as f64,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use editoast_models::DbConnectionPoolV2;
+    use editoast_schemas::train_schedule::TrainScheduleBase;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::core::mocking::MockingClient;
+    use crate::models::fixtures::create_fast_rolling_stock;
+    use crate::models::fixtures::create_small_infra;
+    use crate::models::fixtures::create_timetable;
+    use crate::views::test_app::TestAppBuilder;
+    use crate::views::train_schedule::TrainScheduleForm;
+
+    #[test]
+    fn exponential_draws_are_seeded() {
+        let distribution = DelayDistribution::Exponential { mean: 60_000 };
+        let draw = |seed| distribution.sample(&mut StdRng::seed_from_u64(seed));
+
+        assert_eq!(draw(42), draw(42));
+        assert_ne!(draw(42), draw(43));
+    }
+
+    /// Both trains run the same simulation: a zone "z1" then a zone "z2", with a 20 s margin
+    fn mocked_core() -> MockingClient {
+        let mut core = MockingClient::new();
+        core.stub("/v2/pathfinding/blocks")
+            .method(reqwest::Method::POST)
+            .response(StatusCode::OK)
+            .json(json!({
+                "blocks": [],
+                "routes": [],
+                "track_section_ranges": [],
+                "path_item_positions": [0, 1_000_000],
+                "length": 1_000_000,
+                "status": "success"
+            }))
+            .finish();
+        let report = json!({
+            "positions": [0, 1_000_000],
+            "times": [0, 100_000],
+            "speeds": [0., 0.],
+            "energy_consumption": 0.,
+            "path_item_times": [0, 100_000],
+        });
+        let mut final_output = report.clone();
+        final_output["signal_critical_positions"] = json!([]);
+        final_output["zone_updates"] = json!([]);
+        final_output["spacing_requirements"] = json!([
+            {"zone": "z1", "begin_time": 0, "end_time": 60_000},
+            {"zone": "z2", "begin_time": 50_000, "end_time": 120_000},
+        ]);
+        final_output["routing_requirements"] = json!([]);
+        core.stub("/v2/standalone_simulation")
+            .method(reqwest::Method::POST)
+            .response(StatusCode::OK)
+            .json(json!({
+                "status": "success",
+                "base": {
+                    "positions": [0, 1_000_000],
+                    "times": [0, 80_000],
+                    "speeds": [0., 0.],
+                    "energy_consumption": 0.,
+                    "path_item_times": [0, 80_000],
+                },
+                "provisional": report,
+                "final_output": final_output,
+                "mrsp": {"boundaries": [], "values": []},
+                "electrical_profiles": {"boundaries": [], "values": []},
+            }))
+            .finish();
+        core.stub("/v2/conflict_detection")
+            .method(reqwest::Method::POST)
+            .response(StatusCode::OK)
+            .json(json!({"conflicts": []}))
+            .finish();
+        core
+    }
+
+    #[rstest]
+    async fn delay_is_passed_on_to_the_following_train() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let infra = create_small_infra(&mut db_pool.get_ok()).await;
+        let rolling_stock =
+            create_fast_rolling_stock(&mut db_pool.get_ok(), "robustness_rolling_stock").await;
+        let timetable = create_timetable(&mut db_pool.get_ok()).await;
+        let mut train_ids = vec![];
+        for start_time in ["2025-01-01T08:00:00Z", "2025-01-01T08:02:00Z"] {
+            let train_schedule: TrainScheduleBase = serde_json::from_value(json!({
+                "train_name": "train",
+                "rolling_stock_name": rolling_stock.name,
+                "start_time": start_time,
+                "path": [
+                    {"id": "a", "track": "TA0", "offset": 0},
+                    {"id": "b", "track": "TA0", "offset": 1_000_000},
+                ],
+                "schedule": [{"at": "b", "arrival": "PT2M"}],
+                "constraint_distribution": "STANDARD",
+            }))
+            .unwrap();
+            let changeset: Changeset<TrainSchedule> = TrainScheduleForm {
+                timetable_id: Some(timetable.id),
+                train_schedule,
+            }
+            .into();
+            train_ids.push(changeset.create(&mut db_pool.get_ok()).await.unwrap().id);
+        }
+        let app = TestAppBuilder::new()
+            .db_pool(db_pool)
+            .core_client(mocked_core().into())
+            .build();
+
+        let request = app
+            .post(&format!(
+                "/timetable/{}/robustness?infra_id={}",
+                timetable.id, infra.id
+            ))
+            .json(&json!({
+                "primary_delays": [{
+                    "train_id": train_ids[0],
+                    "delay": {"distribution": "fixed", "duration": 300_000},
+                }],
+            }));
+        let report: RobustnessReport = app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        // The second train follows 60 s after the first one on "z1": it gets 240 s of the 300 s
+        // delay, then makes up 10 s by the end of its run, like the first train
+        assert_eq!(
+            report.trains,
+            vec![
+                TrainDelays {
+                    train_id: train_ids[0],
+                    primary_delay: 300_000,
+                    secondary_delay: 0,
+                    arrival_delays: vec![ScheduleItemDelay {
+                        at: "b".to_owned(),
+                        delay: 280_000,
+                    }],
+                },
+                TrainDelays {
+                    train_id: train_ids[1],
+                    primary_delay: 0,
+                    secondary_delay: 240_000,
+                    arrival_delays: vec![ScheduleItemDelay {
+                        at: "b".to_owned(),
+                        delay: 230_000,
+                    }],
+                },
+            ]
+        );
+        assert_eq!(report.total_secondary_delay, 240_000);
+        assert_eq!(report.robustness_score, 300. / 540.);
+        assert_eq!(report.conflict_count, 0.);
+    }
+}
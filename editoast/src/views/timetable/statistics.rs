@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::Extension;
+use editoast_authz::BuiltinRole;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::ElectricalProfileSetIdQueryParam;
+use super::InfraIdQueryParam;
+use super::TimetableError;
+use super::TimetableIdParam;
+use crate::core::simulation::ReportTrain;
+use crate::core::simulation::SimulationResponse;
+use crate::error::Result;
+use crate::infra_cache::InfraCache;
+use crate::infra_cache::ObjectCache;
+use crate::models::prelude::*;
+use crate::models::timetable::TimetableWithTrains;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::Infra;
+use crate::views::path::pathfinding::PathfindingFailure;
+use crate::views::path::pathfinding::PathfindingResult;
+use crate::views::train_schedule::train_simulation_batch;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+use crate::RetrieveBatch;
+
+crate::routes! {
+    "/statistics" => statistics,
+}
+
+editoast_common::schemas! {
+    TimetableStatistics,
+    RollingStockStatistics,
+    LineStatistics,
+    PunctualityStatistics,
+}
+
+/// Aggregated figures over the trains of a timetable
+///
+/// Apart from `train_count` and `failures`, only the trains whose simulation succeeded are
+/// taken into account.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+struct TimetableStatistics {
+    /// Number of trains in the timetable
+    train_count: u64,
+    /// Number of trains whose simulation succeeded
+    simulated_train_count: u64,
+    /// Distance travelled by the trains in km
+    train_km: f64,
+    /// Sum of the run times of the trains in ms
+    total_run_time: u64,
+    /// Mean run time of a train in ms
+    mean_run_time: u64,
+    /// Energy consumption of the trains of each rolling stock, ordered by name
+    rolling_stocks: Vec<RollingStockStatistics>,
+    /// Average speed of the trains on each line, ordered by line code
+    lines: Vec<LineStatistics>,
+    punctuality: PunctualityStatistics,
+    /// Number of failed simulations by failure kind
+    failures: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+struct RollingStockStatistics {
+    rolling_stock_name: String,
+    train_count: u64,
+    /// Distance travelled by the trains of this rolling stock in km
+    train_km: f64,
+    /// Total energy consumption of the trains of this rolling stock in kWh
+    energy_consumption: f64,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+struct LineStatistics {
+    line_code: i32,
+    /// Distance travelled on the line in km
+    train_km: f64,
+    /// Time spent running on the line in ms
+    run_time: u64,
+    /// Average speed on the line in km/h
+    average_speed: f64,
+}
+
+/// Compares the requested arrival times of the schedules with the simulated ones
+///
+/// A margin is the requested arrival time minus the simulated one: it is negative when the
+/// train arrives late.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+struct PunctualityStatistics {
+    /// Number of schedule points with a requested arrival time
+    scheduled_point_count: u64,
+    /// Number of schedule points reached after their requested arrival time
+    late_point_count: u64,
+    /// Mean margin in ms
+    mean_margin: Option<i64>,
+    /// Smallest margin in ms
+    min_margin: Option<i64>,
+}
+
+/// Name a simulation failure, consistently with the simulation summary statuses
+fn failure_kind(simulation: &SimulationResponse, pathfinding: &PathfindingResult) -> &'static str {
+    match (simulation, pathfinding) {
+        (SimulationResponse::SimulationFailed { .. }, _) => "simulation_failed",
+        (_, PathfindingResult::Failure(PathfindingFailure::PathfindingInputError(_))) => {
+            "pathfinding_input_error"
+        }
+        (_, PathfindingResult::Failure(PathfindingFailure::PathfindingNotFound(_))) => {
+            "pathfinding_not_found"
+        }
+        _ => "pathfinding_failure",
+    }
+}
+
+/// Interpolate the time in ms at which the train reaches a path offset
+fn time_at(report: &ReportTrain, offset: u64) -> f64 {
+    let index = report
+        .positions
+        .partition_point(|&position| position < offset);
+    if index == 0 {
+        return report.times.first().copied().unwrap_or_default() as f64;
+    }
+    if index == report.positions.len() {
+        return report.times.last().copied().unwrap_or_default() as f64;
+    }
+    let (start, end) = (report.positions[index - 1], report.positions[index]);
+    let (start_time, end_time) = (report.times[index - 1], report.times[index]);
+    let ratio = (offset - start) as f64 / (end - start) as f64;
+    start_time as f64 + ratio * (end_time as f64 - start_time as f64)
+}
+
+impl TimetableStatistics {
+    fn compute(
+        trains: &[TrainSchedule],
+        simulations: &[(SimulationResponse, PathfindingResult)],
+        line_code: impl Fn(&str) -> Option<i32>,
+    ) -> Self {
+        let mut statistics = TimetableStatistics {
+            train_count: trains.len() as u64,
+            ..Default::default()
+        };
+        let mut rolling_stocks: BTreeMap<&str, RollingStockStatistics> = BTreeMap::new();
+        // Distance in mm and time in ms spent on each line
+        let mut lines: BTreeMap<i32, (u64, f64)> = BTreeMap::new();
+        let mut margins = vec![];
+
+        for (train, (simulation, pathfinding)) in trains.iter().zip(simulations) {
+            let report = match simulation {
+                SimulationResponse::Success { final_output, .. } => &final_output.report_train,
+                _ => {
+                    *statistics
+                        .failures
+                        .entry(failure_kind(simulation, pathfinding).to_owned())
+                        .or_default() += 1;
+                    continue;
+                }
+            };
+            let length = report.positions.last().copied().unwrap_or_default();
+            let run_time = report.times.last().copied().unwrap_or_default();
+            statistics.simulated_train_count += 1;
+            statistics.train_km += length as f64 / 1_000_000.;
+            statistics.total_run_time += run_time;
+
+            let rolling_stock = rolling_stocks
+                .entry(&train.rolling_stock_name)
+                .or_insert_with(|| RollingStockStatistics {
+                    rolling_stock_name: train.rolling_stock_name.clone(),
+                    ..Default::default()
+                });
+            rolling_stock.train_count += 1;
+            rolling_stock.train_km += length as f64 / 1_000_000.;
+            // Core reports the energy consumption in J
+            rolling_stock.energy_consumption += report.energy_consumption / 3_600_000.;
+
+            if let PathfindingResult::Success(path) = pathfinding {
+                let mut offset = 0;
+                for range in &path.track_section_ranges {
+                    let range_length = range.end - range.begin;
+                    let (start, end) = (offset, offset + range_length);
+                    offset = end;
+                    let Some(line_code) = line_code(range.track_section.as_str()) else {
+                        continue;
+                    };
+                    let (distance, time) = lines.entry(line_code).or_default();
+                    *distance += range_length;
+                    *time += time_at(report, end) - time_at(report, start);
+                }
+            }
+
+            for schedule_item in &train.schedule {
+                let Some(arrival) = &schedule_item.arrival else {
+                    continue;
+                };
+                let simulated_arrival = train
+                    .path
+                    .iter()
+                    .position(|path_item| path_item.id == schedule_item.at)
+                    .and_then(|index| report.path_item_times.get(index));
+                if let Some(&simulated_arrival) = simulated_arrival {
+                    margins.push(arrival.num_milliseconds() - simulated_arrival as i64);
+                }
+            }
+        }
+
+        if statistics.simulated_train_count > 0 {
+            statistics.mean_run_time = statistics.total_run_time / statistics.simulated_train_count;
+        }
+        statistics.rolling_stocks = rolling_stocks.into_values().collect();
+        statistics.lines = lines
+            .into_iter()
+            .map(|(line_code, (distance, time))| LineStatistics {
+                line_code,
+                train_km: distance as f64 / 1_000_000.,
+                run_time: time.round() as u64,
+                average_speed: if time > 0. {
+                    // mm/ms is m/s
+                    distance as f64 / time * 3.6
+                } else {
+                    0.
+                },
+            })
+            .collect();
+        statistics.punctuality = PunctualityStatistics {
+            scheduled_point_count: margins.len() as u64,
+            late_point_count: margins.iter().filter(|&&margin| margin < 0).count() as u64,
+            mean_margin: (!margins.is_empty())
+                .then(|| margins.iter().sum::<i64>() / margins.len() as i64),
+            min_margin: margins.iter().min().copied(),
+        };
+        statistics
+    }
+}
+
+/// Aggregate the simulations of the trains of a timetable
+///
+/// Computes the train-km, run times, energy consumption per rolling stock, average speed per
+/// line, punctuality margins and the number of failed simulations by failure kind.
+#[utoipa::path(
+    get, path = "",
+    tag = "timetable",
+    params(TimetableIdParam, InfraIdQueryParam, ElectricalProfileSetIdQueryParam),
+    responses(
+        (status = 200, description = "The statistics of the timetable", body = TimetableStatistics),
+        (status = 404, description = "Timetable or infra not found"),
+    ),
+)]
+async fn statistics(
+    State(AppState {
+        db_pool,
+        valkey: valkey_client,
+        simulation_store,
+        core_client,
+        infra_caches,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Path(TimetableIdParam { id: timetable_id }): Path<TimetableIdParam>,
+    Query(InfraIdQueryParam { infra_id }): Query<InfraIdQueryParam>,
+    Query(ElectricalProfileSetIdQueryParam {
+        electrical_profile_set_id,
+    }): Query<ElectricalProfileSetIdQueryParam>,
+) -> Result<Json<TimetableStatistics>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead, BuiltinRole::TimetableRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
+        TimetableError::NotFound { timetable_id }
+    })
+    .await?;
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+    let (trains, _): (Vec<_>, _) = TrainSchedule::retrieve_batch(conn, timetable.train_ids).await?;
+
+    let simulations = train_simulation_batch(
+        conn,
+        valkey_client,
+        simulation_store.as_ref(),
+        core_client,
+        &trains,
+        &infra,
+        electrical_profile_set_id,
+    )
+    .await?;
+
+    let infra_cache = InfraCache::get_or_load(conn, &infra_caches, &infra).await?;
+    let line_code = |track: &str| {
+        infra_cache
+            .track_sections()
+            .get(track)
+            .and_then(|track| ObjectCache::unwrap_track_section(track).line_code)
+    };
+    Ok(Json(TimetableStatistics::compute(
+        &trains,
+        &simulations,
+        line_code,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::core::pathfinding::PathfindingResultSuccess;
+    use crate::core::simulation::CompleteReportTrain;
+    use crate::models::fixtures::create_small_infra;
+    use crate::models::fixtures::create_timetable;
+    use crate::views::test_app::TestAppBuilder;
+
+    fn train(rolling_stock_name: &str) -> TrainSchedule {
+        TrainSchedule {
+            rolling_stock_name: rolling_stock_name.to_owned(),
+            path: serde_json::from_value(json!([
+                {"id": "a", "track": "A", "offset": 0},
+                {"id": "b", "track": "B", "offset": 1000000},
+            ]))
+            .unwrap(),
+            schedule: serde_json::from_value(json!([{"at": "b", "arrival": "PT2M"}])).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn success() -> (SimulationResponse, PathfindingResult) {
+        let report_train = ReportTrain {
+            positions: vec![0, 1_000_000, 2_000_000],
+            times: vec![0, 40_000, 140_000],
+            // 10 kWh
+            energy_consumption: 36_000_000.,
+            path_item_times: vec![0, 140_000],
+            ..Default::default()
+        };
+        let simulation = SimulationResponse::Success {
+            base: Default::default(),
+            provisional: Default::default(),
+            final_output: CompleteReportTrain {
+                report_train,
+                ..Default::default()
+            },
+            mrsp: Default::default(),
+            electrical_profiles: Default::default(),
+        };
+        let path = PathfindingResult::Success(PathfindingResultSuccess {
+            blocks: vec![],
+            routes: vec![],
+            track_section_ranges: serde_json::from_value(json!([
+                {"track_section": "A", "begin": 0, "end": 1000000, "direction": "START_TO_STOP"},
+                {"track_section": "B", "begin": 0, "end": 1000000, "direction": "START_TO_STOP"},
+            ]))
+            .unwrap(),
+            length: 2_000_000,
+            path_item_positions: vec![0, 2_000_000],
+        });
+        (simulation, path)
+    }
+
+    #[test]
+    fn compute_statistics() {
+        let trains = [train("R2D2"), train("R2D2"), train("C3PO")];
+        let failure = (
+            SimulationResponse::PathfindingFailed {
+                pathfinding_failed: PathfindingFailure::PathfindingInputError(
+                    crate::core::pathfinding::PathfindingInputError::NotEnoughPathItems,
+                ),
+            },
+            PathfindingResult::Failure(PathfindingFailure::PathfindingInputError(
+                crate::core::pathfinding::PathfindingInputError::NotEnoughPathItems,
+            )),
+        );
+        let simulations = [success(), success(), failure];
+
+        let statistics = TimetableStatistics::compute(&trains, &simulations, |track| match track {
+            "A" => Some(1),
+            "B" => Some(2),
+            _ => None,
+        });
+
+        assert_eq!(statistics.train_count, 3);
+        assert_eq!(statistics.simulated_train_count, 2);
+        assert_eq!(statistics.train_km, 4.);
+        assert_eq!(statistics.total_run_time, 280_000);
+        assert_eq!(statistics.mean_run_time, 140_000);
+        assert_eq!(
+            statistics.rolling_stocks,
+            vec![RollingStockStatistics {
+                rolling_stock_name: "R2D2".to_owned(),
+                train_count: 2,
+                train_km: 4.,
+                energy_consumption: 20.,
+            }]
+        );
+        assert_eq!(statistics.lines.len(), 2);
+        assert_eq!(statistics.lines[0].run_time, 80_000);
+        assert_eq!(statistics.lines[0].average_speed, 90.);
+        assert_eq!(statistics.lines[1].average_speed, 36.);
+        assert_eq!(
+            statistics.punctuality,
+            PunctualityStatistics {
+                scheduled_point_count: 2,
+                late_point_count: 2,
+                mean_margin: Some(-20_000),
+                min_margin: Some(-20_000),
+            }
+        );
+        assert_eq!(
+            statistics.failures,
+            BTreeMap::from([("pathfinding_input_error".to_owned(), 1)])
+        );
+    }
+
+    #[rstest]
+    async fn statistics_of_an_empty_timetable() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+
+        let request = app.get(&format!(
+            "/timetable/{}/statistics?infra_id={}",
+            timetable.id, infra.id
+        ));
+        let statistics: TimetableStatistics =
+            app.fetch(request).assert_status(StatusCode::OK).json_into();
+
+        assert_eq!(statistics, TimetableStatistics::default());
+    }
+}
@@ -1,3 +1,5 @@
+pub(crate) mod batch;
+mod booking;
 mod failure_handler;
 pub(crate) mod request;
 
@@ -8,9 +10,11 @@ use axum::extract::State;
 use axum::Extension;
 use chrono::DateTime;
 use chrono::Duration;
+use chrono::NaiveDate;
 use chrono::Utc;
 use editoast_authz::BuiltinRole;
 use editoast_derive::EditoastError;
+use editoast_models::DbConnection;
 use editoast_models::DbConnectionPoolV2;
 use editoast_schemas::primitives::PositiveDuration;
 use editoast_schemas::train_schedule::MarginValue;
@@ -21,9 +25,12 @@ use failure_handler::SimulationFailureHandler;
 use opentelemetry::trace::TraceContextExt;
 use opentelemetry::trace::TraceId;
 use request::convert_steps;
+use request::RankingCriterion;
 use request::Request;
 use serde::Deserialize;
 use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::sync::Arc;
 use thiserror::Error;
@@ -46,22 +53,39 @@ use crate::models::prelude::*;
 use crate::models::stdcm_log::StdcmLog;
 use crate::models::timetable::Timetable;
 use crate::models::train_schedule::TrainSchedule;
+use crate::models::work_schedules::WorkSchedule;
 use crate::models::Infra;
 use crate::models::RollingStockModel;
+use crate::simulation_store::DisabledStore;
+use crate::simulation_store::SimulationResultStore;
 use crate::views::path::pathfinding::PathfindingResult;
+use crate::views::stdcm_search_environment::check_timetable_access;
+use crate::views::stdcm_search_environment::retrieve_selected_search_environment;
+use crate::views::stdcm_search_environment::StdcmSearchEnvironmentError;
 use crate::views::train_schedule::consist_train_simulation_batch;
 use crate::views::train_schedule::train_simulation_batch;
+use crate::views::Authentication;
 use crate::views::AuthenticationExt;
 use crate::views::AuthorizationError;
 use crate::AppState;
 use crate::ValkeyClient;
 
 editoast_common::schemas! {
+    StdcmSolution,
+    batch::schemas(),
+    booking::schemas(),
     request::schemas(),
 }
 
+/// The minimum time between the departures of two solutions, in ms
+const ALTERNATIVE_DEPARTURE_GAP: u64 = 5 * 60 * 1000;
+
 crate::routes! {
-    "/stdcm" => stdcm,
+    "/stdcm" => {
+        stdcm,
+        &batch,
+        &booking,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
@@ -74,6 +98,14 @@ enum StdcmResponse {
         simulation: SimulationResponse,
         path: PathfindingResultSuccess,
         departure_time: DateTime<Utc>,
+        /// Time from the departure to the arrival, in ms
+        run_time: u64,
+        /// Engineering allowance added to the run time to avoid conflicts, in ms
+        allowance: u64,
+        /// The criteria the solutions are ranked with
+        ranking: Vec<RankingCriterion>,
+        /// The other solutions, ranked after this one
+        alternatives: Vec<StdcmSolution>,
     },
     Conflicts {
         pathfinding_result: PathfindingResult,
@@ -84,6 +116,120 @@ enum StdcmResponse {
     },
 }
 
+/// A path found by STDCM, departing at a given time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+struct StdcmSolution {
+    simulation: SimulationResponse,
+    path: PathfindingResultSuccess,
+    departure_time: DateTime<Utc>,
+    /// Time from the departure to the arrival, in ms
+    run_time: u64,
+    /// Engineering allowance added to the run time to avoid conflicts, in ms
+    allowance: u64,
+}
+
+impl StdcmSolution {
+    fn new(
+        simulation: SimulationResponse,
+        path: PathfindingResultSuccess,
+        departure_time: DateTime<Utc>,
+    ) -> Self {
+        let (run_time, allowance) = match &simulation {
+            SimulationResponse::Success {
+                provisional,
+                final_output,
+                ..
+            } => {
+                let run_time = final_output.report_train.times.last().copied();
+                let provisional_run_time = provisional.times.last().copied();
+                (
+                    run_time.unwrap_or_default(),
+                    run_time
+                        .zip(provisional_run_time)
+                        .map_or(0, |(run_time, provisional)| {
+                            run_time.saturating_sub(provisional)
+                        }),
+                )
+            }
+            _ => (0, 0),
+        };
+        Self {
+            simulation,
+            path,
+            departure_time,
+            run_time,
+            allowance,
+        }
+    }
+
+    fn into_success(
+        self,
+        ranking: Vec<RankingCriterion>,
+        alternatives: Vec<Self>,
+    ) -> StdcmResponse {
+        StdcmResponse::Success {
+            simulation: self.simulation,
+            path: self.path,
+            departure_time: self.departure_time,
+            run_time: self.run_time,
+            allowance: self.allowance,
+            ranking,
+            alternatives,
+        }
+    }
+}
+
+impl RankingCriterion {
+    fn compare(&self, a: &StdcmSolution, b: &StdcmSolution) -> Ordering {
+        match self {
+            RankingCriterion::EarliestDeparture => a.departure_time.cmp(&b.departure_time),
+            RankingCriterion::ShortestRunTime => a.run_time.cmp(&b.run_time),
+            RankingCriterion::LeastAllowance => a.allowance.cmp(&b.allowance),
+        }
+    }
+}
+
+/// Sort the solutions with the given criteria, each one breaking the ties of the previous ones
+fn rank_solutions(solutions: &mut [StdcmSolution], ranking: &[RankingCriterion]) {
+    solutions.sort_by(|a, b| {
+        ranking
+            .iter()
+            .fold(Ordering::Equal, |ordering, criterion| {
+                ordering.then_with(|| criterion.compare(a, b))
+            })
+            .then_with(|| a.departure_time.cmp(&b.departure_time))
+    });
+}
+
+/// The request looking for a solution departing some time after the previous one
+///
+/// Returns `None` if the departure window of the request is over.
+fn alternative_request(
+    request: &crate::core::stdcm::Request,
+    previous_departure: DateTime<Utc>,
+) -> Option<crate::core::stdcm::Request> {
+    let start_time = previous_departure + Duration::milliseconds(ALTERNATIVE_DEPARTURE_GAP as i64);
+    let shift = u64::try_from((start_time - request.start_time).num_milliseconds()).ok()?;
+    let maximum_departure_delay = request.maximum_departure_delay.checked_sub(shift)?;
+    // Work schedules are relative to the start time of the request
+    let work_schedules = request
+        .work_schedules
+        .iter()
+        .filter(|work_schedule| work_schedule.end_time > shift)
+        .map(|work_schedule| crate::core::stdcm::WorkSchedule {
+            start_time: work_schedule.start_time.saturating_sub(shift),
+            end_time: work_schedule.end_time - shift,
+            track_ranges: work_schedule.track_ranges.clone(),
+        })
+        .collect();
+    Some(crate::core::stdcm::Request {
+        start_time,
+        maximum_departure_delay,
+        work_schedules,
+        ..request.clone()
+    })
+}
+
 #[derive(Debug, Error, EditoastError, Serialize)]
 #[editoast_error(base_id = "stdcm_v2")]
 enum StdcmError {
@@ -107,6 +253,39 @@ struct InfraIdQueryParam {
     infra: i64,
 }
 
+/// Use the search environment selected by the request, if any
+///
+/// The search environment must search paths in the timetable and infra of the request. Without
+/// selection, the user must have access to the timetable, see [check_timetable_access].
+async fn use_selected_search_environment(
+    conn: &mut DbConnection,
+    auth: &Authentication,
+    stdcm_request: &mut Request,
+    timetable_id: i64,
+    infra_id: i64,
+) -> Result<()> {
+    let Some(search_env) = retrieve_selected_search_environment(
+        conn,
+        auth,
+        stdcm_request.search_environment_id,
+        stdcm_request.search_environment_name.clone(),
+    )
+    .await?
+    else {
+        return check_timetable_access(conn, auth, timetable_id).await;
+    };
+    if search_env.timetable_id != timetable_id || search_env.infra_id != infra_id {
+        return Err(StdcmSearchEnvironmentError::Mismatch {
+            name: search_env.name,
+            timetable_id,
+            infra_id,
+        }
+        .into());
+    }
+    stdcm_request.use_search_environment(&search_env);
+    Ok(())
+}
+
 /// This function computes a STDCM and returns the result.
 /// It first checks user authorization, then retrieves timetable, infrastructure,
 /// train schedules, and rolling stock data, and runs train simulations.
@@ -116,6 +295,10 @@ struct InfraIdQueryParam {
 /// If the simulation fails, the function uses a virtual train to detect conflicts
 /// with existing train schedules. It then returns both the conflict information
 /// and the pathfinding result from the virtual train's simulation.
+///
+/// When several solutions are requested, the next ones are searched departing at least 5 minutes
+/// after the previous one, within the departure window. They are then ranked with the requested
+/// criteria.
 #[utoipa::path(
     post, path = "",
     tag = "stdcm",
@@ -132,13 +315,14 @@ async fn stdcm(
         config,
         db_pool,
         valkey: valkey_client,
+        simulation_store,
         core_client,
         ..
     }): State<AppState>,
     Extension(auth): AuthenticationExt,
     Path(id): Path<i64>,
     Query(query): Query<InfraIdQueryParam>,
-    Json(stdcm_request): Json<Request>,
+    Json(mut stdcm_request): Json<Request>,
 ) -> Result<Json<StdcmResponse>> {
     let authorized = auth
         .check_roles([BuiltinRole::Stdcm].into())
@@ -161,6 +345,8 @@ async fn stdcm(
 
     let timetable_id = id;
     let infra_id = query.infra;
+    use_selected_search_environment(&mut conn, &auth, &mut stdcm_request, timetable_id, infra_id)
+        .await?;
 
     // 1.  Infra / Timetable / Trains / Simulation / Rolling Stock
 
@@ -169,169 +355,28 @@ async fn stdcm(
     })
     .await?;
 
-    let rolling_stock =
-        RollingStockModel::retrieve_or_fail(&mut conn, stdcm_request.rolling_stock_id, || {
-            StdcmError::RollingStockNotFound {
-                rolling_stock_id: stdcm_request.rolling_stock_id,
-            }
-        })
-        .await?
-        .into();
-
-    let physics_consist_parameters = PhysicsConsistParameters {
-        max_speed: stdcm_request.max_speed,
-        total_length: stdcm_request.total_length,
-        total_mass: stdcm_request.total_mass,
-        towed_rolling_stock: stdcm_request
-            .get_towed_rolling_stock(&mut conn)
-            .await?
-            .map(From::from),
-        traction_engine: rolling_stock,
-    };
+    let alternative_count = stdcm_request.alternative_count;
+    let ranking = stdcm_request.get_ranking();
 
-    // 2. Compute the earliest start time and maximum departure delay
-    let virtual_train_run = VirtualTrainRun::simulate(
+    // 2. to 5. Build the STDCM request sent to core
+    let search = match StdcmSearch::prepare(
+        &mut conn,
         db_pool.clone(),
-        valkey_client.clone(),
+        valkey_client,
+        simulation_store.as_ref(),
         core_client.clone(),
         &stdcm_request,
         &infra,
-        &physics_consist_parameters,
         timetable_id,
     )
-    .await?;
-
-    // Only the success variant of the simulation response contains the simulation run time.
-    let Some(simulation_run_time) = virtual_train_run.simulation.simulation_run_time() else {
-        return Ok(Json(StdcmResponse::PreprocessingSimulationError {
-            error: virtual_train_run.simulation,
-        }));
-    };
-
-    let earliest_departure_time = stdcm_request.get_earliest_departure_time(simulation_run_time);
-    let latest_simulation_end = stdcm_request.get_latest_simulation_end(simulation_run_time);
-
-    let timetable = Timetable::retrieve_or_fail(&mut conn, timetable_id, || {
-        StdcmError::TimetableNotFound { timetable_id }
-    })
-    .await?;
-
-    // Filter trains
-    // The goal is to filter out as many trains as possible whose schedules overlap
-    // with the LMR train being searched for.
-    // The diagram below shows an LMR train inserted into a timetable.
-
-    // '?': unscheduled arrival times.
-    // '|': scheduled arrival times.
-    // tA: earliest_departure_time
-    // tB: latest_simulation_end
-    //
-    //                           tA                     tB
-    //       LMR Train           |----------------------|
-    // Train 1            |--------------|
-    // Train 2      |------------|
-    //                                        |----------?   Train 3
-    // Train 4        |-------?
-    //                         Train 5  |---------?
-    //                                       |----------?   Train 6
-
-    // Step 1 (SQL Filter):
-    // Trains that depart after the latest arrival time of the LMR train are excluded.
-    // In this example, Train 3 and Train 6 are filtered out.
-
-    // It's not easy to write an SQL query to filter trains when the train departure time < latest_simulation_ended
-    // because there are two cases : when the train departure time > tA (Step 2) and the train departure time < tA (Step 3).
-
-    // Step 2 (Rust filter) :
-    // If the train departure time > LMR train departure (tA), the train is kept (e.g., train_5)
-    // Step 3 (Rust filter) :
-    // For trains departing before the LMR train departure (tA):
-
-    // If the train's arrival time is unscheduled (?), the train is kept (e.g., Train 4 and Train 5).
-    // If the train's arrival time is scheduled (|), the train is kept only if its arrival time is after the LMR train's earliest departure time.
-    // Train 1 is kept and train 2 is filtered out.
-
-    // Step 1
-    let mut train_schedules = timetable
-        .schedules_before_date(&mut conn, latest_simulation_end)
-        .await?;
-
-    train_schedules.retain(|train_schedule| {
-        // Step 2 and 3
-        train_schedule.start_time >= earliest_departure_time
-            || train_schedule
-                .schedule
-                .last()
-                .and_then(|last_schedule_item| {
-                    train_schedule.path.last().and_then(|last_path_item| {
-                        (last_schedule_item.at == last_path_item.id).then_some(last_schedule_item)
-                    })
-                })
-                .and_then(|last_schedule_item| {
-                    last_schedule_item.arrival.clone().map(|arrival| {
-                        train_schedule.start_time + *arrival > earliest_departure_time
-                    })
-                })
-                .unwrap_or(true)
-    });
-
-    // 3. Get scheduled train requirements
-    let simulations: Vec<_> = train_simulation_batch(
-        &mut conn,
-        valkey_client.clone(),
-        core_client.clone(),
-        &train_schedules,
-        &infra,
-        stdcm_request.electrical_profile_set_id,
-    )
     .await?
-    .into_iter()
-    .map(|(sim, _)| sim)
-    .collect();
-
-    let trains_requirements = build_train_requirements(
-        train_schedules.clone(),
-        simulations.clone(),
-        earliest_departure_time,
-        latest_simulation_end,
-    );
-
-    // 4. Retrieve work schedules
-    let work_schedules = stdcm_request.get_work_schedules(&mut conn).await?;
-
-    // 5. Build STDCM request
-    let stdcm_request = crate::core::stdcm::Request {
-        infra: infra.id,
-        expected_version: infra.version.clone(),
-        rolling_stock_loading_gauge: physics_consist_parameters.traction_engine.loading_gauge,
-        rolling_stock_supported_signaling_systems: physics_consist_parameters
-            .traction_engine
-            .supported_signaling_systems
-            .clone(),
-        physics_consist: physics_consist_parameters.into(),
-        temporary_speed_limits: stdcm_request
-            .get_temporary_speed_limits(&mut conn, simulation_run_time)
-            .await?,
-        comfort: stdcm_request.comfort,
-        path_items: stdcm_request
-            .get_stdcm_path_items(&mut conn, infra_id)
-            .await?,
-        start_time: earliest_departure_time,
-        trains_requirements,
-        maximum_departure_delay: stdcm_request.get_maximum_departure_delay(simulation_run_time),
-        maximum_run_time: stdcm_request.get_maximum_run_time(simulation_run_time),
-        speed_limit_tag: stdcm_request.speed_limit_tags,
-        time_gap_before: stdcm_request.time_gap_before,
-        time_gap_after: stdcm_request.time_gap_after,
-        margin: stdcm_request.margin,
-        time_step: Some(2000),
-        work_schedules: work_schedules
-            .iter()
-            .filter_map(|ws| {
-                ws.as_core_work_schedule(earliest_departure_time, latest_simulation_end)
-            })
-            .collect(),
+    {
+        Ok(search) => search,
+        Err(error) => {
+            return Ok(Json(StdcmResponse::PreprocessingSimulationError { error }));
+        }
     };
+    let stdcm_request = &search.core_request;
 
     let stdcm_response = stdcm_request.fetch(core_client.as_ref()).await?;
 
@@ -357,9 +402,14 @@ async fn stdcm(
             StdcmLog::log(
                 conn,
                 trace_id.map(|trace_id| trace_id.to_string()),
-                stdcm_request,
+                stdcm_request.clone(),
                 stdcm_response.clone(),
                 user_id,
+                search
+                    .virtual_train_run
+                    .train_schedule
+                    .rolling_stock_name
+                    .clone(),
             )
             .in_current_span(),
         )
@@ -372,15 +422,43 @@ async fn stdcm(
             simulation,
             path,
             departure_time,
-        } => Ok(Json(StdcmResponse::Success {
-            simulation,
-            path,
-            departure_time,
-        })),
+        } => {
+            // 8. Look for the next solutions, departing later
+            let mut solutions = vec![StdcmSolution::new(simulation, path, departure_time)];
+            while solutions.len() < alternative_count as usize {
+                let previous_departure = solutions.last().unwrap().departure_time;
+                let Some(request) = alternative_request(stdcm_request, previous_departure) else {
+                    break;
+                };
+                // A failure to find an extra solution does not discard the ones already found
+                match request.fetch(core_client.as_ref()).await {
+                    Ok(crate::core::stdcm::Response::Success {
+                        simulation,
+                        path,
+                        departure_time,
+                    }) => solutions.push(StdcmSolution::new(simulation, path, departure_time)),
+                    _ => break,
+                }
+            }
+
+            rank_solutions(&mut solutions, &ranking);
+            let mut solutions = solutions.into_iter();
+            let best = solutions.next().unwrap();
+            Ok(Json(best.into_success(ranking, solutions.collect())))
+        }
         crate::core::stdcm::Response::PreprocessingSimulationError { error } => {
             Ok(Json(StdcmResponse::PreprocessingSimulationError { error }))
         }
         crate::core::stdcm::Response::PathNotFound => {
+            let StdcmSearch {
+                virtual_train_run,
+                train_schedules,
+                simulations,
+                work_schedules,
+                earliest_departure_time,
+                latest_simulation_end,
+                ..
+            } = search;
             let simulation_failure_handler = SimulationFailureHandler {
                 core_client,
                 infra_id,
@@ -398,6 +476,262 @@ async fn stdcm(
     }
 }
 
+/// An STDCM request ready to be sent to core
+///
+/// It keeps the trains it was built from, to look for the conflicts of the searched train if no
+/// path is found.
+struct StdcmSearch {
+    core_request: crate::core::stdcm::Request,
+    virtual_train_run: VirtualTrainRun,
+    train_schedules: Vec<TrainSchedule>,
+    simulations: Vec<SimulationResponse>,
+    work_schedules: Vec<WorkSchedule>,
+    earliest_departure_time: DateTime<Utc>,
+    latest_simulation_end: DateTime<Utc>,
+}
+
+impl StdcmSearch {
+    /// Build the core request searching a path for `stdcm_request` among the trains of a timetable
+    ///
+    /// Returns the simulation of the searched train instead if it fails.
+    #[allow(clippy::too_many_arguments)]
+    async fn prepare(
+        conn: &mut DbConnection,
+        db_pool: Arc<DbConnectionPoolV2>,
+        valkey_client: Arc<ValkeyClient>,
+        simulation_store: &dyn SimulationResultStore,
+        core_client: Arc<CoreClient>,
+        stdcm_request: &Request,
+        infra: &Infra,
+        timetable_id: i64,
+    ) -> Result<std::result::Result<Self, SimulationResponse>> {
+        let rolling_stock =
+            RollingStockModel::retrieve_or_fail(conn, stdcm_request.rolling_stock_id, || {
+                StdcmError::RollingStockNotFound {
+                    rolling_stock_id: stdcm_request.rolling_stock_id,
+                }
+            })
+            .await?
+            .into();
+
+        let physics_consist_parameters = PhysicsConsistParameters {
+            max_speed: stdcm_request.max_speed,
+            total_length: stdcm_request.total_length,
+            total_mass: stdcm_request.total_mass,
+            towed_rolling_stock: stdcm_request
+                .get_towed_rolling_stock(conn)
+                .await?
+                .map(From::from),
+            traction_engine: rolling_stock,
+        };
+
+        // 2. Compute the earliest start time and maximum departure delay
+        let virtual_train_run = VirtualTrainRun::simulate(
+            db_pool,
+            valkey_client.clone(),
+            core_client.clone(),
+            stdcm_request,
+            infra,
+            &physics_consist_parameters,
+            timetable_id,
+        )
+        .await?;
+
+        // Only the success variant of the simulation response contains the simulation run time.
+        let Some(simulation_run_time) = virtual_train_run.simulation.simulation_run_time() else {
+            return Ok(Err(virtual_train_run.simulation));
+        };
+
+        let earliest_departure_time =
+            stdcm_request.get_earliest_departure_time(simulation_run_time);
+        let latest_simulation_end = stdcm_request.get_latest_simulation_end(simulation_run_time);
+
+        let timetable = Timetable::retrieve_or_fail(conn, timetable_id, || {
+            StdcmError::TimetableNotFound { timetable_id }
+        })
+        .await?;
+
+        // Filter trains
+        // The goal is to filter out as many trains as possible whose schedules overlap
+        // with the LMR train being searched for.
+        // The diagram below shows an LMR train inserted into a timetable.
+
+        // '?': unscheduled arrival times.
+        // '|': scheduled arrival times.
+        // tA: earliest_departure_time
+        // tB: latest_simulation_end
+        //
+        //                           tA                     tB
+        //       LMR Train           |----------------------|
+        // Train 1            |--------------|
+        // Train 2      |------------|
+        //                                        |----------?   Train 3
+        // Train 4        |-------?
+        //                         Train 5  |---------?
+        //                                       |----------?   Train 6
+
+        // Step 1 (SQL Filter):
+        // Trains that depart after the latest arrival time of the LMR train are excluded.
+        // In this example, Train 3 and Train 6 are filtered out.
+
+        // It's not easy to write an SQL query to filter trains when the train departure time < latest_simulation_ended
+        // because there are two cases : when the train departure time > tA (Step 2) and the train departure time < tA (Step 3).
+
+        // Step 2 (Rust filter) :
+        // If the train departure time > LMR train departure (tA), the train is kept (e.g., train_5)
+        // Step 3 (Rust filter) :
+        // For trains departing before the LMR train departure (tA):
+
+        // If the train's arrival time is unscheduled (?), the train is kept (e.g., Train 4 and Train 5).
+        // If the train's arrival time is scheduled (|), the train is kept only if its arrival time is after the LMR train's earliest departure time.
+        // Train 1 is kept and train 2 is filtered out.
+
+        let train_schedules = filter_train_schedules(
+            timetable
+                .schedules_before_date(conn, latest_simulation_end)
+                .await?,
+            earliest_departure_time,
+            latest_simulation_end,
+        );
+
+        // 3. Get scheduled train requirements
+        let simulations: Vec<_> = train_simulation_batch(
+            conn,
+            valkey_client.clone(),
+            simulation_store,
+            core_client.clone(),
+            &train_schedules,
+            infra,
+            stdcm_request.electrical_profile_set_id,
+        )
+        .await?
+        .into_iter()
+        .map(|(sim, _)| sim)
+        .collect();
+
+        let trains_requirements = build_train_requirements(
+            train_schedules.clone(),
+            simulations.clone(),
+            earliest_departure_time,
+            latest_simulation_end,
+        );
+
+        // 4. Retrieve work schedules
+        let work_schedules = stdcm_request.get_work_schedules(conn).await?;
+
+        // 5. Build STDCM request
+        let core_request = crate::core::stdcm::Request {
+            infra: infra.id,
+            expected_version: infra.version.clone(),
+            rolling_stock_loading_gauge: physics_consist_parameters.traction_engine.loading_gauge,
+            rolling_stock_supported_signaling_systems: physics_consist_parameters
+                .traction_engine
+                .supported_signaling_systems
+                .clone(),
+            physics_consist: physics_consist_parameters.into(),
+            temporary_speed_limits: stdcm_request
+                .get_temporary_speed_limits(conn, simulation_run_time)
+                .await?,
+            comfort: stdcm_request.comfort,
+            path_items: stdcm_request.get_stdcm_path_items(conn, infra.id).await?,
+            start_time: earliest_departure_time,
+            trains_requirements,
+            maximum_departure_delay: stdcm_request.get_maximum_departure_delay(simulation_run_time),
+            maximum_run_time: stdcm_request.get_maximum_run_time(simulation_run_time),
+            speed_limit_tag: stdcm_request.speed_limit_tags.clone(),
+            time_gap_before: stdcm_request.time_gap_before,
+            time_gap_after: stdcm_request.time_gap_after,
+            margin: stdcm_request.margin,
+            time_step: Some(2000),
+            work_schedules: work_schedules
+                .iter()
+                .filter_map(|ws| {
+                    ws.as_core_work_schedule(earliest_departure_time, latest_simulation_end)
+                })
+                .collect(),
+        };
+
+        Ok(Ok(Self {
+            core_request,
+            virtual_train_run,
+            train_schedules,
+            simulations,
+            work_schedules,
+            earliest_departure_time,
+            latest_simulation_end,
+        }))
+    }
+}
+
+/// Keep the trains which may run between the earliest departure time and the latest simulation
+/// end of the LMR train, following the steps described in [StdcmSearch::prepare]
+fn filter_train_schedules(
+    trains: Vec<TrainSchedule>,
+    earliest_departure_time: DateTime<Utc>,
+    latest_simulation_end: DateTime<Utc>,
+) -> Vec<TrainSchedule> {
+    // Step 1
+    // Trains with an operating calendar are moved to each day from the day before the LMR train
+    // departure, for the ones running overnight, to the day of its latest simulation end. The
+    // ones which do not run these days are excluded.
+    let departure_day = earliest_departure_time.date_naive();
+    let mut train_schedules = calendar_trains_between(
+        trains,
+        departure_day - Duration::days(1),
+        latest_simulation_end.date_naive(),
+    );
+    train_schedules.retain(|train_schedule| train_schedule.start_time <= latest_simulation_end);
+
+    train_schedules.retain(|train_schedule| {
+        // Step 2 and 3
+        train_schedule.start_time >= earliest_departure_time
+            || train_schedule
+                .schedule
+                .last()
+                .and_then(|last_schedule_item| {
+                    train_schedule.path.last().and_then(|last_path_item| {
+                        (last_schedule_item.at == last_path_item.id).then_some(last_schedule_item)
+                    })
+                })
+                .and_then(|last_schedule_item| {
+                    last_schedule_item.arrival.clone().map(|arrival| {
+                        train_schedule.start_time + *arrival > earliest_departure_time
+                    })
+                })
+                .unwrap_or(true)
+    });
+    train_schedules
+}
+
+/// Move the trains with an operating calendar to each day they run from the first to the last
+/// given day
+///
+/// The ones which do not run these days are excluded, the trains without calendar are kept as is.
+fn calendar_trains_between(
+    trains: Vec<TrainSchedule>,
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+) -> Vec<TrainSchedule> {
+    trains
+        .into_iter()
+        .flat_map(|train| match train.calendar {
+            Some(_) => first_day
+                .iter_days()
+                .take_while(|day| *day <= last_day)
+                .filter_map(|day| train.on_day(day))
+                .collect(),
+            None => vec![train],
+        })
+        .collect()
+}
+
+/// Move the trains with an operating calendar to the given day
+///
+/// The ones which do not run that day are excluded, the trains without calendar are kept as is.
+fn calendar_trains_on_day(trains: Vec<TrainSchedule>, day: NaiveDate) -> Vec<TrainSchedule> {
+    calendar_trains_between(trains, day, day)
+}
+
 /// Build the list of scheduled train requirements, only including requirements
 /// that overlap with the possible simulation times.
 fn build_train_requirements(
@@ -456,18 +790,46 @@ fn build_train_requirements(
                 )
             })
             .collect();
-        trains_requirements.insert(
-            train.id,
-            TrainRequirements {
-                start_time,
-                spacing_requirements,
-                routing_requirements,
-            },
-        );
+        let requirements = TrainRequirements {
+            start_time,
+            spacing_requirements,
+            routing_requirements,
+        };
+        // The instances of a train with an operating calendar running on several days share its id
+        match trains_requirements.entry(train.id) {
+            Entry::Occupied(mut entry) => merge_train_requirements(entry.get_mut(), requirements),
+            Entry::Vacant(entry) => {
+                entry.insert(requirements);
+            }
+        }
     }
     trains_requirements
 }
 
+/// Merge the requirements of two instances of a train, relative to the earliest start time
+fn merge_train_requirements(requirements: &mut TrainRequirements, mut other: TrainRequirements) {
+    if other.start_time < requirements.start_time {
+        std::mem::swap(requirements, &mut other);
+    }
+    let offset = (other.start_time - requirements.start_time).num_milliseconds() as u64;
+    requirements
+        .spacing_requirements
+        .extend(other.spacing_requirements.into_iter().map(|mut req| {
+            req.begin_time += offset;
+            req.end_time += offset;
+            req
+        }));
+    requirements
+        .routing_requirements
+        .extend(other.routing_requirements.into_iter().map(|mut req| {
+            req.begin_time += offset;
+            for zone in req.zones.iter_mut() {
+                zone.end_time += offset;
+            }
+            req
+        }));
+}
+
 /// Returns true if the resource use is at least partially in the simulation time range
 fn is_resource_in_range(
     earliest_sim_time: DateTime<Utc>,
@@ -527,12 +889,16 @@ impl VirtualTrainRun {
             speed_limit_tag: stdcm_request.speed_limit_tags.clone(),
             power_restrictions: vec![],
             options: Default::default(),
+            train_service_id: None,
+            calendar: None,
         };
 
         // Compute simulation of a train schedule
+        // The consist of the virtual train is specific to this request, not worth persisting
         let (simulation, pathfinding) = consist_train_simulation_batch(
             &mut db_pool.get().await?,
             valkey_client,
+            &DisabledStore,
             core_client,
             infra,
             &[train_schedule.clone()],
@@ -569,9 +935,16 @@ fn build_single_margin(margin: Option<MarginValue>) -> Margins {
 mod tests {
     use axum::http::StatusCode;
     use chrono::DateTime;
+    use chrono::TimeZone;
+    use chrono::Timelike;
+    use editoast_authz::authorizer::StorageDriver;
+    use editoast_authz::authorizer::UserInfo;
     use editoast_common::units;
+    use editoast_models::DbConnection;
     use editoast_models::DbConnectionPoolV2;
+    use editoast_schemas::rolling_stock::LoadingGaugeType;
     use editoast_schemas::rolling_stock::RollingResistance;
+    use editoast_schemas::rolling_stock::RollingStockSupportedSignalingSystems;
     use editoast_schemas::train_schedule::Comfort;
     use editoast_schemas::train_schedule::OperationalPointIdentifier;
     use editoast_schemas::train_schedule::OperationalPointReference;
@@ -579,6 +952,7 @@ mod tests {
     use pretty_assertions::assert_eq;
     use rstest::rstest;
     use serde_json::json;
+    use std::collections::HashSet;
     use std::str::FromStr;
     use uuid::Uuid;
 
@@ -590,12 +964,15 @@ mod tests {
     use crate::core::simulation::PhysicsConsist;
     use crate::core::simulation::ReportTrain;
     use crate::core::simulation::SpeedLimitProperties;
+    use crate::models::auth::PgAuthDriver;
     use crate::models::fixtures::create_fast_rolling_stock;
     use crate::models::fixtures::create_simple_rolling_stock;
     use crate::models::fixtures::create_small_infra;
     use crate::models::fixtures::create_timetable;
     use crate::models::fixtures::create_towed_rolling_stock;
+    use crate::models::stdcm_search_environment::StdcmSearchEnvironment;
     use crate::views::test_app::TestAppBuilder;
+    use crate::views::test_app::TestRequestExt;
     use crate::views::timetable::stdcm::request::PathfindingItem;
     use crate::views::timetable::stdcm::request::StepTimingData;
     use crate::views::timetable::stdcm::PathfindingResult;
@@ -655,6 +1032,10 @@ mod tests {
             total_length: None,
             max_speed: None,
             loading_gauge_type: None,
+            alternative_count: 1,
+            ranking: vec![],
+            search_environment_id: None,
+            search_environment_name: None,
         }
     }
 
@@ -895,6 +1276,55 @@ mod tests {
         );
     }
 
+    #[rstest]
+    async fn stdcm_in_restricted_search_environment_timetable_is_forbidden() {
+        let db_pool = DbConnectionPoolV2::for_tests();
+        let user = UserInfo {
+            identity: "ungranted_user".to_owned(),
+            name: "ungranted_user".to_owned(),
+        };
+        let app = TestAppBuilder::new()
+            .db_pool(db_pool.clone())
+            .core_client(core_mocking_client().into())
+            .enable_authorization(true)
+            .user(user.clone())
+            .roles(HashSet::from([BuiltinRole::Stdcm]))
+            .build();
+        let small_infra = create_small_infra(&mut db_pool.get_ok()).await;
+        let timetable = create_timetable(&mut db_pool.get_ok()).await;
+        let rolling_stock =
+            create_fast_rolling_stock(&mut db_pool.get_ok(), &Uuid::new_v4().to_string()).await;
+
+        // The timetable is only searched by a search environment granted to another user
+        let granted_user = PgAuthDriver::<BuiltinRole>::new(db_pool.clone().into())
+            .ensure_user(&UserInfo {
+                identity: "granted_user".to_owned(),
+                name: "granted_user".to_owned(),
+            })
+            .await
+            .expect("Failed to create user");
+        let search_env = StdcmSearchEnvironment::changeset()
+            .name(Uuid::new_v4().to_string())
+            .infra_id(small_infra.id)
+            .timetable_id(timetable.id)
+            .search_window_begin(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+            .search_window_end(Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap())
+            .create(&mut db_pool.get_ok())
+            .await
+            .expect("Failed to create search environment");
+        search_env
+            .grant(&mut db_pool.get_ok(), &[granted_user])
+            .await
+            .expect("Failed to grant search environment");
+
+        let request = app
+            .post(format!("/timetable/{}/stdcm?infra={}", timetable.id, small_infra.id).as_str())
+            .by_user(user)
+            .json(&stdcm_payload(rolling_stock.id));
+
+        app.fetch(request).assert_status(StatusCode::FORBIDDEN);
+    }
+
     fn conflict_data() -> Conflict {
         Conflict {
             train_ids: vec![0, 1],
@@ -947,12 +1377,97 @@ mod tests {
                     simulation: simulation_response(),
                     path,
                     departure_time: DateTime::from_str("2024-01-02T00:00:00Z")
-                        .expect("Failed to parse datetime")
+                        .expect("Failed to parse datetime"),
+                    run_time: 0,
+                    allowance: 0,
+                    ranking: vec![RankingCriterion::EarliestDeparture],
+                    alternatives: vec![],
                 }
             );
         }
     }
 
+    fn solution(departure_minute: u32, run_time: u64, allowance: u64) -> StdcmSolution {
+        StdcmSolution {
+            simulation: simulation_response(),
+            path: pathfinding_result_success(),
+            departure_time: Utc
+                .with_ymd_and_hms(2024, 1, 2, 8, departure_minute, 0)
+                .unwrap(),
+            run_time,
+            allowance,
+        }
+    }
+
+    #[rstest]
+    #[case::default(vec![RankingCriterion::EarliestDeparture], vec![0, 10, 20])]
+    #[case::shortest_run_time(vec![RankingCriterion::ShortestRunTime], vec![10, 20, 0])]
+    #[case::least_allowance_then_run_time(
+        vec![RankingCriterion::LeastAllowance, RankingCriterion::ShortestRunTime],
+        vec![20, 0, 10]
+    )]
+    fn solutions_are_ranked(
+        #[case] ranking: Vec<RankingCriterion>,
+        #[case] expected_departure_minutes: Vec<u32>,
+    ) {
+        let mut solutions = vec![
+            solution(0, 3_600_000, 0),
+            solution(10, 3_000_000, 300_000),
+            solution(20, 3_000_000, 0),
+        ];
+
+        rank_solutions(&mut solutions, &ranking);
+
+        let departure_minutes: Vec<_> = solutions
+            .iter()
+            .map(|solution| solution.departure_time.minute())
+            .collect();
+        assert_eq!(departure_minutes, expected_departure_minutes);
+    }
+
+    #[test]
+    fn alternatives_are_searched_within_the_departure_window() {
+        let start_time = Utc.with_ymd_and_hms(2024, 1, 2, 8, 0, 0).unwrap();
+        let request = crate::core::stdcm::Request {
+            infra: 1,
+            expected_version: "1".to_owned(),
+            path_items: vec![],
+            rolling_stock_loading_gauge: LoadingGaugeType::G1,
+            rolling_stock_supported_signaling_systems: RollingStockSupportedSignalingSystems(
+                vec![],
+            ),
+            comfort: Comfort::Standard,
+            speed_limit_tag: None,
+            physics_consist: PhysicsConsistParameters::from_traction_engine(
+                create_simple_rolling_stock(),
+            )
+            .into(),
+            trains_requirements: HashMap::new(),
+            time_step: None,
+            start_time,
+            maximum_departure_delay: 1_200_000,
+            maximum_run_time: 7_200_000,
+            time_gap_before: 0,
+            time_gap_after: 0,
+            margin: None,
+            work_schedules: vec![crate::core::stdcm::WorkSchedule {
+                start_time: 600_000,
+                end_time: 900_000,
+                track_ranges: vec![],
+            }],
+            temporary_speed_limits: vec![],
+        };
+
+        let alternative = alternative_request(&request, start_time + Duration::minutes(2))
+            .expect("the departure window is not over");
+        assert_eq!(alternative.start_time, start_time + Duration::minutes(7));
+        assert_eq!(alternative.maximum_departure_delay, 780_000);
+        assert_eq!(alternative.work_schedules[0].start_time, 180_000);
+        assert_eq!(alternative.work_schedules[0].end_time, 480_000);
+
+        assert!(alternative_request(&request, start_time + Duration::minutes(16)).is_none());
+    }
+
     #[rstest]
     async fn stdcm_return_conflicts() {
         let db_pool = DbConnectionPoolV2::for_tests();
@@ -998,6 +1513,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn overnight_calendar_trains_are_kept() {
+        // Daily trains departing at 23:00 and arriving 3 hours later
+        let train = |id, arrival| TrainSchedule {
+            id,
+            start_time: DateTime::parse_from_rfc3339("2025-03-03T23:00:00Z")
+                .unwrap()
+                .to_utc(),
+            path: serde_json::from_value(json!([
+                {"id": "a", "track": "A", "offset": 0},
+                {"id": "b", "track": "B", "offset": 1000000},
+            ]))
+            .unwrap(),
+            schedule: serde_json::from_value(json!([{"at": "b", "arrival": arrival}])).unwrap(),
+            calendar: Some(
+                serde_json::from_value(json!({
+                    "type": "period",
+                    "start_date": "2025-03-03",
+                    "end_date": "2025-03-31",
+                    "weekdays": "1111111",
+                }))
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+        let trains = vec![train(1, "PT3H"), train(2, "PT1H")];
+        let earliest_departure_time = DateTime::parse_from_rfc3339("2025-03-05T01:00:00Z")
+            .unwrap()
+            .to_utc();
+        let latest_simulation_end = DateTime::parse_from_rfc3339("2025-03-05T23:30:00Z")
+            .unwrap()
+            .to_utc();
+
+        let trains = filter_train_schedules(trains, earliest_departure_time, latest_simulation_end);
+
+        // The first train still runs when the LMR train departs, the second one has arrived
+        let start_times: Vec<_> = trains
+            .iter()
+            .map(|train| (train.id, train.start_time.to_rfc3339()))
+            .collect();
+        assert_eq!(
+            start_times,
+            vec![
+                (1, "2025-03-04T23:00:00+00:00".to_owned()),
+                (1, "2025-03-05T23:00:00+00:00".to_owned()),
+                (2, "2025-03-05T23:00:00+00:00".to_owned()),
+            ]
+        );
+    }
+
     #[rstest]
     // A day before the 'start_time' -> FILTERED OUT
     #[case("2024-03-13 06:00:00Z", "2024-03-13 12:00:00Z", true)]
@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::Extension;
+use chrono::DateTime;
+use chrono::Utc;
+use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use editoast_models::DbConnectionPoolV2;
+use editoast_schemas::train_schedule::TrainScheduleBase;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::booking::booked_train_schedule;
+use super::request::Request;
+use super::use_selected_search_environment;
+use super::InfraIdQueryParam;
+use super::StdcmSearch;
+use super::StdcmSolution;
+use crate::core::conflict_detection::TrainRequirements;
+use crate::core::simulation::SimulationResponse;
+use crate::core::AsCoreRequest;
+use crate::core::CoreClient;
+use crate::error::Result;
+use crate::models::prelude::*;
+use crate::models::timetable::Timetable;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::train_schedule::TrainScheduleChangeset;
+use crate::models::Infra;
+use crate::simulation_store::SimulationResultStore;
+use crate::views::Authentication;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+use crate::ValkeyClient;
+
+crate::routes! {
+    "/batch" => batch,
+}
+
+editoast_common::schemas! {
+    StdcmBatchForm,
+    StdcmBatchResult,
+    StdcmBatchReport,
+}
+
+/// The maximum number of requests in a batch
+pub(crate) const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "stdcm_batch")]
+enum StdcmBatchError {
+    #[error("Timetable '{timetable_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    TimetableNotFound { timetable_id: i64 },
+    #[error("Infra '{infra_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    InfraNotFound { infra_id: i64 },
+    #[error("A batch has at most {max} requests")]
+    #[editoast_error(status = 400)]
+    TooManyRequests { max: usize },
+}
+
+fn default_train_name_prefix() -> String {
+    "STDCM".to_owned()
+}
+
+/// STDCM requests processed in order, each found path being taken into account by the next ones
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub(crate) struct StdcmBatchForm {
+    #[schema(inline)]
+    pub(crate) requests: Vec<Request>,
+    /// The timetable the found paths are added to, nothing is persisted if not given
+    pub(crate) target_timetable_id: Option<i64>,
+    /// The train schedules are named after this prefix and the position of their request
+    #[serde(default = "default_train_name_prefix")]
+    #[schema(default = "STDCM")]
+    pub(crate) train_name_prefix: String,
+    /// The labels of the created train schedules
+    #[serde(default)]
+    pub(crate) labels: Vec<String>,
+}
+
+/// The outcome of a request of a batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub(crate) enum StdcmBatchResult {
+    Success {
+        departure_time: DateTime<Utc>,
+        /// Time from the departure to the arrival, in ms
+        run_time: u64,
+        /// Engineering allowance added to the run time to avoid conflicts, in ms
+        allowance: u64,
+        /// The created train schedule, if the batch is persisted
+        train_schedule_id: Option<i64>,
+    },
+    PathNotFound,
+    PreprocessingSimulationError {
+        error: SimulationResponse,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub(crate) struct StdcmBatchReport {
+    /// The outcome of each request, in the order of the batch
+    pub(crate) results: Vec<StdcmBatchResult>,
+    /// The number of requests a path was found for
+    pub(crate) success_count: usize,
+}
+
+/// The resources used by a found path, for the next requests of the batch to avoid them
+fn found_path_requirements(
+    simulation: &SimulationResponse,
+    departure_time: DateTime<Utc>,
+) -> Option<TrainRequirements> {
+    let SimulationResponse::Success { final_output, .. } = simulation else {
+        return None;
+    };
+    Some(TrainRequirements {
+        start_time: departure_time,
+        spacing_requirements: final_output.spacing_requirements.clone(),
+        routing_requirements: final_output.routing_requirements.clone(),
+    })
+}
+
+/// Process the requests of a batch in order, among the trains of a timetable
+///
+/// The requirements of each found path are kept in memory for the next requests. If a target
+/// timetable is given, the found paths are created in it once the whole batch is processed.
+///
+/// The user must have access to the search environments selected by the requests. A batch has
+/// at most [MAX_BATCH_SIZE] requests.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn process_batch(
+    db_pool: Arc<DbConnectionPoolV2>,
+    valkey_client: Arc<ValkeyClient>,
+    simulation_store: &dyn SimulationResultStore,
+    core_client: Arc<CoreClient>,
+    auth: &Authentication,
+    timetable_id: i64,
+    infra: &Infra,
+    StdcmBatchForm {
+        mut requests,
+        target_timetable_id,
+        train_name_prefix,
+        labels,
+    }: StdcmBatchForm,
+) -> Result<StdcmBatchReport> {
+    if requests.len() > MAX_BATCH_SIZE {
+        return Err(StdcmBatchError::TooManyRequests {
+            max: MAX_BATCH_SIZE,
+        }
+        .into());
+    }
+    for request in &requests {
+        request.validate()?;
+    }
+    let conn = &mut db_pool.get().await?;
+    for timetable_id in std::iter::once(timetable_id).chain(target_timetable_id) {
+        if !Timetable::exists(conn, timetable_id).await? {
+            return Err(StdcmBatchError::TimetableNotFound { timetable_id }.into());
+        }
+    }
+    for request in &mut requests {
+        use_selected_search_environment(conn, auth, request, timetable_id, infra.id).await?;
+    }
+
+    let mut results = Vec::with_capacity(requests.len());
+    // Negative ids are used for the found paths, not to collide with the trains of the timetable
+    let mut found_requirements: HashMap<i64, TrainRequirements> = HashMap::new();
+    let mut found_trains = vec![];
+    for (index, request) in requests.iter().enumerate() {
+        let search = match StdcmSearch::prepare(
+            conn,
+            db_pool.clone(),
+            valkey_client.clone(),
+            simulation_store,
+            core_client.clone(),
+            request,
+            infra,
+            timetable_id,
+        )
+        .await?
+        {
+            Ok(search) => search,
+            Err(error) => {
+                results.push(StdcmBatchResult::PreprocessingSimulationError { error });
+                continue;
+            }
+        };
+        let rolling_stock_name = search.virtual_train_run.train_schedule.rolling_stock_name;
+        let mut core_request = search.core_request;
+        core_request.trains_requirements.extend(
+            found_requirements
+                .iter()
+                .map(|(id, requirements)| (*id, requirements.clone())),
+        );
+
+        let (simulation, path, departure_time) =
+            match core_request.fetch(core_client.as_ref()).await? {
+                crate::core::stdcm::Response::Success {
+                    simulation,
+                    path,
+                    departure_time,
+                } => (simulation, path, departure_time),
+                crate::core::stdcm::Response::PathNotFound => {
+                    results.push(StdcmBatchResult::PathNotFound);
+                    continue;
+                }
+                crate::core::stdcm::Response::PreprocessingSimulationError { error } => {
+                    results.push(StdcmBatchResult::PreprocessingSimulationError { error });
+                    continue;
+                }
+            };
+
+        if let Some(requirements) = found_path_requirements(&simulation, departure_time) {
+            found_requirements.insert(-(index as i64) - 1, requirements);
+        }
+        if target_timetable_id.is_some() {
+            if let Some(train_schedule) = booked_train_schedule(
+                &core_request,
+                &simulation,
+                &path,
+                departure_time,
+                rolling_stock_name,
+            ) {
+                found_trains.push((
+                    index,
+                    TrainScheduleBase {
+                        train_name: format!("{train_name_prefix} {}", index + 1),
+                        labels: labels.clone(),
+                        ..train_schedule
+                    },
+                ));
+            }
+        }
+        let solution = StdcmSolution::new(simulation, path, departure_time);
+        results.push(StdcmBatchResult::Success {
+            departure_time,
+            run_time: solution.run_time,
+            allowance: solution.allowance,
+            train_schedule_id: None,
+        });
+    }
+
+    if let Some(target_timetable_id) = target_timetable_id {
+        // The created rows are not returned in insertion order, they are matched by their name
+        let indexes: HashMap<_, _> = found_trains
+            .iter()
+            .map(|(index, train_schedule)| (train_schedule.train_name.clone(), *index))
+            .collect();
+        let changesets: Vec<TrainScheduleChangeset> = found_trains
+            .into_iter()
+            .map(|(_, train_schedule)| {
+                TrainScheduleChangeset::from(train_schedule).timetable_id(target_timetable_id)
+            })
+            .collect();
+        let created: Vec<TrainSchedule> = TrainSchedule::create_batch(conn, changesets).await?;
+        for train_schedule in created {
+            let Some(&index) = indexes.get(&train_schedule.train_name) else {
+                continue;
+            };
+            if let StdcmBatchResult::Success {
+                train_schedule_id, ..
+            } = &mut results[index]
+            {
+                *train_schedule_id = Some(train_schedule.id);
+            }
+        }
+    }
+
+    let success_count = results
+        .iter()
+        .filter(|result| matches!(result, StdcmBatchResult::Success { .. }))
+        .count();
+    Ok(StdcmBatchReport {
+        results,
+        success_count,
+    })
+}
+
+/// Process a batch of STDCM requests, each found path being taken into account by the next ones
+///
+/// The requests are processed in order. Each one is searched among the trains of the timetable
+/// and the paths found for the previous requests of the batch. A batch has at most 100 requests.
+///
+/// If a target timetable is given, a train schedule is created in it for each found path, once the
+/// whole batch is processed.
+#[utoipa::path(
+    post, path = "",
+    tag = "stdcm",
+    params(("infra" = i64, Query, description = "The infra id"),
+        ("id" = i64, Path, description = "timetable_id"),
+    ),
+    request_body = StdcmBatchForm,
+    responses(
+        (status = 200, body = StdcmBatchReport, description = "The outcome of each request"),
+        (status = 400, description = "The batch has too many requests"),
+        (status = 404, description = "Timetable or infra not found"),
+    )
+)]
+async fn batch(
+    State(AppState {
+        db_pool,
+        valkey: valkey_client,
+        simulation_store,
+        core_client,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Path(timetable_id): Path<i64>,
+    Query(InfraIdQueryParam { infra: infra_id }): Query<InfraIdQueryParam>,
+    Json(form): Json<StdcmBatchForm>,
+) -> Result<Json<StdcmBatchReport>> {
+    let required_roles = if form.target_timetable_id.is_some() {
+        [BuiltinRole::Stdcm, BuiltinRole::TimetableWrite].into()
+    } else {
+        [BuiltinRole::Stdcm].into()
+    };
+    let authorized = auth
+        .check_roles(required_roles)
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let infra = Infra::retrieve_or_fail(&mut db_pool.get().await?, infra_id, || {
+        StdcmBatchError::InfraNotFound { infra_id }
+    })
+    .await?;
+    let report = process_batch(
+        db_pool,
+        valkey_client,
+        simulation_store.as_ref(),
+        core_client,
+        &auth,
+        timetable_id,
+        &infra,
+        form,
+    )
+    .await?;
+    Ok(Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::core::simulation::SpacingRequirement;
+    use crate::models::fixtures::create_small_infra;
+    use crate::models::fixtures::create_timetable;
+    use crate::views::test_app::TestAppBuilder;
+
+    #[test]
+    fn found_path_requirements_start_at_the_departure() {
+        let mut simulation = SimulationResponse::default();
+        if let SimulationResponse::Success { final_output, .. } = &mut simulation {
+            final_output.spacing_requirements = vec![SpacingRequirement {
+                zone: "zone.1".to_owned(),
+                begin_time: 0,
+                end_time: 60_000,
+            }];
+        }
+        let departure_time = DateTime::parse_from_rfc3339("2025-03-03T08:10:00Z")
+            .unwrap()
+            .to_utc();
+
+        let requirements = found_path_requirements(&simulation, departure_time).unwrap();
+
+        assert_eq!(requirements.start_time, departure_time);
+        assert_eq!(requirements.spacing_requirements.len(), 1);
+    }
+
+    #[rstest]
+    async fn batch_requires_an_existing_target_timetable() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+
+        let request = app
+            .post(&format!(
+                "/timetable/{}/stdcm/batch?infra={}",
+                timetable.id, infra.id
+            ))
+            .json(&json!({"requests": [], "target_timetable_id": 0}));
+
+        app.fetch(request).assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[rstest]
+    async fn batch_with_too_many_requests_is_rejected() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let infra = create_small_infra(&mut pool.get_ok()).await;
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+
+        let stdcm_request = json!({"steps": [], "rolling_stock_id": 0, "comfort": "STANDARD"});
+        let request = app
+            .post(&format!(
+                "/timetable/{}/stdcm/batch?infra={}",
+                timetable.id, infra.id
+            ))
+            .json(&json!({"requests": vec![stdcm_request; MAX_BATCH_SIZE + 1]}));
+
+        let error: serde_json::Value = app
+            .fetch(request)
+            .assert_status(StatusCode::BAD_REQUEST)
+            .json_into();
+        assert_eq!(error["type"], "editoast:stdcm_batch:TooManyRequests");
+        assert_eq!(error["context"]["max"], MAX_BATCH_SIZE);
+    }
+}
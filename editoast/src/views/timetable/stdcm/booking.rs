@@ -0,0 +1,465 @@
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::State;
+use axum::Extension;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use editoast_schemas::primitives::NonBlankString;
+use editoast_schemas::primitives::PositiveDuration;
+use editoast_schemas::train_schedule::PathItem;
+use editoast_schemas::train_schedule::PathItemLocation;
+use editoast_schemas::train_schedule::ReceptionSignal;
+use editoast_schemas::train_schedule::ScheduleItem;
+use editoast_schemas::train_schedule::TrainScheduleBase;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use super::build_single_margin;
+use super::calendar_trains_on_day;
+use crate::core::conflict_detection::ConflictDetectionRequest;
+use crate::core::pathfinding::PathfindingResultSuccess;
+use crate::core::simulation::SimulationResponse;
+use crate::core::AsCoreRequest;
+use crate::error::Result;
+use crate::models::prelude::*;
+use crate::models::stdcm_log::StdcmLog;
+use crate::models::timetable::Timetable;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::train_schedule::TrainScheduleChangeset;
+use crate::models::Infra;
+use crate::views::path::projection::PathProjection;
+use crate::views::path::projection::TrackLocationFromPath;
+use crate::views::timetable::trains_requirements;
+use crate::views::train_schedule::train_simulation_batch;
+use crate::views::train_schedule::TrainScheduleResult;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+
+crate::routes! {
+    "/book" => book,
+}
+
+editoast_common::schemas! {
+    StdcmBookingForm,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "stdcm_booking")]
+enum StdcmBookingError {
+    #[error("An 'stdcm_log_id' or a 'trace_id' is required")]
+    #[editoast_error(status = 400)]
+    MissingIdAndTraceId,
+    #[error("STDCM log entry '{stdcm_log_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    NotFound { stdcm_log_id: i64 },
+    #[error("STDCM log entry '{trace_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    TraceIdNotFound { trace_id: String },
+    #[error("STDCM log entry '{stdcm_log_id}' holds no solution")]
+    #[editoast_error(status = 400)]
+    NoSolution { stdcm_log_id: i64 },
+    #[error("The rolling stock of STDCM log entry '{stdcm_log_id}' is unknown")]
+    #[editoast_error(status = 400)]
+    UnknownRollingStock { stdcm_log_id: i64 },
+    #[error("Timetable '{timetable_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    TimetableNotFound { timetable_id: i64 },
+    #[error("Infra '{infra_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    InfraNotFound { infra_id: i64 },
+    #[error("The booked train could not be simulated")]
+    #[editoast_error(status = 400)]
+    SimulationFailed,
+    #[error("The slot is not available anymore, {conflict_count} conflict(s) found")]
+    #[editoast_error(status = 409)]
+    Conflicts { conflict_count: usize },
+}
+
+/// The STDCM result to book, and the train schedule to create from it
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+struct StdcmBookingForm {
+    /// The STDCM log holding the result, either this or `trace_id` must be given
+    stdcm_log_id: Option<i64>,
+    /// The trace id of the STDCM request
+    trace_id: Option<String>,
+    train_name: String,
+    #[serde(default)]
+    labels: Vec<String>,
+    /// Used to simulate the trains of the timetable when checking for conflicts
+    electrical_profile_set_id: Option<i64>,
+}
+
+/// Build the train schedule running in the slot found by STDCM
+///
+/// Each step of the request is located on the path of the solution, and timed with its
+/// simulation. Returns `None` if the simulation of the solution failed.
+pub(super) fn booked_train_schedule(
+    stdcm_request: &crate::core::stdcm::Request,
+    simulation: &SimulationResponse,
+    path: &PathfindingResultSuccess,
+    departure_time: DateTime<Utc>,
+    rolling_stock_name: String,
+) -> Option<TrainScheduleBase> {
+    let SimulationResponse::Success { final_output, .. } = simulation else {
+        return None;
+    };
+    let path_item_times = &final_output.report_train.path_item_times;
+    if path_item_times.len() != stdcm_request.path_items.len()
+        || path.path_item_positions.len() != stdcm_request.path_items.len()
+    {
+        return None;
+    }
+
+    let projection = PathProjection::new(&path.track_section_ranges);
+    let last_index = stdcm_request.path_items.len() - 1;
+    let mut path_items = vec![];
+    let mut schedule = vec![];
+    for (index, (path_item, (&position, &time))) in stdcm_request
+        .path_items
+        .iter()
+        .zip(path.path_item_positions.iter().zip(path_item_times))
+        .enumerate()
+    {
+        let id: NonBlankString = format!("step-{index}").into();
+        let (TrackLocationFromPath::One(location) | TrackLocationFromPath::Two(location, _)) =
+            projection.get_location(position);
+        path_items.push(PathItem {
+            id: id.clone(),
+            deleted: false,
+            location: PathItemLocation::TrackOffset(location),
+        });
+        if index == 0 {
+            continue;
+        }
+        // The train stops at its destination, as the one searched by STDCM
+        let stop_duration = match path_item.stop_duration {
+            None if index == last_index => Some(0),
+            stop_duration => stop_duration,
+        };
+        schedule.push(ScheduleItem {
+            at: id,
+            arrival: Some(positive_duration(time)),
+            stop_for: stop_duration.map(positive_duration),
+            reception_signal: ReceptionSignal::Open,
+            locked: true,
+        });
+    }
+
+    Some(TrainScheduleBase {
+        rolling_stock_name,
+        start_time: departure_time,
+        path: path_items,
+        schedule,
+        margins: build_single_margin(stdcm_request.margin),
+        comfort: stdcm_request.comfort,
+        speed_limit_tag: stdcm_request
+            .speed_limit_tag
+            .clone()
+            .filter(|tag| !tag.is_empty())
+            .map(NonBlankString),
+        ..Default::default()
+    })
+}
+
+fn positive_duration(milliseconds: u64) -> PositiveDuration {
+    PositiveDuration::try_from(Duration::milliseconds(milliseconds as i64))
+        .expect("a duration in ms is positive")
+}
+
+/// Book an STDCM result into a timetable
+///
+/// The result is taken from the STDCM log with the given id or trace id. The created train
+/// schedule runs in the found slot, with the rolling stock, stops, margin and speed limit tag of
+/// the request.
+///
+/// The timetable is locked while the train is checked for conflicts against its trains, so that
+/// a slot cannot be booked twice.
+#[utoipa::path(
+    post, path = "",
+    tag = "stdcm",
+    params(("id" = i64, Path, description = "timetable_id")),
+    request_body = StdcmBookingForm,
+    responses(
+        (status = 200, description = "The booked train schedule", body = TrainScheduleResult),
+        (status = 404, description = "STDCM log, timetable or infra not found"),
+        (status = 409, description = "The booked train conflicts with the trains of the timetable"),
+    ),
+)]
+async fn book(
+    State(AppState {
+        db_pool,
+        valkey: valkey_client,
+        simulation_store,
+        core_client,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Path(timetable_id): Path<i64>,
+    Json(StdcmBookingForm {
+        stdcm_log_id,
+        trace_id,
+        train_name,
+        labels,
+        electrical_profile_set_id,
+    }): Json<StdcmBookingForm>,
+) -> Result<Json<TrainScheduleResult>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::Stdcm, BuiltinRole::TimetableWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let stdcm_log = match (stdcm_log_id, trace_id) {
+        (Some(stdcm_log_id), _) => {
+            StdcmLog::retrieve_or_fail(conn, stdcm_log_id, || StdcmBookingError::NotFound {
+                stdcm_log_id,
+            })
+            .await?
+        }
+        (None, Some(trace_id)) => {
+            StdcmLog::retrieve_or_fail(conn, Some(trace_id.clone()), || {
+                StdcmBookingError::TraceIdNotFound { trace_id }
+            })
+            .await?
+        }
+        (None, None) => return Err(StdcmBookingError::MissingIdAndTraceId.into()),
+    };
+
+    let stdcm_log_id = stdcm_log.id;
+    let crate::core::stdcm::Response::Success {
+        simulation,
+        path,
+        departure_time,
+    } = &stdcm_log.response
+    else {
+        return Err(StdcmBookingError::NoSolution { stdcm_log_id }.into());
+    };
+    let rolling_stock_name = stdcm_log
+        .rolling_stock_name
+        .clone()
+        .ok_or(StdcmBookingError::UnknownRollingStock { stdcm_log_id })?;
+    let train_schedule = booked_train_schedule(
+        &stdcm_log.request,
+        simulation,
+        path,
+        *departure_time,
+        rolling_stock_name,
+    )
+    .ok_or(StdcmBookingError::NoSolution { stdcm_log_id })?;
+    let train_schedule = TrainScheduleBase {
+        train_name,
+        labels,
+        ..train_schedule
+    };
+
+    let infra_id = stdcm_log.request.infra;
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || StdcmBookingError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+    let departure_day = departure_time.date_naive();
+
+    conn.transaction(|conn| {
+        Box::pin(async move {
+            Timetable::lock(&mut conn.clone(), timetable_id)
+                .await?
+                .ok_or(StdcmBookingError::TimetableNotFound { timetable_id })?;
+            let booked_train = TrainScheduleChangeset::from(train_schedule)
+                .timetable_id(timetable_id)
+                .create(&mut conn.clone())
+                .await?;
+
+            let trains = TrainSchedule::list(
+                &mut conn.clone(),
+                SelectionSettings::new()
+                    .filter(move || TrainSchedule::TIMETABLE_ID.eq(timetable_id)),
+            )
+            .await?;
+            let trains = calendar_trains_on_day(trains, departure_day);
+            let simulations = train_simulation_batch(
+                &mut conn.clone(),
+                valkey_client,
+                simulation_store.as_ref(),
+                core_client.clone(),
+                &trains,
+                &infra,
+                electrical_profile_set_id,
+            )
+            .await?;
+            let trains_requirements = trains_requirements(&trains, simulations);
+            if !trains_requirements.contains_key(&booked_train.id) {
+                return Err(StdcmBookingError::SimulationFailed.into());
+            }
+
+            let conflicts = ConflictDetectionRequest {
+                infra: infra_id,
+                expected_version: infra.version,
+                trains_requirements,
+                work_schedules: None,
+            }
+            .fetch(&core_client)
+            .await?
+            .conflicts;
+            let conflict_count = conflicts
+                .iter()
+                .filter(|conflict| conflict.train_ids.contains(&booked_train.id))
+                .count();
+            if conflict_count > 0 {
+                return Err(StdcmBookingError::Conflicts { conflict_count }.into());
+            }
+
+            Ok(Json(booked_train.into()))
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::http::StatusCode;
+    use editoast_schemas::infra::Direction;
+    use editoast_schemas::infra::TrackOffset;
+    use editoast_schemas::rolling_stock::LoadingGaugeType;
+    use editoast_schemas::rolling_stock::RollingStockSupportedSignalingSystems;
+    use editoast_schemas::train_schedule::Comfort;
+    use editoast_schemas::train_schedule::MarginValue;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::core::pathfinding::TrackRange;
+    use crate::core::simulation::PhysicsConsistParameters;
+    use crate::core::stdcm::PathItem as StdcmPathItem;
+    use crate::models::fixtures::create_simple_rolling_stock;
+    use crate::models::fixtures::create_timetable;
+    use crate::views::test_app::TestAppBuilder;
+
+    fn stdcm_path_item(track: &str, offset: u64, stop_duration: Option<u64>) -> StdcmPathItem {
+        StdcmPathItem {
+            locations: vec![TrackOffset::new(track, offset)],
+            stop_duration,
+            step_timing_data: None,
+        }
+    }
+
+    #[test]
+    fn booked_train_runs_in_the_found_slot() {
+        let stdcm_request = crate::core::stdcm::Request {
+            infra: 1,
+            expected_version: "1".to_owned(),
+            path_items: vec![
+                stdcm_path_item("A", 100_000, None),
+                stdcm_path_item("B", 500_000, Some(60_000)),
+                stdcm_path_item("B", 900_000, None),
+            ],
+            rolling_stock_loading_gauge: LoadingGaugeType::G1,
+            rolling_stock_supported_signaling_systems: RollingStockSupportedSignalingSystems(
+                vec![],
+            ),
+            comfort: Comfort::Standard,
+            speed_limit_tag: Some("MA100".to_owned()),
+            physics_consist: PhysicsConsistParameters::from_traction_engine(
+                create_simple_rolling_stock(),
+            )
+            .into(),
+            trains_requirements: HashMap::new(),
+            time_step: None,
+            start_time: DateTime::parse_from_rfc3339("2025-03-03T08:00:00Z")
+                .unwrap()
+                .to_utc(),
+            maximum_departure_delay: 1_200_000,
+            maximum_run_time: 7_200_000,
+            time_gap_before: 0,
+            time_gap_after: 0,
+            margin: Some(MarginValue::Percentage(5.0)),
+            work_schedules: vec![],
+            temporary_speed_limits: vec![],
+        };
+        let path = PathfindingResultSuccess {
+            blocks: vec![],
+            routes: vec![],
+            track_section_ranges: vec![
+                TrackRange::new("A", 100_000, 300_000, Direction::StartToStop),
+                TrackRange::new("B", 0, 900_000, Direction::StartToStop),
+            ],
+            length: 1_100_000,
+            path_item_positions: vec![0, 700_000, 1_100_000],
+        };
+        let mut simulation = SimulationResponse::default();
+        if let SimulationResponse::Success { final_output, .. } = &mut simulation {
+            final_output.report_train.path_item_times = vec![0, 50_000, 140_000];
+        }
+        let departure_time = DateTime::parse_from_rfc3339("2025-03-03T08:10:00Z")
+            .unwrap()
+            .to_utc();
+
+        let train_schedule = booked_train_schedule(
+            &stdcm_request,
+            &simulation,
+            &path,
+            departure_time,
+            "fast_rolling_stock".to_owned(),
+        )
+        .unwrap();
+
+        assert_eq!(train_schedule.start_time, departure_time);
+        let locations: Vec<_> = train_schedule
+            .path
+            .iter()
+            .map(|path_item| path_item.location.clone())
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                PathItemLocation::TrackOffset(TrackOffset::new("A", 100_000)),
+                PathItemLocation::TrackOffset(TrackOffset::new("B", 500_000)),
+                PathItemLocation::TrackOffset(TrackOffset::new("B", 900_000)),
+            ]
+        );
+        let schedule: Vec<_> = train_schedule
+            .schedule
+            .iter()
+            .map(|schedule_item| {
+                (
+                    schedule_item.at.0.clone(),
+                    schedule_item.arrival.as_deref().map(Duration::num_seconds),
+                    schedule_item.stop_for.as_deref().map(Duration::num_seconds),
+                )
+            })
+            .collect();
+        assert_eq!(
+            schedule,
+            vec![
+                ("step-1".to_owned(), Some(50), Some(60)),
+                ("step-2".to_owned(), Some(140), Some(0)),
+            ]
+        );
+        assert_eq!(train_schedule.margins.values.len(), 1);
+        assert_eq!(train_schedule.speed_limit_tag.unwrap().0, "MA100");
+    }
+
+    #[rstest]
+    async fn booking_requires_a_log() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+
+        let request = app
+            .post(&format!("/timetable/{}/stdcm/book", timetable.id))
+            .json(&json!({"train_name": "booked"}));
+
+        app.fetch(request).assert_status(StatusCode::BAD_REQUEST);
+    }
+}
@@ -19,6 +19,7 @@ use validator::Validate;
 
 use crate::core::pathfinding::PathfindingInputError;
 use crate::error::Result;
+use crate::models::stdcm_search_environment::StdcmSearchEnvironment;
 use crate::models::temporary_speed_limits::TemporarySpeedLimit;
 use crate::models::towed_rolling_stock::TowedRollingStockModel;
 use crate::models::work_schedules::WorkSchedule;
@@ -33,8 +34,12 @@ use super::StdcmError;
 
 editoast_common::schemas! {
     PathfindingItem,
+    RankingCriterion,
 }
 
+/// The maximum number of solutions a request can ask for
+pub(super) const MAX_ALTERNATIVE_COUNT: u8 = 10;
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, ToSchema)]
 pub(crate) struct PathfindingItem {
     /// The stop duration in milliseconds, None if the train does not stop.
@@ -68,6 +73,22 @@ pub(crate) struct StepTimingData {
     pub(crate) arrival_time_tolerance_after: u64,
 }
 
+/// A criterion ranking the solutions of an STDCM request
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RankingCriterion {
+    /// The earliest departure first
+    EarliestDeparture,
+    /// The shortest run time first, from the departure to the arrival
+    ShortestRunTime,
+    /// The least engineering allowance first, added to the run time to avoid conflicts
+    LeastAllowance,
+}
+
+fn default_alternative_count() -> u8 {
+    1
+}
+
 /// An STDCM request
 #[editoast_derive::annotate_units]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Validate, ToSchema)]
@@ -117,9 +138,44 @@ pub(crate) struct Request {
     #[serde(default, with = "units::meter_per_second::option")]
     pub(crate) max_speed: Option<quantities::Velocity>,
     pub(crate) loading_gauge_type: Option<LoadingGaugeType>,
+    /// Number of solutions to look for, departing at least 5 minutes apart
+    #[serde(default = "default_alternative_count")]
+    #[schema(default = 1, minimum = 1, maximum = 10)]
+    pub(crate) alternative_count: u8,
+    /// Criteria ranking the solutions, each one breaking the ties of the previous ones
+    ///
+    /// The solutions are ranked by earliest departure if empty.
+    #[serde(default)]
+    pub(crate) ranking: Vec<RankingCriterion>,
+    /// The search environment to search the path in, by id
+    ///
+    /// Its electrical profile set, work schedule group and temporary speed limit group replace the
+    /// ones of the request.
+    #[serde(default)]
+    pub(crate) search_environment_id: Option<i64>,
+    /// The search environment to search the path in, by name
+    #[serde(default)]
+    pub(crate) search_environment_name: Option<String>,
 }
 
 impl Request {
+    /// Takes the electrical profile set, work schedule group and temporary speed limit group of the
+    /// search environment
+    pub(crate) fn use_search_environment(&mut self, search_env: &StdcmSearchEnvironment) {
+        self.electrical_profile_set_id = search_env.electrical_profile_set_id;
+        self.work_schedule_group_id = search_env.work_schedule_group_id;
+        self.temporary_speed_limit_group_id = search_env.temporary_speed_limit_group_id;
+    }
+
+    /// Returns the criteria ranking the solutions, the earliest departure being the default one
+    pub(super) fn get_ranking(&self) -> Vec<RankingCriterion> {
+        if self.ranking.is_empty() {
+            vec![RankingCriterion::EarliestDeparture]
+        } else {
+            self.ranking.clone()
+        }
+    }
+
     /// Returns the earliest time that has been set on any step
     pub(super) fn get_earliest_step_time(&self) -> DateTime<Utc> {
         // Get the earliest time that has been specified for any step
@@ -325,6 +381,18 @@ impl<'de> Deserialize<'de> for Request {
             }
         }
 
+        if !(1..=MAX_ALTERNATIVE_COUNT).contains(&request.alternative_count) {
+            return Err(serde::de::Error::custom(format!(
+                "the alternative_count must be between 1 and {MAX_ALTERNATIVE_COUNT}"
+            )));
+        }
+
+        if request.search_environment_id.is_some() && request.search_environment_name.is_some() {
+            return Err(serde::de::Error::custom(
+                "the search environment must be selected either by id or by name",
+            ));
+        }
+
         Ok(request)
     }
 }
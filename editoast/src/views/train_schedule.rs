@@ -13,6 +13,7 @@ use axum::extract::Query;
 use axum::extract::State;
 use axum::response::IntoResponse;
 use axum::Extension;
+use chrono::NaiveDate;
 use editoast_authz::BuiltinRole;
 use editoast_derive::EditoastError;
 use editoast_models::DbConnection;
@@ -23,6 +24,7 @@ use serde::Deserialize;
 use serde::Serialize;
 use thiserror::Error;
 use tracing::info;
+use tracing::warn;
 use utoipa::IntoParams;
 use utoipa::ToSchema;
 
@@ -50,6 +52,8 @@ use crate::models::infra::Infra;
 use crate::models::prelude::*;
 use crate::models::train_schedule::TrainSchedule;
 use crate::models::train_schedule::TrainScheduleChangeset;
+use crate::simulation_store::SimulationResultKey;
+use crate::simulation_store::SimulationResultStore;
 use crate::views::path::pathfinding::pathfinding_from_train;
 use crate::views::path::pathfinding::PathfindingFailure;
 use crate::views::path::pathfinding::PathfindingResult;
@@ -134,6 +138,7 @@ impl From<TrainSchedule> for TrainScheduleResult {
                 speed_limit_tag: value.speed_limit_tag.map(Into::into),
                 power_restrictions: value.power_restrictions,
                 options: value.options,
+                calendar: value.calendar,
             },
         }
     }
@@ -300,13 +305,13 @@ async fn put(
 #[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams, ToSchema)]
 #[into_params(parameter_in = Query)]
 pub struct InfraIdQueryParam {
-    infra_id: i64,
+    pub infra_id: i64,
 }
 #[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams, ToSchema)]
 #[into_params(parameter_in = Query)]
 pub struct ElectricalProfileSetIdQueryParam {
     #[param(nullable = false)]
-    electrical_profile_set_id: Option<i64>,
+    pub electrical_profile_set_id: Option<i64>,
 }
 
 /// Retrieve the space, speed and time curve of a given train
@@ -321,6 +326,7 @@ pub struct ElectricalProfileSetIdQueryParam {
 async fn simulation(
     State(AppState {
         valkey: valkey_client,
+        simulation_store,
         core_client,
         db_pool,
         ..
@@ -359,6 +365,7 @@ async fn simulation(
     let (simulation, _) = train_simulation_batch(
         &mut db_pool.get().await?,
         valkey_client,
+        simulation_store.as_ref(),
         core_client,
         &[train_schedule],
         &infra,
@@ -371,12 +378,26 @@ async fn simulation(
     Ok(Json(simulation))
 }
 
+/// Keep the trains running on the given day, their start time moved to that day
+///
+/// All the trains are kept unchanged if no day is given.
+pub fn trains_on_day(trains: Vec<TrainSchedule>, day: Option<NaiveDate>) -> Vec<TrainSchedule> {
+    match day {
+        Some(day) => trains
+            .iter()
+            .filter_map(|train| train.on_day(day))
+            .collect(),
+        None => trains,
+    }
+}
+
 /// Compute in batch the simulation of a list of train schedule
 ///
 /// Note: The order of the returned simulations is the same as the order of the train schedules.
 pub async fn train_simulation_batch(
     conn: &mut DbConnection,
     valkey_client: Arc<ValkeyClient>,
+    simulation_store: &dyn SimulationResultStore,
     core: Arc<CoreClient>,
     train_schedules: &[TrainSchedule],
     infra: &Infra,
@@ -398,6 +419,7 @@ pub async fn train_simulation_batch(
     consist_train_simulation_batch(
         conn,
         valkey_client,
+        simulation_store,
         core.clone(),
         infra,
         train_schedules,
@@ -407,9 +429,14 @@ pub async fn train_simulation_batch(
     .await
 }
 
+/// Compute in batch the simulation of a list of train schedule with the given consists
+///
+/// Results are looked up in Valkey, then in the persistent simulation result store. Only the
+/// trains found in neither are simulated by core.
 pub async fn consist_train_simulation_batch(
     conn: &mut DbConnection,
     valkey_client: Arc<ValkeyClient>,
+    simulation_store: &dyn SimulationResultStore,
     core: Arc<CoreClient>,
     infra: &Infra,
     train_schedules: &[TrainSchedule],
@@ -478,29 +505,66 @@ pub async fn consist_train_simulation_batch(
         );
 
         // Compute unique hash of the simulation input
-        let simulation_hash =
-            train_simulation_input_hash(infra.id, &infra.version, &simulation_request);
-        to_sim.push((index, simulation_hash, simulation_request));
+        let simulation_key = SimulationResultKey {
+            hash: train_simulation_input_hash(infra.id, &infra.version, &simulation_request),
+            infra_id: infra.id,
+            infra_version: infra.version.clone(),
+            rolling_stock_name: train_schedule.rolling_stock_name.clone(),
+            electrical_profile_set_id,
+        };
+        to_sim.push((index, simulation_key, simulation_request));
     }
 
     let cached_results: Vec<Option<SimulationResponse>> = valkey_conn
-        .json_get_bulk(&to_sim.iter().map(|(_, hash, _)| hash).collect::<Vec<_>>())
+        .json_get_bulk(
+            &to_sim
+                .iter()
+                .map(|(_, key, _)| &key.hash)
+                .collect::<Vec<_>>(),
+        )
         .await?;
 
+    // Look up the results evicted from Valkey in the persistent store
+    let evicted_keys: Vec<_> = to_sim
+        .iter()
+        .zip(&cached_results)
+        .filter(|(_, sim_cached)| sim_cached.is_none())
+        .map(|((_, key, _), _)| key)
+        .collect();
+    let stored_results = if evicted_keys.is_empty() {
+        vec![]
+    } else {
+        simulation_store
+            .get_bulk(&evicted_keys)
+            .await
+            .unwrap_or_else(|error| {
+                warn!(%error, "Could not read the simulation result store");
+                vec![None; evicted_keys.len()]
+            })
+    };
+
     let nb_hit = cached_results.iter().flatten().count();
-    let nb_miss = to_sim.len() - nb_hit;
-    info!(nb_hit, nb_miss, "Hit cache");
+    let nb_store_hit = stored_results.iter().flatten().count();
+    let nb_miss = to_sim.len() - nb_hit - nb_store_hit;
+    info!(nb_hit, nb_store_hit, nb_miss, "Hit cache");
 
     // Compute simulation from core
     let mut futures = Vec::with_capacity(nb_miss);
-    let mut futures_index_hash = Vec::with_capacity(nb_miss);
-    for ((train_index, train_hash, sim_request), sim_cached) in to_sim.iter().zip(cached_results) {
+    let mut futures_index_key = Vec::with_capacity(nb_miss);
+    let mut restored = Vec::with_capacity(nb_store_hit);
+    let mut stored_results = stored_results.into_iter();
+    for ((train_index, train_key, sim_request), sim_cached) in to_sim.iter().zip(cached_results) {
         if let Some(sim_cached) = sim_cached {
             simulation_results[*train_index] = sim_cached;
             continue;
         }
+        if let Some(sim_stored) = stored_results.next().flatten() {
+            simulation_results[*train_index] = sim_stored;
+            restored.push((*train_index, &train_key.hash));
+            continue;
+        }
         futures.push(Box::pin(sim_request.fetch(core.as_ref())));
-        futures_index_hash.push((*train_index, train_hash));
+        futures_index_key.push((*train_index, train_key));
     }
 
     let simulated: Vec<_> = futures::future::join_all(futures)
@@ -509,7 +573,7 @@ pub async fn consist_train_simulation_batch(
         .collect();
 
     let mut is_cacheable = vec![false; train_schedules.len()];
-    for (&(train_index, _), sim_res) in futures_index_hash.iter().zip(simulated) {
+    for (&(train_index, _), sim_res) in futures_index_key.iter().zip(simulated) {
         (simulation_results[train_index], is_cacheable[train_index]) = match sim_res {
             Ok(sim) => (sim, true),
             // TODO: only make HTTP status code errors non-fatal
@@ -517,13 +581,27 @@ pub async fn consist_train_simulation_batch(
         }
     }
 
-    let to_cache: Vec<_> = futures_index_hash
+    let to_store: Vec<_> = futures_index_key
         .into_iter()
         .filter(|&(train_index, _)| is_cacheable[train_index])
-        .map(|(train_index, train_hash)| (train_hash, &simulation_results[train_index]))
+        .map(|(train_index, train_key)| (train_key, &simulation_results[train_index]))
         .collect();
 
-    // Cache the simulation response
+    // Persist the simulation response
+    if let Err(error) = simulation_store.set_bulk(&to_store).await {
+        warn!(%error, "Could not write to the simulation result store");
+    }
+
+    // Cache the simulation response, along with the ones restored from the store
+    let to_cache: Vec<_> = to_store
+        .iter()
+        .map(|(train_key, sim)| (&train_key.hash, *sim))
+        .chain(
+            restored
+                .into_iter()
+                .map(|(train_index, train_hash)| (train_hash, &simulation_results[train_index])),
+        )
+        .collect();
     valkey_conn.json_set_bulk(&to_cache).await?;
 
     // Return the response
@@ -669,6 +747,7 @@ async fn simulation_summary(
     State(AppState {
         db_pool,
         valkey: valkey_client,
+        simulation_store,
         core_client: core,
         ..
     }): State<AppState>,
@@ -704,6 +783,7 @@ async fn simulation_summary(
     let simulations = train_simulation_batch(
         conn,
         valkey_client,
+        simulation_store.as_ref(),
         core,
         &train_schedules,
         &infra,
@@ -825,6 +905,46 @@ mod tests {
     use crate::views::test_app::TestApp;
     use crate::views::test_app::TestAppBuilder;
 
+    #[test]
+    fn trains_on_day_keeps_running_trains() {
+        let start_time = DateTime::parse_from_rfc3339("2025-03-03T07:30:00Z")
+            .unwrap()
+            .to_utc();
+        let daily = TrainSchedule {
+            id: 1,
+            start_time,
+            calendar: Some(
+                serde_json::from_value(json!({
+                    "type": "period",
+                    "start_date": "2025-03-03",
+                    "end_date": "2025-03-31",
+                    "weekdays": "1111100",
+                }))
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+        let single = TrainSchedule {
+            id: 2,
+            start_time,
+            ..Default::default()
+        };
+        let trains = vec![daily, single];
+
+        let day = NaiveDate::from_ymd_opt(2025, 3, 4).unwrap();
+        let running = trains_on_day(trains.clone(), Some(day));
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].id, 1);
+        assert_eq!(
+            running[0].start_time,
+            DateTime::parse_from_rfc3339("2025-03-04T07:30:00Z").unwrap()
+        );
+
+        let sunday = NaiveDate::from_ymd_opt(2025, 3, 9).unwrap();
+        assert!(trains_on_day(trains.clone(), Some(sunday)).is_empty());
+        assert_eq!(trains_on_day(trains, None).len(), 2);
+    }
+
     #[rstest]
     async fn train_schedule_get() {
         let app = TestAppBuilder::default_app();
@@ -2,6 +2,7 @@ use axum::extract::Json;
 use axum::extract::State;
 use axum::Extension;
 use chrono::DateTime;
+use chrono::NaiveDate;
 use chrono::Utc;
 use editoast_authz::BuiltinRole;
 use editoast_common::units;
@@ -38,6 +39,7 @@ use crate::views::path::pathfinding::PathfindingResult;
 use crate::views::path::projection::PathProjection;
 use crate::views::path::projection::TrackLocationFromPath;
 use crate::views::train_schedule::train_simulation_batch;
+use crate::views::train_schedule::trains_on_day;
 use crate::views::train_schedule::CompleteReportTrain;
 use crate::views::train_schedule::ReportTrain;
 use crate::views::train_schedule::SignalCriticalPosition;
@@ -62,6 +64,9 @@ struct ProjectPathForm {
     ids: HashSet<i64>,
     #[schema(inline)]
     path: ProjectPathInput,
+    /// Only project the trains running on this day (in UTC), moved to that day
+    #[serde(default)]
+    day: Option<NaiveDate>,
 }
 
 /// Project path input is described by a list of routes and a list of track range
@@ -130,6 +135,7 @@ async fn project_path(
     State(AppState {
         db_pool,
         valkey: valkey_client,
+        simulation_store,
         core_client,
         ..
     }): State<AppState>,
@@ -139,6 +145,7 @@ async fn project_path(
         ids: train_ids,
         path,
         electrical_profile_set_id,
+        day,
     }): Json<ProjectPathForm>,
 ) -> Result<Json<HashMap<i64, ProjectPathTrainResult>>> {
     let authorized = auth
@@ -176,6 +183,7 @@ async fn project_path(
             }
         })
         .await?;
+    let trains = trains_on_day(trains, day);
 
     let (rolling_stocks, _): (Vec<_>, _) = RollingStockModel::retrieve_batch(
         &mut db_pool.get().await?,
@@ -188,6 +196,7 @@ async fn project_path(
     let simulations = train_simulation_batch(
         &mut db_pool.get().await?,
         valkey_client.clone(),
+        simulation_store.as_ref(),
         core_client.clone(),
         &trains,
         &infra,
@@ -0,0 +1,497 @@
+use axum::extract::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use chrono::DateTime;
+use chrono::Utc;
+use editoast_authz::BuiltinRole;
+use editoast_derive::EditoastError;
+use editoast_models::DbConnectionPoolV2;
+use editoast_schemas::train_schedule::Recurrence;
+use editoast_schemas::train_schedule::TrainScheduleBase;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use crate::core::simulation::SimulationResponse;
+use crate::error::Result;
+use crate::models::prelude::*;
+use crate::models::train_schedule::TrainSchedule;
+use crate::models::train_service::TrainService;
+use crate::models::Infra;
+use crate::views::train_schedule::train_simulation_batch;
+use crate::views::train_schedule::ElectricalProfileSetIdQueryParam;
+use crate::views::train_schedule::InfraIdQueryParam;
+use crate::views::AuthenticationExt;
+use crate::views::AuthorizationError;
+use crate::AppState;
+
+crate::routes! {
+    "/train_service/{id}" => {
+        get,
+        put,
+        delete,
+        "/simulation" => simulation,
+    },
+}
+
+editoast_common::schemas! {
+    TrainServiceForm,
+    TrainServiceResult,
+    TrainServiceSimulation,
+}
+
+/// The maximum number of occurrences of a train service
+const MAX_OCCURRENCES: usize = 10_000;
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "train_service")]
+pub enum TrainServiceError {
+    #[error("Train service '{train_service_id}', could not be found")]
+    #[editoast_error(status = 404)]
+    NotFound { train_service_id: i64 },
+    #[error("Infra '{infra_id}', could not be found")]
+    #[editoast_error(status = 404)]
+    InfraNotFound { infra_id: i64 },
+    #[error("The train service has more than {max} occurrences")]
+    #[editoast_error(status = 400)]
+    TooManyOccurrences { max: usize },
+}
+
+#[derive(IntoParams, Deserialize)]
+struct TrainServiceIdParam {
+    /// A train service ID
+    id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TrainServiceForm {
+    /// The schedule of the first occurrence, repeated by the following ones
+    pub base: TrainScheduleBase,
+    pub recurrence: Recurrence,
+    /// Whether a train schedule is stored in the timetable for each occurrence
+    #[serde(default)]
+    pub expanded: bool,
+}
+
+impl TrainServiceForm {
+    /// Fails if the service has too many occurrences
+    ///
+    /// The occurrences falling on an exception day are counted, so that the check is bounded
+    /// however many of them are skipped.
+    pub fn validate(&self) -> Result<()> {
+        let count = self
+            .recurrence
+            .scheduled_departures(self.base.start_time)
+            .take(MAX_OCCURRENCES + 1)
+            .count();
+        if count > MAX_OCCURRENCES {
+            return Err(TrainServiceError::TooManyOccurrences {
+                max: MAX_OCCURRENCES,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    pub fn into_changeset(self, timetable_id: i64) -> Changeset<TrainService> {
+        TrainService::changeset()
+            .timetable_id(timetable_id)
+            .base(self.base)
+            .recurrence(self.recurrence)
+            .expanded(self.expanded)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TrainServiceResult {
+    pub id: i64,
+    pub timetable_id: i64,
+    #[serde(flatten)]
+    pub train_service: TrainServiceForm,
+    /// The departures of the occurrences
+    pub departures: Vec<DateTime<Utc>>,
+    /// The ids of the train schedules of the occurrences, sorted by start time
+    ///
+    /// Empty if the service is not expanded.
+    pub train_ids: Vec<i64>,
+}
+
+impl TrainServiceResult {
+    pub fn new(train_service: TrainService, trains: &[TrainSchedule]) -> Self {
+        Self {
+            id: train_service.id,
+            timetable_id: train_service.timetable_id,
+            departures: train_service
+                .recurrence
+                .departures(train_service.base.start_time)
+                .collect(),
+            train_ids: trains.iter().map(|train| train.id).collect(),
+            train_service: TrainServiceForm {
+                base: train_service.base,
+                recurrence: train_service.recurrence,
+                expanded: train_service.expanded,
+            },
+        }
+    }
+}
+
+/// Return a specific train service
+#[utoipa::path(
+    get, path = "",
+    tag = "train_service",
+    params(TrainServiceIdParam),
+    responses(
+        (status = 200, description = "The train service", body = TrainServiceResult),
+        (status = 404, description = "Train service not found"),
+    )
+)]
+async fn get(
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+    Path(TrainServiceIdParam {
+        id: train_service_id,
+    }): Path<TrainServiceIdParam>,
+) -> Result<Json<TrainServiceResult>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::TimetableRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let train_service = TrainService::retrieve_or_fail(conn, train_service_id, || {
+        TrainServiceError::NotFound { train_service_id }
+    })
+    .await?;
+    let trains = train_service.expanded_trains(conn).await?;
+    Ok(Json(TrainServiceResult::new(train_service, &trains)))
+}
+
+/// Update the pattern of a train service
+///
+/// The train schedules of an expanded service are updated to match the new pattern, keeping
+/// their ids in order of start time. Occurrences no longer in the pattern are deleted.
+#[utoipa::path(
+    put, path = "",
+    tag = "train_service",
+    params(TrainServiceIdParam),
+    request_body = TrainServiceForm,
+    responses(
+        (status = 200, description = "The updated train service", body = TrainServiceResult),
+        (status = 404, description = "Train service not found"),
+    )
+)]
+async fn put(
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+    Path(TrainServiceIdParam {
+        id: train_service_id,
+    }): Path<TrainServiceIdParam>,
+    Json(train_service_form): Json<TrainServiceForm>,
+) -> Result<Json<TrainServiceResult>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::TimetableWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+    train_service_form.validate()?;
+
+    let conn = &mut db_pool.get().await?;
+    conn.transaction(|conn| {
+        Box::pin(async move {
+            let train_service = TrainService::changeset()
+                .base(train_service_form.base)
+                .recurrence(train_service_form.recurrence)
+                .expanded(train_service_form.expanded)
+                .update_or_fail(&mut conn.clone(), train_service_id, || {
+                    TrainServiceError::NotFound { train_service_id }
+                })
+                .await?;
+            let trains = train_service
+                .sync_expanded_trains(&mut conn.clone())
+                .await?;
+            Ok(Json(TrainServiceResult::new(train_service, &trains)))
+        })
+    })
+    .await
+}
+
+/// Delete a train service and the train schedules of its occurrences
+#[utoipa::path(
+    delete, path = "",
+    tag = "train_service",
+    params(TrainServiceIdParam),
+    responses(
+        (status = 204, description = "The train service was deleted"),
+        (status = 404, description = "Train service not found"),
+    )
+)]
+async fn delete(
+    State(db_pool): State<DbConnectionPoolV2>,
+    Extension(auth): AuthenticationExt,
+    Path(TrainServiceIdParam {
+        id: train_service_id,
+    }): Path<TrainServiceIdParam>,
+) -> Result<impl IntoResponse> {
+    let authorized = auth
+        .check_roles([BuiltinRole::TimetableWrite].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    TrainService::delete_static_or_fail(conn, train_service_id, || TrainServiceError::NotFound {
+        train_service_id,
+    })
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The simulation shared by the occurrences of a train service
+#[derive(Debug, Serialize, ToSchema)]
+#[cfg_attr(test, derive(Deserialize))]
+struct TrainServiceSimulation {
+    /// The departures of the occurrences
+    departures: Vec<DateTime<Utc>>,
+    /// The simulation of an occurrence, relative to its departure
+    simulation: SimulationResponse,
+}
+
+/// Simulate a train service without expanding it
+///
+/// The occurrences only differ by their departure time, the base schedule is simulated once.
+#[utoipa::path(
+    get, path = "",
+    tag = "train_service",
+    params(TrainServiceIdParam, InfraIdQueryParam, ElectricalProfileSetIdQueryParam),
+    responses(
+        (status = 200, description = "The simulation of the occurrences", body = TrainServiceSimulation),
+        (status = 404, description = "Train service or infra not found"),
+    )
+)]
+async fn simulation(
+    State(AppState {
+        valkey: valkey_client,
+        simulation_store,
+        core_client,
+        db_pool,
+        ..
+    }): State<AppState>,
+    Extension(auth): AuthenticationExt,
+    Path(TrainServiceIdParam {
+        id: train_service_id,
+    }): Path<TrainServiceIdParam>,
+    Query(InfraIdQueryParam { infra_id }): Query<InfraIdQueryParam>,
+    Query(ElectricalProfileSetIdQueryParam {
+        electrical_profile_set_id,
+    }): Query<ElectricalProfileSetIdQueryParam>,
+) -> Result<Json<TrainServiceSimulation>> {
+    let authorized = auth
+        .check_roles([BuiltinRole::InfraRead, BuiltinRole::TimetableRead].into())
+        .await
+        .map_err(AuthorizationError::AuthError)?;
+    if !authorized {
+        return Err(AuthorizationError::Forbidden.into());
+    }
+
+    let conn = &mut db_pool.get().await?;
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || TrainServiceError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+    let train_service = TrainService::retrieve_or_fail(conn, train_service_id, || {
+        TrainServiceError::NotFound { train_service_id }
+    })
+    .await?;
+
+    let departures = train_service
+        .recurrence
+        .departures(train_service.base.start_time)
+        .collect();
+    let train_schedule = virtual_train_schedule(&train_service);
+    let (simulation, _) = train_simulation_batch(
+        conn,
+        valkey_client,
+        simulation_store.as_ref(),
+        core_client,
+        &[train_schedule],
+        &infra,
+        electrical_profile_set_id,
+    )
+    .await?
+    .pop()
+    .unwrap();
+
+    Ok(Json(TrainServiceSimulation {
+        departures,
+        simulation,
+    }))
+}
+
+/// The first occurrence of a service, as a train schedule which is not stored
+fn virtual_train_schedule(train_service: &TrainService) -> TrainSchedule {
+    let TrainScheduleBase {
+        train_name,
+        labels,
+        rolling_stock_name,
+        start_time,
+        path,
+        schedule,
+        margins,
+        initial_speed,
+        comfort,
+        constraint_distribution,
+        speed_limit_tag,
+        power_restrictions,
+        options,
+        calendar,
+    } = train_service.base.clone();
+    TrainSchedule {
+        id: 0,
+        train_name,
+        labels: labels.into_iter().map(Some).collect(),
+        rolling_stock_name,
+        timetable_id: train_service.timetable_id,
+        start_time,
+        schedule,
+        margins,
+        initial_speed,
+        comfort,
+        path,
+        constraint_distribution,
+        speed_limit_tag: speed_limit_tag.map(|tag| tag.0),
+        power_restrictions,
+        options,
+        train_service_id: Some(train_service.id),
+        calendar,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use chrono::Duration;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::fixtures::create_timetable;
+    use crate::models::fixtures::simple_train_schedule_base;
+    use crate::views::test_app::TestAppBuilder;
+
+    #[rstest]
+    async fn train_service_expand_and_edit() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+        let base = simple_train_schedule_base();
+
+        let request = app
+            .post(&format!("/timetable/{}/train_service", timetable.id))
+            .json(&json!({
+                "base": base,
+                "recurrence": {
+                    "interval": "PT30M",
+                    "last_departure": base.start_time + Duration::hours(2),
+                },
+                "expanded": true,
+            }));
+        let created: TrainServiceResult =
+            app.fetch(request).assert_status(StatusCode::OK).json_into();
+        assert_eq!(created.departures.len(), 5);
+        assert_eq!(created.train_ids.len(), 5);
+
+        let edited_base = TrainScheduleBase {
+            train_name: "edited".to_owned(),
+            ..base.clone()
+        };
+        let request = app
+            .put(&format!("/train_service/{}", created.id))
+            .json(&json!({
+                "base": edited_base,
+                "recurrence": {
+                    "interval": "PT1H",
+                    "last_departure": base.start_time + Duration::hours(2),
+                },
+                "expanded": true,
+            }));
+        let updated: TrainServiceResult =
+            app.fetch(request).assert_status(StatusCode::OK).json_into();
+        assert_eq!(updated.train_ids, created.train_ids[..3]);
+
+        let trains: Vec<TrainSchedule> =
+            TrainSchedule::retrieve_batch_unchecked(&mut pool.get_ok(), updated.train_ids)
+                .await
+                .unwrap();
+        assert!(trains.iter().all(|train| train.train_name == "edited"));
+        assert!(
+            !TrainSchedule::exists(&mut pool.get_ok(), created.train_ids[4])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[rstest]
+    async fn train_service_too_many_occurrences() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+        let base = simple_train_schedule_base();
+
+        let request = app
+            .post(&format!("/timetable/{}/train_service", timetable.id))
+            .json(&json!({
+                "base": base,
+                "recurrence": {
+                    "interval": "PT1S",
+                    "last_departure": base.start_time + Duration::days(1),
+                },
+            }));
+        app.fetch(request).assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[rstest]
+    async fn train_service_delete() {
+        let app = TestAppBuilder::default_app();
+        let pool = app.db_pool();
+        let timetable = create_timetable(&mut pool.get_ok()).await;
+        let form = TrainServiceForm {
+            base: simple_train_schedule_base(),
+            recurrence: serde_json::from_value(json!({
+                "interval": "PT1H",
+                "last_departure": simple_train_schedule_base().start_time + Duration::hours(1),
+            }))
+            .unwrap(),
+            expanded: true,
+        };
+        let train_service = form
+            .into_changeset(timetable.id)
+            .create(&mut pool.get_ok())
+            .await
+            .expect("Failed to create train service");
+        let trains = train_service
+            .sync_expanded_trains(&mut pool.get_ok())
+            .await
+            .unwrap();
+
+        let request = app.delete(&format!("/train_service/{}", train_service.id));
+        app.fetch(request).assert_status(StatusCode::NO_CONTENT);
+
+        assert!(!TrainSchedule::exists(&mut pool.get_ok(), trains[0].id)
+            .await
+            .unwrap());
+    }
+}